@@ -0,0 +1,75 @@
+use serde::{
+    Serialize,
+    Deserialize
+};
+use std::str::FromStr;
+
+/// How a node misbehaves, to test the protocols under attack. Everything is
+/// off for an honest node.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct Byzantine {
+    /// Send a proposal to half of the nodes and a conflicting proposal to the
+    /// other half
+    pub equivocate: bool,
+    /// Do not send the proposals
+    pub withhold_proposals: bool,
+    /// Do not send the votes
+    pub withhold_votes: bool,
+    /// Send every message this many milliseconds late
+    pub delay: u64,
+    /// Send every message again some time later
+    pub replay: bool,
+    /// Send a copy of every message with a broken signature
+    pub malformed: bool,
+}
+
+impl Byzantine {
+    pub fn is_honest(&self) -> bool {
+        *self == Byzantine::default()
+    }
+}
+
+/// Parse a comma separated list of behaviours, such as
+/// `equivocate,withhold-votes,delay=200`
+impl FromStr for Byzantine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut b = Byzantine::default();
+        for item in s.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let (name, value) = match item.split_once('=') {
+                Some((n, v)) => (n, Some(v)),
+                None => (item, None),
+            };
+            match (name, value) {
+                ("equivocate", None) => b.equivocate = true,
+                ("withhold-proposals", None) => b.withhold_proposals = true,
+                ("withhold-votes", None) => b.withhold_votes = true,
+                ("replay", None) => b.replay = true,
+                ("malformed", None) => b.malformed = true,
+                ("delay", Some(v)) => {
+                    b.delay = v.parse()
+                        .map_err(|_| format!("invalid delay: {}", v))?;
+                }
+                _ => return Err(format!("unknown behaviour: {}", item)),
+            }
+        }
+        Ok(b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byzantine_from_str() {
+        let b: Byzantine = "equivocate, withhold-votes,delay=200".parse().unwrap();
+        assert!(b.equivocate && b.withhold_votes && !b.withhold_proposals);
+        assert_eq!(b.delay, 200);
+        assert!("".parse::<Byzantine>().unwrap().is_honest());
+        assert!("delay".parse::<Byzantine>().is_err());
+        assert!("lie".parse::<Byzantine>().is_err());
+    }
+}
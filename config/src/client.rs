@@ -2,12 +2,14 @@ use serde::{
     Serialize, 
     Deserialize
 };
-use types::Replica;
-use crypto::Algorithm;
+use types::{FaultModel, QuorumPolicy, Replica};
+use crypto::{Algorithm, hash::HashAlgorithm};
 use fnv::FnvHashMap as HashMap;
 use super::{
     ParseError,
-    is_valid_replica
+    LeaderElection,
+    is_valid_replica,
+    validate_vrf_pks,
 };
 use std::fs::File;
 use std::io::prelude::*;
@@ -18,10 +20,23 @@ use toml::from_str;
 pub struct Client {
     pub net_map:HashMap<Replica, String>,
     pub crypto_alg:Algorithm,
+    /// The hash function of the chain, the same for all nodes and clients
+    #[serde(default)]
+    pub hash_alg:HashAlgorithm,
     pub server_pk:HashMap<Replica, Vec<u8>>,
+    /// Leader election, and the VRF public keys of the nodes for
+    /// `LeaderElection::VRF`
+    #[serde(default)]
+    pub leader_election:LeaderElection,
+    #[serde(default)]
+    pub vrf_pk_map:HashMap<Replica, Vec<u8>>,
 
     pub num_nodes: usize,
     pub num_faults: usize,
+    /// The voting power of every node, the same as in the configs of the
+    /// nodes. Empty gives every node one vote.
+    #[serde(default)]
+    pub weights: HashMap<Replica, u64>,
     pub block_size:usize,
     pub payload:usize,
 
@@ -42,6 +57,14 @@ impl Client {
                 return Err(ParseError::InvalidMapEntry(*repl.0));
             }
         }
+        if !self.weights.is_empty() {
+            if let Some(r) = self.net_map.keys().find(|r| self.weights.get(r).is_none_or(|w| *w == 0)) {
+                return Err(ParseError::InvalidWeight(*r));
+            }
+            if let Some(r) = self.weights.keys().find(|r| !is_valid_replica(**r, self.num_nodes)) {
+                return Err(ParseError::InvalidMapEntry(*r));
+            }
+        }
         match self.crypto_alg {
             Algorithm::ED25519 => {
                 for repl in &self.server_pk {
@@ -63,11 +86,35 @@ impl Client {
                     }
                 }
             }
+            Algorithm::BLS => {
+                for repl in &self.server_pk {
+                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                    if repl.1.len() != crypto::BLS_PK_SIZE {
+                        return Err(ParseError::InvalidPkSize(repl.1.len()));
+                    }
+                    // A key of the right size may still not be a point of G1,
+                    // and the contexts expect every key to decode
+                    if crypto::bls::PublicKey::decode(repl.1).is_err() {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                }
+            }
             Algorithm::RSA => {
-                // Because unimplemented
-                return Err(ParseError::Unimplemented("RSA"));
+                for repl in &self.server_pk {
+                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                    if crypto::rsa::PublicKey::decode_pkcs1(repl.1).is_err() {
+                        return Err(ParseError::InvalidPk(*repl.0));
+                    }
+                }
             }
         }
+        if self.leader_election == LeaderElection::VRF {
+            validate_vrf_pks(&self.vrf_pk_map, self.num_nodes)?;
+        }
         Ok(())
     }
 
@@ -76,14 +123,32 @@ impl Client {
             net_map: HashMap::default(),
             block_size: 0,
             crypto_alg: Algorithm::ED25519,
+            hash_alg: HashAlgorithm::SHA256,
             num_faults: 0,
+            weights: HashMap::default(),
             num_nodes:0,
             server_pk: HashMap::default(),
+            leader_election: LeaderElection::Deterministic,
+            vrf_pk_map: HashMap::default(),
             payload:0,
             root_cert:Vec::new(),
         }
     } 
 
+    /// The votes that the nodes need under the fault model `model`, which
+    /// are also the acks the client needs before it trusts a block
+    pub fn quorum_policy(&self, model: FaultModel) -> QuorumPolicy {
+        QuorumPolicy::new(model, 0..self.num_nodes, self.num_faults, self.weights.clone())
+    }
+
+    /// Use the hash function of this chain for every hash computed in this
+    /// process. Call this before creating any block or transaction; it fails
+    /// if the process already uses another hash function.
+    pub fn use_hash_algorithm(&self) -> Result<(), ParseError> {
+        crypto::hash::set_hash_algorithm(self.hash_alg)
+            .map_err(|current| ParseError::ConflictingHashAlgorithm(self.hash_alg, current))
+    }
+
     pub fn from_json(filename:String) -> Client {
         let f = File::open(filename)
             .unwrap();
@@ -132,4 +197,34 @@ impl Client {
         }
         log::info!("Talking to servers: {:?}", self.net_map);
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use types::Threshold;
+
+    #[test]
+    fn acks_follow_the_weights_of_the_nodes() {
+        let mut config = Client::new();
+        config.num_nodes = 4;
+        config.num_faults = 1;
+        for i in 0..4 {
+            config.net_map.insert(i, format!("127.0.0.1:{}", 10000+i));
+            config.server_pk.insert(i, vec![0; crypto::ED25519_PK_SIZE]);
+        }
+        assert!(config.validate().is_ok());
+        let quorum = config.quorum_policy(FaultModel::Synchronous);
+        assert!(!quorum.is_met(Threshold::Honest, vec![0]));
+        assert!(quorum.is_met(Threshold::Honest, vec![0, 1]));
+
+        // The faulty node is the heaviest one
+        config.weights = (0..4).map(|i| (i, if i == 3 { 3 } else { 1 })).collect();
+        assert!(config.validate().is_ok());
+        let quorum = config.quorum_policy(FaultModel::Synchronous);
+        assert!(!quorum.is_met(Threshold::Honest, vec![0, 1, 2]));
+        assert!(quorum.is_met(Threshold::Honest, vec![0, 1, 2, 3]));
+
+        config.weights.insert(2, 0);
+        assert!(matches!(config.validate(), Err(ParseError::InvalidWeight(2))));
+    }
+}
@@ -1,17 +1,30 @@
+use crypto::hash::HashAlgorithm;
+use types::FaultModel;
+
 #[derive(Debug)]
 pub enum ParseError {
     // actual, expected
     InvalidMapLen(usize, usize),
     // f, n
     IncorrectFaults(usize, usize),
+    // f, n, the fault model of the protocol
+    ExceedsFaultModel(usize, usize, FaultModel),
+    // r, whose weight is missing or zero
+    InvalidWeight(usize),
     // r
     InvalidMapEntry(usize),
     // pk_size
     InvalidPkSize(usize),
     // sk_size
     InvalidSkSize(usize),
+    // r, public key that could not be decoded
+    InvalidPk(usize),
+    // secret key that could not be decoded
+    InvalidSk,
     // feature name that is not implemented
     Unimplemented(&'static str),
+    // the hash function of the config, the one this process already uses
+    ConflictingHashAlgorithm(HashAlgorithm, HashAlgorithm),
 }
 
 impl std::fmt::Display for ParseError {
@@ -21,6 +34,10 @@ impl std::fmt::Display for ParseError {
             write!(f, "invalid map length: expected {}, got {}", exp, actual),
             ParseError::IncorrectFaults(fault, n) => 
             write!(f, "n > 2f not satisfied since {} !> 2x{}", n, fault),
+            ParseError::ExceedsFaultModel(fault, n, model) =>
+            write!(f, "{} nodes cannot tolerate {} faults in the {} model", n, fault, model),
+            ParseError::InvalidWeight(r) =>
+            write!(f, "invalid weight for {} replica", r),
             ParseError::InvalidMapEntry(r) => 
             write!(f, "invalid map entry for {} replica", r),
             ParseError::InvalidPkSize(s) => 
@@ -29,6 +46,12 @@ impl std::fmt::Display for ParseError {
             write!(f, "{} feature is not yet implemented", feature),
             ParseError::InvalidSkSize(s) =>
             write!(f, "invalid secret key size ({})", s),
+            ParseError::InvalidPk(r) =>
+            write!(f, "invalid public key for {} replica", r),
+            ParseError::InvalidSk =>
+            write!(f, "invalid secret key"),
+            ParseError::ConflictingHashAlgorithm(alg, current) =>
+            write!(f, "the chain uses {:?}, but this process already hashes with {:?}", alg, current),
         }
     }
 }
@@ -38,10 +61,15 @@ impl std::error::Error for ParseError {
         match *self {
             ParseError::InvalidMapLen(_,_) => "invalid map length",
             ParseError::IncorrectFaults(_,_) => "incorrect f and n values",
+            ParseError::ExceedsFaultModel(_,_,_) => "too many faults for the fault model",
+            ParseError::InvalidWeight(_) => "invalid weight",
             ParseError::InvalidMapEntry(_) => "incorrect map entry",
             ParseError::InvalidPkSize(_) => "invalid public key size",
             ParseError::InvalidSkSize(_) => "invalid secret key size",
+            ParseError::InvalidPk(_) => "invalid public key",
+            ParseError::InvalidSk => "invalid secret key",
             ParseError::Unimplemented(_) => "feature unimplemented",
+            ParseError::ConflictingHashAlgorithm(_,_) => "conflicting hash function",
         }
     }
 
@@ -0,0 +1,338 @@
+use serde::{
+    Serialize,
+    Deserialize
+};
+use types::Replica;
+use crypto::Algorithm;
+use argon2::{Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use zeroize::Zeroize;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+/// The environment variable that the node binaries read the keystore password
+/// from.
+pub const KEYSTORE_PASSWORD_ENV: &str = "LIBCHATTER_KEYSTORE_PASSWORD";
+
+/// The version of the keystore format written by this library
+pub const KEYSTORE_VERSION: u32 = 1;
+
+const KEY_SIZE: usize = 32;
+const SALT_SIZE: usize = 16;
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    // the keystore file could not be read or written
+    Io(std::io::Error),
+    // the keystore file could not be parsed
+    Format(String),
+    // version found in the file
+    UnsupportedVersion(u32),
+    // the key could not be derived from the password
+    Kdf(String),
+    // wrong password or a tampered keystore
+    Decrypt,
+    // environment variable that was not set
+    NoPassword(&'static str),
+}
+
+impl std::fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KeystoreError::Io(e) =>
+            write!(f, "keystore io error: {}", e),
+            KeystoreError::Format(e) =>
+            write!(f, "invalid keystore file: {}", e),
+            KeystoreError::UnsupportedVersion(v) =>
+            write!(f, "unsupported keystore version {}", v),
+            KeystoreError::Kdf(e) =>
+            write!(f, "failed to derive the keystore key: {}", e),
+            KeystoreError::Decrypt =>
+            write!(f, "failed to decrypt the keystore: wrong password or corrupted file"),
+            KeystoreError::NoPassword(var) =>
+            write!(f, "no keystore password: {} is not set", var),
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {
+    fn description(&self) -> &str {
+        match *self {
+            KeystoreError::Io(_) => "keystore io error",
+            KeystoreError::Format(_) => "invalid keystore file",
+            KeystoreError::UnsupportedVersion(_) => "unsupported keystore version",
+            KeystoreError::Kdf(_) => "key derivation failed",
+            KeystoreError::Decrypt => "keystore decryption failed",
+            KeystoreError::NoPassword(_) => "no keystore password",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn std::error::Error> {
+        match *self {
+            KeystoreError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for KeystoreError {
+    fn from(e: std::io::Error) -> Self {
+        KeystoreError::Io(e)
+    }
+}
+
+/// The secret material of a node that is kept in a keystore.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Secrets {
+    /// The protocol secret key (same format as `Node::secret_key_bytes`)
+    pub secret_key_bytes: Vec<u8>,
+    /// The VRF secret key (same format as `Node::vrf_secret_key`)
+    pub vrf_secret_key: Vec<u8>,
+    /// The TLS certificate private key (same format as `Node::my_cert_key`)
+    pub my_cert_key: Vec<u8>,
+}
+
+impl std::fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Secrets")
+            .field("secret_key_bytes", &self.secret_key_bytes.len())
+            .field("vrf_secret_key", &self.vrf_secret_key.len())
+            .field("my_cert_key", &self.my_cert_key.len())
+            .finish()
+    }
+}
+
+impl Drop for Secrets {
+    fn drop(&mut self) {
+        self.secret_key_bytes.zeroize();
+        self.vrf_secret_key.zeroize();
+        self.my_cert_key.zeroize();
+    }
+}
+
+/// Argon2id parameters used to derive the encryption key from the password
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+}
+
+impl KdfParams {
+    fn generate() -> KdfParams {
+        let mut salt = vec![0u8; SALT_SIZE];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+            salt,
+        }
+    }
+
+    fn derive(&self, password: &[u8]) -> Result<[u8; KEY_SIZE], KeystoreError> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(KEY_SIZE))
+            .map_err(|e| KeystoreError::Kdf(e.to_string()))?;
+        let mut key = [0u8; KEY_SIZE];
+        Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, &self.salt, &mut key)
+            .map_err(|e| KeystoreError::Kdf(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+/// A keystore holds the secrets of one node, encrypted with
+/// XChaCha20-Poly1305 under a key derived from a password with Argon2id.
+///
+/// The id and the algorithm are stored in the clear, and are authenticated as
+/// the associated data of the ciphertext.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Keystore {
+    pub version: u32,
+    pub id: Replica,
+    pub crypto_alg: Algorithm,
+    pub kdf: KdfParams,
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+impl Keystore {
+    /// Encrypt the secrets of node `id` with `password`
+    pub fn seal(id: Replica, crypto_alg: Algorithm, secrets: &Secrets, password: &[u8]) -> Result<Keystore, KeystoreError> {
+        let kdf = KdfParams::generate();
+        let mut ks = Keystore {
+            version: KEYSTORE_VERSION,
+            id,
+            crypto_alg,
+            kdf,
+            nonce: Vec::new(),
+            ciphertext: Vec::new(),
+        };
+        let mut key = ks.kdf.derive(password)?;
+        let cipher = XChaCha20Poly1305::new((&key).into());
+        key.zeroize();
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut plain = bincode::serialize(secrets)
+            .map_err(|e| KeystoreError::Format(e.to_string()))?;
+        let payload = chacha20poly1305::aead::Payload {
+            msg: &plain,
+            aad: &ks.associated_data(),
+        };
+        let ciphertext = cipher.encrypt(&nonce, payload)
+            .map_err(|_| KeystoreError::Decrypt)?;
+        plain.zeroize();
+        ks.nonce = nonce.to_vec();
+        ks.ciphertext = ciphertext;
+        Ok(ks)
+    }
+
+    /// Decrypt the secrets in this keystore with `password`
+    pub fn open(&self, password: &[u8]) -> Result<Secrets, KeystoreError> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(KeystoreError::UnsupportedVersion(self.version));
+        }
+        if self.nonce.len() != 24 {
+            return Err(KeystoreError::Format(format!("invalid nonce size ({})", self.nonce.len())));
+        }
+        let mut key = self.kdf.derive(password)?;
+        let cipher = XChaCha20Poly1305::new((&key).into());
+        key.zeroize();
+        let payload = chacha20poly1305::aead::Payload {
+            msg: &self.ciphertext,
+            aad: &self.associated_data(),
+        };
+        let mut plain = cipher.decrypt(XNonce::from_slice(&self.nonce), payload)
+            .map_err(|_| KeystoreError::Decrypt)?;
+        let secrets = bincode::deserialize(&plain)
+            .map_err(|e| KeystoreError::Format(e.to_string()));
+        plain.zeroize();
+        secrets
+    }
+
+    /// Encrypt the secrets again under `new_password`, with a fresh salt and
+    /// nonce
+    pub fn reencrypt(&self, old_password: &[u8], new_password: &[u8]) -> Result<Keystore, KeystoreError> {
+        let secrets = self.open(old_password)?;
+        Keystore::seal(self.id, self.crypto_alg.clone(), &secrets, new_password)
+    }
+
+    fn associated_data(&self) -> Vec<u8> {
+        bincode::serialize(&(self.version, self.id, &self.crypto_alg))
+            .expect("failed to serialize the keystore header")
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Keystore, KeystoreError> {
+        let f = File::open(path)?;
+        serde_json::from_reader(f)
+            .map_err(|e| KeystoreError::Format(e.to_string()))
+    }
+
+    /// Write the keystore to `path`, readable by its owner only. The file is
+    /// replaced atomically, so a crash leaves either the old or the new
+    /// keystore.
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<(), KeystoreError> {
+        let path = path.as_ref();
+        let mut tmp_name = path.file_name()
+            .ok_or_else(|| KeystoreError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput, "the keystore path is not a file")))?
+            .to_os_string();
+        tmp_name.push(format!(".tmp.{}", std::process::id()));
+        let tmp = path.with_file_name(tmp_name);
+        let res = self.write_new(&tmp)
+            .and_then(|_| std::fs::rename(&tmp, path).map_err(KeystoreError::from));
+        if res.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        res
+    }
+
+    /// Write the keystore to a new file that only its owner can read, and
+    /// sync it to the disk
+    fn write_new(&self, path: &Path) -> Result<(), KeystoreError> {
+        let mut opts = OpenOptions::new();
+        opts.write(true).create_new(true);
+        #[cfg(unix)]
+        opts.mode(0o600);
+        let mut f = opts.open(path)?;
+        serde_json::to_writer_pretty(&mut f, self)
+            .map_err(|e| KeystoreError::Format(e.to_string()))?;
+        f.flush()?;
+        f.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Read a keystore password from the environment variable `var`
+pub fn password_from_env(var: &'static str) -> Result<Vec<u8>, KeystoreError> {
+    std::env::var(var)
+        .map(String::into_bytes)
+        .map_err(|_| KeystoreError::NoPassword(var))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets() -> Secrets {
+        Secrets {
+            secret_key_bytes: vec![1; 64],
+            vrf_secret_key: vec![3; 32],
+            my_cert_key: vec![2; 100],
+        }
+    }
+
+    #[test]
+    fn keystore_seal_open() {
+        let ks = Keystore::seal(3, Algorithm::ED25519, &secrets(), b"password").unwrap();
+        let s = ks.open(b"password").unwrap();
+        assert_eq!(s.secret_key_bytes, vec![1; 64]);
+        assert_eq!(s.my_cert_key, vec![2; 100]);
+        assert!(matches!(ks.open(b"wrong"), Err(KeystoreError::Decrypt)));
+
+        // The header is authenticated
+        let mut tampered = ks.clone();
+        tampered.id = 4;
+        assert!(matches!(tampered.open(b"password"), Err(KeystoreError::Decrypt)));
+    }
+
+    #[test]
+    fn keystore_reencrypt() {
+        let ks = Keystore::seal(0, Algorithm::BLS, &secrets(), b"old").unwrap();
+        let ks2 = ks.reencrypt(b"old", b"new").unwrap();
+        assert_ne!(ks.kdf.salt, ks2.kdf.salt);
+        assert!(ks2.open(b"old").is_err());
+        assert_eq!(ks2.open(b"new").unwrap().my_cert_key, vec![2; 100]);
+    }
+
+    #[test]
+    fn keystore_file() {
+        let dir = std::env::temp_dir().join(format!("keystore-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("keystore-1.json");
+        let ks = Keystore::seal(1, Algorithm::ED25519, &secrets(), b"old").unwrap();
+        ks.to_file(&path).unwrap();
+        // Replacing the file works, and leaves no temporary file behind
+        let ks = ks.reencrypt(b"old", b"new").unwrap();
+        ks.to_file(&path).unwrap();
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        let read = Keystore::from_file(&path).unwrap();
+        assert_eq!(read.open(b"new").unwrap().secret_key_bytes, vec![1; 64]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,33 @@
+use serde::{
+    Serialize,
+    Deserialize
+};
+use std::str::FromStr;
+
+/// How the leaders are chosen
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum LeaderElection {
+    /// The fixed schedule of the protocol, known to everyone in advance
+    #[default]
+    Deterministic,
+    /// Every leader proves a VRF output on the round, and the next leader is
+    /// derived from it. Nobody knows the next leader before the current leader
+    /// proposes, and everyone (including the clients) can check it.
+    VRF,
+    /// The fixed schedule, except that the nodes that did not get a block into
+    /// the recent history of the chain give their turns to the others
+    Reputation,
+}
+
+impl FromStr for LeaderElection {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Deterministic" => Ok(LeaderElection::Deterministic),
+            "VRF" => Ok(LeaderElection::VRF),
+            "Reputation" => Ok(LeaderElection::Reputation),
+            _ => Err("no match"),
+        }
+    }
+}
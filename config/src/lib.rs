@@ -7,10 +7,38 @@ pub use node::*;
 mod error;
 pub use error::*;
 
+mod keystore;
+pub use keystore::*;
+
+mod leader;
+pub use leader::*;
+
+mod byzantine;
+pub use byzantine::*;
+
 fn is_valid_replica(r:types::Replica, n:usize) -> bool {
     n>r as usize
 }
 
+/// Check that there is a valid VRF public key for every node
+fn validate_vrf_pks(
+    vrf_pk_map: &fnv::FnvHashMap<types::Replica, Vec<u8>>,
+    n: usize,
+) -> Result<(), ParseError> {
+    if vrf_pk_map.len() != n {
+        return Err(ParseError::InvalidMapLen(n, vrf_pk_map.len()));
+    }
+    for repl in vrf_pk_map {
+        if !is_valid_replica(*repl.0, n) {
+            return Err(ParseError::InvalidMapEntry(*repl.0));
+        }
+        if crypto::vrf::PublicKey::decode(repl.1).is_err() {
+            return Err(ParseError::InvalidPk(*repl.0));
+        }
+    }
+    Ok(())
+}
+
 /// The amount of time to sleep to be sure that all the other nodes of the group
 /// are up and listening.
 ///
@@ -2,15 +2,25 @@ use serde::{
     Serialize, 
     Deserialize
 };
-use types::Replica;
-use crypto::Algorithm;
+use types::{FaultModel, QuorumPolicy, Replica};
+use crypto::{Algorithm, hash::HashAlgorithm};
 use fnv::FnvHashMap as HashMap;
 use super::{
     ParseError,
-    is_valid_replica
+    KeystoreError,
+    LeaderElection,
+    Byzantine,
+    Keystore,
+    Secrets,
+    KEYSTORE_PASSWORD_ENV,
+    is_valid_replica,
+    password_from_env,
+    validate_vrf_pks,
 };
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
+use zeroize::Zeroize;
 use serde_json::from_reader;
 use toml::from_str;
 
@@ -24,19 +34,65 @@ pub struct Node {
     pub id: Replica,
     pub num_nodes: usize,
     pub num_faults: usize,
+    /// The voting power of every node, for weighted voting. Empty gives every
+    /// node one vote.
+    #[serde(default)]
+    pub weights: HashMap<Replica, u64>,
     pub block_size:usize,
     pub client_port: u16,
     pub payload: usize,
+    /// Number of workers that verify signatures before the messages reach the
+    /// reactor. 0 verifies them in the reactor.
+    #[serde(default)]
+    pub verify_workers: usize,
+    /// Refuse to start if the delay measured to the other nodes is above
+    /// `delta`, instead of only logging it
+    #[serde(default)]
+    pub strict_delta: bool,
+    /// How this node misbehaves, for testing
+    #[serde(default)]
+    pub byzantine: Byzantine,
+    /// Follow the configuration transactions that add and remove nodes (Sync
+    /// HotStuff only). The nodes of `net_map` and `pk_map` then need not be
+    /// numbered `0..num_nodes`, since the removed ones leave gaps.
+    #[serde(default)]
+    pub reconfigurable: bool,
+    /// The directory where the node keeps the state that it needs after a
+    /// restart: the log of what it signed (Apollo, Artemis, Sync HotStuff,
+    /// HotStuff and PBFT), which it checks before signing anything. A Sync
+    /// HotStuff node that finds its log there also catches up with the
+    /// others.
+    #[serde(default)]
+    pub state_dir: Option<String>,
     
     /// Crypto primitives
     pub crypto_alg: Algorithm,
+    /// The hash function of the chain, the same for all nodes and clients
+    #[serde(default)]
+    pub hash_alg: HashAlgorithm,
     pub pk_map: HashMap<Replica, Vec<u8>>,
     pub secret_key_bytes: Vec<u8>,
 
+    /// Leader election
+    #[serde(default)]
+    pub leader_election: LeaderElection,
+    /// The VRF public keys of all the nodes, used with `LeaderElection::VRF`
+    #[serde(default)]
+    pub vrf_pk_map: HashMap<Replica, Vec<u8>>,
+    #[serde(default)]
+    pub vrf_secret_key: Vec<u8>,
+
     /// OpenSSL Certificate Details
     pub my_cert: Vec<u8>,
     pub my_cert_key: Vec<u8>,
     pub root_cert: Vec<u8>,
+
+    /// An encrypted keystore holding `secret_key_bytes`, `vrf_secret_key` and
+    /// `my_cert_key`.
+    /// When set, both are left empty in the config file and are filled in by
+    /// `unlock_keystore`. A relative path is relative to the config file.
+    #[serde(default)]
+    pub keystore: Option<String>,
 }
 
 impl Node {
@@ -48,14 +104,33 @@ impl Node {
             return Err(ParseError::IncorrectFaults(self.num_faults, self.num_nodes));
         }
         for repl in &self.net_map {
-            if !is_valid_replica(*repl.0, self.num_nodes) {
+            if !self.is_member(*repl.0) {
                 return Err(ParseError::InvalidMapEntry(*repl.0));
             }
         }
+        if self.reconfigurable {
+            if self.verify_workers > 0 {
+                return Err(ParseError::Unimplemented("reconfiguration with verification workers"));
+            }
+            if self.leader_election != LeaderElection::Deterministic {
+                return Err(ParseError::Unimplemented("reconfiguration with this leader election"));
+            }
+            if !self.weights.is_empty() {
+                return Err(ParseError::Unimplemented("reconfiguration with weighted voting"));
+            }
+        }
+        if !self.weights.is_empty() {
+            if let Some(r) = self.net_map.keys().find(|r| self.weights.get(r).is_none_or(|w| *w == 0)) {
+                return Err(ParseError::InvalidWeight(*r));
+            }
+            if let Some(r) = self.weights.keys().find(|r| !self.is_member(**r)) {
+                return Err(ParseError::InvalidMapEntry(*r));
+            }
+        }
         match self.crypto_alg {
             Algorithm::ED25519 => {
                 for repl in &self.pk_map {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !self.is_member(*repl.0) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::ED25519_PK_SIZE {
@@ -68,7 +143,7 @@ impl Node {
             }
             Algorithm::SECP256K1 => {
                 for repl in &self.pk_map {
-                    if !is_valid_replica(*repl.0, self.num_nodes) {
+                    if !self.is_member(*repl.0) {
                         return Err(ParseError::InvalidMapEntry(*repl.0));
                     }
                     if repl.1.len() != crypto::SECP256K1_PK_SIZE {
@@ -79,31 +154,158 @@ impl Node {
                     return Err(ParseError::InvalidSkSize(self.secret_key_bytes.len()));
                 }
             }
+            Algorithm::BLS => {
+                for repl in &self.pk_map {
+                    if !self.is_member(*repl.0) {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                    if repl.1.len() != crypto::BLS_PK_SIZE {
+                        return Err(ParseError::InvalidPkSize(repl.1.len()));
+                    }
+                    // A key of the right size may still not be a point of G1,
+                    // and the contexts expect every key to decode
+                    if crypto::bls::PublicKey::decode(repl.1).is_err() {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                }
+                if self.secret_key_bytes.len() != crypto::BLS_PVT_SIZE {
+                    return Err(ParseError::InvalidSkSize(self.secret_key_bytes.len()));
+                }
+            }
             Algorithm::RSA => {
-                // Because unimplemented
-                return Err(ParseError::Unimplemented("RSA"));
+                for repl in &self.pk_map {
+                    if !self.is_member(*repl.0) {
+                        return Err(ParseError::InvalidMapEntry(*repl.0));
+                    }
+                    if crypto::rsa::PublicKey::decode_pkcs1(repl.1).is_err() {
+                        return Err(ParseError::InvalidPk(*repl.0));
+                    }
+                }
+                // PKCS#8 keys do not have a fixed size, so try decoding
+                let mut sk = self.secret_key_bytes.clone();
+                if crypto::rsa::Keypair::from_pkcs8(&mut sk).is_err() {
+                    return Err(ParseError::InvalidSk);
+                }
+            }
+        }
+        if self.leader_election == LeaderElection::VRF {
+            validate_vrf_pks(&self.vrf_pk_map, self.num_nodes)?;
+            if self.vrf_secret_key.len() != crypto::vrf::VRF_PVT_SIZE {
+                return Err(ParseError::InvalidSkSize(self.vrf_secret_key.len()));
             }
         }
         Ok(())
     }
 
+    /// Validate the config for a protocol with the fault model `model`, which
+    /// may tolerate fewer faults than `validate` allows
+    pub fn validate_for(&self, model: FaultModel) -> Result<(), ParseError> {
+        self.validate()?;
+        if !self.quorum_policy(model).is_tolerated() {
+            return Err(ParseError::ExceedsFaultModel(self.num_faults, self.num_nodes, model));
+        }
+        Ok(())
+    }
+
+    /// The votes that the certificates need under the fault model `model`
+    pub fn quorum_policy(&self, model: FaultModel) -> QuorumPolicy {
+        let members: Vec<Replica> = if self.reconfigurable {
+            self.net_map.keys().cloned().collect()
+        } else {
+            (0..self.num_nodes).collect()
+        };
+        QuorumPolicy::new(model, members, self.num_faults, self.weights.clone())
+    }
+
+    /// Whether `r` is a node of the config: one of `0..num_nodes`, or any
+    /// node of `net_map` if the nodes can change
+    fn is_member(&self, r: Replica) -> bool {
+        if self.reconfigurable {
+            self.net_map.contains_key(&r)
+        } else {
+            is_valid_replica(r, self.num_nodes)
+        }
+    }
+
     pub fn new() -> Node {
         Node{
             block_size: 0,
             client_port: 0,
             crypto_alg: Algorithm::ED25519,
+            hash_alg: HashAlgorithm::SHA256,
             delta: 50,
             id: 0,
             net_map: HashMap::default(),
             num_faults: 0,
+            weights: HashMap::default(),
             num_nodes: 0,
             pk_map: HashMap::default(),
             secret_key_bytes: Vec::new(),
+            leader_election: LeaderElection::Deterministic,
+            vrf_pk_map: HashMap::default(),
+            vrf_secret_key: Vec::new(),
             payload: 0,
+            verify_workers: 0,
+            strict_delta: false,
+            byzantine: Byzantine::default(),
+            reconfigurable: false,
+            state_dir: None,
             my_cert: Vec::new(),
             root_cert:Vec::new(),
             my_cert_key: Vec::new(),
+            keystore: None,
+        }
+    }
+
+    /// Use the hash function of this chain for every hash computed in this
+    /// process. Call this before creating any block or transaction; it fails
+    /// if the process already uses another hash function.
+    pub fn use_hash_algorithm(&self) -> Result<(), ParseError> {
+        crypto::hash::set_hash_algorithm(self.hash_alg)
+            .map_err(|current| ParseError::ConflictingHashAlgorithm(self.hash_alg, current))
+    }
+
+    /// Decrypt the keystore of this node, if any, with the password in
+    /// `KEYSTORE_PASSWORD_ENV` and fill in the secret keys.
+    ///
+    /// `config_dir` is the directory of the config file, against which a
+    /// relative keystore path is resolved.
+    pub fn unlock_keystore(&mut self, config_dir: &Path) -> Result<(), KeystoreError> {
+        let path = match &self.keystore {
+            None => return Ok(()),
+            Some(p) => config_dir.join(p),
+        };
+        let ks = Keystore::from_file(path)?;
+        if ks.id != self.id || ks.crypto_alg != self.crypto_alg {
+            return Err(KeystoreError::Format(format!(
+                "keystore is for node {} ({:?}), not node {} ({:?})",
+                ks.id, ks.crypto_alg, self.id, self.crypto_alg)));
         }
+        let mut password = password_from_env(KEYSTORE_PASSWORD_ENV)?;
+        let secrets = ks.open(&password);
+        password.zeroize();
+        let secrets = secrets?;
+        self.secret_key_bytes = secrets.secret_key_bytes.clone();
+        self.vrf_secret_key = secrets.vrf_secret_key.clone();
+        self.my_cert_key = secrets.my_cert_key.clone();
+        Ok(())
+    }
+
+    /// Move the secret keys of this node into a keystore encrypted with
+    /// `password`, leaving them empty in the config. The caller must write
+    /// the keystore to `keystore_path`, relative to the config file.
+    pub fn seal_keystore(&mut self, keystore_path: String, password: &[u8]) -> Result<Keystore, KeystoreError> {
+        let secrets = Secrets {
+            secret_key_bytes: self.secret_key_bytes.clone(),
+            vrf_secret_key: self.vrf_secret_key.clone(),
+            my_cert_key: self.my_cert_key.clone(),
+        };
+        let ks = Keystore::seal(self.id, self.crypto_alg.clone(), &secrets, password)?;
+        self.secret_key_bytes.zeroize();
+        self.vrf_secret_key.zeroize();
+        self.my_cert_key.zeroize();
+        self.keystore = Some(keystore_path);
+        Ok(ks)
     }
 
     pub fn from_json(filename:String) -> Node {
@@ -178,4 +380,29 @@ impl Node {
     pub fn client_ip(&self) -> String {
         format!("0.0.0.0:{}", self.client_port)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bls_keys_must_be_points() {
+        let mut config = Node::new();
+        config.crypto_alg = Algorithm::BLS;
+        config.num_nodes = 4;
+        config.num_faults = 1;
+        for i in 0..4 {
+            config.net_map.insert(i, format!("127.0.0.1:{}", 7000+i));
+            let kp = crypto::bls::Keypair::generate();
+            config.pk_map.insert(i, kp.public().encode().to_vec());
+            if i == 0 {
+                config.secret_key_bytes = kp.encode().to_vec();
+            }
+        }
+        assert!(config.validate().is_ok());
+
+        // The right size, but not a key
+        config.pk_map.insert(2, vec![0xff; crypto::BLS_PK_SIZE]);
+        assert!(matches!(config.validate(), Err(ParseError::InvalidMapEntry(2))));
+    }
+}
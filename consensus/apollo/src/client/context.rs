@@ -1,8 +1,10 @@
 use std::time::SystemTime;
 use fnv::FnvHashMap as HashMap;
 
-use types::apollo::{GENESIS_BLOCK, Propose, Round, Storage};
-use crypto::hash::Hash;
+use types::apollo::{CertType, GENESIS_BLOCK, Propose, Replica, Round, Storage};
+use crypto::hash::{EMPTY_HASH, Hash};
+use config::Client;
+use consensus::{EvidenceChecker, Reputation, VrfBeacon};
 use std::sync::Arc;
 
 
@@ -12,24 +14,134 @@ pub struct Context {
     pub time_map: HashMap<Hash, SystemTime>,
     pub latency_map: HashMap<Hash, (SystemTime, SystemTime)>,
     pub storage: Storage,
+    /// The highest round we accepted a proposal in
     pub round: Round,
-    pub future_msgs: HashMap<Round, Propose>,
+    /// The proposals we accepted, on any fork: Map of block hash to its
+    /// proposal
+    pub props: HashMap<Hash, Arc<Propose>>,
+    /// The proposals waiting for their parent: Map of the parent hash to them
+    pub waiting: HashMap<Hash, Vec<Propose>>,
+    /// The last block we committed
+    pub last_committed: Arc<Propose>,
+    /// The seeds for VRF leader election, if enabled
+    pub beacon: Option<VrfBeacon>,
+    /// The VRF seed that follows every proposal we accepted, if VRF leader
+    /// election is enabled
+    pub seeds: HashMap<Hash, Hash>,
+    /// The leader schedule from the history of the chain, if reputation-based
+    /// leader election is enabled
+    pub reputation: Option<Reputation>,
+    /// The proofs of misbehaviour in the blocks we accepted
+    pub evidence: EvidenceChecker,
+    num_nodes: usize,
+    num_faults: usize,
 }
 
 impl Context {
-    pub fn new() -> Self {
+    pub fn new(config: &Client) -> Self {
         let genesis_arc = Arc::new(GENESIS_BLOCK);
+        let mut genesis = Propose::new(GENESIS_BLOCK.hash);
+        genesis.block = Some(genesis_arc.clone());
+        let genesis = Arc::new(genesis);
         let mut cx = Context {
             pending: 0,
             num_cmds: 0,
             time_map: HashMap::default(),
             latency_map: HashMap::default(),
             storage: Storage::new(100_000),
-            round:1,
-            future_msgs: HashMap::default(),
+            round: 0,
+            props: HashMap::default(),
+            waiting: HashMap::default(),
+            last_committed: genesis.clone(),
+            beacon: VrfBeacon::from_client(config),
+            seeds: HashMap::default(),
+            reputation: Reputation::from_client(config),
+            evidence: EvidenceChecker::from_client(config),
+            num_nodes: config.num_nodes,
+            num_faults: config.num_faults,
         };
         cx.storage.add_delivered_block(genesis_arc.clone());
         cx.storage.add_committed_block(genesis_arc);
+        cx.seeds.insert(genesis.block_hash, EMPTY_HASH);
+        cx.props.insert(genesis.block_hash, genesis);
         cx
     }
-}
\ No newline at end of file
+
+    /// The accepted proposal of the parent of `p`
+    pub fn parent_of(&self, p: &Propose) -> Option<Arc<Propose>> {
+        let prev = p.block.as_ref()?.header.prev;
+        if prev == p.block_hash {
+            // Only the genesis block is its own parent
+            return None;
+        }
+        self.props.get(&prev).cloned()
+    }
+
+    /// The rounds and the leaders of the accepted proposals in the chain that
+    /// ends with `tip`, the latest first
+    fn chain_leaders(&self, tip: &Propose) -> impl Iterator<Item = (Round, Replica)> + '_ {
+        let tip = self.props.get(&tip.block_hash).cloned();
+        std::iter::successors(tip, move |p| self.parent_of(p))
+            .filter(|p| p.round > 0)
+            .map(|p| (p.round, p.sig.origin))
+    }
+
+    /// Check that the proposal `p` comes from the legitimate leader of its
+    /// round on top of `parent`, and find the seed that follows it.
+    ///
+    /// The blame certificates are checked by the nodes, so we only check that
+    /// the skipped rounds have one.
+    pub fn check_leader(&mut self, p: &Propose, parent: &Propose) -> bool {
+        let block = p.block.as_ref().unwrap();
+        if p.round <= parent.round {
+            return false;
+        }
+        let cert_rounds: Vec<_> = block.header.blame_certificates.iter()
+            .filter_map(|c| match c.msg {
+                CertType::Blame(_, r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        if (parent.round+1..p.round).any(|r| !cert_rounds.contains(&r)) {
+            return false;
+        }
+        let after_quit = cert_rounds.contains(&(p.round-1));
+        let parent_seed = self.seeds.get(&parent.block_hash).copied()
+            .unwrap_or(EMPTY_HASH);
+        let default = (p.round-1) % self.num_nodes;
+        let leader = match (&self.beacon, &self.reputation) {
+            _ if after_quit => default,
+            (Some(_), _) => VrfBeacon::pick_at(&parent_seed, self.num_nodes),
+            (None, Some(rep)) => rep.leader(p.round, default, self.chain_leaders(parent)),
+            (None, None) => default,
+        };
+        if p.sig.origin != leader {
+            return false;
+        }
+        if let Some(b) = &self.beacon {
+            let seed = p.vrf.as_ref()
+                .and_then(|proof| b.verify_at(&parent_seed, leader, p.round, proof));
+            match seed {
+                None => return false,
+                Some(seed) => {
+                    self.seeds.insert(p.block_hash, seed);
+                }
+            }
+        }
+        true
+    }
+
+    /// Find the proposal committed by the accepted proposal `p`: the one f
+    /// rounds before it, if there is a proposal in every round in between
+    pub fn commit_of(&self, p: &Arc<Propose>) -> Option<Arc<Propose>> {
+        let mut commit = p.clone();
+        for _ in 0..self.num_faults {
+            let parent = self.parent_of(&commit)?;
+            if parent.round+1 != commit.round {
+                return None;
+            }
+            commit = parent;
+        }
+        Some(commit)
+    }
+}
@@ -36,7 +36,7 @@ pub async fn start(
             }
         }
     });
-    let mut cx = Context::new();
+    let mut cx = Context::new(c);
     cx.pending = window;
     // =============
     // Statistics
@@ -63,15 +63,7 @@ pub async fn start(
                 ClientMsg::NewBlock(p,_pl) => p,
                 _ => continue,
             };
-            update_props(prop, &mut cx);
-            while let Some(p) = cx.future_msgs.remove(&cx.round) {
-                let b = p.block.clone().unwrap();
-                if !cx.storage.is_delivered_by_hash(&b.header.prev) {
-                    panic!("Got an undelivered block");
-                }
-                cx.storage.add_delivered_block(b);
-                cx.round += 1;
-            }
+            handle_new_block(prop, &mut cx, SystemTime::now());
         }
         log::info!("Finally at round {}", cx.round);
         (net_recv, cx)
@@ -111,11 +103,10 @@ pub async fn start(
                     ClientMsg::NewBlock(p, _pl) => p,
                     _ => continue,
                 };
-                update_props(prop, &mut cx);
+                handle_new_block(prop, &mut cx, now);
                 while let Ok(Some((_, ClientMsg::NewBlock(p,_)))) = net_recv.try_next() {
-                    update_props(p, &mut cx);
+                    handle_new_block(p, &mut cx, now);
                 }
-                handle_new_blocks(c, &mut cx, now);
             } 
         }
         if cx.num_cmds > m as u128 {
@@ -126,39 +117,69 @@ pub async fn start(
     }
 }
 
-fn update_props(p: Propose, cx:&mut Context) {
-    if p.round < cx.round {
-        if cx.storage.is_delivered_by_hash(&p.block_hash) {
-            log::warn!("Got a block {} from the past - {}", p.round, cx.round);
-            return;
-        } else {
-            // Someone equivocated.
-            panic!("equivocation detected");
+/// Accept the proposal `p` and the proposals waiting for it, and commit the
+/// blocks that they commit
+fn handle_new_block(p: Propose, cx: &mut Context, now: SystemTime) {
+    let mut ready = vec![p];
+    while let Some(p) = ready.pop() {
+        if cx.props.contains_key(&p.block_hash) {
+            log::debug!("Got a block {} from the past - {}", p.round, cx.round);
+            continue;
         }
-    }
-    cx.future_msgs.insert(p.round, p);
-}
-
-// Handle future blocks
-fn handle_new_blocks(c: &Client, cx: &mut Context, now: SystemTime) {
-    while let Some(p) = cx.future_msgs.remove(&cx.round) {
         let b = p.block.clone().unwrap();
+        let parent = match cx.props.get(&b.header.prev) {
+            None => {
+                cx.waiting.entry(b.header.prev).or_default().push(p);
+                continue;
+            }
+            Some(x) => x.clone(),
+        };
+        if !cx.check_leader(&p, &parent) {
+            log::warn!("Got a block from an illegitimate leader in round {}", p.round);
+            continue;
+        }
+        if !cx.evidence.check(&b.header.evidence) {
+            log::warn!("Got a block with invalid evidence in round {}", p.round);
+            continue;
+        }
         cx.storage.add_delivered_block(b.clone());
-        if !cx.storage.is_delivered_by_hash(&b.header.prev) {
-            panic!("Do not have parent for this block {:?}, yet",b);
+        let p = Arc::new(p);
+        cx.props.insert(p.block_hash, p.clone());
+        cx.round = std::cmp::max(cx.round, p.round);
+        if let Some(children) = cx.waiting.remove(&p.block_hash) {
+            ready.extend(children);
         }
-        cx.pending += c.block_size;
-        if cx.round <= c.num_faults {
-            cx.round += 1;
-            return;
+        log::debug!("Adding block ht:{} in round {}", b.header.height, p.round);
+        if let Some(commit) = cx.commit_of(&p) {
+            do_commit(commit, cx, now);
         }
-        log::debug!("Adding block ht:{} in round {}", b.header.height, cx.round);
-        let commit_round = cx.round - c.num_faults;
-        let commit_block = cx.storage.delivered_block_from_ht(commit_round)
-            .expect(format!("Must be in the height map:cxr: {}, cmr:{}", cx.round, commit_round).as_str());
-        
-        // Use f+1 rule to commit the block
-        cx.num_cmds += c.block_size as u128;
+    }
+}
+
+/// Commit `commit` and all its ancestors that are not committed yet
+fn do_commit(commit: Arc<Propose>, cx: &mut Context, now: SystemTime) {
+    let height = |p: &Propose| p.block.as_ref().unwrap().header.height;
+    let mut new_commits = Vec::new();
+    let mut p = commit.clone();
+    while height(&p) > height(&cx.last_committed) {
+        new_commits.push(p.clone());
+        p = cx.parent_of(&p).unwrap();
+    }
+    if p.block_hash != cx.last_committed.block_hash {
+        if !new_commits.is_empty() {
+            log::warn!("Got a commit for round {} that conflicts with our chain", commit.round);
+        }
+        return;
+    }
+    cx.last_committed = commit;
+
+    // Use f+1 rule to commit the blocks
+    for p in new_commits.into_iter().rev() {
+        let commit_block = p.block.clone().unwrap();
+        cx.storage.add_committed_block(commit_block.clone());
+        // Blocks may not be full when the leaders run out of transactions
+        cx.pending += commit_block.body.tx_hashes.len();
+        cx.num_cmds += commit_block.body.tx_hashes.len() as u128;
         for t in &commit_block.body.tx_hashes {
             if let Some(old) = cx.time_map.get(t) {
                 cx.latency_map.insert(t.clone(), (old.clone(), now));
@@ -167,6 +188,5 @@ fn handle_new_blocks(c: &Client, cx: &mut Context, now: SystemTime) {
                 cx.num_cmds -= 1;
             }
         }
-        cx.round += 1;
     }
-}
\ No newline at end of file
+}
@@ -2,4 +2,7 @@
 
 pub mod client;
 pub mod node;
-pub mod normal_client;
\ No newline at end of file
+pub mod normal_client;
+
+mod protocol;
+pub use protocol::*;
\ No newline at end of file
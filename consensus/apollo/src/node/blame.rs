@@ -1,7 +1,123 @@
-use types::apollo::Vote;
+use types::{CertKind, Threshold, apollo::{CertType, Certificate, Evidence, ProtocolMsg, Replica, Round, Vote}};
+use super::{context::Context, phase::{Event, Phase}};
+use std::sync::Arc;
 
-use super::context::Context;
+/// Called when the round `r` has not seen a proposal in time
+pub async fn on_timeout(r: Round, cx: &mut Context) {
+    if r != cx.round() || cx.blamed >= r || cx.phase == Phase::Quit {
+        return;
+    }
+    if !cx.expect_proposal || !cx.has_work() {
+        // The leader had nothing to propose for a whole timeout, which is not
+        // its fault
+        cx.start_timer(std::time::Duration::ZERO);
+        return;
+    }
+    do_blame(cx).await;
+}
 
-pub async fn on_receive_blame(v: Vote, _bc: &mut Context) {
-    panic!("Received a blame message: {:?}", v);
-}
\ No newline at end of file
+/// Blame the leader of the current round, for not proposing in time or for
+/// equivocating
+pub async fn do_blame(cx: &mut Context) {
+    let r = cx.round();
+    if cx.blamed >= r {
+        return;
+    }
+    cx.blamed = r;
+    log::info!("Blaming the leader {} of round {}", cx.round_leader(), r);
+    let msg = CertType::Blame(cx.round_leader(), r);
+    let auth = cx.my_secret_key.sign(&util::io::to_bytes(&msg))
+        .expect("Failed to sign a blame");
+    let cert = Certificate {
+        msg,
+        votes: vec![Vote { origin: cx.myid(), auth }],
+        agg: None,
+    };
+    cx.multicast(Arc::new(ProtocolMsg::Blame(cert.clone()))).await;
+    on_receive_blame(cert, cx).await;
+}
+
+pub async fn on_receive_blame(c: Certificate, cx: &mut Context) {
+    let (leader, r) = match c.msg {
+        CertType::Blame(l, r) => (l, r),
+        _ => {
+            log::warn!("Blame message for {:?}", c.msg);
+            return;
+        }
+    };
+    if r < cx.round() {
+        log::debug!("Blame for an old round {}", r);
+        return;
+    }
+    let origin = match c.verify_vote(CertKind::Blame, &cx.pub_key_map) {
+        Ok(x) => x,
+        Err(e) => {
+            log::warn!("Invalid blame message: {}", e);
+            return;
+        }
+    };
+    let votes = cx.blame_map.entry((leader, r)).or_default();
+    votes.insert(origin, c.votes[0].clone());
+    let blamers: Vec<Replica> = votes.keys().cloned().collect();
+    // Promote it to a certificate if it has f+1 blames
+    if !cx.quorum().is_met(Threshold::Honest, blamers) {
+        return;
+    }
+    let votes = cx.blame_map.remove(&(leader, r)).unwrap();
+    let cert = Certificate {
+        msg: c.msg,
+        votes: votes.into_values().collect(),
+        agg: None,
+    };
+    on_quit_round(Arc::new(cert), cx).await;
+}
+
+pub async fn on_receive_quit(c: Certificate, cx: &mut Context) {
+    match cx.check_blame_cert(&c) {
+        None => log::warn!("Invalid blame certificate {:?}", c.msg),
+        Some(r) if r < cx.round() => log::debug!("Already past round {}", r),
+        Some(_) => on_quit_round(Arc::new(c), cx).await,
+    }
+}
+
+/// Quit the round blamed by the certificate `c`
+///
+/// We forward the certificate so that everyone quits within \Delta, and wait
+/// 2\Delta for the proposals still in flight before sending our status to the
+/// leader of the next round (see `on_status_event`).
+pub async fn on_quit_round(c: Arc<Certificate>, cx: &mut Context) {
+    let r = match c.msg {
+        CertType::Blame(_, r) => r,
+        _ => unreachable!("only blame certificates are quit certificates"),
+    };
+    log::info!("Quitting round {} with the blames of {:?}", r,
+        c.votes.iter().map(|v| v.origin).collect::<Vec<_>>());
+    cx.multicast(Arc::new(ProtocolMsg::QuitView(c.as_ref().clone()))).await;
+
+    cx.quit_certs.insert(r, c);
+    cx.blame_map.retain(|(_, br), _| *br > r);
+    cx.statuses.clear();
+    cx.phase = Phase::Quit;
+    cx.set_round(r+1, true);
+    let wait = cx.deltas(2);
+    cx.events.insert(Event::Status(r+1), wait);
+}
+
+/// Store a checked proof of misbehaviour, and forward it to the others if it
+/// is the first one against its offender
+pub async fn on_evidence(e: Evidence, cx: &mut Context) {
+    if cx.evidence.add(e.clone()) {
+        cx.multicast(Arc::new(ProtocolMsg::Evidence(e))).await;
+    }
+}
+
+pub async fn on_receive_evidence(e: Evidence, cx: &mut Context) {
+    if cx.evidence.is_faulty(e.offender()) {
+        return;
+    }
+    if let Err(err) = e.verify(&cx.pub_key_map) {
+        log::warn!("Invalid evidence against {}: {}", e.offender(), err);
+        return;
+    }
+    on_evidence(e, cx).await;
+}
@@ -1,20 +1,43 @@
+use types::{CommittedBlock, apollo::Propose};
 use super::context::Context;
+use std::sync::Arc;
 
-pub async fn do_commit(cx: &mut Context) {
+/// Commit the chain up to the proposal f rounds before `p`, if there is a
+/// proposal in every round in between
+pub async fn do_commit(p: &Arc<Propose>, cx: &mut Context) {
     log::debug!("Trying to commit blocks");
 
+    let mut commit = p.clone();
+    for _ in 0..cx.num_faults() {
+        match cx.parent_of(&commit) {
+            Some(parent) if parent.round+1 == commit.round => commit = parent,
+            _ => return,
+        }
+    }
+
     // Add all parents if not committed already
-    let commit_round = cx.round() - cx.num_faults();
-    let p = cx.prop_chain_by_round.get(&commit_round).unwrap();
-    let mut hash = p.block_hash;
-    while !cx.storage.is_committed_by_hash(&hash) {
-        let b_rc = cx.storage.delivered_block_from_hash(&hash).unwrap();
-        cx.storage.add_committed_block(b_rc.clone());
-        hash = b_rc.header.prev;
+    let mut new_commits = Vec::new();
+    while !cx.storage.is_committed_by_hash(&commit.block_hash) {
+        cx.storage.add_committed_block(commit.block.clone().unwrap());
+        cx.evidence.on_included(&commit.block.as_ref().unwrap().header.evidence);
+        let parent = cx.parent_of(&commit);
+        new_commits.push(commit);
+        commit = match parent {
+            None => break,
+            Some(x) => x,
+        };
     }
 
-    if !cx.is_client_apollo_enabled() {
-        let p = p.clone();
-        cx.multicast_client(p).await;
+    for p in new_commits.into_iter().rev() {
+        let block = p.block.clone().unwrap();
+        let txs = cx.storage.take_committed(&block.body.tx_hashes);
+        cx.commit_sink.publish(CommittedBlock{
+            block,
+            txs,
+            justification: None,
+        });
+        if !cx.is_client_apollo_enabled() {
+            cx.multicast_client(p).await;
+        }
     }
-}
\ No newline at end of file
+}
@@ -43,7 +43,17 @@ impl Context {
 
     /// Multicast (Sendall) message to all the clients
     pub(crate) async fn multicast_client(&mut self, msg: Arc<Propose>) {
-        if let Err(e) = self.cli_send.send(msg)
+        if let Err(e) = self.cli_send.send((msg, true))
+            .await {
+            log::warn!(
+                "Server channel closed with error: {}", e);
+        };
+    }
+
+    /// Multicast a proposal that the clients may have missed, without the
+    /// payload that they already got with it
+    pub(crate) async fn resend_client(&mut self, msg: Arc<Propose>) {
+        if let Err(e) = self.cli_send.send((msg, false))
             .await {
             log::warn!(
                 "Server channel closed with error: {}", e);
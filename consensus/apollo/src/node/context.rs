@@ -1,11 +1,16 @@
 use std::collections::VecDeque;
 use fnv::FnvHashMap as HashMap;
-use crypto::hash::Hash;
-use crypto::{Keypair, PublicKey, ed25519, secp256k1};
+use crypto::hash::{EMPTY_HASH, Hash};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
 use futures::channel::mpsc::UnboundedSender;
-use types::apollo::{Block, GENESIS_BLOCK, Propose, ProtocolMsg, Replica, Storage, Round};
+use tokio_util::time::DelayQueue;
+use types::apollo::{CertType, Certificate, GENESIS_BLOCK, Propose, ProtocolMsg, Replica, Storage, Round, Vote};
+use types::{CertKind, FaultModel, QuorumPolicy, Threshold};
 use config::Node;
+use consensus::{CommitSink, EvidencePool, Reputation, VrfBeacon, Wal};
 use std::sync::Arc;
+use std::time::Duration;
+use super::phase::{Event, Phase};
 
 pub struct Context {
     /// Config context
@@ -13,8 +18,14 @@ pub struct Context {
     num_nodes: usize,
     /// The number of faults in the system
     num_faults: usize,
+    /// The votes that the certificates need
+    quorum: QuorumPolicy,
     /// My ID
     myid: Replica,
+    /// The number of transactions in a block
+    block_size: usize,
+    /// The synchronous bound on the message delays, in milliseconds
+    delta: u64,
     /// Everyone's public keys
     pub pub_key_map:HashMap<Replica, PublicKey>,
     /// My key 
@@ -24,32 +35,68 @@ pub struct Context {
 
     /// Network context
     pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
-    pub cli_send: UnboundedSender<Arc<Propose>>,
+    /// Proposals for the clients, and whether they need the payload
+    pub cli_send: UnboundedSender<(Arc<Propose>, bool)>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
 
     // Reordering context
     pub prop_buf: VecDeque<(Replica, Propose)>,
     pub relay_buf: VecDeque<(Replica, Propose)>,
     pub other_buf: VecDeque<(Replica, ProtocolMsg)>,
-    pub future_msgs: HashMap<Round, (Replica, Propose)>,
+    /// Checked proposals that we could not accept yet, by their round
+    pub future_msgs: HashMap<Round, Arc<Propose>>,
 
     /// Storage context
     /// Where the blockchain and transactions are stored
     pub storage: Storage,
-    /// The chain of proposals: Map of block hash to its proposal
+    /// The first proposal we checked in every round, to detect equivocations
     pub prop_chain_by_round: HashMap<Round, Arc<Propose>>,
+    /// All the proposals we checked, on any fork: Map of block hash to its
+    /// proposal
     pub prop_chain_by_hash: HashMap<Hash, Arc<Propose>>,
+    /// The proposal at the end of the chain we extend
+    pub tip: Arc<Propose>,
 
     /// Round state
     round: Round,
     round_leader: Replica,
+    /// Whether the current round follows a quit round
+    after_quit: bool,
+    /// The seeds for VRF leader election, if enabled
+    pub beacon: Option<VrfBeacon>,
+    /// The VRF seed that follows every proposal we checked, if VRF leader
+    /// election is enabled
+    pub seeds: HashMap<Hash, Hash>,
+    /// The leader schedule from the history of the chain, if reputation-based
+    /// leader election is enabled
+    pub reputation: Option<Reputation>,
 
     // Protocol state
-    pub last_seen_block: Arc<Block>,
     /// The blocks we are waiting for, to handle propose messages
     pub prop_waiting: HashMap<Hash, Propose>,
     /// The blocks we are waiting for to handle the propose message
     pub prop_waiting_parent: HashMap<Hash, Propose>,
     pub req_ctr:u64,
+
+    // Blame and view change state
+    pub phase: Phase,
+    /// Timers for the rounds and the view changes
+    pub events: DelayQueue<Event>,
+    /// The blames we have for the leader of a round
+    pub blame_map: HashMap<(Replica, Round), HashMap<Replica, Vote>>,
+    /// The last round we blamed
+    pub blamed: Round,
+    /// Whether the leader had something to propose when we started the timer
+    pub expect_proposal: bool,
+    /// The blame certificates of the rounds we quit
+    pub quit_certs: HashMap<Round, Arc<Certificate>>,
+    /// The tips of the others, when we are the leader after a quit round
+    pub statuses: Vec<Hash>,
+    /// The proofs of misbehaviour that we heard of
+    pub evidence: EvidencePool,
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
 }
 
 const EXTRA_SPACE:usize = 100;
@@ -57,13 +104,15 @@ const EXTRA_SPACE:usize = 100;
 impl Context {
     pub fn new(config:&Node,
         net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
-        cli_send: UnboundedSender<Arc<Propose>>,
+        cli_send: UnboundedSender<(Arc<Propose>, bool)>,
+        commit_sink: CommitSink,
         is_apollo_enabled: bool,
     ) -> Self {
         let mut c = Context{
             num_nodes: config.num_nodes,
             relay_buf: VecDeque::new(),
             num_faults: config.num_faults,
+            quorum: config.quorum_policy(FaultModel::Synchronous),
             myid: config.id,
             my_secret_key: match config.crypto_alg {
                 crypto::Algorithm::ED25519 => {
@@ -79,16 +128,36 @@ impl Context {
                     let kp = secp256k1::Keypair::from(sk);
                     Arc::new(Keypair::Secp256k1(kp))
                 }
-                _ => panic!("Unimplemented algorithm"),
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Arc::new(Keypair::Bls(kp))
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Arc::new(Keypair::Rsa(kp))
+                }
             },
+            block_size: config.block_size,
+            delta: config.delta,
             pub_key_map: HashMap::default(),
             net_send,
             cli_send,
+            commit_sink,
             storage: Storage::new(EXTRA_SPACE*config.block_size),
             round_leader: 0,
             round: 1,
+            after_quit: false,
+            beacon: VrfBeacon::from_node(config),
+            seeds: HashMap::default(),
+            reputation: Reputation::from_node(config),
             future_msgs: HashMap::default(),
-            last_seen_block: Arc::new(GENESIS_BLOCK),
+            tip: Arc::new(genesis_proposal()),
             is_client_apollo_enabled: is_apollo_enabled,
             req_ctr:0,
             prop_waiting:HashMap::default(),
@@ -97,30 +166,29 @@ impl Context {
             prop_chain_by_round: HashMap::default(),
             prop_buf: VecDeque::new(),
             other_buf: VecDeque::new(),
+            phase: Phase::Normal,
+            events: DelayQueue::new(),
+            blame_map: HashMap::default(),
+            blamed: 0,
+            expect_proposal: false,
+            quit_certs: HashMap::default(),
+            statuses: Vec::new(),
+            evidence: EvidencePool::new(),
+            wal: Wal::from_node(config, "apollo"),
         };
-        for (id,mut pk_data) in &config.pk_map {
-            if *id == c.myid {
-                continue;
-            }
-            let pk = match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let kp = ed25519::PublicKey::decode(
-                        &mut pk_data
-                    ).expect("Failed to decode the secret key from the config");
-                    PublicKey::Ed25519(kp)
-                },
-                crypto::Algorithm::SECP256K1 => {
-                    let sk = secp256k1::PublicKey::decode(&pk_data).expect("Failed to decode the secret key from the config");
-                    PublicKey::Secp256k1(sk)
-                }
-                _ => panic!("Unimplemented algorithm"),
-            };
+        // Our own key is needed to check the certificates with our votes
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
             c.pub_key_map.insert(*id, pk);
         }
         // Initialize storage
-        c.storage.add_delivered_block(
-            c.last_seen_block.clone()
-        );
+        let genesis = c.tip.clone();
+        c.storage.add_delivered_block(genesis.block.clone().unwrap());
+        c.storage.add_committed_block(genesis.block.clone().unwrap());
+        c.seeds.insert(genesis.block_hash, EMPTY_HASH);
+        c.prop_chain_by_hash.insert(genesis.block_hash, genesis);
+        c.set_round(1, false);
         c
     }
 
@@ -134,11 +202,21 @@ impl Context {
         self.num_faults
     }
 
+    #[inline]
+    pub(crate) fn quorum(&self) -> &QuorumPolicy {
+        &self.quorum
+    }
+
     #[inline]
     pub(crate) fn myid(&self) -> Replica {
         self.myid
     }
 
+    #[inline]
+    pub(crate) fn block_size(&self) -> usize {
+        self.block_size
+    }
+
     #[inline]
     pub(crate) fn round(&self) -> Replica {
         self.round
@@ -154,20 +232,156 @@ impl Context {
         self.round_leader
     }
 
-    pub(crate) fn update_round(&mut self) {
-        self.round_leader = self.next_leader();
-        self.round += 1;
+    #[inline]
+    pub(crate) fn is_after_quit(&self) -> bool {
+        self.after_quit
     }
 
-    pub(crate) fn next_leader(&self) -> Replica {
-        self.next_of(self.round_leader)
+    /// `delta` times `n` as a duration
+    pub(crate) fn deltas(&self, n: u64) -> Duration {
+        Duration::from_millis(n*self.delta)
     }
 
-    pub(crate) fn next_of(&self, prev: Replica) -> Replica {
-        if prev+1 == self.num_nodes {
-            0 as Replica
+    /// Move to `round`, and find its leader.
+    ///
+    /// The round after a quit round is always led by the next node in turn,
+    /// even with VRF leader election, so that everyone knows where to send the
+    /// status without agreeing on the chain first.
+    pub(crate) fn set_round(&mut self, round: Round, after_quit: bool) {
+        self.round = round;
+        self.after_quit = after_quit;
+        self.round_leader = if after_quit {
+            (round-1) % self.num_nodes
         } else {
-            prev+1
+            let tip = self.tip.clone();
+            self.leader_after(&tip, round, false)
+        };
+    }
+
+    /// The leader of `round` in a chain that extends `parent`
+    pub(crate) fn leader_after(&self, parent: &Propose, round: Round, after_quit: bool) -> Replica {
+        let default = (round-1) % self.num_nodes;
+        match (&self.beacon, &self.reputation) {
+            _ if after_quit => default,
+            (Some(_), _) => VrfBeacon::pick_at(&self.seed_after(parent), self.num_nodes),
+            (None, Some(rep)) => rep.leader(round, default, self.chain_leaders(parent)),
+            (None, None) => default,
         }
     }
+
+    /// The rounds and the leaders of the checked proposals in the chain that
+    /// ends with `tip`, the latest first
+    fn chain_leaders(&self, tip: &Propose) -> impl Iterator<Item = (Round, Replica)> + '_ {
+        let tip = self.prop_chain_by_hash.get(&tip.block_hash).cloned();
+        std::iter::successors(tip, move |p| self.parent_of(p))
+            .filter(|p| p.round > 0)
+            .map(|p| (p.round, p.sig.origin))
+    }
+
+    /// The VRF seed that follows the checked proposal `p`
+    pub(crate) fn seed_after(&self, p: &Propose) -> Hash {
+        self.seeds.get(&p.block_hash).copied().unwrap_or(EMPTY_HASH)
+    }
+
+    /// The checked proposal of the parent of `p`
+    pub(crate) fn parent_of(&self, p: &Propose) -> Option<Arc<Propose>> {
+        let prev = p.block.as_ref()?.header.prev;
+        if prev == p.block_hash {
+            // Only the genesis block is its own parent
+            return None;
+        }
+        self.prop_chain_by_hash.get(&prev).cloned()
+    }
+
+    /// Whether we can move from our tip to `to`, a proposal of the same round
+    /// on another fork.
+    ///
+    /// This is only safe when both forks have proposals in the same rounds
+    /// since they split, which means that the same leaders equivocated in all
+    /// of them, and we have not committed anything on our fork.
+    pub(crate) fn can_switch(&self, to: &Arc<Propose>) -> bool {
+        let mut from = self.tip.clone();
+        let mut to = to.clone();
+        while from.block_hash != to.block_hash {
+            if from.round != to.round || 
+                self.storage.is_committed_by_hash(&from.block_hash) 
+            {
+                return false;
+            }
+            match (self.parent_of(&from), self.parent_of(&to)) {
+                (Some(f), Some(t)) if f.round+1 == from.round && t.round+1 == to.round => {
+                    from = f;
+                    to = t;
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Whether we have the blame certificates to propose in the current round
+    /// on top of `parent`, which are needed for all the rounds in between
+    pub(crate) fn can_justify(&self, parent: &Propose) -> bool {
+        let first = std::cmp::min(parent.round+1, self.round-1);
+        (first..self.round).all(|r| self.quit_certs.contains_key(&r))
+    }
+
+    /// Check a blame certificate, and return the round it blames if it is
+    /// valid
+    pub(crate) fn check_blame_cert(&self, c: &Certificate) -> Option<Round> {
+        let round = match c.msg {
+            CertType::Blame(_, r) => r,
+            _ => return None,
+        };
+        if let Err(e) = c.verify(CertKind::Blame, &self.pub_key_map, &self.quorum, Threshold::Honest) {
+            log::warn!("Invalid blame certificate for round {}: {}", round, e);
+            return None;
+        }
+        Some(round)
+    }
+
+    /// Whether the leader of the round has something to propose
+    pub(crate) fn has_work(&self) -> bool {
+        self.storage.get_tx_pool_size() >= self.block_size || self.has_pending_commits()
+    }
+
+    /// Whether some transactions on our chain still wait for proposals to
+    /// commit them
+    pub(crate) fn has_pending_commits(&self) -> bool {
+        let mut p = self.tip.clone();
+        while !self.storage.is_committed_by_hash(&p.block_hash) {
+            if !p.block.as_ref().unwrap().body.tx_hashes.is_empty() {
+                return true;
+            }
+            p = match self.parent_of(&p) {
+                None => break,
+                Some(x) => x,
+            };
+        }
+        false
+    }
+
+    /// Start the timer that blames the leader of the current round, after
+    /// `extra` time on top of the usual wait
+    ///
+    /// As the leader, we also start the timer after which we propose without
+    /// a full block, if needed to commit the blocks before.
+    pub(crate) fn start_timer(&mut self, extra: Duration) {
+        // The leader relays the previous proposal and proposes within 2\Delta
+        // of us, with some slack for building the block
+        let timeout = self.deltas(4) + extra;
+        self.events.insert(Event::Timeout(self.round), timeout);
+        self.expect_proposal = self.has_work();
+        if self.round_leader == self.myid {
+            let wait = self.deltas(2) + extra;
+            self.events.insert(Event::Propose(self.round), wait);
+        }
+    }
+}
+
+/// The proposal of the genesis block, which is the parent of round 1
+fn genesis_proposal() -> Propose {
+    let mut p = Propose::new(GENESIS_BLOCK.hash);
+    p.block = Some(Arc::new(GENESIS_BLOCK));
+    p
 }
@@ -19,8 +19,17 @@ pub async fn process_message(cx:&mut Context)
             ProtocolMsg::Request(rid, h) => {
                 on_recv_request(sender, rid, h, cx).await;
             }
-            ProtocolMsg::Blame(v) => {
-                on_receive_blame(v, cx).await;
+            ProtocolMsg::Blame(c) => {
+                on_receive_blame(c, cx).await;
+            }
+            ProtocolMsg::QuitView(c) => {
+                on_receive_quit(c, cx).await;
+            }
+            ProtocolMsg::Status(r, p) => {
+                on_receive_status(sender, r, p, cx).await;
+            }
+            ProtocolMsg::Evidence(e) => {
+                on_receive_evidence(e, cx).await;
             }
             _x => {
                 debug_assert!(
@@ -33,8 +42,15 @@ pub async fn process_message(cx:&mut Context)
         };
     }
 
-    while let Some((sender, p)) = cx.future_msgs.remove(&cx.round()) {
-        delivery_check(sender, p, cx).await;
+    // Accept the proposals we checked before reaching their round
+    while let Some(p) = cx.future_msgs.remove(&cx.round()) {
+        let round = cx.round();
+        if !on_receive_proposal(p.clone(), cx).await {
+            if cx.round() == round {
+                cx.future_msgs.insert(round, p);
+            }
+            break;
+        }
     }
 }
 
@@ -49,7 +65,7 @@ pub fn handle_message(sender: Replica, message: ProtocolMsg, cx: &mut Context) {
 
 pub async fn delivery_check(sender:Replica, p: Propose, cx: &mut Context) {
     // Check if the proposals are already processed
-    if cx.prop_chain_by_round.contains_key(&p.round) {
+    if cx.prop_chain_by_hash.contains_key(&p.block_hash) {
         log::debug!("Already handled {:?} before", p);
         return;
     }
@@ -68,7 +84,7 @@ pub async fn delivery_check(sender:Replica, p: Propose, cx: &mut Context) {
 
     let parent_hash = parent_hash.unwrap();
 
-    if !cx.storage.is_delivered_by_hash(&parent_hash) {
+    if !cx.prop_chain_by_hash.contains_key(&parent_hash) {
         let msg = Arc::new(ProtocolMsg::Request(cx.req_ctr, parent_hash));
         cx.storage.add_delivered_block(p.block.clone().unwrap());
         cx.prop_waiting_parent.insert(parent_hash, p);
@@ -77,7 +93,7 @@ pub async fn delivery_check(sender:Replica, p: Propose, cx: &mut Context) {
     }
 
     // By now all parents and the current block is delivered
-    debug_assert!(cx.storage.is_delivered_by_hash(&parent_hash));
+    debug_assert!(cx.prop_chain_by_hash.contains_key(&parent_hash));
 
     // Mark this block as delivered, since all its parents are delivered
     let block = p.block.clone().unwrap();
@@ -86,20 +102,20 @@ pub async fn delivery_check(sender:Replica, p: Propose, cx: &mut Context) {
     cx.storage.add_delivered_block(block);
 
     let mut block_hash = p.block_hash;
-    if cx.round() < p.round {
-        cx.future_msgs.insert(p.round, (sender, p));
-    } else {
-        try_receive_proposal(p, sender, cx).await;
-    }
     cx.prop_waiting.remove(&block_hash);
+    if !try_receive_proposal(p, cx).await {
+        // The children of an invalid proposal are invalid too
+        while let Some(p_new) = cx.prop_waiting_parent.remove(&block_hash) {
+            block_hash = p_new.block_hash;
+        }
+        return;
+    }
 
     while let Some(mut p_new) = cx.prop_waiting_parent.remove(&block_hash) {
         block_hash = p_new.block_hash;
         p_new.block = Some(cx.storage.delivered_block_from_hash(&block_hash).unwrap());
-        if cx.round() < p_new.round {
-            cx.future_msgs.insert(p_new.round, (sender, p_new));
-        } else {
-            try_receive_proposal(p_new, sender, cx).await;
+        if !try_receive_proposal(p_new, cx).await {
+            break;
         }
     }
-}
\ No newline at end of file
+}
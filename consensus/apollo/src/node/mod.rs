@@ -26,6 +26,13 @@ pub use message::*;
 mod blame;
 pub use blame::*;
 
+// View change logic
+mod phase;
+pub use phase::*;
+
+mod status;
+pub use status::*;
+
 // Communication logic
 mod comms;
 pub use comms::*;
\ No newline at end of file
@@ -0,0 +1,26 @@
+use types::apollo::Round;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    /// Waiting for the proposal of the current round, or proposing it as the
+    /// leader
+    Normal,
+    /// We quit a round on a blame certificate, and are waiting for the
+    /// proposals still in flight before sending our status to the next leader
+    Quit,
+    /// We sent our status, and are waiting for the proposal of the next leader
+    Wait,
+    /// We are the next leader, and are collecting the status of the others
+    StatusWait,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event {
+    /// The round has not seen a proposal in time
+    Timeout(Round),
+    /// We finished waiting after quitting the round before the given round
+    Status(Round),
+    /// We, as the leader of the given round, finished collecting the status
+    /// messages, or waited too long for a full block
+    Propose(Round),
+}
@@ -1,120 +1,313 @@
-use types::apollo::{Block, Propose, ProtocolMsg, Transaction, Replica};
+use types::apollo::{Block, Evidence, Propose, ProtocolMsg, Transaction};
 use types::BlockTrait;
 use types::WireReady;
 use super::*;
+use consensus::SignKind;
 use std::sync::Arc;
 
-/// Creates a block using the last seen block as the parent
+/// Creates a block using the tip of our chain as the parent
 /// Then adds the block as delivered
 /// do_propose is called after ensuring that we have sufficient transactions and that we are the leader for this round
 pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
     // Get the parent
-    let parent = cx.last_seen_block.as_ref();
+    let parent = cx.tip.clone();
+    let parent_block = parent.block.as_ref().unwrap();
 
     // Create a block
-    let mut new_block = Block::with_tx(txs);
-    new_block.header.prev = parent.hash;
-    new_block.header.author = cx.myid(); 
-    new_block.header.height =  parent.header.height+1;
+    let mut new_block = Block::with_tx(txs.clone());
+    new_block.header.prev = parent.block_hash;
+    new_block.header.author = cx.myid();
+    new_block.header.height =  parent_block.header.height+1;
+    // Justify the rounds we skipped since the parent with their blame
+    // certificates, and the quit round before this one
+    if cx.is_after_quit() {
+        let first = std::cmp::min(parent.round+1, cx.round()-1);
+        for r in first..cx.round() {
+            let c = cx.quit_certs.get(&r)
+                .expect("Proposing without the certificates of the skipped rounds");
+            new_block.header.blame_certificates.push(c.as_ref().clone());
+        }
+    }
+    new_block.header.evidence = cx.evidence.pending();
     // Finally, compute the hash
     let new_block = new_block.init();
 
+    // Never propose two blocks in a round, even across restarts
+    let round = cx.round();
+    let may_sign = cx.wal.as_mut()
+        .map_or(true, |w| w.record((SignKind::Proposal, 0, round), &new_block.hash));
+    if !may_sign {
+        log::error!("Already proposed another block in round {}", round);
+        for tx in txs {
+            cx.storage.add_transaction(tx.as_ref().clone());
+        }
+        return;
+    }
+
     // Create a proposal
     let mut p = Propose::new(new_block.hash);
     p.round = cx.round();
     p.sig.origin = cx.myid();
+    p.vrf = cx.beacon.as_ref().map(|b| b.prove_at(&cx.seed_after(&parent), p.round));
     p.sign_block(&new_block, cx.my_secret_key.as_ref());
     p.block = Some(Arc::new(new_block.clone()));
 
     let msg = Arc::new(ProtocolMsg::RawNewProposal(p.clone(), new_block.clone()));
-    let p_arc = Arc::new(p);
     cx.multicast(msg).await;
     if cx.is_client_apollo_enabled() {
-        cx.multicast_client(p_arc.clone()).await;
+        // The clients only hear about a proposal from its leader, and miss it
+        // if the leader crashes while sending it
+        if parent.sig.origin != cx.myid() && cx.parent_of(&parent).is_some() {
+            cx.resend_client(parent.clone()).await;
+        }
+        cx.multicast_client(Arc::new(p.clone())).await;
     }
 
     // Make this block delivered
     cx.storage.add_delivered_block(Arc::new(new_block));
 
     // Self handle new propose
-    on_receive_proposal(p_arc, cx).await;
+    try_receive_proposal(p, cx).await;
 }
 
-/// Try to receive a new propose message
-pub async fn try_receive_proposal(p: Propose, from:Replica, cx:&mut Context) {
-    if cx.round() > p.round {
-        log::debug!("Got a proposal from the past");
-        return;
+/// Check a new proposal whose parent has already been checked, and accept it
+/// if it is for the current round.
+///
+/// Proposals from the past and on other forks are checked as well, since we
+/// may have to switch to their chain after a quit round.
+///
+/// Returns false if the proposal is invalid
+pub async fn try_receive_proposal(p: Propose, cx:&mut Context) -> bool {
+    let block = p.block.clone().unwrap();
+    let parent = match cx.prop_chain_by_hash.get(&block.header.prev) {
+        None => {
+            log::debug!("Checking a proposal before its parent");
+            return false;
+        }
+        Some(x) => x.clone(),
+    };
+    let parent_block = parent.block.as_ref().unwrap();
+    if p.round <= parent.round ||
+        block.header.height != parent_block.header.height+1
+    {
+        log::warn!("Proposal for round {} does not follow its parent", p.round);
+        return false;
     }
-    if cx.round() < p.round {
-        log::debug!("Got a proposal from the future");
-        cx.future_msgs.insert(p.round, (from,p));
-        return;
+
+    // Skipping rounds needs the blame certificates of all of them. The round
+    // just before is also justified when the leader comes after a quit round.
+    let mut cert_rounds = Vec::new();
+    for c in &block.header.blame_certificates {
+        match cx.check_blame_cert(c) {
+            Some(r) => cert_rounds.push(r),
+            None => {
+                log::warn!("Invalid blame certificate in the proposal for round {}", p.round);
+                return false;
+            }
+        }
     }
-    let b_hash = p.block.as_ref().map(|b| b.hash).unwrap();
-    if !cx.storage.is_delivered_by_hash(&b_hash) {
-        // I don't have the chain for this. Ask chain from the sender
-        let msg = Arc::new(ProtocolMsg::Request(cx.req_ctr, b_hash));
-        let job = cx.c_send(from, msg).await;
-        cx.prop_waiting.insert(b_hash, p);
-        job.await.unwrap();
-        return;
+    if (parent.round+1..p.round).any(|r| !cert_rounds.contains(&r)) {
+        log::warn!("Proposal for round {} skips rounds without blame certificates", p.round);
+        return false;
     }
+    let after_quit = cert_rounds.contains(&(p.round-1));
 
-    // I have the chain
-
-    let block = p.block.clone().unwrap();
-    // 1) Is it correctly signed?
-    if cx.round_leader() != p.sig.origin {
-        return;
-    }
-    if cx.round_leader() != block.get_author() {
-        return;
+    // 1) Is it from the leader of the round?
+    let leader = cx.leader_after(&parent, p.round, after_quit);
+    if p.sig.origin != leader || block.get_author() != leader {
+        log::debug!("Proposal for round {} not from the leader {}", p.round, leader);
+        return false;
     }
+    // 2) Is it correctly signed?
     if p.sig.origin != cx.myid() && !p.check_sig(block.as_ref(), &cx.pub_key_map[&p.sig.origin]) {
-        return;
+        log::warn!("Proposal for round {} not correctly signed", p.round);
+        return false;
+    }
+    // 3) Are the proofs of misbehaviour in the block correct?
+    if p.sig.origin != cx.myid() {
+        if let Some(e) = block.header.evidence.iter().find(|e| e.verify(&cx.pub_key_map).is_err()) {
+            log::warn!("Invalid evidence against {} in the proposal for round {}", e.offender(), p.round);
+            return false;
+        }
+    }
+    // 4) Is the VRF proof of the leader, which decides the next leader,
+    //    correct?
+    if let Some(b) = &cx.beacon {
+        let seed = p.vrf.as_ref()
+            .and_then(|proof| b.verify_at(&cx.seed_after(&parent), leader, p.round, proof));
+        match seed {
+            Some(seed) => {
+                cx.seeds.insert(p.block_hash, seed);
+            }
+            None => {
+                log::warn!("Invalid VRF proof in the proposal for round {}", p.round);
+                return false;
+            }
+        }
+    }
+
+    let p = Arc::new(p);
+    // Is this is an equivocation?
+    match cx.prop_chain_by_round.get(&p.round) {
+        None => {
+            cx.prop_chain_by_round.insert(p.round, p.clone());
+        }
+        Some(x) if x.sig.origin == p.sig.origin && x.block_hash != p.block_hash => {
+            log::warn!(
+                "Equivocation detected in round {} by {}: {:?}, {:?}", p.round, p.sig.origin, x.block_hash, p.block_hash);
+            on_evidence(Evidence::new(x.signed(), p.signed()), cx).await;
+        }
+        Some(_) => {}
+    }
+    for e in &block.header.evidence {
+        cx.evidence.add(e.clone());
     }
+    cx.prop_chain_by_hash.insert(p.block_hash, p.clone());
 
-    on_receive_proposal(Arc::new(p), cx).await;
+    if cx.round() < p.round {
+        log::debug!("Got a proposal from the future");
+        cx.future_msgs.insert(p.round, p);
+    } else if !on_receive_proposal(p.clone(), cx).await && cx.round() == p.round {
+        // We may accept it once we finish the quit round
+        cx.future_msgs.insert(p.round, p);
+    }
+    true
 }
 
-/// Called when a proposal is received due to:
+/// Called to accept a checked proposal when:
 /// - A block getting delivered
 /// - Receiving a proposal directly
+/// - We finish a quit round
 ///
 /// Note: This will only execute once all the block for of the proposal is delivered
-pub async fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) {
+///
+/// Returns true if we moved our tip to this proposal
+pub async fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
+    let parent = match cx.parent_of(&p) {
+        None => return false,
+        Some(x) => x,
+    };
+    let extends_tip = parent.block_hash == cx.tip.block_hash;
+    let accept = match cx.phase {
+        // Keep extending our chain with the proposals in flight, so that the
+        // next leader hears about them
+        Phase::Quit => extends_tip,
+        Phase::Normal => p.round == cx.round() &&
+            (extends_tip || (parent.round == cx.tip.round && cx.can_switch(&parent))),
+        // Only accept a chain that is at least as high as the one in our
+        // status
+        Phase::Wait | Phase::StatusWait => p.round == cx.round() &&
+            parent.round >= cx.tip.round,
+    };
+    if !accept {
+        log::debug!("Not accepting the proposal for round {} in round {}", p.round, cx.round());
+        return false;
+    }
     log::debug!("Handling valid proposal: {:?}", p);
+    if !extends_tip {
+        log::info!("Switching to the chain of round {} by {}", parent.round, parent.sig.origin);
+    }
     let block = p.block.clone().unwrap();
-    // Is this is an equivocation?
-    //
-    // We check for equivocation by checking that if we already have a block at
-    // this height. If there is one, it must be an equivocation. Because, if
-    // this was a repeated block, we must have returned from the previous check
-    // for repeated blocks.
-    if let Some(x) = cx.storage.delivered_block_from_ht(block.header.height) {
-        if (x.hash != block.hash) && x.header.author == block.header.author {
-            log::warn!(
-                "Equivocation detected: {:?}, {:?}", cx.storage.delivered_block_from_ht(block.header.height), block);
-            return;
-        }
+
+    // Remove transactions from the pool
+    cx.storage.clear(&block.body.tx_hashes);
+    let was_quit = cx.phase == Phase::Quit;
+    cx.tip = p.clone();
+    if p.round < cx.round() {
+        // A late proposal for a round we quit
+        debug_assert!(was_quit);
+        return true;
     }
+    cx.set_round(p.round+1, false);
+    cx.phase = Phase::Normal;
+    cx.start_timer(std::time::Duration::ZERO);
 
     let msg = Arc::new(ProtocolMsg::Relay(p.as_ref().clone()));
-    let job = cx.c_send(cx.next_leader(), msg).await;
+    let job = cx.c_send(cx.round_leader(), msg).await;
 
-    // Remove transactions from the pool
-    cx.storage.clear(&block.body.tx_hashes);
-    cx.prop_chain_by_hash.insert(p.block_hash, p.clone());
-    cx.prop_chain_by_round.insert(p.round, p.clone());
+    // Trigger commit rule, unless a round was blamed while it was in flight
+    if !was_quit {
+        do_commit(&p, cx).await;
+    }
+
+    job.await.expect("Concurrent relaying failed");
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Node;
+    use consensus::CommitSink;
+    use crypto::{Algorithm, ed25519};
+    use types::apollo::{CertType, Certificate, Round, Vote, GENESIS_BLOCK};
+
+    const N: usize = 4;
+
+    /// Node 3 of four, in round 1
+    fn context(keys: &[ed25519::Keypair]) -> Context {
+        let mut config = Node::new();
+        config.num_nodes = N;
+        config.num_faults = 1;
+        config.block_size = 1;
+        config.id = 3;
+        config.crypto_alg = Algorithm::ED25519;
+        config.secret_key_bytes = keys[3].encode().to_vec();
+        config.pk_map = keys.iter().enumerate()
+            .map(|(i, k)| (i, k.public().encode().to_vec()))
+            .collect();
+        let (net_send, _) = futures::channel::mpsc::unbounded();
+        let (cli_send, _) = futures::channel::mpsc::unbounded();
+        Context::new(&config, net_send, cli_send, CommitSink::new(None), false)
+    }
 
-    // Trigger commit rule
-    if cx.round() > cx.num_faults() {
-        do_commit(cx).await;
+    /// The blame certificate of the leader of `round`, signed by `f+1` nodes
+    fn blame_cert(keys: &[ed25519::Keypair], round: Round) -> Certificate {
+        let mut c = Certificate::empty_cert();
+        c.msg = CertType::Blame((round-1) % N, round);
+        for (origin, k) in keys.iter().enumerate().take(2) {
+            let auth = k.sign(&util::io::to_bytes(&c.msg));
+            c.votes.push(Vote { origin, auth });
+        }
+        c
     }
 
-    cx.last_seen_block = block.clone();
-    cx.update_round();
+    /// The proposal of the default leader of `round` on the genesis block
+    fn propose(keys: &[ed25519::Keypair], round: Round, certs: Vec<Certificate>) -> Propose {
+        let leader = (round-1) % N;
+        let mut b = Block::with_tx(Vec::new());
+        b.header.prev = GENESIS_BLOCK.hash;
+        b.header.author = leader;
+        b.header.height = 1;
+        b.header.blame_certificates = certs;
+        let b = b.init();
+        let mut p = Propose::new(b.hash);
+        p.round = round;
+        p.sig.origin = leader;
+        p.sign_block(&b, &crypto::Keypair::Ed25519(keys[leader].clone()));
+        p.block = Some(Arc::new(b));
+        p
+    }
 
-    job.await.expect("Concurrent relaying failed");
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn skipped_rounds_need_blame_certificates() {
+        let keys: Vec<_> = (0..N).map(|_| ed25519::Keypair::generate()).collect();
+        let mut cx = context(&keys);
+
+        // The leader of round 2 skips round 1 without blaming its leader
+        assert!(!try_receive_proposal(propose(&keys, 2, Vec::new()), &mut cx).await);
+        // A certificate for another round does not help
+        let other = propose(&keys, 2, vec![blame_cert(&keys, 3)]);
+        assert!(!try_receive_proposal(other, &mut cx).await);
+        // Neither does a blame with too few votes
+        let mut weak = blame_cert(&keys, 1);
+        weak.votes.pop();
+        assert!(!try_receive_proposal(propose(&keys, 2, vec![weak]), &mut cx).await);
+        assert!(!cx.prop_chain_by_round.contains_key(&2));
+
+        // With the blame certificate of round 1, it is a valid proposal
+        let p = propose(&keys, 2, vec![blame_cert(&keys, 1)]);
+        assert!(try_receive_proposal(p, &mut cx).await);
+        assert!(cx.prop_chain_by_round.contains_key(&2));
+    }
+}
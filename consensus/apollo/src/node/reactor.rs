@@ -11,7 +11,8 @@ use futures::channel::mpsc::{
 use futures::{StreamExt, SinkExt};
 use types::apollo::{ClientMsg, Payload, ProtocolMsg, Replica, Transaction};
 use config::Node;
-use super::{context::Context, proposal::*,message::*};
+use consensus::CommitSink;
+use super::{context::Context, proposal::*, message::*, blame::*, status::*, phase::{Event, Phase}};
 use std::sync::Arc;
 
 pub async fn reactor(
@@ -20,33 +21,36 @@ pub async fn reactor(
     net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
     cli_send: UnboundedSender<Arc<ClientMsg>>,
+    commits: CommitSink,
     mut cli_recv: UnboundedReceiver<Transaction>,
 ) {
     // Optimization to improve latency when the payloads are high
     let (send, mut recv) = unbounded_channel();
 
-    let mut cx = Context::new(config, net_send, send, is_client_apollo_enabled);
+    let mut cx = Context::new(config, net_send, send, commits, is_client_apollo_enabled);
 
     let block_size = config.block_size;
     let myid = config.id;
     let pl_size = config.payload;
 
     let cli_send_p = cli_send;
-    let rt = tokio::runtime::Builder::new_multi_thread()
+    let rt = consensus::ReactorRuntime::new(tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
-        .unwrap();
+        .unwrap());
     let payload_adder = async move {
         let mut cli_send = cli_send_p;
         loop {
-            let prop_arc = recv.next().await.unwrap();
-            let payload = Payload::with_payload(pl_size);
+            let (prop_arc, with_payload) = recv.next().await.unwrap();
+            let payload = Payload::with_payload(if with_payload {pl_size} else {0});
             let prop = prop_arc.as_ref().clone();
             let bl = prop.block.as_ref().unwrap().as_ref().clone();
             cli_send.send(Arc::new(ClientMsg::RawNewBlock(prop, bl, payload))).await.unwrap();
         }
     };
     rt.spawn(payload_adder);
+    // Blame the leader of the first round if it does not propose
+    cx.start_timer(std::time::Duration::ZERO);
     loop {
         tokio::select! {
             pmsg_opt = net_recv.next() => {
@@ -54,7 +58,7 @@ pub async fn reactor(
                 if let None = pmsg_opt {
                     log::error!(
                         "Protocol message channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let (sender, pmsg) = pmsg_opt.unwrap();
                 handle_message(sender, pmsg, &mut cx);
@@ -63,6 +67,17 @@ pub async fn reactor(
                 }
                 process_message(&mut cx).await;
             },
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                // A timer fired
+                let ev = ev_opt.unwrap().expect("Timer error").into_inner();
+                log::debug!("Handling event {:?}", ev);
+                match ev {
+                    Event::Timeout(r) => on_timeout(r, &mut cx).await,
+                    Event::Status(r) => on_status_event(r, &mut cx).await,
+                    Event::Propose(r) => on_propose_event(r, &mut cx).await,
+                }
+                process_message(&mut cx).await;
+            },
             tx_opt = cli_recv.next() => {
                 // We received a message from the client
                 match tx_opt {
@@ -74,7 +89,8 @@ pub async fn reactor(
             }
         }
         // Do we have sufficient commands, and are we the next leader?
-        if cx.storage.get_tx_pool_size() >= block_size && 
+        if cx.phase == Phase::Normal &&
+            cx.storage.get_tx_pool_size() >= block_size && 
             cx.round_leader() == myid 
         {
             log::debug!(
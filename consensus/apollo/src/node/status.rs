@@ -0,0 +1,99 @@
+use types::apollo::{Propose, ProtocolMsg, Replica, Round};
+use super::{context::Context, message::delivery_check, phase::{Event, Phase}, proposal::do_propose};
+use std::sync::Arc;
+
+/// Called 2\Delta after we quit the round before `r`, once the proposals in
+/// flight have reached us.
+///
+/// We send our tip to the leader of `r`, who proposes on the highest tip it
+/// hears about.
+pub async fn on_status_event(r: Round, cx: &mut Context) {
+    if r != cx.round() || cx.phase != Phase::Quit {
+        return;
+    }
+    // Give the leader the time to collect the statuses before it proposes
+    cx.start_timer(cx.deltas(2));
+    if cx.round_leader() == cx.myid() {
+        log::debug!("Collecting the statuses for round {}", r);
+        cx.phase = Phase::StatusWait;
+        let wait = cx.deltas(2);
+        cx.events.insert(Event::Propose(r), wait);
+        return;
+    }
+    cx.phase = Phase::Wait;
+    if cx.parent_of(&cx.tip).is_none() {
+        // The leader already knows the genesis block
+        return;
+    }
+    let tip = cx.tip.as_ref().clone();
+    let block = tip.block.clone().unwrap().as_ref().clone();
+    let msg = Arc::new(ProtocolMsg::RawStatus(r, tip, block));
+    cx.send(cx.round_leader(), msg).await;
+}
+
+pub async fn on_receive_status(sender: Replica, r: Round, p: Propose, cx: &mut Context) {
+    if r != cx.round() || cx.round_leader() != cx.myid() {
+        log::debug!("Status for round {} from {} not meant for us", r, sender);
+        return;
+    }
+    cx.statuses.push(p.block_hash);
+    // Check the proposal (and its chain) like any other proposal
+    delivery_check(sender, p, cx).await;
+}
+
+/// Called when we, as the leader of `r`, finished collecting the statuses, or
+/// waited too long for a full block.
+///
+/// After the statuses, we move our tip to the highest status that we can
+/// extend, so that the reactor proposes on it. Otherwise, we propose whatever
+/// we have if blocks on our chain wait for more proposals to commit.
+pub async fn on_propose_event(r: Round, cx: &mut Context) {
+    if r != cx.round() || cx.round_leader() != cx.myid() {
+        return;
+    }
+    match cx.phase {
+        Phase::StatusWait => on_statuses(r, cx),
+        Phase::Normal if cx.storage.get_tx_pool_size() < cx.block_size()
+            && cx.has_pending_commits() =>
+        {
+            log::debug!("Proposing a partial block in round {}", r);
+            let txs = cx.storage.cleave(cx.storage.get_tx_pool_size());
+            do_propose(txs, cx).await;
+        }
+        _ => {}
+    }
+}
+
+fn on_statuses(r: Round, cx: &mut Context) {
+    let mut best = cx.tip.clone();
+    for h in std::mem::take(&mut cx.statuses) {
+        let p = match cx.prop_chain_by_hash.get(&h) {
+            None => continue,
+            Some(x) => x.clone(),
+        };
+        if p.round > best.round && p.round < r && cx.can_justify(&p) {
+            best = p;
+        }
+    }
+    if !cx.can_justify(&best) {
+        log::warn!("Cannot justify proposing in round {} on round {}", r, best.round);
+        return;
+    }
+    if best.block_hash != cx.tip.block_hash {
+        log::info!("Proposing on the chain of round {} by {}", best.round, best.sig.origin);
+        // The transactions on this chain are no longer pending
+        let mut p = best.clone();
+        while !cx.storage.is_committed_by_hash(&p.block_hash) {
+            cx.storage.clear(&p.block.as_ref().unwrap().body.tx_hashes);
+            p = match cx.parent_of(&p) {
+                None => break,
+                Some(x) => x,
+            };
+        }
+        cx.tip = best;
+    }
+    cx.phase = Phase::Normal;
+    // Propose without a full block if we still wait for one after this
+    let wait = cx.deltas(2);
+    cx.events.insert(Event::Propose(r), wait);
+}
@@ -8,7 +8,7 @@ use config::Client;
 use types::apollo::{Block, ClientMsg, Transaction};
 use futures::channel::mpsc::channel;
 use crypto::hash::Hash;
-use consensus::statistics;
+use consensus::{EvidenceChecker, statistics};
 use std::sync::Arc;
 use util::codec::EnCodec;
 use util::codec::Decodec;
@@ -22,6 +22,7 @@ struct Context {
     finished_map:HashSet<Hash>,
     latency_map: HashMap<Hash, (SystemTime, SystemTime)>,
     num_cmds: u128,
+    evidence: EvidenceChecker,
 }
 
 impl std::fmt::Debug for Context {
@@ -37,7 +38,7 @@ impl std::fmt::Debug for Context {
 }
 
 impl Context {
-    pub fn new() -> Self {
+    pub fn new(c: &Client) -> Self {
         Self {
             pending: 0,
             time_map: HashMap::default(),
@@ -45,6 +46,7 @@ impl Context {
             finished_map: HashSet::default(),
             latency_map: HashMap::default(),
             num_cmds: 0,
+            evidence: EvidenceChecker::from_client(c),
         }
     }
 }
@@ -75,7 +77,7 @@ pub async fn start(
             }
         }
     });
-    let mut cx = Context::new();
+    let mut cx = Context::new(c);
     cx.pending = window;
     // let mut time_map = HashMap::new();
     // let mut count_map:HashMap<Hash, usize> = HashMap::new();
@@ -134,6 +136,10 @@ fn process_blocks(c:&Client, now: SystemTime, new_blocks: &mut VecDeque<Arc<Bloc
     for b in new_blocks.into_iter() {
         // Check if the block is valid?
         if !cx.count_map.contains_key(&b.hash) {
+            if !cx.evidence.check(&b.header.evidence) {
+                log::warn!("Got a block with invalid evidence");
+                continue;
+            }
             cx.count_map.insert(b.hash, 1);
             continue;
         }
@@ -145,8 +151,9 @@ fn process_blocks(c:&Client, now: SystemTime, new_blocks: &mut VecDeque<Arc<Bloc
         if cx.finished_map.contains(&b.hash) {
             continue;
         }
-        cx.pending += c.block_size;
-        cx.num_cmds += c.block_size as u128;
+        // Blocks may not be full when the leaders run out of transactions
+        cx.pending += b.body.tx_hashes.len();
+        cx.num_cmds += b.body.tx_hashes.len() as u128;
         for t in &b.body.tx_hashes {
             if let Some(old) = cx.time_map.get(t) {
                 cx.latency_map.insert(t.clone(), (old.clone(),now));
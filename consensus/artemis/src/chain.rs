@@ -0,0 +1,243 @@
+use std::{collections::VecDeque, convert::TryInto, sync::Arc};
+use consensus::{Reputation, VrfBeacon};
+use crypto::{hash::{EMPTY_HASH, Hash}, vrf};
+use fnv::FnvHashMap as HashMap;
+use linked_hash_map::LinkedHashMap;
+use types::artemis::{Replica, Round, UCRVote, View};
+
+/// The round leader state at the start of a round
+#[derive(Clone)]
+struct Leaders {
+    /// The current round
+    round: Round,
+    /// The current round leader
+    round_leader: Replica,
+    /// The last f leaders
+    last_f_leaders: LinkedHashMap<Replica,()>,
+    /// Eligible leaders
+    eligible_leaders: Vec<Replica>,
+    /// The VRF seed, if VRF leader election is enabled
+    seed: Hash,
+    /// The rounds and the leaders of the last votes, the latest first, if
+    /// reputation-based leader election is enabled
+    recent: VecDeque<(Round, Replica)>,
+}
+
+/// The UCR vote chain, along with the round leaders that it elects.
+///
+/// This is shared by the nodes and the clients. The votes are accepted one
+/// round at a time. The first vote of a view is cast by the view leader, and
+/// the others by the round leader, who is picked among the nodes that did not
+/// lead one of the last f rounds. With reputation-based leader election, the
+/// nodes that did not cast a vote in the recent rounds are only picked when
+/// no other node is eligible, or when they are on probation.
+///
+/// Different nodes can accept different votes for a round if its leader
+/// equivocates. The view change then hands everyone the highest chain, and
+/// we go back to the round where the chains split with `rewind`.
+pub(crate) struct VoteChain {
+    num_nodes: usize,
+    num_faults: usize,
+    leaders: Leaders,
+    /// The leaders at the start of the rounds since the last commit
+    history: HashMap<Round, Leaders>,
+    /// The seeds for VRF leader election, if enabled
+    beacon: Option<VrfBeacon>,
+    /// The leader schedule from the recent votes, if reputation-based leader
+    /// election is enabled
+    reputation: Option<Reputation>,
+    /// The accepted votes since the last commit: Map of round to vote
+    pub votes: HashMap<Round, Arc<UCRVote>>,
+    /// The leaders who signed the accepted votes: Map of round to leader
+    signers: HashMap<Round, Replica>,
+}
+
+impl VoteChain {
+    pub fn new(num_nodes: usize, num_faults: usize, beacon: Option<VrfBeacon>, reputation: Option<Reputation>) -> Self {
+        let mut leaders = Leaders {
+            round: 1,
+            round_leader: num_faults-1,
+            last_f_leaders: LinkedHashMap::with_capacity(num_nodes),
+            eligible_leaders: Vec::with_capacity(num_nodes),
+            seed: EMPTY_HASH,
+            recent: VecDeque::new(),
+        };
+        // Initialize the leaders
+        for i in 0..num_faults {
+            leaders.last_f_leaders.insert(i, ());
+        }
+        for i in num_faults..num_nodes {
+            leaders.eligible_leaders.push(i);
+        }
+        log::info!("Using last f leaders: {:?}", leaders.last_f_leaders);
+        log::info!("Using eligible leaders: {:?}", leaders.eligible_leaders);
+        VoteChain {
+            num_nodes,
+            num_faults,
+            leaders,
+            history: HashMap::default(),
+            beacon,
+            reputation,
+            votes: HashMap::default(),
+            signers: HashMap::default(),
+        }
+    }
+
+    /// Returns the current round: the round after the highest vote
+    #[inline]
+    pub fn round(&self) -> Round {
+        self.leaders.round
+    }
+
+    /// Returns the current round leader
+    #[inline]
+    pub fn round_leader(&self) -> Replica {
+        self.leaders.round_leader
+    }
+
+    /// Returns the highest vote
+    pub fn highest(&self) -> Option<Arc<UCRVote>> {
+        self.votes.get(&(self.round()-1)).cloned()
+    }
+
+    /// Returns the votes from `round` onwards, in order
+    pub fn since(&self, round: Round) -> Vec<UCRVote> {
+        (round..self.round())
+            .filter_map(|r| self.votes.get(&r))
+            .map(|v| v.as_ref().clone())
+            .collect()
+    }
+
+    /// Returns the leader who must cast the vote `v` of the current round:
+    /// the view leader if it is the first vote of its view, and the round
+    /// leader otherwise
+    pub fn leader_of(&self, v: &UCRVote) -> Replica {
+        let last_view: View = self.highest().map_or(0, |h| h.view);
+        if v.view > last_view {
+            v.view % self.num_nodes
+        } else {
+            self.round_leader()
+        }
+    }
+
+    /// Returns the accepted vote of `round` and the leader who signed it
+    pub fn accepted(&self, round: Round) -> Option<(Arc<UCRVote>, Replica)> {
+        let v = self.votes.get(&round)?;
+        Some((v.clone(), self.signers[&round]))
+    }
+
+    /// Prove our VRF output for the current round. Only nodes can do this.
+    pub fn prove(&self) -> Option<vrf::Proof> {
+        self.beacon.as_ref().map(|b| b.prove(self.round()))
+    }
+
+    /// Accept the vote `v` of the current round, and go to the next round.
+    /// The caller must check that `v` is signed by `leader_of(v)`.
+    ///
+    /// Returns false if the VRF proof of the leader is missing or incorrect.
+    pub fn push(&mut self, v: Arc<UCRVote>) -> bool {
+        debug_assert_eq!(v.round, self.round());
+        let leader = self.leader_of(&v);
+        if let Some(b) = &mut self.beacon {
+            // Check the VRF proof of the leader, which decides the next round
+            // leader
+            let seed = v.vrf.as_ref()
+                .and_then(|proof| b.verify(leader, v.round, proof));
+            match seed {
+                Some(seed) => b.advance(seed),
+                None => return false,
+            }
+        }
+        self.history.insert(self.round(), self.leaders.clone());
+        if let Some(rep) = &self.reputation {
+            let recent = &mut self.leaders.recent;
+            recent.push_front((v.round, leader));
+            recent.truncate(rep.window());
+        }
+        self.signers.insert(v.round, leader);
+        self.votes.insert(v.round, v);
+        self.update_round();
+        true
+    }
+
+    /// Go back to the start of `round`, dropping the votes from then on
+    pub fn rewind(&mut self, round: Round) {
+        let leaders = match self.history.get(&round) {
+            None => return,
+            Some(x) => x.clone(),
+        };
+        if let Some(b) = &mut self.beacon {
+            b.advance(leaders.seed);
+        }
+        self.leaders = leaders;
+        self.votes.retain(|r, _| *r < round);
+        self.signers.retain(|r, _| *r < round);
+        self.history.retain(|r, _| *r < round);
+    }
+
+    /// Returns the round whose vote commits, if the last f+1 votes are in
+    /// consecutive rounds of the same view
+    pub fn commit_round(&self) -> Option<Round> {
+        let last = self.round()-1;
+        if last <= self.num_faults {
+            return None;
+        }
+        let view = self.votes.get(&last)?.view;
+        let first = last - self.num_faults;
+        for r in first..last {
+            if self.votes.get(&r)?.view != view {
+                return None;
+            }
+        }
+        Some(first)
+    }
+
+    /// Forget the votes and the leaders before the committed `round`
+    pub fn prune(&mut self, round: Round) {
+        self.votes.retain(|r, _| *r >= round);
+        self.signers.retain(|r, _| *r >= round);
+        self.history.retain(|r, _| *r > round);
+    }
+
+    /// Goes to the next round
+    fn update_round(&mut self) {
+        // First update the round leader
+        let (new_leader, idx) = self.compute_next_round_leader();
+        let leaders = &mut self.leaders;
+        leaders.round_leader = new_leader;
+        // Then update the round
+        // This order is important, otherwise, some other parts of the code may call next_round_leader() and changing the round before setting cx.round_leader = cx.next_round_leader() will cause problems (I LEARNT IT THE HARD WAY)
+        leaders.round += 1;
+        // Make the f^th leader elligible again
+        let (eligible_again,_) = leaders.last_f_leaders.pop_front().unwrap();
+        leaders.last_f_leaders.insert(leaders.round_leader, ());
+        leaders.eligible_leaders[idx] = eligible_again;
+        if let Some(b) = &self.beacon {
+            leaders.seed = *b.seed();
+        }
+    }
+
+    /// This is a private function that returns both the next leader and its index in the eligible leaders vector
+    fn compute_next_round_leader(&self) -> (Replica, usize) {
+        let leaders = &self.leaders;
+        // Only pick among the eligible leaders with a good reputation, if any
+        let active = self.reputation.as_ref()
+            .and_then(|rep| rep.active(leaders.round+1, leaders.recent.iter().copied()));
+        let mut candidates: Vec<usize> = (0..leaders.eligible_leaders.len())
+            .filter(|i| active.as_ref().is_none_or(|a| a.contains(&leaders.eligible_leaders[*i])))
+            .collect();
+        if candidates.is_empty() {
+            candidates = (0..leaders.eligible_leaders.len()).collect();
+        }
+        let pick = match &self.beacon {
+            Some(b) => b.pick(candidates.len()),
+            None => {
+                let data = (leaders.round+1).to_be_bytes();
+                let h = crypto::hash::do_hash(&data);
+                usize::from_be_bytes(h[24..].try_into().unwrap()) % candidates.len()
+            }
+        };
+        let idx = candidates[pick];
+        (leaders.eligible_leaders[idx], idx)
+    }
+}
@@ -1,14 +1,13 @@
 use config::Client;
 use fnv::FnvHashMap as HashMap;
 use crypto::hash::Hash;
-use std::{sync::Arc, convert::TryInto};
-use types::artemis::{Block, GENESIS_BLOCK, Payload, Round, Storage, UCRVote, Replica};
+use std::sync::Arc;
+use types::artemis::{Block, GENESIS_BLOCK, Payload, Round, Storage, UCRVote};
 use std::time::SystemTime;
-use linked_hash_map::LinkedHashMap;
+use consensus::{EvidenceChecker, Reputation, VrfBeacon};
+use crate::chain::VoteChain;
 
 pub(crate) struct Context {
-    /// The config for this instance of the protocol
-    pub config: Arc<Client>,
     /// The number of unproposed transactions
     pub pending: usize,
     /// The number of committed commands
@@ -19,23 +18,18 @@ pub(crate) struct Context {
     pub latency_map: HashMap<Hash, (SystemTime, SystemTime)>,
     /// To hold all of our blocks
     pub storage: Storage,
-    /// Prop chain
-    pub prop_chain: HashMap<Round, Arc<UCRVote>>,
-    /// The current round
-    round: Round,
-    /// The current round leader
-    pub round_leader: Replica,
+    /// The vote chain, along with the round state
+    pub chain: VoteChain,
+    /// The round of the last vote that committed
+    pub committed_round: Round,
     /// Future messages
     pub future_msgs: HashMap<Round, (UCRVote, Vec<(Block, Payload)>)>,
-
-    /// The last f leaders
-    last_f_leaders: LinkedHashMap<Replica,()>,
-    /// Eligible leaders
-    eligible_leaders: Vec<Replica>,
+    /// The proofs of misbehaviour in the blocks we got
+    pub evidence: EvidenceChecker,
 }
 
 impl Context {
-    pub fn new(config: Arc<Client>) -> Self {
+    pub fn new(config: &Client) -> Self {
         let genesis_arc = Arc::new(GENESIS_BLOCK);
         let mut cx = Context {
             pending: 0,
@@ -43,56 +37,19 @@ impl Context {
             time_map: HashMap::default(),
             latency_map: HashMap::default(),
             storage: Storage::new(100_000),
-            round: 1,
-            prop_chain:HashMap::default(),
-            round_leader:config.num_faults-1,
+            chain: VoteChain::new(config.num_nodes, config.num_faults, VrfBeacon::from_client(config), Reputation::from_client(config)),
+            committed_round: 0,
             future_msgs: HashMap::default(),
-            last_f_leaders: LinkedHashMap::with_capacity(config.num_nodes),
-            eligible_leaders: Vec::with_capacity(config.num_nodes),
-            config,
+            evidence: EvidenceChecker::from_client(config),
         };
         cx.storage.add_delivered_block(genesis_arc);
-         // Initialize the leaders
-        for i in 0..cx.config.num_faults {
-            cx.last_f_leaders.insert(i, ());
-        }
-        for i in cx.config.num_faults..cx.config.num_nodes {
-            cx.eligible_leaders.push(i);
-        }
         cx
     }
 
-    /// Goes to the next round
-    pub(crate) fn update_round(&mut self) {
-        // First update the round leader
-        let (new_leader, idx) = self.compute_next_round_leader();
-        self.round_leader = new_leader;
-        // Then update the round
-        // This order is important, otherwise, some other parts of the code may call next_round_leader() and changing the round before setting cx.round_leader = cx.next_round_leader() will cause problems (I LEARNT IT THE HARD WAY)
-        self.round += 1;
-        // Make the f^th leader elligible again
-        let (eligible_again,_) = self.last_f_leaders.pop_front().unwrap();
-        self.last_f_leaders.insert(self.round_leader, ());
-        self.eligible_leaders[idx] = eligible_again;
-    }
-    
-    /// This is a private function that returns both the next leader and its index in the eligible leaders vector
-    /// Returns the next round leader
-    /// This function will be called several times before finally updating the round leader
-    /// When changing the leader do not change the following variables:
-    /// - round
-    /// - eligible_leaders
-    fn compute_next_round_leader(&self) -> (Replica, usize) {
-        let data = (self.round+1).to_be_bytes();
-        let h = crypto::hash::do_hash(&data);
-        let idx = usize::from_be_bytes(h[24..].try_into().unwrap()) % self.eligible_leaders.len();
-        (self.eligible_leaders[idx], idx)
-    }
-
-     /// Returns the current round 
+    /// Returns the current round 
     /// We want to ensure read only access to this value
     #[inline]
-    pub const fn round(&self) -> Round {
-        self.round
+    pub fn round(&self) -> Round {
+        self.chain.round()
     }
 }
@@ -1,5 +1,6 @@
 use std::time::SystemTime;
 use config::Client;
+use types::BlockTrait;
 use types::artemis::{Block, ClientMsg, Payload, Transaction, UCRVote};
 use tokio::sync::mpsc::{Receiver, channel};
 use consensus::statistics;
@@ -45,7 +46,7 @@ pub async fn start(
         client_network.setup(servers, EnCodec::new(), Decodec::new()).await;
 
     let payload = c.payload;
-    let mut cx = Context::new(c.clone());
+    let mut cx = Context::new(&c);
     let mut recv = setup_tx_factory(payload).await;
     let m = metric;
     cx.pending = window;
@@ -79,10 +80,15 @@ pub async fn start(
                 log::debug!("Got a client message: {:?} from {}", msg, sender);
                 match msg {
                     ClientMsg::NewBlock(v, block_vec) => try_new_round(v, block_vec, &mut cx, now).await,
+                    ClientMsg::VoteChain(votes, block_vec) => on_vote_chain(votes, block_vec, &mut cx, now),
                     _ => continue,
                 };
-                while let Ok(Some((_, ClientMsg::NewBlock(v,block_vec)))) = net_recv.try_next() {
-                    try_new_round(v, block_vec, &mut cx, now).await;
+                while let Ok(Some((_, msg))) = net_recv.try_next() {
+                    match msg {
+                        ClientMsg::NewBlock(v, block_vec) => try_new_round(v, block_vec, &mut cx, now).await,
+                        ClientMsg::VoteChain(votes, block_vec) => on_vote_chain(votes, block_vec, &mut cx, now),
+                        _ => continue,
+                    }
                 }
             } 
         }
@@ -103,46 +109,123 @@ async fn try_new_round(v: UCRVote,
 {
     if cx.round() < v.round {
         log::debug!("We got a vote from the future");
-        // TODO
         cx.future_msgs.insert(v.round, (v, block_vec));
         return;
     }
     if cx.round() > v.round {
-        log::warn!("We got a vote from a round that we have already processed for");
+        log::debug!("We got a vote from a round that we have already processed for");
         return;
     }
-    new_round(v, block_vec, cx, ts).await;
-    while let Some((v, block_vec)) = cx.future_msgs.remove(&cx.round()) {
-        new_round(v, block_vec, cx, ts).await;
-    }
+    new_round(v, block_vec, cx, ts);
+    process_future_msgs(cx, ts);
 }
 
 /// Processing votes for the correct round
-async fn new_round(v: UCRVote, 
+fn new_round(v: UCRVote, 
     block_vec: Vec<(Block, Payload)>, 
     cx:&mut Context,
     ts: SystemTime,
 ) 
 {
+    // The first vote of a view comes in a vote chain
+    if cx.chain.leader_of(&v) != cx.chain.round_leader() {
+        log::warn!("Got the first vote of view {} outside a new view", v.view);
+        return;
+    }
+    add_blocks(block_vec, cx);
+    if !cx.chain.push(Arc::new(v)) {
+        log::warn!("Got a vote from an illegitimate leader in round {}", cx.round());
+        return;
+    }
+    try_commit(cx, ts);
+}
+
+/// A node sent us its vote chain `votes`, when starting a view or along with
+/// its vote. We follow it, going back to the round where it splits from our
+/// chain if needed.
+fn on_vote_chain(votes: Vec<UCRVote>,
+    block_vec: Vec<(Block, Payload)>,
+    cx: &mut Context,
+    ts: SystemTime,
+)
+{
+    let last = match votes.last() {
+        None => return,
+        Some(x) => x,
+    };
+    // We only move to a higher chain, or to a new view that replaces the
+    // votes after the highest one its leader heard about
+    let is_higher = match cx.chain.votes.get(&last.round) {
+        None => last.round >= cx.round(),
+        Some(mine) => last.view > mine.view,
+    };
+    if !is_higher {
+        return;
+    }
+    let view = last.view;
+    add_blocks(block_vec, cx);
+    for v in votes {
+        if v.round < cx.round() {
+            match cx.chain.votes.get(&v.round) {
+                Some(mine) if mine.hash == v.hash && mine.view == v.view => continue,
+                _ if v.round <= cx.committed_round => continue,
+                _ => cx.chain.rewind(v.round),
+            }
+        }
+        if v.round > cx.round() {
+            log::warn!("Missing the votes from round {} to {}", cx.round(), v.round);
+            return;
+        }
+        if !cx.chain.push(Arc::new(v)) {
+            log::warn!("Got a vote from an illegitimate leader in round {}", cx.round());
+            return;
+        }
+    }
+    try_commit(cx, ts);
+    cx.future_msgs.retain(|_, (v, _)| v.view >= view);
+    process_future_msgs(cx, ts);
+}
+
+/// Process the votes that we got before their round
+fn process_future_msgs(cx: &mut Context, ts: SystemTime) {
+    while let Some((v, block_vec)) = cx.future_msgs.remove(&cx.round()) {
+        new_round(v, block_vec, cx, ts);
+    }
+}
+
+/// Add the blocks that we have not seen yet, which frees up their space in
+/// the window
+fn add_blocks(block_vec: Vec<(Block, Payload)>, cx: &mut Context) {
     for (b, _) in block_vec {
+        if cx.storage.is_delivered_by_hash(&b.get_hash()) {
+            continue;
+        }
+        if !cx.evidence.check(&b.blk.header.evidence) {
+            log::warn!("Got a block with invalid evidence");
+            continue;
+        }
         cx.pending += b.blk.body.tx_hashes.len();
         cx.storage.add_delivered_block(Arc::new(b));
     }
-    let v = Arc::new(v);
-    cx.prop_chain.insert(v.round, v.clone());
-    if v.round <= cx.config.num_faults {
-        // Nothing to commit
-        cx.update_round();
-        return;
-    }
-    
-    let com_round = v.round - cx.config.num_faults;
-    let v = cx.prop_chain.get(&com_round)
+}
+
+/// Commit the vote f rounds before the last one, if the last f+1 votes are in
+/// the same view
+fn try_commit(cx: &mut Context, ts: SystemTime) {
+    let com_round = match cx.chain.commit_round() {
+        None => return,
+        Some(r) => r,
+    };
+    let v = cx.chain.votes.get(&com_round)
         .expect("Must have in prop map");
 
     let mut com_hash = v.hash;
     while !cx.storage.is_committed_by_hash(&com_hash) {
-        let b_rc = cx.storage.delivered_block_from_hash(&com_hash).expect("Trying to commit an undelivered block");
+        let b_rc = match cx.storage.delivered_block_from_hash(&com_hash) {
+            // We reached the genesis block
+            None => break,
+            Some(x) => x,
+        };
         cx.storage.add_committed_block(b_rc.clone());
         com_hash = b_rc.blk.header.prev;
         // For every committed block, update the statistics
@@ -153,7 +236,6 @@ async fn new_round(v: UCRVote,
             }
         }
     }
-
-    // At the end, move to the next round
-    cx.update_round();
-}
\ No newline at end of file
+    cx.committed_round = com_round;
+    cx.chain.prune(com_round);
+}
@@ -5,6 +5,10 @@ use types::artemis::{Replica, ProtocolMsg, ClientMsg, Transaction};
 
 pub mod client;
 pub mod node;
+mod chain;
+
+mod protocol;
+pub use protocol::*;
 
 pub type NetSend = UnboundedSender<(Replica, Arc<ProtocolMsg>)>;
 pub type NetRecv = UnboundedReceiver<(Replica, ProtocolMsg)>;
@@ -1,7 +1,147 @@
-use types::artemis::Vote;
-use super::*;
+use types::{CertKind, Threshold, artemis::{CertType, Certificate, Evidence, ProtocolMsg, Replica, Round, UCRVote, View, Vote}};
+use super::{context::Context, phase::{Event, Phase}};
+use std::sync::Arc;
 
-pub async fn on_receive_blame(v: Vote, _bc: &mut Context) {
-    log::warn!("Received a blame message: {:?}", v);
-    panic!("Currently unimplemented");
-}
\ No newline at end of file
+/// Called when the round `r` of `view` has not seen a vote in time
+pub async fn on_timeout(view: View, r: Round, cx: &mut Context) {
+    if view != cx.view || r != cx.round() || cx.blamed {
+        return;
+    }
+    match cx.phase {
+        Phase::Normal if !cx.expect_work || !cx.has_work() => {
+            // The leaders had nothing to do for a whole timeout, which is not
+            // their fault
+            cx.start_timer(std::time::Duration::ZERO);
+        }
+        // The view leader did not start its view
+        Phase::Normal | Phase::Wait => do_blame(cx).await,
+        Phase::Quit | Phase::StatusWait => {}
+    }
+}
+
+/// Blame the current view, for a view leader that does not send blocks or
+/// equivocates, or a round leader that does not vote. The view leader is
+/// responsible for the progress of its view, so we blame it in all cases.
+pub async fn do_blame(cx: &mut Context) {
+    if cx.blamed {
+        return;
+    }
+    cx.blamed = true;
+    log::info!("Blaming the leader {} of view {} in round {}", cx.view_leader, cx.view, cx.round());
+    let msg = CertType::Blame(cx.view_leader, cx.view);
+    let auth = cx.my_secret_key.sign(&util::io::to_bytes(&msg))
+        .expect("Failed to sign a blame");
+    let cert = Certificate {
+        msg,
+        votes: vec![Vote { origin: cx.myid(), auth }],
+        agg: None,
+    };
+    cx.multicast(Arc::new(ProtocolMsg::Blame(cert.clone()))).await;
+    on_receive_blame(cert, cx).await;
+}
+
+pub async fn on_receive_blame(c: Certificate, cx: &mut Context) {
+    let view = match c.msg {
+        CertType::Blame(l, v) if l == v % cx.num_nodes() => v,
+        _ => {
+            log::warn!("Blame message for {:?}", c.msg);
+            return;
+        }
+    };
+    if has_quit(view, cx) {
+        log::debug!("Blame for an old view {}", view);
+        return;
+    }
+    let origin = match c.verify_vote(CertKind::Blame, &cx.pub_key_map) {
+        Ok(x) => x,
+        Err(e) => {
+            log::warn!("Invalid blame message: {}", e);
+            return;
+        }
+    };
+    let votes = cx.blame_map.entry(view).or_default();
+    votes.insert(origin, c.votes[0].clone());
+    let blamers: Vec<Replica> = votes.keys().cloned().collect();
+    // Promote it to a certificate if it has f+1 blames
+    if !cx.quorum().is_met(Threshold::Honest, blamers) {
+        return;
+    }
+    let votes = cx.blame_map.remove(&view).unwrap();
+    let cert = Certificate {
+        msg: c.msg,
+        votes: votes.into_values().collect(),
+        agg: None,
+    };
+    on_quit_view(Arc::new(cert), cx).await;
+}
+
+pub async fn on_receive_quit(c: Certificate, cx: &mut Context) {
+    match cx.check_blame_cert(&c) {
+        None => log::warn!("Invalid blame certificate {:?}", c.msg),
+        Some(v) if has_quit(v, cx) => log::debug!("Already quit view {}", v),
+        Some(_) => on_quit_view(Arc::new(c), cx).await,
+    }
+}
+
+/// Whether we already quit `view`
+pub(crate) fn has_quit(view: View, cx: &Context) -> bool {
+    view < cx.view || (view == cx.view && cx.phase == Phase::Quit)
+}
+
+/// Quit the view blamed by the certificate `c`
+///
+/// We forward the certificate so that everyone quits within \Delta, and wait
+/// 2\Delta for the votes still in flight before sending our status to the
+/// leader of the next view (see `on_status_event`).
+pub async fn on_quit_view(c: Arc<Certificate>, cx: &mut Context) {
+    let view = match c.msg {
+        CertType::Blame(_, v) => v,
+        _ => unreachable!("only blame certificates are quit certificates"),
+    };
+    log::info!("Quitting view {} in round {} with the blames of {:?}", view, cx.round(),
+        c.votes.iter().map(|v| v.origin).collect::<Vec<_>>());
+    cx.multicast(Arc::new(ProtocolMsg::QuitView(c.as_ref().clone()))).await;
+
+    cx.quit_cert = Some(c);
+    cx.blame_map.retain(|v, _| *v > view);
+    cx.view = view;
+    cx.view_leader = view % cx.num_nodes();
+    cx.phase = Phase::Quit;
+    let wait = cx.deltas(2);
+    cx.events.insert(Event::Status(view+1), wait);
+}
+
+/// Check a vote for a round in which we accepted a vote. If the same leader
+/// signed both for different blocks in the same view, returns the proof that
+/// it equivocated.
+pub(crate) fn find_equivocation(v: &UCRVote, cx: &Context) -> Option<Evidence> {
+    let (old, leader) = cx.chain.accepted(v.round)?;
+    if old.view != v.view || old.hash == v.hash || cx.evidence.is_faulty(leader) {
+        return None;
+    }
+    let pk = cx.pub_key_map.get(&leader)?;
+    if !v.check_sig(pk) {
+        return None;
+    }
+    log::warn!("The leader {} of round {} equivocated", leader, v.round);
+    Some(Evidence::new(old.signed(leader), v.signed(leader)))
+}
+
+/// Store a checked proof of misbehaviour, and forward it to the others if it
+/// is the first one against its offender
+pub async fn on_evidence(e: Evidence, cx: &mut Context) {
+    if cx.evidence.add(e.clone()) {
+        cx.multicast(Arc::new(ProtocolMsg::Evidence(e))).await;
+    }
+}
+
+pub async fn on_receive_evidence(e: Evidence, cx: &mut Context) {
+    if cx.evidence.is_faulty(e.offender()) {
+        return;
+    }
+    if let Err(err) = e.verify(&cx.pub_key_map) {
+        log::warn!("Invalid evidence against {}: {}", e.offender(), err);
+        return;
+    }
+    on_evidence(e, cx).await;
+}
@@ -1,22 +1,42 @@
-use types::BlockTrait;
+use types::{BlockTrait, CommittedBlock};
+use std::sync::Arc;
 use super::*;
 
 /// Do commit is called to trigger committing of blocks
-/// Caller needs to ensure that `cx.round > cx.num_faults()`
+/// We commit the vote f rounds before the last one, if the last f+1 votes are in the same view
 pub fn do_commit(cx: &mut Context) {
     log::debug!("Trying to commit");
-    debug_assert!(cx.round() > cx.num_faults());
 
     // Get the r-f^th vote
-    let commit_round = cx.round() - cx.num_faults();
-    let v = cx.vote_chain.get(&commit_round).unwrap();    
+    let commit_round = match cx.chain.commit_round() {
+        None => return,
+        Some(r) => r,
+    };
+    let v = cx.chain.votes.get(&commit_round).unwrap().clone();
 
     let mut com_hash = v.hash;
+    let mut new_commits = Vec::new();
     // Commit com_hash and its parents
     while !cx.storage.is_committed_by_hash(&com_hash) {
         let b = cx.storage.delivered_block_from_hash(&com_hash).unwrap();
         log::debug!("Committing block - {} in round {}", b.get_height(), v.round);
         cx.storage.add_committed_block(b.clone());
+        cx.evidence.on_included(&b.blk.header.evidence);
         com_hash = b.blk.header.prev;
+        new_commits.push(b);
     }
-}
\ No newline at end of file
+    // Publish them from the oldest, with no certificate since the votes of
+    // the rounds commit them
+    for b in new_commits.into_iter().rev() {
+        let txs = cx.storage.take_committed(&b.blk.body.tx_hashes);
+        if cx.commit_sink.is_subscribed() {
+            cx.commit_sink.publish(CommittedBlock{
+                block: Arc::new(b.blk.clone()),
+                txs,
+                justification: None,
+            });
+        }
+    }
+    cx.committed_round = commit_round;
+    cx.chain.prune(commit_round);
+}
@@ -1,12 +1,18 @@
-use std::{collections::VecDeque, convert::TryInto};
+use std::collections::VecDeque;
 use crypto::hash::Hash;
-use crypto::{Keypair, PublicKey, ed25519, secp256k1};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
 use futures::channel::mpsc::UnboundedSender;
-use types::artemis::{Block, ClientMsg, GENESIS_BLOCK, ProtocolMsg, Replica, Round, Storage, UCRVote, View};
+use tokio_util::time::DelayQueue;
+use types::BlockTrait;
+use types::artemis::{Block, CertType, Certificate, ClientMsg, GENESIS_BLOCK, ProtocolMsg, Replica, Round, Storage, UCRVote, View, Vote};
+use types::{CertKind, FaultModel, QuorumPolicy, Threshold};
 use config::Node;
+use consensus::{CommitSink, EvidencePool, Reputation, VrfBeacon, Wal};
 use std::sync::Arc;
+use std::time::Duration;
 use fnv::FnvHashMap as HashMap;
-use linked_hash_map::LinkedHashMap;
+use crate::chain::VoteChain;
+use super::phase::{Event, Phase};
 
 /// Config context
 pub struct Context {
@@ -14,8 +20,14 @@ pub struct Context {
     num_nodes: usize,
     /// The number of faults
     num_faults: usize,
+    /// The votes that the certificates need
+    quorum: QuorumPolicy,
     /// myid in the protocol
     myid: Replica,
+    /// The number of transactions in a block
+    block_size: usize,
+    /// The synchronous bound on the message delays, in milliseconds
+    delta: u64,
     /// Map of node IDs and public keys 
     pub pub_key_map: HashMap<Replica, PublicKey>,
     /// My Secret Key
@@ -29,25 +41,21 @@ pub struct Context {
     pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     /// A channel to communicate to all the nodes
     pub cli_send: UnboundedSender<Arc<ClientMsg>>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
     
     /// Storage context. Permanent storage goes here.
     /// The blockchain and transactions are stored here.
     pub storage: Storage,
-    /// The vote chain: Map of block hash to its proposal
-    pub vote_chain: HashMap<Round, Arc<UCRVote>>,
+    /// The vote chain, along with the round state
+    pub(crate) chain: VoteChain,
+    /// The round of the last vote that committed
+    pub committed_round: Round,
     
-    /// The current round leader
-    pub round_leader: Replica,
-    /// The last f leaders
-    last_f_leaders: LinkedHashMap<Replica,()>,
-    /// Eligible leaders
-    eligible_leaders: Vec<Replica>,
     /// The current view leader
     pub view_leader: Replica,
     /// The current view
     pub view: View,
-    /// The current round
-    round: Round,
     /// The last observed block
     pub last_seen_block: Arc<Block>,
     /// The last block for which we have seen vote messages for
@@ -72,6 +80,8 @@ pub struct Context {
     /// - UCRVote
     /// - Relay
     /// - Blame
+    /// - QuitView
+    /// - Status
     /// - Request
     pub other_buf: VecDeque<(Replica, ProtocolMsg)>,
 
@@ -81,6 +91,29 @@ pub struct Context {
     /// Undelivered blocks (h, b)
     /// The block b with hash h is waiting for something to get delivered
     pub undelivered_blocks: HashMap<Hash, Block>,
+    /// The new view messages, which we handle before the blocks of the new
+    /// view leader
+    pub new_view_waiting: VecDeque<(Replica, ProtocolMsg)>,
+    /// The votes of a view that we have not started yet
+    pub future_view_votes: Vec<(Replica, UCRVote)>,
+
+    // Blame and view change state
+    pub phase: Phase,
+    /// Timers for the rounds and the view changes
+    pub events: DelayQueue<Event>,
+    /// The blames we have for the leader of a view
+    pub blame_map: HashMap<View, HashMap<Replica, Vote>>,
+    /// Whether we blamed the current view
+    pub blamed: bool,
+    /// Whether there was work for the leaders when the timer of the current
+    /// round started
+    pub expect_work: bool,
+    /// The certificate of the view we quit
+    pub quit_cert: Option<Arc<Certificate>>,
+    /// The proofs of misbehaviour that we heard of
+    pub evidence: EvidencePool,
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
 }
 
 const EXTRA_SPACE:usize = 100;
@@ -89,6 +122,7 @@ impl Context {
     pub fn new(config:&Node,
         net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
         cli_send: UnboundedSender<Arc<ClientMsg>>,
+        commit_sink: CommitSink,
         apollo_enabled: bool,
     ) -> Self
     {
@@ -96,7 +130,10 @@ impl Context {
         let mut c = Context{
             num_nodes: config.num_nodes,
             num_faults: config.num_faults,
+            quorum: config.quorum_policy(FaultModel::Synchronous),
             myid: config.id,
+            block_size: config.block_size,
+            delta: config.delta,
             my_secret_key: match config.crypto_alg {
                 crypto::Algorithm::ED25519 => {
                     let mut sk_copy = config.secret_key_bytes.clone();
@@ -111,98 +148,65 @@ impl Context {
                     let kp = secp256k1::Keypair::from(sk);
                     Arc::new(Keypair::Secp256k1(kp))
                 }
-                _ => panic!("Unimplemented algorithm"),
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Arc::new(Keypair::Bls(kp))
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Arc::new(Keypair::Rsa(kp))
+                }
             },
             pub_key_map: HashMap::default(),
             net_send,
             cli_send,
+            commit_sink,
             storage: Storage::new(EXTRA_SPACE*config.block_size),
             view_leader: 0,
-            round_leader:config.num_faults-1,
-            last_f_leaders: LinkedHashMap::with_capacity(config.num_nodes),
-            eligible_leaders: Vec::with_capacity(config.num_nodes),
+            chain: VoteChain::new(config.num_nodes, config.num_faults, VrfBeacon::from_node(config), Reputation::from_node(config)),
+            committed_round: 0,
             view:0,
-            round: 1,
             last_seen_block: genesis_arc.clone(),
             last_voted_block: genesis_arc,
             is_client_apollo_enabled: apollo_enabled,
             req_ctr:0,
             vote_waiting:HashMap::default(),
             vote_ready:HashMap::default(),
-            vote_chain: HashMap::default(),
             block_parent_waiting:HashMap::default(),
             undelivered_blocks:HashMap::default(),
             block_processing_waiting: VecDeque::new(),
             response_waiting: VecDeque::new(),
             other_buf: VecDeque::new(),
+            new_view_waiting: VecDeque::new(),
+            future_view_votes: Vec::new(),
+            phase: Phase::Normal,
+            events: DelayQueue::new(),
+            blame_map: HashMap::default(),
+            blamed: false,
+            expect_work: false,
+            quit_cert: None,
+            evidence: EvidencePool::new(),
+            wal: Wal::from_node(config, "artemis"),
         };
-        for (id,mut pk_data) in &config.pk_map {
-            if *id == c.myid {
-                continue;
-            }
-            let pk = match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let kp = ed25519::PublicKey::decode(
-                        &mut pk_data
-                    ).expect("Failed to decode the secret key from the config");
-                    PublicKey::Ed25519(kp)
-                },
-                crypto::Algorithm::SECP256K1 => {
-                    let sk = secp256k1::PublicKey::decode(&pk_data).expect("Failed to decode the secret key from the config");
-                    PublicKey::Secp256k1(sk)
-                }
-                _ => panic!("Unimplemented algorithm"),
-            };
+        // Our own key is needed to check the certificates with our votes
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
             c.pub_key_map.insert(*id, pk);
         }
         // Initialize storage with the genesis block
         c.storage.add_delivered_block(
             c.last_seen_block.clone()
         );
-        // Initialize the leaders
-        for i in 0..config.num_faults {
-            c.last_f_leaders.insert(i, ());
-        }
-        for i in config.num_faults..config.num_nodes {
-            c.eligible_leaders.push(i);
-        }
-        log::info!("Using last f leaders: {:?}", c.last_f_leaders);
-        log::info!("Using eligible leaders: {:?}", c.eligible_leaders);
         c
     }
 
-    /// Goes to the next round
-    pub(crate) fn update_round(&mut self) {
-        // First update the round leader
-        let (new_leader, idx) = self.compute_next_round_leader();
-        self.round_leader = new_leader;
-        // Then update the round
-        // This order is important, otherwise, some other parts of the code may call next_round_leader() and changing the round before setting cx.round_leader = cx.next_round_leader() will cause problems (I LEARNT IT THE HARD WAY)
-        self.round += 1;
-        // Make the f^th leader elligible again
-        let (eligible_again,_) = self.last_f_leaders.pop_front().unwrap();
-        self.last_f_leaders.insert(self.round_leader, ());
-        self.eligible_leaders[idx] = eligible_again;
-    }
-
-    /// Returns the next round leader
-    /// This function will be called several times before finally updating the round leader
-    /// When changing the leader do not change the following variables:
-    /// - round
-    /// - eligible_leaders
-    pub(crate) fn next_round_leader(&self) -> Replica {
-        let (leader, _) = self.compute_next_round_leader();
-        leader
-    }
-
-    /// This is a private function that returns both the next leader and its index in the eligible leaders vector
-    fn compute_next_round_leader(&self) -> (Replica, usize) {
-        let data = (self.round+1).to_be_bytes();
-        let h = crypto::hash::do_hash(&data);
-        let idx = usize::from_be_bytes(h[24..].try_into().unwrap()) % self.eligible_leaders.len();
-        (self.eligible_leaders[idx], idx)
-    }
-
     /// Returns the number of nodes
     #[inline]
     pub const fn num_nodes(&self) -> usize {
@@ -215,6 +219,12 @@ impl Context {
         self.num_faults
     }
 
+    /// Returns the votes that the certificates need
+    #[inline]
+    pub const fn quorum(&self) -> &QuorumPolicy {
+        &self.quorum
+    }
+
     /// Returns the ID of this node
     #[inline]
     pub const fn myid(&self) -> usize {
@@ -227,10 +237,102 @@ impl Context {
         self.is_client_apollo_enabled
     }
 
-    /// Returns the current round 
-    /// We want to ensure read only access to this value
+    /// Returns the current round
     #[inline]
-    pub const fn round(&self) -> Round {
-        self.round
+    pub fn round(&self) -> Round {
+        self.chain.round()
+    }
+
+    /// Returns the current round leader
+    #[inline]
+    pub fn round_leader(&self) -> Replica {
+        self.chain.round_leader()
+    }
+
+    /// `delta` times `n` as a duration
+    pub(crate) fn deltas(&self, n: u64) -> Duration {
+        Duration::from_millis(n*self.delta)
+    }
+
+    /// Whether the delivered block with hash `h` extends the block `anc`
+    pub(crate) fn extends(&self, h: &Hash, anc: &Block) -> bool {
+        let mut b = match self.storage.delivered_block_from_hash(h) {
+            None => return false,
+            Some(x) => x,
+        };
+        while b.get_height() > anc.get_height() {
+            b = match self.storage.delivered_block_from_hash(&b.blk.header.prev) {
+                None => return false,
+                Some(x) => x,
+            };
+        }
+        b.get_hash() == anc.get_hash()
+    }
+
+    /// The blocks after the last committed block up to the block with hash
+    /// `h`, in order
+    pub(crate) fn uncommitted_blocks(&self, h: &Hash) -> Vec<Block> {
+        let mut blocks = VecDeque::new();
+        let mut tail = *h;
+        while !self.storage.is_committed_by_hash(&tail) {
+            let b = match self.storage.delivered_block_from_hash(&tail) {
+                None => break,
+                Some(x) => x,
+            };
+            // Only the genesis block is its own parent
+            if b.blk.header.prev == tail {
+                break;
+            }
+            tail = b.blk.header.prev;
+            blocks.push_front(b.as_ref().clone());
+        }
+        blocks.into()
+    }
+
+    /// Check a blame certificate, and return the view it blames if it is
+    /// valid
+    pub(crate) fn check_blame_cert(&self, c: &Certificate) -> Option<View> {
+        let view = match c.msg {
+            CertType::Blame(l, v) if l == v % self.num_nodes => v,
+            _ => return None,
+        };
+        if let Err(e) = c.verify(CertKind::Blame, &self.pub_key_map, &self.quorum, Threshold::Honest) {
+            log::warn!("Invalid blame certificate for view {}: {}", view, e);
+            return None;
+        }
+        Some(view)
+    }
+
+    /// Whether the leaders have something to do: the view leader has enough
+    /// transactions for a block, or the round leader has blocks to vote for
+    /// Check the proofs of misbehaviour in the block `b` that we do not have
+    /// yet, and store them. Returns false if any of them is invalid.
+    pub(crate) fn check_evidence(&mut self, b: &Block) -> bool {
+        for e in &b.blk.header.evidence {
+            if self.evidence.is_faulty(e.offender()) {
+                continue;
+            }
+            if let Err(err) = e.verify(&self.pub_key_map) {
+                log::warn!("Invalid evidence against {} in block {}: {}", e.offender(), b.get_height(), err);
+                return false;
+            }
+            self.evidence.add(e.clone());
+        }
+        true
+    }
+
+    pub(crate) fn has_work(&self) -> bool {
+        self.storage.get_tx_pool_size() >= self.block_size ||
+            self.last_seen_block.get_height() > self.last_voted_block.get_height()
+    }
+
+    /// Start the timer that blames the view, after `extra` time on top of
+    /// the usual wait
+    pub(crate) fn start_timer(&mut self, extra: Duration) {
+        // The view leader sends a block, and the round leader votes for it
+        // within 2\Delta, with some slack for building them
+        let timeout = self.deltas(4) + extra;
+        self.events.insert(Event::Timeout(self.view, self.round()), timeout);
+        self.expect_work = self.has_work();
     }
 }
@@ -1,6 +1,6 @@
 use types::{BlockTrait, artemis::{Block, ProtocolMsg, Transaction}};
 use types::WireReady;
-use super::context::Context;
+use super::{context::Context, blame::do_blame};
 use std::sync::Arc;
 
 /// Dispatch block is called by the view leader to create candidate blocks and send it to all the nodes
@@ -11,6 +11,7 @@ pub async fn do_new_block(txs: Vec<Arc<Transaction>>, cx:&mut Context)
     new_block.blk.header.prev = cx.last_seen_block.get_hash();
     new_block.blk.header.author = cx.myid();
     new_block.blk.header.height = cx.last_seen_block.get_height()+1;
+    new_block.blk.header.evidence = cx.evidence.pending();
     new_block.sig.origin = cx.myid();
     let mut new_block = new_block.init();
     new_block.sign(&cx.my_secret_key);
@@ -48,6 +49,17 @@ pub async fn on_receive_new_block_direct(cx:&mut Context, blk: Block) {
         log::warn!("Got an invalid signature");
         return;
     }
+    if !cx.check_evidence(&blk) {
+        return;
+    }
+    // The view leader must extend the last block it sent
+    if blk.blk.header.prev != cx.last_seen_block.get_hash() {
+        if blk.get_height() <= cx.last_seen_block.get_height() {
+            log::warn!("The view leader {} equivocated at height {}", cx.view_leader, blk.get_height());
+            do_blame(cx).await;
+        }
+        return;
+    }
     log::debug!("Successfully dealt with the view leader's block: {:?}", blk);
     // We have a valid signed and delivered block
     do_delivery(blk,cx);
@@ -58,11 +70,7 @@ pub fn do_delivery(blk: Block, cx:&mut Context) {
     // Add it to storage
     let b_hash = blk.get_hash();
     let b_rc = Arc::new(blk);
-    cx.storage.add_delivered_block(b_rc.clone());
-    // We have a new delivered block
-    if cx.last_seen_block.get_height() < b_rc.get_height() {
-        cx.last_seen_block = b_rc;
-    }
+    add_delivered_block(b_rc, cx);
     
     // If this was undelivered remove it
     cx.undelivered_blocks.remove(&b_hash);
@@ -78,11 +86,7 @@ pub fn do_delivery(blk: Block, cx:&mut Context) {
         // This block may trigger delivery of children
         if let Some(b) = cx.undelivered_blocks.remove(&child) {
             // We have a new delivered block
-            let b_rc = Arc::new(b);
-            cx.storage.add_delivered_block(b_rc.clone());
-            if cx.last_seen_block.get_height() < b_rc.get_height() {
-                cx.last_seen_block = b_rc;
-            }
+            add_delivered_block(Arc::new(b), cx);
         }
         // Check if any vote gets delivered because this block got delivered
         if let Some(v) = cx.vote_waiting.remove(&child) {
@@ -91,4 +95,18 @@ pub fn do_delivery(blk: Block, cx:&mut Context) {
         // Repeat these steps with the block (child) that was waiting for this block
         b_hash = child;
     }
-} 
\ No newline at end of file
+}
+
+/// Add a delivered block to the storage, and remove its transactions from
+/// our pool
+fn add_delivered_block(b_rc: Arc<Block>, cx: &mut Context) {
+    // The votes vouch for the blocks that we did not get from the view
+    // leader, but we still learn from their proofs
+    cx.check_evidence(&b_rc);
+    cx.storage.add_delivered_block(b_rc.clone());
+    cx.storage.clear(&b_rc.blk.body.tx_hashes);
+    // We have a new block that extends the last observed block
+    if b_rc.blk.header.prev == cx.last_seen_block.get_hash() {
+        cx.last_seen_block = b_rc;
+    }
+}
@@ -5,8 +5,11 @@ use super::*;
 /// Buffer and re-order messages by queueing messages. This function adds the message to the correct queues. So that when dequeueing we dequeue them correctly.
 pub fn buffer_message(sender: Replica, message: ProtocolMsg, cx: &mut Context) {
     match message {
-        ProtocolMsg::Invalid | ProtocolMsg::RawNewBlock(..) | ProtocolMsg::RawResponse(..) | ProtocolMsg::RawUCRVote(..) => 
+        ProtocolMsg::Invalid | ProtocolMsg::RawNewBlock(..) | ProtocolMsg::RawResponse(..) | ProtocolMsg::RawUCRVote(..) | 
+        ProtocolMsg::RawStatus(..) | ProtocolMsg::RawNewView(..) => 
         (),
+        x @ ProtocolMsg::NewView(..) => 
+            cx.new_view_waiting.push_back((sender, x)),
         ProtocolMsg::NewBlock(b) => 
             cx.block_processing_waiting.push_back(b),
         ProtocolMsg::Response(_, blk) => 
@@ -18,11 +21,18 @@ pub fn buffer_message(sender: Replica, message: ProtocolMsg, cx: &mut Context) {
 
 /// Process message dequeues buffered messages and tries reacting to them.
 /// We handle messaeges in the following order:
+/// - New views (`new_view_waiting`), so that we accept the blocks of the new view leader
 /// - New blocks (`block_processing_waiting`)
 /// - Responses (`response_waiting`)
 /// - Other messages (`other_buf`)
 pub async fn process_message(cx:&mut Context) 
 {
+    // Start the new views
+    while let Some((sender, msg)) = cx.new_view_waiting.pop_front() {
+        if let ProtocolMsg::NewView(c, votes, blocks) = msg {
+            on_receive_new_view(sender, c, votes, blocks, cx).await;
+        }
+    }
     // Process view leader's blocks
     while let Some(b) = cx.block_processing_waiting.pop_front() {
         on_receive_new_block_direct(cx, b).await;
@@ -44,8 +54,14 @@ pub async fn process_message(cx:&mut Context)
                 try_receive_round_vote(cx, sender, v).await,
             ProtocolMsg::Request(req_id,h) => 
                 handle_request(sender, req_id, h, cx).await,
-            ProtocolMsg::Blame(v) => 
-                on_receive_blame(v, cx).await,
+            ProtocolMsg::Blame(c) => 
+                on_receive_blame(c, cx).await,
+            ProtocolMsg::QuitView(c) => 
+                on_receive_quit(c, cx).await,
+            ProtocolMsg::Status(v, votes, blocks) => 
+                on_receive_status(sender, v, votes, blocks, cx).await,
+            ProtocolMsg::Evidence(e) => 
+                on_receive_evidence(e, cx).await,
             _ => panic!("unreachable"),
         }
     }
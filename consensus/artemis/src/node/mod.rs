@@ -6,6 +6,12 @@ pub use commit::*;
 mod blame;
 pub use blame::*;
 
+/// View change logic
+mod phase;
+pub use phase::*;
+mod view_change;
+pub use view_change::*;
+
 /// UCR logic
 mod round_vote;
 pub use round_vote::*;
@@ -0,0 +1,27 @@
+use types::artemis::{Round, View};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    /// The view leader sends blocks, and the round leaders vote for them
+    Normal,
+    /// We quit a view on a blame certificate, and are waiting for the votes
+    /// still in flight before sending our status to the next view leader
+    Quit,
+    /// We sent our status, and are waiting for the next view leader to start
+    /// its view
+    Wait,
+    /// We are the next view leader, and are collecting the status of the
+    /// others
+    StatusWait,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event {
+    /// The round of the view has not seen a vote in time
+    Timeout(View, Round),
+    /// We finished waiting after quitting the view before the given view
+    Status(View),
+    /// We, as the leader of the given view, finished collecting the status
+    /// messages
+    NewView(View),
+}
@@ -7,11 +7,16 @@ use futures::channel::mpsc::unbounded as unbounded_channel;
 use futures::{StreamExt, SinkExt};
 use types::artemis::{ClientMsg, Payload};
 use config::Node;
+use consensus::CommitSink;
 use super::{
     context::Context, 
     buffer_message, 
     process_message, 
     do_new_block,
+    on_timeout,
+    on_status_event,
+    on_new_view_event,
+    phase::{Event, Phase},
 };
 use std::sync::Arc;
 use crate::{NetSend,NetRecv,ClientSend,ClientRecv, node::round_vote::try_round_vote};
@@ -22,12 +27,13 @@ pub async fn reactor(
     net_send: NetSend,
     mut net_recv: NetRecv,
     cli_send: ClientSend,
+    commits: CommitSink,
     mut cli_recv: ClientRecv,
 ) {
     // Optimization to improve latency when the payloads are high
     let (send, mut recv) = unbounded_channel();
 
-    let mut cx = Context::new(config, net_send, send, is_client_apollo_enabled);
+    let mut cx = Context::new(config, net_send, send, commits, is_client_apollo_enabled);
     let block_size = config.block_size;
     let myid = config.id;
     let pl_size = config.payload*config.block_size;
@@ -45,19 +51,23 @@ pub async fn reactor(
                     }).collect();
                     ClientMsg::RawNewBlock(v, block_vec)
                 },
+                // The vote chains resend blocks without payloads
+                x @ ClientMsg::RawVoteChain(..) => x,
                 _ => continue,
             };
             cli_send.send(Arc::new(msg)).await.unwrap();
         };
     };
     #[cfg(feature="parallel")]
-    let rt = tokio::runtime::Builder::new_multi_thread()
+    let rt = consensus::ReactorRuntime::new(tokio::runtime::Builder::new_multi_thread()
             .enable_all()
             .build()
-            .unwrap();
+            .unwrap());
     #[cfg(not(feature="parallel"))]
     let rt = tokio::runtime::Handle::current();
     rt.spawn(payload_adder);
+    // Blame the first view if it does not make progress
+    cx.start_timer(std::time::Duration::ZERO);
     loop {
         tokio::select! {
             // Received a protocol message
@@ -65,7 +75,7 @@ pub async fn reactor(
                 if let None = pmsg_opt {
                     log::error!(
                         "Protocol message channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let (sender, pmsg) = pmsg_opt.unwrap();
                 // So basically, we extract all currently available messages and then replay them in order
@@ -75,6 +85,17 @@ pub async fn reactor(
                 }
                 process_message(&mut cx).await;
             },
+            // A timer fired
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                let ev = ev_opt.unwrap().expect("Timer error").into_inner();
+                log::debug!("Handling event {:?}", ev);
+                match ev {
+                    Event::Timeout(v, r) => on_timeout(v, r, &mut cx).await,
+                    Event::Status(v) => on_status_event(v, &mut cx).await,
+                    Event::NewView(v) => on_new_view_event(v, &mut cx).await,
+                }
+                process_message(&mut cx).await;
+            },
             // Received a client message
             tx_opt = cli_recv.next() => {
                 // We received a message from the client
@@ -85,7 +106,8 @@ pub async fn reactor(
             }
         }
         // Do we have sufficient commands, and are we the view leader?
-        if cx.storage.get_tx_pool_size() >= block_size && 
+        if cx.phase == Phase::Normal &&
+            cx.storage.get_tx_pool_size() >= block_size && 
             cx.view_leader == myid 
         {
             log::debug!(
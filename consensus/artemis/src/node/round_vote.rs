@@ -1,5 +1,7 @@
 use types::{BlockTrait, artemis::{ClientMsg, Payload, ProtocolMsg, Replica, UCRVote}};
 use super::*;
+use super::phase::Phase;
+use consensus::SignKind;
 use std::{collections::VecDeque, sync::Arc};
 
 /// Called to check if we are ready to do UCR voting
@@ -8,8 +10,12 @@ use std::{collections::VecDeque, sync::Arc};
 /// 2. Do I have new blocks?
 pub async fn try_round_vote(cx: &mut Context) {
     // I am not the next round leader, return
-    if cx.myid() != cx.round_leader {
-        log::trace!("I {} am not the leader for {}", cx.myid(), cx.round_leader);
+    if cx.myid() != cx.round_leader() {
+        log::trace!("I {} am not the leader for {}", cx.myid(), cx.round_leader());
+        return;
+    }
+    // We do not vote while changing views
+    if cx.phase != Phase::Normal {
         return;
     }
     // Do I have any new blocks that I can vote for?
@@ -27,6 +33,14 @@ pub async fn do_round_vote(cx: &mut Context) {
     v.hash = cx.last_seen_block.get_hash();
     v.round = cx.round();
     v.view = cx.view;
+    v.vrf = cx.chain.prove();
+    // Never vote for two chains in a round, even across restarts
+    let may_sign = cx.wal.as_mut()
+        .map_or(true, |w| w.record((SignKind::UcrVote, v.view, v.round), &v.hash));
+    if !may_sign {
+        log::error!("Already voted for another chain in round {} of view {}", v.round, v.view);
+        return;
+    }
     v.compute_sig(&cx.my_secret_key);
     // Multicast the vote
     let msg = Arc::new(ProtocolMsg::RawUCRVote(v.clone()));
@@ -41,6 +55,14 @@ pub async fn do_round_vote(cx: &mut Context) {
     let block_vec = block_vec.into_iter().map(|b|{
         (b,Payload::empty())
     }).collect();
+    // Resend the votes before ours, in case the clients missed the vote of a
+    // crashed round leader
+    let votes = cx.chain.since(cx.committed_round);
+    if !votes.is_empty() {
+        let blocks = cx.uncommitted_blocks(&cx.last_voted_block.get_hash());
+        let blocks = blocks.into_iter().map(|b| (b, Payload::empty())).collect();
+        cx.multicast_client(Arc::new(ClientMsg::RawVoteChain(votes, blocks))).await;
+    }
     let msg = Arc::new(ClientMsg::RawNewBlock(v.clone(), block_vec));
     cx.multicast_client(msg).await;
     // Process self vote
@@ -51,8 +73,17 @@ pub async fn do_round_vote(cx: &mut Context) {
 /// If it is, then we call `on_receive_round_vote`, otherwise we request it from the sender
 /// Also checks if we got votes from the future/past
 pub async fn try_receive_round_vote(cx:&mut Context, from: Replica, ucr_vote: UCRVote) {
+    // Keep the votes of a view that we have not started for when we start it
+    if ucr_vote.view > cx.view {
+        log::debug!("Got a vote for the future view {}", ucr_vote.view);
+        cx.future_view_votes.push((from, ucr_vote));
+        return;
+    }
     // We may get multiple votes from relay and do_round_vote
     if cx.round() > ucr_vote.round {
+        if let Some(e) = find_equivocation(&ucr_vote, cx) {
+            on_evidence(e, cx).await;
+        }
         log::debug!("Discarding duplicate votes for round {}, already in round {}", ucr_vote.round, cx.round());
         return;
     }
@@ -91,39 +122,75 @@ pub async fn try_receive_round_vote(cx:&mut Context, from: Replica, ucr_vote: UC
 
 /// `on_receive_vote` is called after ensuring that we have the chain, and we are ready to process this message for this round
 pub async fn on_receive_round_vote(cx:&mut Context, ucr_vote: UCRVote) {
-    // Check signature
     if cx.view != ucr_vote.view {
         log::warn!("Invalid view in UCR vote message");
         return;
     }
-    // The view is correct by now
-    if cx.myid() != cx.round_leader {
-        if !ucr_vote.check_sig(&cx.pub_key_map[&cx.round_leader]) {
-            log::warn!("Invalid signature on the UCR Vote");
-            return;
-        }
+    // The votes in flight when we quit the view go in our status
+    if cx.phase != Phase::Normal && cx.phase != Phase::Quit {
+        log::debug!("Discarding a vote for round {} while changing views", ucr_vote.round);
+        return;
+    }
+    // Only the new view leader casts the first vote of a view
+    if cx.chain.leader_of(&ucr_vote) != cx.round_leader() {
+        log::warn!("Got the first vote of view {} outside a new view", ucr_vote.view);
+        return;
+    }
+    if !accept_vote(cx, ucr_vote.clone()) {
+        return;
+    }
+    if cx.phase != Phase::Normal {
+        return;
     }
-    // The signature is correct by now
-
-    // Add this to our vote chain
-    cx.vote_chain.insert(ucr_vote.round, Arc::new(ucr_vote.clone()));
 
     // Trigger commit rule
-    if cx.round() > cx.num_faults() {
-        do_commit(cx);
-    }
+    do_commit(cx);
 
-    // Update the last voted block
-    let last_voted_block = cx.storage.delivered_block_from_hash(&ucr_vote.hash)
-        .expect("Obtained a vote for an unknown hash");
-    cx.last_voted_block = last_voted_block.clone();
-    
-    // Relay the vote
+    // Relay the vote to the leader of the next round
     let msg = Arc::new(ProtocolMsg::Relay(ucr_vote));
-    let job = cx.c_send(cx.next_round_leader(), msg).await;
-    
-    // Update leaders, and round
-    cx.update_round();
+    let job = cx.c_send(cx.round_leader(), msg).await;
     log::debug!("Going to the next round  {}", cx.round());
+    cx.start_timer(std::time::Duration::ZERO);
     job.await.unwrap();
-}
\ No newline at end of file
+}
+
+/// Add the vote `v` of the current round to our vote chain, if its leader
+/// signed it, and it extends the chain. Then go to the next round.
+///
+/// The caller must ensure that the block of `v` is delivered.
+pub(crate) fn accept_vote(cx: &mut Context, v: UCRVote) -> bool {
+    if v.round != cx.round() {
+        log::warn!("Got a vote for round {} in round {}", v.round, cx.round());
+        return false;
+    }
+    // Check signature
+    let leader = cx.chain.leader_of(&v);
+    if cx.myid() != leader && !v.check_sig(&cx.pub_key_map[&leader]) {
+        log::warn!("Invalid signature on the UCR Vote");
+        return false;
+    }
+    // The signature is correct by now
+
+    // Check that the vote does not abandon the blocks voted before
+    let block = match cx.storage.delivered_block_from_hash(&v.hash) {
+        None => {
+            log::warn!("Obtained a vote for an unknown hash");
+            return false;
+        }
+        Some(x) => x,
+    };
+    if !cx.extends(&v.hash, &cx.last_voted_block) {
+        log::warn!("The vote for round {} does not extend the last voted block", v.round);
+        return false;
+    }
+
+    // Add this to our vote chain, after checking the VRF proof of the leader
+    if !cx.chain.push(Arc::new(v)) {
+        log::warn!("Invalid VRF proof in the UCR vote for round {}", cx.round());
+        return false;
+    }
+
+    // Update the last voted block
+    cx.last_voted_block = block;
+    true
+}
@@ -0,0 +1,214 @@
+use types::BlockTrait;
+use types::artemis::{Block, Certificate, ClientMsg, GENESIS_BLOCK, Payload, ProtocolMsg, Replica, Round, UCRVote, View};
+use super::{context::Context, blame::{find_equivocation, has_quit, on_evidence, on_quit_view}, coordinator::do_delivery, phase::{Event, Phase}, round_vote::{accept_vote, try_receive_round_vote}};
+use std::sync::Arc;
+
+/// Called 2\Delta after we quit the view before `v`, once the votes in flight
+/// have reached us.
+///
+/// We send our vote chain to the leader of `v`, who starts its view with the
+/// highest chain it hears about.
+pub async fn on_status_event(v: View, cx: &mut Context) {
+    if cx.phase != Phase::Quit || cx.view+1 != v {
+        return;
+    }
+    cx.view = v;
+    cx.view_leader = v % cx.num_nodes();
+    cx.blamed = false;
+    // Give the leader the time to collect the statuses before blaming it
+    cx.start_timer(cx.deltas(2));
+    if cx.view_leader == cx.myid() {
+        log::debug!("Collecting the statuses for view {}", v);
+        cx.phase = Phase::StatusWait;
+        let wait = cx.deltas(2);
+        cx.events.insert(Event::NewView(v), wait);
+        return;
+    }
+    cx.phase = Phase::Wait;
+    let votes = cx.chain.since(cx.committed_round);
+    if votes.is_empty() {
+        return;
+    }
+    let blocks = cx.uncommitted_blocks(&cx.last_voted_block.get_hash());
+    let msg = Arc::new(ProtocolMsg::RawStatus(v, votes, blocks));
+    cx.send(cx.view_leader, msg).await;
+}
+
+pub async fn on_receive_status(sender: Replica, v: View, votes: Vec<UCRVote>, blocks: Vec<Block>, cx: &mut Context) {
+    if v != cx.view || cx.phase != Phase::StatusWait {
+        log::debug!("Status for view {} from {} not meant for us", v, sender);
+        return;
+    }
+    // We only move to a higher chain
+    match votes.last() {
+        Some(h) if h.round >= cx.round() => {}
+        _ => return,
+    }
+    check_chain(&votes, cx).await;
+    deliver_chain(blocks, cx);
+    if !adopt_chain(votes, cx) {
+        log::warn!("Invalid vote chain in the status from {}", sender);
+    }
+}
+
+/// Called when we, as the leader of `v`, finished collecting the statuses.
+///
+/// We start the view with our vote on the highest chain, and on the blocks
+/// that we saw after it, so that they are not lost if the round leader did
+/// not vote for them.
+pub async fn on_new_view_event(v: View, cx: &mut Context) {
+    if cx.view != v || cx.phase != Phase::StatusWait {
+        return;
+    }
+    let cert = match &cx.quit_cert {
+        None => return,
+        Some(c) => c.as_ref().clone(),
+    };
+    let mut vote = UCRVote::new();
+    vote.hash = if cx.extends(&cx.last_seen_block.get_hash(), &cx.last_voted_block) {
+        cx.last_seen_block.get_hash()
+    } else {
+        cx.last_voted_block.get_hash()
+    };
+    vote.round = cx.round();
+    vote.view = v;
+    vote.vrf = cx.chain.prove();
+    vote.compute_sig(&cx.my_secret_key);
+    log::info!("Starting view {} in round {}", v, vote.round);
+
+    let mut votes = cx.chain.since(cx.committed_round);
+    votes.push(vote.clone());
+    let blocks = cx.uncommitted_blocks(&vote.hash);
+    let msg = Arc::new(ProtocolMsg::RawNewView(cert, votes.clone(), blocks.clone()));
+    cx.multicast(msg).await;
+    let block_vec = blocks.into_iter().map(|b| (b, Payload::empty())).collect();
+    cx.multicast_client(Arc::new(ClientMsg::RawVoteChain(votes, block_vec))).await;
+
+    if !accept_vote(cx, vote) {
+        log::error!("Failed to accept our own vote for view {}", v);
+        return;
+    }
+    start_view(cx).await;
+}
+
+/// Called when the leader of the view after the one blamed by `c` starts its
+/// view with the vote chain `votes`
+pub async fn on_receive_new_view(sender: Replica, c: Certificate, votes: Vec<UCRVote>, blocks: Vec<Block>, cx: &mut Context) {
+    let w = match cx.check_blame_cert(&c) {
+        None => {
+            log::warn!("Invalid blame certificate in a new view from {}", sender);
+            return;
+        }
+        Some(w) => w,
+    };
+    let v = w+1;
+    if v < cx.view || (v == cx.view && cx.phase != Phase::Wait) {
+        log::debug!("Already in view {}, got the start of view {}", cx.view, v);
+        return;
+    }
+    let first = match votes.last() {
+        None => return,
+        Some(x) => x.clone(),
+    };
+    if first.view != v || votes.windows(2).any(|p| p[0].round+1 != p[1].round) {
+        log::warn!("Invalid vote chain in the new view from {}", sender);
+        return;
+    }
+    // The view must extend the highest vote we accepted, which the leader got
+    // in our status
+    check_chain(&votes, cx).await;
+    let is_valid = first.round >= cx.round() && {
+        deliver_chain(blocks, cx);
+        adopt_chain(votes, cx)
+    };
+    if !is_valid {
+        log::warn!("The leader of view {} did not start on the highest vote chain", v);
+        // Make sure that we still take part in the view change, and blame it
+        if !has_quit(w, cx) {
+            on_quit_view(Arc::new(c), cx).await;
+        }
+        return;
+    }
+    log::info!("Started view {} in round {}", v, first.round);
+    cx.view = v;
+    cx.view_leader = v % cx.num_nodes();
+    start_view(cx).await;
+}
+
+/// Start the view, after accepting the first vote of the view
+async fn start_view(cx: &mut Context) {
+    cx.phase = Phase::Normal;
+    cx.blamed = false;
+    cx.quit_cert = None;
+    let view = cx.view;
+    cx.blame_map.retain(|v, _| *v >= view);
+    // The view leader extends the block of the first vote
+    cx.last_seen_block = cx.last_voted_block.clone();
+    cx.start_timer(std::time::Duration::ZERO);
+    // Handle the votes of this view that came before it started
+    let (now, later) = std::mem::take(&mut cx.future_view_votes)
+        .into_iter()
+        .partition(|(_, v)| v.view == view);
+    cx.future_view_votes = later;
+    for (sender, v) in now {
+        try_receive_round_vote(cx, sender, v).await;
+    }
+}
+
+/// Look for equivocations in a vote chain, before we switch to it
+async fn check_chain(votes: &[UCRVote], cx: &mut Context) {
+    for v in votes {
+        if let Some(e) = find_equivocation(v, cx) {
+            on_evidence(e, cx).await;
+        }
+    }
+}
+
+/// Deliver the blocks of a vote chain, in order. The votes vouch for them, so
+/// we do not need the signature of the view leader that made them.
+fn deliver_chain(blocks: Vec<Block>, cx: &mut Context) {
+    for b in blocks {
+        if !cx.storage.is_delivered_by_hash(&b.get_hash()) &&
+            cx.storage.is_delivered_by_hash(&b.blk.header.prev)
+        {
+            do_delivery(b, cx);
+        }
+    }
+}
+
+/// Extend our vote chain with `votes`, which are in order of rounds. If the
+/// chains disagree on an uncommitted round, its leader equivocated, and we
+/// go back to that round to follow `votes`.
+///
+/// Returns false if some vote is invalid.
+fn adopt_chain(votes: Vec<UCRVote>, cx: &mut Context) -> bool {
+    for v in votes {
+        if v.round < cx.round() {
+            match cx.chain.votes.get(&v.round) {
+                Some(mine) if mine.hash == v.hash && mine.view == v.view => continue,
+                // Nothing changes before the last commit
+                _ if v.round <= cx.committed_round => continue,
+                _ => {
+                    log::info!("Switching to another vote chain from round {}", v.round);
+                    rewind(v.round, cx);
+                }
+            }
+        }
+        if v.round > cx.round() {
+            log::warn!("Missing the votes from round {} to {}", cx.round(), v.round);
+            return false;
+        }
+        if !cx.storage.is_delivered_by_hash(&v.hash) || !accept_vote(cx, v) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Go back to the start of `round`, which is after the last commit
+fn rewind(round: Round, cx: &mut Context) {
+    cx.chain.rewind(round);
+    cx.last_voted_block = cx.chain.highest()
+        .and_then(|v| cx.storage.delivered_block_from_hash(&v.hash))
+        .unwrap_or_else(|| Arc::new(GENESIS_BLOCK));
+}
@@ -1,3 +1,6 @@
 mod reactor;
 
-pub use reactor::*;
\ No newline at end of file
+pub use reactor::*;
+
+mod protocol;
+pub use protocol::*;
@@ -0,0 +1,96 @@
+use config::Node;
+use consensus::{NodeOptions, Reactor};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{UnboundedReceiver, channel};
+use types::dummy::{Block, ProtocolMsg, Transaction};
+use std::sync::Arc;
+
+/// The dummy protocol, for `consensus::run`
+pub struct Dummy;
+
+impl consensus::Protocol for Dummy {
+    const NAME: &'static str = "dummy";
+    const FAULT_MODEL: types::FaultModel = types::FaultModel::Synchronous;
+
+    type ProtocolMsg = ProtocolMsg;
+    type ClientMsg = Block;
+    type Transaction = Transaction;
+
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor {
+        // Setup client network
+        let client_network = net::tokio_manager::Protocol::<Transaction, Block>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (cli_send, cli_recv) = 
+        cli_net_rt.block_on(
+            client_network.client_setup(
+                config.client_ip(),
+                util::codec::EnCodec::new(),
+                util::codec::Decodec::new()
+            )
+        );
+        let mut cli_recv = consensus::merge_local_txs(cli_net_rt, cli_recv, local_txs);
+
+        // Setup the protocol network
+        let protocol_network = net::tokio_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (net_send, mut net_recv) = 
+        prot_net_rt.block_on(
+            protocol_network.server_setup(
+                config.net_map.clone(), 
+                util::codec::EnCodec::new(), 
+                util::codec::Decodec::new()
+            )
+        );
+
+        let config = config.clone();
+        let commits = opts.commit_sink();
+        Box::pin(async move {
+            // The reactor uses bounded channels, so bridge them to the
+            // network
+            let (r_net_send, mut r_net_recv) = channel(100_000);
+            let (r_net_in_send, r_net_in_recv) = channel(100_000);
+            let (r_cli_send, mut r_cli_recv) = channel(100_000);
+            let (r_cli_in_send, r_cli_in_recv) = channel(100_000);
+            tokio::spawn(async move {
+                while let Some((r, m)) = r_net_recv.recv().await {
+                    if net_send.send((r, Arc::new(m))).is_err() {
+                        break;
+                    }
+                }
+            });
+            tokio::spawn(async move {
+                while let Some((_, m)) = net_recv.recv().await {
+                    if r_net_in_send.send(m).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            tokio::spawn(async move {
+                while let Some(b) = r_cli_recv.recv().await {
+                    if cli_send.send(Arc::new(b)).is_err() {
+                        break;
+                    }
+                }
+            });
+            tokio::spawn(async move {
+                while let Some(tx) = cli_recv.recv().await {
+                    if r_cli_in_send.send(tx).await.is_err() {
+                        break;
+                    }
+                }
+            });
+            crate::reactor(
+                &config,
+                r_net_send,
+                r_net_in_recv,
+                r_cli_send,
+                commits,
+                r_cli_in_recv
+            ).await
+        })
+    }
+}
@@ -1,6 +1,7 @@
 use tokio::sync::mpsc::{Sender, Receiver};
-use types::dummy::{Block, Height, Propose, ProtocolMsg, Replica, Transaction};
+use types::{CommittedBlock, dummy::{Block, Height, Propose, ProtocolMsg, Replica, Transaction}};
 use config::Node;
+use consensus::CommitSink;
 use std::sync::Arc;
 
 pub async fn reactor(
@@ -8,6 +9,7 @@ pub async fn reactor(
     net_send: Sender<(Replica, ProtocolMsg)>,
     mut net_recv: Receiver<ProtocolMsg>,
     cli_send: Sender<Block>,
+    mut commits: CommitSink,
     mut cli_recv: Receiver<Transaction>
 ) {
     tokio::spawn(async move {
@@ -41,7 +43,8 @@ pub async fn reactor(
          txs.push(Arc::new(tx));
          println!("Got new transactions [{}] from a client", num_new_tx);
          if txs.len() == config.block_size {
-             let b = Block::with_tx(txs.drain(..).collect());
+             let block_txs: Vec<_> = txs.drain(..).collect();
+             let b = Block::with_tx(block_txs.clone());
              if let Err(e) = cli_send.send(b.clone()).await {
                  println!("Failed to send the blocks to the clients");
                  println!("Error: {}", e);
@@ -49,6 +52,12 @@ pub async fn reactor(
              let mut block = b;
              block.header.height = height;
              block.hash = block.compute_hash();
+             // There is no agreement here, so we commit what we propose
+             commits.publish(CommittedBlock{
+                 block: Arc::new(block.clone()),
+                 txs: block_txs.into_iter().map(Some).collect(),
+                 justification: None,
+             });
              let new_b = Propose::new(block.hash);
              if let Err(e) = net_send.send(
                  (send_all,ProtocolMsg::RawNewProposal(new_b, block))).await {
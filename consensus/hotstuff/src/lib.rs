@@ -0,0 +1,11 @@
+/// Chained HotStuff consensus reactors
+///
+/// Unlike the other protocols in this repo, HotStuff does not rely on
+/// synchrony for safety: it tolerates f < n/3 faults, and a pacemaker moves
+/// the nodes to the next view when the leader does not make progress.
+
+pub mod node;
+pub mod client;
+
+mod protocol;
+pub use protocol::*;
@@ -0,0 +1,74 @@
+use types::{CommittedBlock, hotstuff::{Block, Certificate, ClientMsg, Payload}};
+use super::{context::{Context, qc_hash, qc_view}, pacemaker::enter_view};
+use std::sync::Arc;
+
+/// Use a certificate that we checked: update the highest certificate and the
+/// lock, commit the blocks with a three-chain on top of them, and move past
+/// the certified view.
+///
+/// The chain b0 <- b1 <- b2 certified by `qc` commits b0 if the three blocks
+/// are from consecutive views.
+pub fn process_qc(qc: &Certificate, cx: &mut Context) {
+    let view = qc_view(qc);
+    let b2 = match cx.storage.delivered_block_from_hash(&qc_hash(qc)) {
+        None => {
+            log::debug!("Certificate for an undelivered block in view {}", view);
+            cx.pending_qc = Some(qc.clone());
+            return;
+        }
+        Some(b) => b,
+    };
+    if view > qc_view(&cx.high_qc) {
+        cx.high_qc = qc.clone();
+    }
+    if let Some(b1) = cx.parent_of(&b2) {
+        let v1 = cx.view_of[&b1.hash];
+        cx.preferred_view = std::cmp::max(cx.preferred_view, v1);
+        if let Some(b0) = cx.parent_of(&b1) {
+            let v0 = cx.view_of[&b0.hash];
+            if view == v1+1 && v1 == v0+1 {
+                commit_block(b0, qc, cx);
+            }
+        }
+    }
+    if view >= cx.view {
+        cx.timeouts = 0;
+        enter_view(view+1, cx);
+    }
+}
+
+/// Commit this block and all its ancestors, with the certificate `qc` of the
+/// three-chain that commits them
+pub fn commit_block(b: Arc<Block>, qc: &Certificate, cx: &mut Context) {
+    // Check if we have already committed this block and its ancestors
+    if cx.storage.is_committed_by_hash(&b.hash) {
+        return;
+    }
+    let mut to_commit = vec![b];
+    while let Some(parent) = cx.parent_of(to_commit.last().unwrap()) {
+        if cx.storage.is_committed_by_hash(&parent.hash) {
+            break;
+        }
+        to_commit.push(parent);
+    }
+
+    // Ship the blocks to the clients, in order
+    for b in to_commit.into_iter().rev() {
+        log::debug!("Committing block at height {}", b.header.height);
+        let txs = cx.storage.take_committed(&b.body.tx_hashes);
+        cx.commit_sink.publish(CommittedBlock{
+            block: b.clone(),
+            txs,
+            justification: Some(qc.clone()),
+        });
+        // The empty blocks only drive the commits of the blocks before
+        if !b.body.tx_hashes.is_empty() {
+            let payload = Payload::with_payload(cx.payload);
+            let msg = ClientMsg::RawNewBlock(b.as_ref().clone(), payload);
+            if let Err(e) = cx.cli_send.send(Arc::new(msg)) {
+                log::warn!("Error sending the block to the client: {}", e);
+            }
+        }
+        cx.storage.add_committed_block(b);
+    }
+}
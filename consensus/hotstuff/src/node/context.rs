@@ -0,0 +1,249 @@
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::time::DelayQueue;
+use types::hotstuff::{Block, CertType, Certificate, GENESIS_BLOCK, Replica, Storage, View, ClientMsg, ProtocolMsg, Propose};
+use types::{CertKind, FaultModel, QuorumPolicy, Threshold};
+use config::Node;
+use consensus::{CommitSink, SignKind, Wal};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use crypto::hash::{EMPTY_HASH, Hash};
+use std::{sync::Arc, time::Duration};
+
+use super::pacemaker::Event;
+
+pub struct Context {
+    /// Networking context
+    pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    pub cli_send: UnboundedSender<Arc<ClientMsg>>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
+
+    /// Data context
+    pub num_nodes: usize,
+    pub myid: Replica,
+    /// The votes in a certificate, and the new view messages that the
+    /// leader waits for: n-f
+    pub quorum: QuorumPolicy,
+    pub payload:usize,
+    pub block_size: usize,
+    pub delta: u64,
+
+    /// PKI
+    pub my_secret_key: Keypair,
+    pub pub_key_map:HashMap<Replica, PublicKey>,
+
+    /// State context
+    pub storage: Storage,
+    /// The view in which each delivered block was proposed
+    pub view_of: HashMap<Hash, View>,
+    /// The votes for the proposal of a view that we collect as the next
+    /// leader
+    pub vote_map: HashMap<(View, Hash), Certificate>,
+    /// The certificate of the highest certified block, which the next block
+    /// extends
+    pub high_qc: Certificate,
+    /// A certificate that we formed for a block that we have not seen yet
+    pub pending_qc: Option<Certificate>,
+    /// Proposals whose parents we have not seen yet, by their parent
+    pub waiting: HashMap<Hash, Vec<Arc<Propose>>>,
+    /// The proposal of every delivered block, for the nodes that fetch them
+    pub proposals: HashMap<Hash, Arc<Propose>>,
+
+    /// Safety rules
+    /// The last view we voted in
+    pub last_voted_view: View,
+    /// The view of the parent of the highest certified block that we saw
+    /// certified. We only vote for blocks that extend a certificate at least
+    /// as high.
+    pub preferred_view: View,
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
+
+    /// Pacemaker context
+    pub view: View,
+    pub events: DelayQueue<Event>,
+    /// The number of views in a row that timed out, for the backoff
+    pub timeouts: u32,
+    /// The last view we proposed in
+    pub proposed_view: View,
+    /// The nodes that sent us a new view message for a view that we lead
+    pub new_views: HashMap<View, HashSet<Replica>>,
+}
+
+const EXTRA_SPACE:usize = 10;
+/// How many proposals we send at most for a fetch request
+pub const FETCH_DEPTH: usize = 32;
+
+impl Context {
+    pub fn new(
+        config: &Node,
+        net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+        cli_send: UnboundedSender<Arc<ClientMsg>>,
+        commit_sink: CommitSink,
+    ) -> Self {
+        let genesis_arc = Arc::new(GENESIS_BLOCK);
+        let mut c = Context {
+            net_send,
+            cli_send,
+            commit_sink,
+            num_nodes: config.num_nodes,
+            my_secret_key: match config.crypto_alg {
+                crypto::Algorithm::ED25519 => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = ed25519::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Ed25519(kp)
+                },
+                crypto::Algorithm::SECP256K1 => {
+                    let sk_copy = config.secret_key_bytes.clone();
+                    let sk = secp256k1::SecretKey::from_bytes(sk_copy).expect("Failed to decode the secret key from the config");
+                    let kp = secp256k1::Keypair::from(sk);
+                    Keypair::Secp256k1(kp)
+                }
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Bls(kp)
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Rsa(kp)
+                }
+            },
+            pub_key_map: HashMap::default(),
+            myid: config.id,
+            quorum: config.quorum_policy(FaultModel::PartiallySynchronous),
+            payload: config.payload*config.block_size,
+            block_size: config.block_size,
+            delta: config.delta,
+            storage: Storage::new(EXTRA_SPACE*config.block_size),
+            view_of: HashMap::default(),
+            vote_map: HashMap::default(),
+            high_qc: Certificate::empty_cert(),
+            pending_qc: None,
+            waiting: HashMap::default(),
+            proposals: HashMap::default(),
+            last_voted_view: 0,
+            preferred_view: 0,
+            wal: Wal::from_node(config, "hotstuff"),
+            view: 0,
+            events: DelayQueue::new(),
+            timeouts: 0,
+            proposed_view: 0,
+            new_views: HashMap::default(),
+        };
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            c.pub_key_map.insert(*id, pk);
+        }
+
+
+        // Initialize storage
+        c.storage.add_delivered_block(genesis_arc.clone());
+        c.storage.add_committed_block(genesis_arc);
+        c.view_of.insert(GENESIS_BLOCK.hash, 0);
+
+        // Never vote or propose again in the views we did before a restart
+        if let Some(w) = &c.wal {
+            if let Some((view, _)) = w.highest(SignKind::Vote) {
+                c.last_voted_view = view;
+            }
+            if let Some((view, _)) = w.highest(SignKind::Proposal) {
+                c.proposed_view = view;
+            }
+        }
+        c
+    }
+
+    /// The leader of `view`, in turn
+    pub fn leader_of(&self, view: View) -> Replica {
+        view % self.num_nodes
+    }
+
+    pub fn deltas(&self, n: u64) -> Duration {
+        Duration::from_millis(n*self.delta)
+    }
+
+    /// Whether `c` certifies a block with n-f correctly signed votes from
+    /// distinct nodes. The empty certificate certifies the genesis block.
+    pub fn is_valid_cert(&self, c: &Certificate) -> bool {
+        match c.msg {
+            CertType::DEFAULT => c.num_votes() == 0,
+            _ => c.verify(CertKind::Vote, &self.pub_key_map, &self.quorum, Threshold::Quorum).is_ok(),
+        }
+    }
+
+    /// Returns the certificate that we ship in proposals. With BLS keys the
+    /// votes are aggregated into a single signature.
+    pub fn finalize_cert(&self, cert: Certificate) -> Certificate {
+        if !matches!(self.my_secret_key, Keypair::Bls(_)) {
+            return cert;
+        }
+        match cert.aggregate() {
+            Ok(agg) => agg,
+            Err(e) => {
+                log::warn!("Failed to aggregate the certificate: {}", e);
+                cert
+            }
+        }
+    }
+
+    /// The parent of a delivered block, unless it is the genesis block
+    pub fn parent_of(&self, b: &Block) -> Option<Arc<Block>> {
+        if b.header.height == 0 {
+            return None;
+        }
+        self.storage.delivered_block_from_hash(&b.header.prev)
+    }
+
+    /// Whether some transactions on the highest certified chain still wait
+    /// for blocks on top of them to commit, at us or at the others. The
+    /// others learn that a block commits from the certificate of the third
+    /// block on top of it, which only the next proposal carries.
+    pub fn has_pending_commits(&self) -> bool {
+        let mut b = self.storage.delivered_block_from_hash(&qc_hash(&self.high_qc));
+        let mut depth = 0;
+        while let Some(x) = b {
+            if depth > 2 && self.storage.is_committed_by_hash(&x.hash) {
+                break;
+            }
+            if !x.body.tx_hashes.is_empty() {
+                return true;
+            }
+            depth += 1;
+            b = self.parent_of(&x);
+        }
+        false
+    }
+
+    /// Record that we sign a message of `kind` for `b` in this view, and
+    /// return whether we may
+    pub fn on_sign(&mut self, kind: SignKind, b: &Block) -> bool {
+        let view = self.view;
+        self.wal.as_mut()
+            .map_or(true, |w| w.record((kind, view, b.header.height), &b.hash))
+    }
+}
+
+/// The view of a certificate, 0 for the genesis block
+pub fn qc_view(c: &Certificate) -> View {
+    match c.msg {
+        CertType::Vote(v, _) => v,
+        _ => 0,
+    }
+}
+
+/// The block that a certificate certifies
+pub fn qc_hash(c: &Certificate) -> Hash {
+    match c.msg {
+        CertType::Vote(_, h) => h,
+        _ => EMPTY_HASH,
+    }
+}
@@ -0,0 +1,117 @@
+/// The pacemaker moves the nodes through the views.
+///
+/// A node enters the next view when it sees a certificate for the proposal
+/// of its view, or when the view times out. On a timeout, it sends the
+/// certificate of its highest certified block to the next leader in a new
+/// view message. The leader then proposes on the highest certificate among
+/// n-f of them. The timeout doubles with every view in a row that times out.
+
+use types::{Threshold, hotstuff::{Certificate, ProtocolMsg, Replica, View}};
+use super::{commit::process_qc, context::Context, proposal::do_propose};
+use std::{sync::Arc, time::Duration};
+
+/// The most times that the timeout doubles
+const MAX_BACKOFF: u32 = 6;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event {
+    /// The view did not make progress in time
+    Timeout(View),
+    /// As the leader of the view, propose what we have even if it is less
+    /// than a block, to commit the blocks before
+    Propose(View),
+}
+
+/// How long we wait for progress in a view
+fn timeout(cx: &Context) -> Duration {
+    cx.deltas(4) * (1 << std::cmp::min(cx.timeouts, MAX_BACKOFF))
+}
+
+/// Move to `view`, if it is ahead of us
+pub fn enter_view(view: View, cx: &mut Context) {
+    if view <= cx.view {
+        return;
+    }
+    log::debug!("Entering view {}", view);
+    cx.view = view;
+    cx.new_views.retain(|v, _| *v >= view);
+    cx.vote_map.retain(|(v, _), _| *v+1 >= view);
+    cx.events.insert(Event::Timeout(view), timeout(cx));
+    if cx.leader_of(view) == cx.myid {
+        cx.events.insert(Event::Propose(view), cx.deltas(2));
+    }
+}
+
+/// The view timed out: send our highest certificate to the next leader, and
+/// move on
+pub fn on_timeout(view: View, cx: &mut Context) {
+    if view != cx.view {
+        return;
+    }
+    // Nobody has anything to propose
+    if cx.storage.get_tx_pool_size() == 0 && !cx.has_pending_commits() {
+        cx.events.insert(Event::Timeout(view), timeout(cx));
+        return;
+    }
+    log::info!("View {} timed out", view);
+    cx.timeouts += 1;
+    let next = view+1;
+    let leader = cx.leader_of(next);
+    let qc = cx.high_qc.clone();
+    enter_view(next, cx);
+    if leader == cx.myid {
+        on_new_view(cx.myid, next, qc, cx);
+    } else if let Err(e) = cx.net_send.send((leader, Arc::new(ProtocolMsg::NewView(next, qc)))) {
+        log::warn!("Failed to send a new view message: {}", e);
+    }
+}
+
+/// A new view message from `sender` for a view that we lead
+pub fn on_new_view(sender: Replica, view: View, qc: Certificate, cx: &mut Context) {
+    if cx.leader_of(view) != cx.myid || view < cx.view {
+        return;
+    }
+    if cx.is_valid_cert(&qc) {
+        process_qc(&qc, cx);
+    } else {
+        log::warn!("Invalid certificate in the new view message from {}", sender);
+    }
+    let is_met = {
+        let senders = cx.new_views.entry(view).or_default();
+        senders.insert(sender);
+        cx.quorum.is_met(Threshold::Quorum, senders.iter().cloned())
+    };
+    // The others moved on without us
+    if is_met {
+        enter_view(view, cx);
+    }
+}
+
+/// Whether we can propose in the current view: as its leader, once we know
+/// that our highest certificate is at least as high as the certificates of
+/// n-f nodes
+pub fn can_propose(cx: &Context) -> bool {
+    cx.leader_of(cx.view) == cx.myid &&
+        cx.proposed_view < cx.view &&
+        (super::context::qc_view(&cx.high_qc)+1 == cx.view ||
+            cx.new_views.get(&cx.view).map_or(false, |s| cx.quorum.is_met(Threshold::Quorum, s.iter().cloned())))
+}
+
+/// Propose a partial block if we still cannot fill one, but the blocks before
+/// need more blocks on top of them to commit
+pub fn on_propose_event(view: View, cx: &mut Context) {
+    if view != cx.view || cx.leader_of(view) != cx.myid || cx.proposed_view >= view {
+        return;
+    }
+    if !can_propose(cx) {
+        // We are still waiting for the certificates of the others
+        cx.events.insert(Event::Propose(view), cx.deltas(1));
+        return;
+    }
+    let pool = cx.storage.get_tx_pool_size();
+    if pool < cx.block_size && (pool > 0 || cx.has_pending_commits()) {
+        log::debug!("Proposing a partial block in view {}", view);
+        let txs = cx.storage.cleave(pool);
+        do_propose(txs, cx);
+    }
+}
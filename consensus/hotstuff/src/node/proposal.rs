@@ -0,0 +1,435 @@
+use types::{EVERYONE, hotstuff::{Block, CertType, Certificate, Propose, ProtocolMsg, Replica, Transaction, Vote, View}};
+use consensus::SignKind;
+use crypto::hash::Hash;
+use super::{commit::process_qc, context::{Context, FETCH_DEPTH, qc_hash, qc_view}, pacemaker::enter_view, vote::on_vote};
+use std::sync::Arc;
+
+/// The view in the header of a block, so that the same block is never
+/// proposed in two views
+fn view_tag(view: View) -> Vec<u8> {
+    (view as u64).to_le_bytes().to_vec()
+}
+
+/// Propose a block on top of the highest certified block. The caller must
+/// check that we can propose in this view.
+pub fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
+    let parent = cx.storage.delivered_block_from_hash(&qc_hash(&cx.high_qc))
+        .expect("The highest certified block is not delivered");
+    let mut new_block = Block::with_tx(txs);
+    new_block.header.author = cx.myid;
+    new_block.header.prev = parent.hash;
+    new_block.header.height = parent.header.height+1;
+    new_block.header.extra = view_tag(cx.view);
+    new_block.hash = new_block.compute_hash();
+
+    if !cx.on_sign(SignKind::Proposal, &new_block) {
+        panic!("Failed to record the proposal of view {} in the WAL", cx.view);
+    }
+    cx.proposed_view = cx.view;
+
+    let mut p = Propose::new();
+    p.block_hash = new_block.hash;
+    p.cert = cx.high_qc.clone();
+    p.view = cx.view;
+    p.proof = match cx.my_secret_key.sign(&p.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the new proposal: {}", e);
+        },
+        Ok(sig) => sig,
+    };
+    log::debug!("Proposing block at height {} in view {}", new_block.header.height, cx.view);
+    let msg = ProtocolMsg::RawNewProposal(p.clone(), new_block.clone());
+    if let Err(e) = cx.net_send.send((EVERYONE, Arc::new(msg))) {
+        log::warn!("Failed to send the proposal: {}", e);
+    }
+    p.block = Some(Arc::new(new_block));
+    on_receive_proposal(Arc::new(p), cx);
+}
+
+/// Check the proposal on its own: the signature of the leader of its view,
+/// and the certificate of its parent
+fn check_proposal(p: &Propose, cx: &Context) -> bool {
+    let b = p.block.as_ref().unwrap();
+    let leader = cx.leader_of(p.view);
+    let pk = match cx.pub_key_map.get(&leader) {
+        None => return false,
+        Some(pk) => pk,
+    };
+    if b.header.author != leader ||
+        p.block_hash != b.hash ||
+        p.parent() != b.header.prev ||
+        b.header.extra != view_tag(p.view) ||
+        !pk.verify(&p.sign_data(), &p.proof)
+    {
+        log::warn!("Got an incorrectly signed proposal for view {}", p.view);
+        return false;
+    }
+    if qc_view(&p.cert) >= p.view || !cx.is_valid_cert(&p.cert) {
+        log::warn!("Invalid certificate in the proposal for view {}", p.view);
+        return false;
+    }
+    true
+}
+
+/// Deliver a proposal, use its certificate, and vote for it if it is safe.
+/// The proposals that waited for its block follow.
+pub fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) {
+    // A queue rather than recursion, as a node that catches up may deliver a
+    // long chain at once
+    let mut ready = vec![p];
+    while let Some(p) = ready.pop() {
+        ready.extend(deliver_proposal(p, cx));
+    }
+}
+
+/// Deliver a proposal whose parent we have, and return the proposals that
+/// waited for it
+fn deliver_proposal(p: Arc<Propose>, cx: &mut Context) -> Vec<Arc<Propose>> {
+    let b = p.block.clone().unwrap();
+    if cx.storage.is_delivered_by_hash(&b.hash) {
+        log::debug!("We have already processed this block");
+        return Vec::new();
+    }
+    if !check_proposal(&p, cx) {
+        return Vec::new();
+    }
+    let parent = match cx.storage.delivered_block_from_hash(&b.header.prev) {
+        None => {
+            wait_for_parent(p, cx);
+            return Vec::new();
+        }
+        Some(x) => x,
+    };
+    if b.header.height != parent.header.height+1 {
+        log::warn!("Proposal for view {} at the wrong height", p.view);
+        return Vec::new();
+    }
+
+    // Deliver the block
+    cx.storage.add_delivered_block(b.clone());
+    cx.storage.clear(&b.body.tx_hashes);
+    cx.view_of.insert(b.hash, p.view);
+    cx.proposals.insert(b.hash, p.clone());
+    process_qc(&p.cert, cx);
+    // The leader may be ahead of us if we missed the certificates before
+    enter_view(p.view, cx);
+
+    if p.view == cx.view &&
+        p.view > cx.last_voted_view &&
+        qc_view(&p.cert) >= cx.preferred_view
+    {
+        do_vote(&p, cx);
+    }
+
+    // Continue with what waited for this block
+    if cx.pending_qc.as_ref().map_or(false, |qc| qc_hash(qc) == b.hash) {
+        let qc = cx.pending_qc.take().unwrap();
+        process_qc(&qc, cx);
+    }
+    cx.waiting.remove(&b.hash).unwrap_or_default()
+}
+
+/// Keep a proposal until its parent is delivered, and ask the others for the
+/// parent the first time that we miss it, unless the parent itself waits
+fn wait_for_parent(p: Arc<Propose>, cx: &mut Context) {
+    let parent = p.block.as_ref().unwrap().header.prev;
+    log::debug!("Waiting for the parent of the proposal for view {}", p.view);
+    let first = !cx.waiting.contains_key(&parent) &&
+        !cx.waiting.values().flatten().any(|c| c.block_hash == parent);
+    let children = cx.waiting.entry(parent).or_default();
+    if !children.iter().any(|c| c.block_hash == p.block_hash) {
+        children.push(p);
+    }
+    if first {
+        if let Err(e) = cx.net_send.send((EVERYONE, Arc::new(ProtocolMsg::Fetch(parent)))) {
+            log::warn!("Failed to send a fetch request: {}", e);
+        }
+    }
+}
+
+/// A node asks for the proposal of a block that it missed. We send it with
+/// the proposals of its ancestors, down to `FETCH_DEPTH` blocks.
+pub fn on_fetch(sender: Replica, hash: Hash, cx: &mut Context) {
+    let mut proposals = Vec::new();
+    let mut next = cx.proposals.get(&hash);
+    while let Some(p) = next {
+        let b = p.block.as_ref().unwrap();
+        proposals.push((p.as_ref().clone(), b.as_ref().clone()));
+        if proposals.len() == FETCH_DEPTH {
+            break;
+        }
+        next = cx.proposals.get(&b.header.prev);
+    }
+    if proposals.is_empty() {
+        return;
+    }
+    proposals.reverse();
+    let msg = ProtocolMsg::RawProposals(proposals);
+    if let Err(e) = cx.net_send.send((sender, Arc::new(msg))) {
+        log::warn!("Failed to send the proposals: {}", e);
+    }
+}
+
+/// Proposals that we asked for, oldest first. They are checked like the ones
+/// from the leaders.
+pub fn on_proposals(proposals: Vec<Propose>, cx: &mut Context) {
+    for p in proposals {
+        on_receive_proposal(Arc::new(p), cx);
+    }
+}
+
+/// Vote for the proposal of this view, and send the vote to the next leader
+fn do_vote(p: &Propose, cx: &mut Context) {
+    let b = p.block.as_ref().unwrap();
+    // Never vote twice in a view, even across restarts
+    if !cx.on_sign(SignKind::Vote, b) {
+        log::warn!("Already voted in view {}", cx.view);
+        return;
+    }
+    cx.last_voted_view = p.view;
+    let mut vote = Certificate::empty_cert();
+    vote.msg = CertType::Vote(p.view, b.hash);
+    let auth = match cx.my_secret_key.sign(&util::io::to_bytes(&vote.msg)) {
+        Err(e) => {
+            panic!("Failed to sign a vote: {}", e);
+        },
+        Ok(sig) => sig,
+    };
+    vote.votes.push(Vote {
+        origin: cx.myid,
+        auth,
+    });
+    let leader = cx.leader_of(p.view+1);
+    if leader == cx.myid {
+        on_vote(cx.myid, vote, cx);
+    } else if let Err(e) = cx.net_send.send((leader, Arc::new(ProtocolMsg::VoteMsg(vote)))) {
+        log::warn!("Failed to send a vote: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::Node;
+    use consensus::CommitSink;
+    use crypto::{Algorithm, Keypair, ed25519};
+    use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+    use types::{WireReady, hotstuff::GENESIS_BLOCK};
+
+    const N: usize = 4;
+
+    struct Fixture {
+        keys: Vec<ed25519::Keypair>,
+        cx: Context,
+        net: UnboundedReceiver<(Replica, Arc<ProtocolMsg>)>,
+    }
+
+    impl Fixture {
+        /// Node 3 of four, which leads the views 3, 7, ...
+        fn new() -> Self {
+            Fixture::with_keys((0..N).map(|_| ed25519::Keypair::generate()).collect())
+        }
+
+        fn with_keys(keys: Vec<ed25519::Keypair>) -> Self {
+            let mut config = Node::new();
+            config.num_nodes = N;
+            config.num_faults = 1;
+            config.block_size = 1;
+            config.id = 3;
+            config.crypto_alg = Algorithm::ED25519;
+            config.secret_key_bytes = keys[3].encode().to_vec();
+            config.pk_map = keys.iter().enumerate()
+                .map(|(i, k)| (i, k.public().encode().to_vec()))
+                .collect();
+            let (net_send, net) = unbounded_channel();
+            let (cli_send, _) = unbounded_channel();
+            let cx = Context::new(&config, net_send, cli_send, CommitSink::new(None));
+            Fixture { keys, cx, net }
+        }
+
+        /// A certificate with the votes of all the nodes for `b` in `view`
+        fn qc(&self, view: View, b: &Block) -> Certificate {
+            let mut qc = Certificate::empty_cert();
+            qc.msg = CertType::Vote(view, b.hash);
+            for (origin, k) in self.keys.iter().enumerate() {
+                let auth = k.sign(&util::io::to_bytes(&qc.msg));
+                qc.votes.push(Vote { origin, auth });
+            }
+            qc
+        }
+
+        /// The proposal of the leader of `view` for a block on top of the
+        /// block that `cert` certifies
+        fn propose(&self, view: View, parent: &Block, cert: Certificate) -> Arc<Propose> {
+            let leader = self.cx.leader_of(view);
+            let mut b = Block::with_tx(Vec::new());
+            b.header.author = leader;
+            b.header.prev = parent.hash;
+            b.header.height = parent.header.height+1;
+            b.header.extra = view_tag(view);
+            b.hash = b.compute_hash();
+            let mut p = Propose::new();
+            p.block_hash = b.hash;
+            p.cert = cert;
+            p.view = view;
+            p.proof = Keypair::Ed25519(self.keys[leader].clone()).sign(&p.sign_data()).unwrap();
+            p.block = Some(Arc::new(b));
+            Arc::new(p)
+        }
+
+        /// The messages that we sent since the last call
+        fn sent(&mut self) -> Vec<(Replica, Arc<ProtocolMsg>)> {
+            let mut sent = Vec::new();
+            while let Ok(m) = self.net.try_recv() {
+                sent.push(m);
+            }
+            sent
+        }
+
+        /// The views of the votes that we sent since the last call
+        fn votes(&mut self) -> Vec<View> {
+            self.sent().into_iter()
+                .filter_map(|(_, m)| match m.as_ref() {
+                    ProtocolMsg::VoteMsg(c) => Some(qc_view(c)),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn is_committed(&self, p: &Propose) -> bool {
+            self.cx.storage.is_committed_by_hash(&p.block_hash)
+        }
+    }
+
+    fn block(p: &Propose) -> &Block {
+        p.block.as_ref().unwrap()
+    }
+
+    #[tokio::test]
+    async fn three_chain_commits() {
+        let mut f = Fixture::new();
+        let b1 = f.propose(1, &GENESIS_BLOCK, Certificate::empty_cert());
+        on_receive_proposal(b1.clone(), &mut f.cx);
+        let b2 = f.propose(2, block(&b1), f.qc(1, block(&b1)));
+        on_receive_proposal(b2.clone(), &mut f.cx);
+        let b3 = f.propose(3, block(&b2), f.qc(2, block(&b2)));
+        on_receive_proposal(b3.clone(), &mut f.cx);
+        assert!(!f.is_committed(&b1));
+
+        // b1 <- b2 <- b3 from consecutive views, certified: b1 commits
+        let b4 = f.propose(4, block(&b3), f.qc(3, block(&b3)));
+        on_receive_proposal(b4.clone(), &mut f.cx);
+        assert!(f.is_committed(&b1));
+        assert!(!f.is_committed(&b2));
+        assert_eq!(f.cx.high_qc.msg, CertType::Vote(3, b3.block_hash));
+        assert_eq!(f.cx.view, 4);
+
+        // View 5 is skipped, so neither b5 <- b6 <- b7 nor b4 <- b5 <- b6
+        // is a three-chain
+        let b5 = f.propose(6, block(&b4), f.qc(4, block(&b4)));
+        on_receive_proposal(b5.clone(), &mut f.cx);
+        assert!(f.is_committed(&b2));
+        let b6 = f.propose(7, block(&b5), f.qc(6, block(&b5)));
+        on_receive_proposal(b6.clone(), &mut f.cx);
+        let b7 = f.propose(8, block(&b6), f.qc(7, block(&b6)));
+        on_receive_proposal(b7.clone(), &mut f.cx);
+        assert!(!f.is_committed(&b3));
+
+        // b5 <- b6 <- b7 is: b5 commits with b3 and b4 before it
+        let b8 = f.propose(9, block(&b7), f.qc(8, block(&b7)));
+        on_receive_proposal(b8, &mut f.cx);
+        for p in [&b3, &b4, &b5] {
+            assert!(f.is_committed(p));
+        }
+        assert!(!f.is_committed(&b6));
+    }
+
+    #[tokio::test]
+    async fn votes_only_for_safe_proposals() {
+        let mut f = Fixture::new();
+        let b1 = f.propose(1, &GENESIS_BLOCK, Certificate::empty_cert());
+        on_receive_proposal(b1.clone(), &mut f.cx);
+        let b2 = f.propose(2, block(&b1), f.qc(1, block(&b1)));
+        on_receive_proposal(b2.clone(), &mut f.cx);
+        let b3 = f.propose(3, block(&b2), f.qc(2, block(&b2)));
+        on_receive_proposal(b3.clone(), &mut f.cx);
+        let b4 = f.propose(4, block(&b3), f.qc(3, block(&b3)));
+        on_receive_proposal(b4.clone(), &mut f.cx);
+        // We lead view 3, so we keep the vote for view 2
+        assert_eq!(f.votes(), vec![1, 3, 4]);
+        assert_eq!(f.cx.last_voted_view, 4);
+        // The certificate of b3 locks us on b2
+        assert_eq!(f.cx.preferred_view, 2);
+
+        // A fork from b1 extends a certificate below the lock
+        let fork = f.propose(5, block(&b1), f.qc(1, block(&b1)));
+        on_receive_proposal(fork, &mut f.cx);
+        assert_eq!(f.cx.view, 5);
+        assert!(f.votes().is_empty());
+        assert_eq!(f.cx.last_voted_view, 4);
+
+        // A fork from b2 does not, even though it skips b3 and b4. We lead
+        // view 7, so we keep the vote.
+        let fork = f.propose(6, block(&b2), f.qc(2, block(&b2)));
+        on_receive_proposal(fork.clone(), &mut f.cx);
+        assert_eq!(f.cx.last_voted_view, 6);
+
+        // Never twice in a view, nor in a view that we left
+        let twin = f.propose(6, block(&b3), f.qc(3, block(&b3)));
+        on_receive_proposal(twin, &mut f.cx);
+        let old = f.propose(5, block(&b4), f.qc(4, block(&b4)));
+        on_receive_proposal(old, &mut f.cx);
+        assert!(f.votes().is_empty());
+        assert_eq!(f.cx.last_voted_view, 6);
+
+        // The votes go to the leader of the next view
+        let b7 = f.propose(7, block(&fork), f.qc(6, block(&fork)));
+        on_receive_proposal(b7, &mut f.cx);
+        let sent = f.sent();
+        assert!(matches!(sent.as_slice(), [(0, m)] if matches!(m.as_ref(), ProtocolMsg::VoteMsg(_))));
+    }
+
+    #[tokio::test]
+    async fn fetches_missing_parents() {
+        let mut f = Fixture::new();
+        let b1 = f.propose(1, &GENESIS_BLOCK, Certificate::empty_cert());
+        let b2 = f.propose(2, block(&b1), f.qc(1, block(&b1)));
+        let b3 = f.propose(3, block(&b2), f.qc(2, block(&b2)));
+        // Two children of b3, e.g. from a timeout
+        let b4 = f.propose(4, block(&b3), f.qc(3, block(&b3)));
+        let b5 = f.propose(5, block(&b3), f.qc(3, block(&b3)));
+        on_receive_proposal(b4.clone(), &mut f.cx);
+        on_receive_proposal(b5.clone(), &mut f.cx);
+        on_receive_proposal(b5.clone(), &mut f.cx);
+        assert_eq!(f.cx.waiting[&b3.block_hash].len(), 2);
+        let fetches: Vec<_> = f.sent().into_iter()
+            .filter_map(|(to, m)| match m.as_ref() {
+                ProtocolMsg::Fetch(h) => Some((to, *h)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(fetches, vec![(EVERYONE, b3.block_hash)]);
+
+        // Another node that has the chain answers with b1, b2 and b3
+        let mut other = Fixture::with_keys(f.keys.clone());
+        for p in [&b1, &b2, &b3] {
+            on_receive_proposal(p.clone(), &mut other.cx);
+        }
+        other.sent();
+        on_fetch(3, b3.block_hash, &mut other.cx);
+        let (to, reply) = other.sent().pop().unwrap();
+        assert_eq!(to, 3);
+        let proposals = match ProtocolMsg::from_bytes(&reply.to_bytes()) {
+            ProtocolMsg::Proposals(ps) => ps,
+            m => panic!("Unexpected reply {:?}", m),
+        };
+        assert_eq!(proposals.iter().map(|p| p.view).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        on_proposals(proposals, &mut f.cx);
+        for p in [&b1, &b2, &b3, &b4, &b5] {
+            assert!(f.cx.storage.is_delivered_by_hash(&p.block_hash));
+        }
+        assert!(f.cx.waiting.is_empty());
+        assert!(f.is_committed(&b1));
+    }
+}
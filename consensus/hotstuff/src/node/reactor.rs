@@ -0,0 +1,83 @@
+/// The core consensus module used for chained HotStuff
+/// 
+/// The reactor reacts to all the messages from the network, and talks to the
+/// clients accordingly.
+
+use tokio::sync::mpsc::{
+    UnboundedSender, 
+    UnboundedReceiver
+};
+use types::hotstuff::{Replica, Transaction, ClientMsg, ProtocolMsg};
+use config::Node;
+use consensus::CommitSink;
+use crate::node::{
+    context::Context,
+    pacemaker::*,
+    proposal::{do_propose, on_fetch, on_proposals, on_receive_proposal},
+    vote::on_vote,
+};
+use tokio_stream::StreamExt;
+use std::sync::Arc;
+
+pub async fn reactor(
+    config:&Node,
+    net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
+    cli_send: UnboundedSender<Arc<ClientMsg>>,
+    commits: CommitSink,
+    mut cli_recv: UnboundedReceiver<Transaction>
+) {
+    let mut cx = Context::new(config, net_send, cli_send, commits);
+    let block_size = config.block_size;
+    // Start from the view after the genesis block
+    enter_view(1, &mut cx);
+    loop {
+        tokio::select! {
+            pmsg_opt = net_recv.recv() => {
+                // Received a protocol message
+                let (sender, protmsg) = match pmsg_opt {
+                    None => break,
+                    Some(x) => x,
+                };
+                log::trace!("Received protocol message: {:?}", protmsg);
+                match protmsg {
+                    ProtocolMsg::NewProposal(p) => on_receive_proposal(Arc::new(p), &mut cx),
+                    ProtocolMsg::VoteMsg(v) => on_vote(sender, v, &mut cx),
+                    ProtocolMsg::NewView(v, qc) => on_new_view(sender, v, qc, &mut cx),
+                    ProtocolMsg::Fetch(h) => on_fetch(sender, h, &mut cx),
+                    ProtocolMsg::Proposals(ps) => on_proposals(ps, &mut cx),
+                    other => log::debug!("Not handling {:?}", other),
+                }
+            },
+            tx_opt = cli_recv.recv() => {
+                // We received a message from the client
+                let tx = match tx_opt {
+                    None => break,
+                    Some(x) => x,
+                };
+                cx.storage.add_transaction(tx);
+            },
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                let ev = match ev_opt {
+                    None => continue,
+                    Some(Ok(x)) => x.into_inner(),
+                    Some(Err(e)) => {
+                        log::warn!("Event queue misfired: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("Triggered event {:?}", ev);
+                match ev {
+                    Event::Timeout(v) => on_timeout(v, &mut cx),
+                    Event::Propose(v) => on_propose_event(v, &mut cx),
+                }
+            }
+        }
+        // Do we have a full block, and can we propose in this view?
+        if cx.storage.get_tx_pool_size() >= block_size && can_propose(&cx) {
+            log::debug!("I {} am the leader of view {}, proposing", cx.myid, cx.view);
+            let txs = cx.storage.cleave(block_size);
+            do_propose(txs, &mut cx);
+        }
+    }
+}
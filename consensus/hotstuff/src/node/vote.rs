@@ -0,0 +1,46 @@
+use types::{CertKind, Threshold, hotstuff::{CertType, Certificate, Replica}};
+use super::{commit::process_qc, context::Context};
+
+/// A vote for the proposal of a view, which we get as the leader of the next
+/// view. With n-f votes, the certificate lets us propose.
+pub fn on_vote(sender: Replica, mut c: Certificate, cx: &mut Context) {
+    let (view, hash) = match c.msg {
+        CertType::Vote(v, h) => (v, h),
+        _ => return,
+    };
+    if cx.leader_of(view+1) != cx.myid || view+1 < cx.view {
+        log::debug!("Vote for view {} in view {}", view, cx.view);
+        return;
+    }
+    match c.verify_vote(CertKind::Vote, &cx.pub_key_map) {
+        Ok(origin) if origin == sender => (),
+        Ok(origin) => {
+            log::warn!("Vote of {} relayed by {}", origin, sender);
+            return;
+        }
+        Err(e) => {
+            log::warn!("Invalid vote from {}: {}", sender, e);
+            return;
+        }
+    }
+    let vote = c.votes.pop().unwrap();
+    let cert = cx.vote_map.entry((view, hash)).or_insert_with(|| {
+        let mut cert = Certificate::empty_cert();
+        cert.msg = CertType::Vote(view, hash);
+        cert
+    });
+    if cert.votes.iter().any(|v| v.origin == vote.origin) {
+        log::debug!("Duplicate vote from {}", vote.origin);
+        return;
+    }
+    let was_met = cx.quorum.is_met(Threshold::Quorum, cert.votes.iter().map(|v| v.origin));
+    cert.votes.push(vote);
+    // Only the vote that completes the certificate goes further
+    if was_met || !cx.quorum.is_met(Threshold::Quorum, cert.votes.iter().map(|v| v.origin)) {
+        return;
+    }
+    let cert = cert.clone();
+    log::debug!("Formed a certificate for view {}", view);
+    let qc = cx.finalize_cert(cert);
+    process_qc(&qc, cx);
+}
@@ -0,0 +1,177 @@
+use config::Node;
+use consensus::{NodeOptions, Reactor};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::UnboundedReceiver;
+use types::hotstuff::{ClientMsg, ProtocolMsg, Transaction};
+
+/// The chained HotStuff protocol, for `consensus::run`
+pub struct HotStuff;
+
+impl consensus::Protocol for HotStuff {
+    const NAME: &'static str = "hotstuff";
+    const FAULT_MODEL: types::FaultModel = types::FaultModel::PartiallySynchronous;
+
+    type ProtocolMsg = ProtocolMsg;
+    type ClientMsg = ClientMsg;
+    type Transaction = Transaction;
+
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor {
+        // Setup client network
+        let client_network = net::tokio_manager::Protocol::<Transaction, ClientMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (cli_send, cli_recv) = 
+        cli_net_rt.block_on(
+            client_network.client_setup(
+                config.client_ip(),
+                util::codec::EnCodec::new(),
+                util::codec::Decodec::new()
+            )
+        );
+        let cli_recv = consensus::merge_local_txs(cli_net_rt, cli_recv, local_txs);
+
+        // Setup the protocol network
+        let protocol_network = net::tokio_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (net_send, net_recv) = 
+        prot_net_rt.block_on(
+            protocol_network.server_setup(
+                config.net_map.clone(), 
+                util::codec::EnCodec::new(), 
+                util::codec::Decodec::new()
+            )
+        );
+
+        // Measure the delay to the other nodes before starting
+        let (net_recv, _delays) = consensus::start_delay_monitor(config, prot_net_rt, net_send.clone(), net_recv);
+        // Misbehave if the config asks for it
+        let net_send = consensus::start_adversary(config, prot_net_rt, net_send);
+
+        let config = config.clone();
+        let commits = opts.commit_sink();
+        Box::pin(async move {
+            crate::node::reactor(
+                &config,
+                net_send,
+                net_recv,
+                cli_send,
+                commits,
+                cli_recv
+            ).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use consensus::{NodeBuilder, NodeError};
+    use crypto::{Algorithm, ed25519, hash::HashAlgorithm};
+    use fnv::FnvHashMap as HashMap;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    /// The configs of a chain of `n` nodes on the loopback interface
+    fn configs(n: usize, base_port: u16, client_base_port: u16) -> Vec<Node> {
+        let (root_cert, certs) = genconfig::tls_certs(n).unwrap();
+        let keys: Vec<_> = (0..n).map(|_| ed25519::Keypair::generate()).collect();
+        let pk_map: HashMap<_, _> = keys.iter().enumerate()
+            .map(|(i, k)| (i, k.public().encode().to_vec()))
+            .collect();
+        let net_map: HashMap<_, _> = (0..n)
+            .map(|i| (i, format!("127.0.0.1:{}", base_port+i as u16)))
+            .collect();
+        certs.into_iter().enumerate()
+            .map(|(i, (my_cert, my_cert_key))| {
+                let mut config = Node::new();
+                config.id = i;
+                config.num_nodes = n;
+                config.num_faults = (n-1)/3;
+                config.block_size = 10;
+                config.delta = 50;
+                config.client_port = client_base_port+i as u16;
+                config.crypto_alg = Algorithm::ED25519;
+                config.secret_key_bytes = keys[i].encode().to_vec();
+                config.pk_map = pk_map.clone();
+                config.net_map = net_map.clone();
+                config.root_cert = root_cert.clone();
+                config.my_cert = my_cert;
+                config.my_cert_key = my_cert_key;
+                config
+            })
+            .collect()
+    }
+
+    #[test]
+    fn node_builder() {
+        let mut configs = configs(4, 24500, 24600);
+
+        // The process hashes with SHA256 once the first node starts
+        let mut blake3 = configs[0].clone();
+        blake3.hash_alg = HashAlgorithm::BLAKE3;
+        configs[0].use_hash_algorithm().unwrap();
+        assert!(matches!(NodeBuilder::<HotStuff>::new(blake3).start(),
+            Err(NodeError::InvalidConfig(_))));
+
+        // Node 0 gets its secret keys from a keystore
+        let dir = std::env::temp_dir().join(format!("hotstuff-node-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let password = "node-builder-test";
+        configs[0].seal_keystore("keystore-0.json".to_string(), password.as_bytes()).unwrap()
+            .to_file(dir.join("keystore-0.json")).unwrap();
+        std::env::set_var(config::KEYSTORE_PASSWORD_ENV, password);
+
+        // Every node waits for the others to connect
+        let starts: Vec<_> = configs.into_iter()
+            .map(|config| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    NodeBuilder::<HotStuff>::new(config).config_dir(dir).start()
+                })
+            })
+            .collect();
+        let nodes: Vec<_> = starts.into_iter()
+            .map(|t| t.join().unwrap().expect("Failed to start a node"))
+            .collect();
+        let mut commits: Vec<_> = nodes.iter().map(|n| n.subscribe()).collect();
+
+        // Like the clients, give the transactions to every node
+        for i in 0..5 {
+            for n in &nodes {
+                n.submit(Transaction::new_dummy_tx(i, 0)).unwrap();
+            }
+        }
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        for c in commits.iter_mut() {
+            let mut txs = 0;
+            while txs < 5 {
+                let b = rt.block_on(async { timeout(Duration::from_secs(30), c.recv()).await })
+                    .expect("No block committed in time")
+                    .unwrap();
+                txs += b.txs.len();
+            }
+            assert_eq!(txs, 5);
+        }
+        for n in &nodes {
+            let status = n.status();
+            assert!(status.running);
+            assert_eq!(status.protocol, "hotstuff");
+            assert_eq!(status.committed_txs, 5);
+        }
+
+        for n in nodes {
+            n.shutdown();
+        }
+        // The subscriptions end with the nodes
+        for mut c in commits {
+            while rt.block_on(c.recv()).is_some() {}
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,5 @@
 use log::debug;
-use types::optsync::{ClientMsg, Payload, Propose};
+use types::{CommittedBlock, optsync::{ClientMsg, Payload, Propose}};
 use crate::node::context::Context;
 use std::sync::Arc;
 
@@ -12,24 +12,44 @@ pub async fn on_commit(p: Arc<Propose>, cx:&mut Context) {
         return;
     }
 
-    // Ship the block to the clients
-    let ship = cx.cli_send.clone();
-    let payload = cx.payload;
-    let ship_b = b.clone();
-    let ship_block = tokio::spawn(async move {
-        let payload = Payload::with_payload(payload);
-        let msg = ClientMsg::RawNewBlock(
-            ship_b.as_ref().clone(), payload);
+    // The ancestors from the previous views may not be committed yet
+    let mut to_commit = vec![b.clone()];
+    let mut prev = b.header.prev;
+    while !cx.storage.is_committed_by_hash(&prev) {
+        let parent = match cx.storage.delivered_block_from_hash(&prev) {
+            None => {
+                debug!("Committing a block with an undelivered ancestor {:?}", prev);
+                break;
+            }
+            Some(x) => x,
+        };
+        prev = parent.header.prev;
+        to_commit.push(parent);
+    }
+
+    // A certificate on the top block also certifies its ancestors
+    let top_cert = cx.cert_map.get(&b.hash).cloned()
+        .or_else(|| cx.resp_cert.get(&b.hash).map(|c| c.as_ref().clone()));
+
+    // Ship the blocks to the clients, in order
+    for b in to_commit.into_iter().rev() {
+        let payload = Payload::with_payload(cx.payload);
+        let msg = ClientMsg::RawNewBlock(b.as_ref().clone(), payload);
         log::debug!(
             "sending msg: {:?} to the client", msg);
-        if let Err(e) = ship.send(Arc::new(msg)) {
+        if let Err(e) = cx.cli_send.send(Arc::new(msg)) {
             println!("Error sending the block to the client: {}", e);
-            ()
         }
         log::debug!(
             "Committed block and sending it to the client now");
-    });
-    cx.last_committed_block_ht = b.header.height;
-    cx.storage.add_committed_block(b.clone());
-    ship_block.await.unwrap();
-}
\ No newline at end of file
+        let txs = cx.storage.take_committed(&b.body.tx_hashes);
+        cx.commit_sink.publish(CommittedBlock{
+            block: b.clone(),
+            txs,
+            justification: cx.cert_map.get(&b.hash).cloned().or_else(|| top_cert.clone()),
+        });
+        cx.last_committed_block_ht = b.header.height;
+        cx.evidence.on_included(&b.header.evidence);
+        cx.storage.add_committed_block(b);
+    }
+}
@@ -1,40 +1,85 @@
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::time::DelayQueue;
-use types::optsync::{Block, Certificate, GENESIS_BLOCK, Height, Replica, Storage, View, ClientMsg, ProtocolMsg, Propose};
+use types::optsync::{Block, CertType, Certificate, GENESIS_BLOCK, Height, Replica, Storage, View, ClientMsg, ProtocolMsg, Propose, Vote};
+use types::{CertError, CertKind, FaultModel, QuorumPolicy, Threshold};
 use config::Node;
-use crypto::{Keypair, PublicKey, ed25519, secp256k1};
-use fnv::FnvHashMap as HashMap;
+use consensus::{CommitSink, EvidencePool, Reputation, SignKind, Wal};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
 use crypto::hash::Hash;
 use std::{sync::Arc, time::Duration};
 
+use super::phase::{Event, Phase};
+
 pub struct Context {
     /// Networking context
     pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     pub cli_send: UnboundedSender<Arc<ClientMsg>>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
 
     /// Data context
     pub num_nodes: usize,
     pub myid: Replica,
-    pub num_faults: usize,
+    /// The votes that the certificates need
+    pub quorum: QuorumPolicy,
     pub payload:usize,
     pub d2: Duration,
+    pub block_size: usize,
+    pub delta: u64,
 
     /// PKI
     pub my_secret_key: Keypair,
     pub pub_key_map:HashMap<Replica, PublicKey>,
+    /// Set if the signatures in the network messages are already checked by
+    /// the verification workers
+    pub pre_verified: bool,
 
     /// State context
     pub storage: Storage,
     pub resp_cert: HashMap<Hash, Arc<Certificate>>, // Contains responsive certificates
     pub cert_map: HashMap<Hash, Certificate>, // Contains all certified blocks
     pub height: Height,
-    pub last_leader: Replica,
     pub last_seen_block: Arc<Block>,
+    /// The certificate of the highest certified block, which we send to the
+    /// next leader in a view change
     pub last_seen_cert: Certificate,
     pub last_committed_block_ht: Height,
     pub vote_map: HashMap<Hash, Certificate>,
     pub view: View,
     pub commit_queue:DelayQueue<Arc<Propose>>,
+
+    /// View change context
+    pub phase: Phase,
+    pub events: DelayQueue<Event>,
+    /// The proposals of the leader in this view, by the parent they extend
+    pub proposal_map: HashMap<Hash, Arc<Propose>>,
+    /// The blames that we got for a view
+    pub blame_map: HashMap<View, HashMap<Replica, Vote>>,
+    /// Set if we blamed the leader of this view
+    pub blamed: bool,
+    /// Set if the leader of this view equivocated, after which we stop voting
+    /// and committing in the view
+    pub equivocated: bool,
+    /// Set if we had transactions to propose when the timer started
+    pub expect_work: bool,
+    /// The proofs of misbehaviour that we heard of
+    pub evidence: EvidencePool,
+    /// Proposals and votes for views that we have not started yet
+    pub future_msgs: Vec<(View, Replica, ProtocolMsg)>,
+
+    /// Leader election context
+    /// The leader of this view
+    leader: Replica,
+    /// The leader schedule from the history of the chain, if reputation-based
+    /// leader election is enabled
+    pub reputation: Option<Reputation>,
+    /// The view in which each delivered block was proposed
+    pub view_of: HashMap<Hash, View>,
+
+    /// Recovery context
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
 }
 
 const EXTRA_SPACE:usize = 10;
@@ -44,12 +89,14 @@ impl Context {
         config: &Node,
         net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
         cli_send: UnboundedSender<Arc<ClientMsg>>,
+        commit_sink: CommitSink,
     ) -> Self {
         let genesis_arc = Arc::new(GENESIS_BLOCK);
         let mut c = Context {
             net_send,
             num_nodes: config.num_nodes,
             cli_send,
+            commit_sink,
             my_secret_key: match config.crypto_alg {
                 crypto::Algorithm::ED25519 => {
                     let mut sk_copy = config.secret_key_bytes.clone();
@@ -64,15 +111,30 @@ impl Context {
                     let kp = secp256k1::Keypair::from(sk);
                     Keypair::Secp256k1(kp)
                 }
-                _ => panic!("Unimplemented algorithm"),
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Bls(kp)
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Rsa(kp)
+                }
             },
             pub_key_map: HashMap::default(),
+            pre_verified: config.verify_workers > 0,
             myid: config.id,
             d2: std::time::Duration::from_millis(2*config.delta),
-            num_faults: config.num_faults,
+            quorum: config.quorum_policy(FaultModel::Synchronous),
+            block_size: config.block_size,
+            delta: config.delta,
             storage: Storage::new(EXTRA_SPACE*config.block_size),
             height: 0,
-            last_leader: 0,
             last_seen_block: genesis_arc.clone(),
             last_committed_block_ht: 0,
             resp_cert: HashMap::default(),
@@ -82,39 +144,200 @@ impl Context {
             vote_map: HashMap::default(),
             payload:config.payload*config.block_size,
             commit_queue: tokio_util::time::DelayQueue::new(),
+            phase: Phase::Normal,
+            events: DelayQueue::new(),
+            proposal_map: HashMap::default(),
+            blame_map: HashMap::default(),
+            blamed: false,
+            equivocated: false,
+            expect_work: false,
+            evidence: EvidencePool::new(),
+            future_msgs: Vec::new(),
+            leader: 0,
+            reputation: Reputation::from_node(config),
+            view_of: HashMap::default(),
+            wal: Wal::from_node(config, "optsync"),
         };
-        for (id,mut pk_data) in config.pk_map.clone() {
-            let pk = match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let kp = ed25519::PublicKey::decode(
-                        &mut pk_data
-                    ).expect("Failed to decode the secret key from the config");
-                    PublicKey::Ed25519(kp)
-                },
-                crypto::Algorithm::SECP256K1 => {
-                    let sk = secp256k1::PublicKey::decode(&pk_data).expect("Failed to decode the secret key from the config");
-                    PublicKey::Secp256k1(sk)
-                }
-                _ => panic!("Unimplemented algorithm"),
-            };
-            c.pub_key_map.insert(id, pk);
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            c.pub_key_map.insert(*id, pk);
         }
 
         // Initialize storage
         c.storage.add_delivered_block(genesis_arc.clone());
         c.storage.add_committed_block(genesis_arc);
         c.cert_map.insert(GENESIS_BLOCK.hash, Certificate::empty_cert());
+
+        // Continue from the view of our last signature before a restart
+        let last_signed = c.wal.as_ref().and_then(|w| {
+            std::cmp::max(w.highest(SignKind::Vote), w.highest(SignKind::Proposal))
+        });
+        if let Some((view, height)) = last_signed {
+            log::info!("Recovered our last signature at height {} in view {}", height, view);
+            c.set_view(view);
+        }
         c
     }
 
-    /// For sync hotstuff, the next leader is the current leader
+    /// Whether we can still sign a message of `kind` at `height` in this view,
+    /// which we may have done before a restart
+    pub fn may_sign(&self, kind: SignKind, height: Height) -> bool {
+        self.wal.as_ref()
+            .is_none_or(|w| w.signed(&(kind, self.view, height)).is_none())
+    }
+
+    /// Record that we sign `hash` at `height` in this view, and return
+    /// whether we may, i.e. we did not sign another hash there before
+    pub fn on_sign(&mut self, kind: SignKind, height: Height, hash: &Hash) -> bool {
+        let view = self.view;
+        self.wal.as_mut()
+            .is_none_or(|w| w.record((kind, view, height), hash))
+    }
+
+    /// For opt sync, the next leader is the leader of the current view
     pub fn next_leader(&self) -> Replica {
-       self.last_leader
+       self.leader_of_view()
     }
 
-    /// Leader of a view
+    /// Leader of the current view
     pub fn leader_of_view(&self) -> Replica {
-        self.view % self.num_nodes
+        self.leader
+    }
+
+    /// The leader of `view`, if we know it. With reputation-based leader
+    /// election, we only know the leader of the view that we are in.
+    pub fn leader_of(&self, view: View) -> Option<Replica> {
+        if view == self.view {
+            Some(self.leader)
+        } else if self.reputation.is_some() {
+            None
+        } else {
+            Some(view % self.num_nodes)
+        }
+    }
+
+    /// Move to `view`, and find its leader
+    pub fn set_view(&mut self, view: View) {
+        if view == self.view {
+            return;
+        }
+        self.view = view;
+        let default = view % self.num_nodes;
+        self.leader = match &self.reputation {
+            None => default,
+            Some(rep) => rep.leader(view, default, self.committed_views()),
+        };
+        if self.leader != default {
+            log::info!("Skipping the demoted leader {} in view {} for {}", default, view, self.leader);
+        }
+    }
+
+    /// The views and the authors of the committed blocks, the latest first.
+    /// Unlike the blocks that we delivered, every honest node agrees on them.
+    fn committed_views(&self) -> impl Iterator<Item = (View, Replica)> + '_ {
+        let tip = self.storage.committed_block_from_ht(self.last_committed_block_ht);
+        std::iter::successors(tip, move |b| self.storage.committed_block_by_hash(&b.header.prev))
+            .map_while(move |b| self.view_of.get(&b.hash).map(|v| (*v, b.header.author)))
+    }
+
+    /// Returns the certificate that we ship in proposals. With BLS keys the
+    /// votes are aggregated into a single signature.
+    pub fn finalize_cert(&self, cert: Certificate) -> Certificate {
+        if !matches!(self.my_secret_key, Keypair::Bls(_)) {
+            return cert;
+        }
+        match cert.aggregate() {
+            Ok(agg) => agg,
+            Err(e) => {
+                log::warn!("Failed to aggregate the certificate: {}", e);
+                cert
+            }
+        }
+    }
+
+    pub fn deltas(&self, n: u64) -> Duration {
+        Duration::from_millis(n*self.delta)
+    }
+
+    /// Whether the distinct signers of `c` meet `t`, without checking the
+    /// signatures
+    pub fn meets(&self, t: Threshold, c: &Certificate) -> bool {
+        self.quorum.is_met(t, signers(c))
+    }
+
+    /// Whether we have enough transactions for the leader to propose
+    pub fn has_work(&self) -> bool {
+        self.storage.get_tx_pool_size() >= self.block_size
+    }
+
+    /// Start the timer that blames the leader if it does not propose after
+    /// the current height, after `extra` time on top of the usual wait
+    pub fn start_timer(&mut self, extra: Duration) {
+        // The next block is proposed once the last one is certified, which
+        // takes 2\Delta, with some slack for building it
+        let timeout = self.deltas(4) + extra;
+        self.events.insert(Event::Timeout(self.view, self.height), timeout);
+        self.expect_work = self.has_work();
+    }
+
+    /// Returns the rank of a certificate: the view of the votes, the height
+    /// of the certified block, if it is delivered, and whether it is a
+    /// responsive certificate.
+    ///
+    /// A block that is committed responsively may have a conflicting block
+    /// certified at the same height by f+1 votes, which the responsive
+    /// certificate outranks.
+    pub fn rank(&self, c: &Certificate) -> Option<(View, Height, bool)> {
+        match &c.msg {
+            CertType::Vote(v, h) => self.storage.delivered_block_from_hash(h)
+                .map(|b| (*v, b.header.height, self.meets(Threshold::Responsive, c))),
+            // The genesis block
+            _ => Some((0, 0, false)),
+        }
+    }
+
+    /// Check that `c` is a certificate on a message of `kind` whose votes
+    /// meet `t`. The signatures are checked unless the verification workers
+    /// already did.
+    pub fn verify_cert(&self, c: &Certificate, kind: CertKind, t: Threshold) -> Result<Vec<Replica>, CertError> {
+        if self.pre_verified {
+            c.check_signers(kind, &self.pub_key_map, &self.quorum, t)
+        } else {
+            c.verify(kind, &self.pub_key_map, &self.quorum, t)
+        }
+    }
+
+    /// Check that `c` is a single vote on a message of `kind`, and return
+    /// its origin
+    pub fn verify_vote(&self, c: &Certificate, kind: CertKind) -> Result<Replica, CertError> {
+        if self.pre_verified {
+            c.check_vote(kind, &self.pub_key_map)
+        } else {
+            c.verify_vote(kind, &self.pub_key_map)
+        }
+    }
+
+    /// Use `c` as the highest certificate if it ranks higher than the one we
+    /// have. The caller must check `c`, and deliver the block it certifies.
+    pub fn update_high_cert(&mut self, c: &Certificate) {
+        let h = match &c.msg {
+            CertType::Vote(_, h) => *h,
+            _ => return,
+        };
+        if self.rank(c) <= self.rank(&self.last_seen_cert) {
+            return;
+        }
+        self.cert_map.entry(h).or_insert_with(|| c.clone());
+        self.last_seen_cert = c.clone();
     }
 }
 
+/// The distinct nodes that signed `c`
+fn signers(c: &Certificate) -> HashSet<Replica> {
+    let mut signers: HashSet<Replica> = c.votes.iter().map(|v| v.origin).collect();
+    if let Some(agg) = &c.agg {
+        signers.extend(agg.signers());
+    }
+    signers
+}
@@ -6,4 +6,6 @@ mod proposal;
 mod blame;
 mod vote;
 mod commit;
-mod process;
\ No newline at end of file
+mod process;
+mod phase;
+mod view_change;
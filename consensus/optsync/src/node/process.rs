@@ -1,32 +1,59 @@
 use std::sync::Arc;
 
-use types::optsync::ProtocolMsg;
+use types::optsync::{ProtocolMsg, Replica};
 use crate::node::{
+    blame::*,
     context::Context,
+    phase::Phase,
     proposal::on_receive_proposal,
+    view_change::on_receive_status,
     vote::on_vote,
     commit::on_commit,
 };
 
-pub(crate) async fn process_msg(cx: &mut Context, protmsg: ProtocolMsg) {
+/// Handle a protocol message from the node `sender`
+pub(crate) async fn process_msg(cx: &mut Context, sender: Replica, protmsg: ProtocolMsg) {
     log::debug!("Received protocol message: {:?}", protmsg);
-    let decision;
-    if let ProtocolMsg::NewProposal(p) = protmsg {
-        log::debug!("Received a proposal: {:?}", p);
-        let p = Arc::new(p);
-        decision = on_receive_proposal(p.clone(), cx).await;
-        log::debug!("Decision for the incoming proposal is {}", decision);
-        if decision {
-            cx.commit_queue.insert(p, cx.d2);
+    match protmsg {
+        ProtocolMsg::NewProposal(p) => {
+            log::debug!("Received a proposal: {:?}", p);
+            if p.view == cx.view+1 {
+                // The next leader can start its view before we do
+                cx.future_msgs.push((p.view, sender, ProtocolMsg::NewProposal(p)));
+                return;
+            }
+            if p.view != cx.view || !matches!(cx.phase, Phase::Normal | Phase::Wait) {
+                log::debug!("Proposal for view {} in view {}", p.view, cx.view);
+                return;
+            }
+            let p = Arc::new(p);
+            let decision = on_receive_proposal(p.clone(), cx).await;
+            log::debug!("Decision for the incoming proposal is {}", decision);
+            if decision {
+                cx.commit_queue.insert(p, cx.d2);
+            }
         }
-    }
-    else if let ProtocolMsg::VoteMsg(v,mut p) = protmsg {
-        log::debug!("Received a vote for a proposal: {:?}", v);
-        decision = on_vote(v, &mut p, cx).await;
-        if decision {
-            log::debug!("Optimistically committing block");
-            // commit p.block_hash and all its children
-            on_commit(Arc::new(p),cx).await;
+        ProtocolMsg::VoteMsg(v, mut p) => {
+            log::debug!("Received a vote for a proposal: {:?}", v);
+            if p.view == cx.view+1 {
+                cx.future_msgs.push((p.view, sender, ProtocolMsg::VoteMsg(v, p)));
+                return;
+            }
+            let decision = on_vote(v, &mut p, cx).await;
+            if decision {
+                log::debug!("Optimistically committing block");
+                // commit p.block_hash and all its ancestors
+                on_commit(Arc::new(p),cx).await;
+            }
+        }
+        ProtocolMsg::NoProgressBlameMsg(c) => on_receive_blame(c, cx).await,
+        ProtocolMsg::EquivcationBlameMsg(e, c) => {
+            on_receive_equivocation(e, c, cx).await
+        }
+        ProtocolMsg::QuitViewMsg(v, c) => on_receive_quit(v, c, cx).await,
+        ProtocolMsg::StatusMsg(c) => on_receive_status(sender, c, cx).await,
+        other => {
+            log::debug!("Not handling {:?}", other);
         }
     }
-}
\ No newline at end of file
+}
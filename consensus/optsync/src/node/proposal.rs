@@ -1,10 +1,10 @@
-use std::collections::HashSet;
-use crate::node::context::Context;
-use crypto::hash::EMPTY_HASH;
-use types::optsync::{
+use crate::node::{blame::check_equivocation, context::Context, phase::Phase};
+use consensus::SignKind;
+use crypto::hash::{EMPTY_HASH, Hash};
+use types::{CertKind, Threshold, optsync::{
     Block, CertType, Certificate, Transaction, Vote, 
     Propose, ProtocolMsg,
-};
+}};
 use std::sync::Arc;
 
 pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
@@ -25,24 +25,30 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return false;
     }
 
-    // Check if the block has sufficient votes
-    if new_block.header.height > 1 && p.cert.votes.len() <= cx.num_faults {
-        log::warn!(
-            "Insufficient votes in the proposal, rejecting the proposal");
-        return false;
-    }
-
     // Check signature for the proposal
     let pk = cx.pub_key_map.get(&new_block.header.author).unwrap();
-    if !pk.verify(&new_block.hash, &p.proof) {
+    if p.block_hash != new_block.hash ||
+        p.parent() != new_block.header.prev ||
+        (!cx.pre_verified && !pk.verify(&p.sign_data(), &p.proof)) 
+    {
         log::warn!(
             "Got an incorrectly signed block");
         return false;
     }
 
+    // Check the proofs of misbehaviour in the block
+    if !cx.pre_verified {
+        if let Some(e) = new_block.header.evidence.iter().find(|e| e.verify(&cx.pub_key_map).is_err()) {
+            log::warn!("Invalid evidence against {} in the proposal", e.offender());
+            return false;
+        }
+    }
+
     // Check if the view is correct
     if cx.view != p.view {
-        panic!("This view check should be unreachable");
+        log::warn!(
+            "Got a proposal for view {} in view {}", p.view, cx.view);
+        return false;
     }
 
     // If the parent is genesis block, then the parent is correctly certified
@@ -50,7 +56,6 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return true;
     }
     // Otherwise check if all the parent certificates are correctly signed
-    let mut uniq_votes = HashSet::with_capacity(cx.num_faults+1);
     if let CertType::Vote(_v, h) = &p.cert.msg {
         // Check if vote message is the same as that in the proposal
         if *h != new_block.header.prev {
@@ -64,26 +69,24 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
     }
 
     log::debug!("Checking certificate: {:?}", p.cert);
-    let data = util::io::to_bytes(&p.cert.msg);
-    for v in &p.cert.votes {
-        // check signature
-        let pk = match cx.pub_key_map.get(&v.origin) {
-            None => {
-                log::warn!("Invalid vote origin");
-                return false;
-            }
-            Some(x) => x,
-        };
-        if !pk.verify(&data, &v.auth) {
-            log::warn!("Invalid vote signature: {:?}", v);
-            return false;
-        }
-        // Add this unique vote
-        uniq_votes.insert(v.origin);
-    }
-    if uniq_votes.len() < cx.num_faults {
+    if let Err(e) = cx.verify_cert(&p.cert, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid certificate in the proposal: {}", e);
         return false;
     }
+    true
+}
+
+/// Check if the proposal extends the last block that we voted for. The first
+/// proposal of a view must instead extend the block that we locked on before
+/// the view change, or a block certified higher than it.
+pub fn check_parent(p: &Propose, cx: &Context) -> bool {
+    let new_block = p.block.as_ref().unwrap();
+    if cx.phase == Phase::Wait {
+        return cx.storage.is_delivered_by_hash(&new_block.header.prev) && (
+            Some(p.parent()) == locked_hash(cx) ||
+            cx.rank(&p.cert) >= cx.rank(&cx.last_seen_cert)
+        );
+    }
     // Is it extending the last known parent?
     if new_block.header.prev != cx.last_seen_block.hash {
         log::warn!("Parent undelivered");
@@ -93,6 +96,14 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
     true
 }
 
+/// The block certified by our highest certificate
+fn locked_hash(cx: &Context) -> Option<Hash> {
+    match &cx.last_seen_cert.msg {
+        CertType::Vote(_, h) => Some(*h),
+        _ => None,
+    }
+}
+
 pub async fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
     // Use decision to start commit timers in the reactor
     let decision = false;
@@ -111,6 +122,17 @@ pub async fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
         log::warn!("Proposal checking failed");
         return decision;
     }
+    for e in &new_block.header.evidence {
+        cx.evidence.add(e.clone());
+    }
+    // Do not vote for the leader again once it equivocates
+    if cx.equivocated || check_equivocation(&p, cx).await {
+        return decision;
+    }
+    if !check_parent(&p, cx) {
+        log::warn!("Proposal does not extend our chain");
+        return decision;
+    }
 
     return on_new_valid_proposal(p, cx).await;
 }
@@ -127,6 +149,12 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
         // TODO: Request and Deliver blocks
         return decision;
     }
+    // Do not vote for a second block at this height, which we may have done
+    // before a restart
+    if !cx.on_sign(SignKind::Vote, new_block.header.height, &new_block.hash) {
+        log::warn!("Already voted at height {} in view {}", new_block.header.height, cx.view);
+        return decision;
+    }
     // Everything looks fine, initiate voting and continue to process this
     // proposal
     let mut my_vote = Certificate::empty_cert();
@@ -168,8 +196,14 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
     cx.storage.add_delivered_block(new_block.clone());
     cx.storage.clear(&new_block.body.tx_hashes);
     cx.height = new_block.header.height;
+    cx.view_of.insert(new_block.hash, p.view);
     cx.last_seen_block = new_block.clone();
-    cx.last_seen_cert = p.cert.clone();
+    cx.update_high_cert(&p.cert);
+    if cx.phase == Phase::Wait {
+        log::info!("Started view {} on height {}", cx.view, new_block.header.height);
+        cx.phase = Phase::Normal;
+    }
+    cx.start_timer(std::time::Duration::ZERO);
 
     // wait for voting to finish?
     if let Err(e) = vote_ship.await {
@@ -184,25 +218,22 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
 
 pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
     // Build the proposal
-    let parent = &cx.last_seen_block;
+    let parent = cx.last_seen_block.clone();
     let mut new_block = Block::with_tx(txs);
 
     // Update block contents here
     new_block.header.author = cx.myid;
     new_block.header.prev = parent.hash;
     new_block.header.height = parent.header.height+1;
+    new_block.header.evidence = cx.evidence.pending();
     
     // Update the hash at the end
     new_block.hash = new_block.compute_hash();
+    // The reactor checked that we did not propose at this height yet
+    if !cx.on_sign(SignKind::Proposal, new_block.header.height, &new_block.hash) {
+        panic!("Failed to record the proposal at height {} in the WAL", new_block.header.height);
+    }
     
-    // Sign the block hash 
-    let proof = match cx.my_secret_key.sign(&new_block.hash) {
-        Err(e) => {
-            panic!("Failed to sign the new proposal: {}", e);
-        },
-        Ok(sig) => sig,
-    };
-
     let mut new_block_cert = Certificate::empty_cert();
     new_block_cert.msg = CertType::Vote(cx.view, new_block.hash);
     let sign_data = util::io::to_bytes(&new_block_cert.msg);
@@ -225,7 +256,6 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
     // The block is ready, build proposal
     let new_block_ref = Arc::new(new_block.clone());
     let mut p = Propose::new();
-    p.proof = proof;
     p.block = Some(new_block_ref.clone());
     p.block_hash = new_block_ref.hash;
     p.cert = match cx.cert_map.get(&parent.hash) {
@@ -235,6 +265,13 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
         Some(x) => x.clone(),
     };
     p.view = cx.view;
+    // Sign the block hash in this view
+    p.proof = match cx.my_secret_key.sign(&p.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the new proposal: {}", e);
+        },
+        Ok(sig) => sig,
+    };
 
     // Ship the proposal
     let ship = cx.net_send.clone();
@@ -257,13 +294,137 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
     // Commit normally, and tell the client after 2\Delta
     cx.vote_map.insert(new_block_ref.hash, new_block_cert);
     cx.height = new_block_ref.header.height;
+    cx.view_of.insert(new_block_ref.hash, cx.view);
     // The leader remains the same
     cx.last_seen_block = new_block_ref.clone();
     cx.last_committed_block_ht = cx.height;
     // The view remains the same
     broadcast.await.expect("failed to broadcast the proposal");
 
+    cx.start_timer(std::time::Duration::ZERO);
+
     let p = Arc::new(p);
     // Leader setting the timer now
     cx.commit_queue.insert(p, cx.d2);
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use config::Node;
+    use consensus::CommitSink;
+    use crypto::{Algorithm, ed25519};
+    use tokio::sync::mpsc::unbounded_channel;
+    use types::{Replica, View};
+
+    pub(crate) const N: usize = 4;
+
+    /// Node 3 of four, in view 0
+    pub(crate) fn context(keys: &[ed25519::Keypair]) -> Context {
+        context_in(keys, None)
+    }
+
+    /// Like `context`, but keeping the state of the node in `state_dir`
+    fn context_in(keys: &[ed25519::Keypair], state_dir: Option<&std::path::Path>) -> Context {
+        let mut config = Node::new();
+        config.state_dir = state_dir.map(|d| d.to_string_lossy().into_owned());
+        config.num_nodes = N;
+        config.num_faults = 1;
+        config.block_size = 1;
+        config.id = 3;
+        config.crypto_alg = Algorithm::ED25519;
+        config.secret_key_bytes = keys[3].encode().to_vec();
+        config.pk_map = keys.iter().enumerate()
+            .map(|(i, k)| (i, k.public().encode().to_vec()))
+            .collect();
+        let (net_send, _) = unbounded_channel();
+        let (cli_send, _) = unbounded_channel();
+        Context::new(&config, net_send, cli_send, CommitSink::new(None))
+    }
+
+    pub(crate) fn keys() -> Vec<ed25519::Keypair> {
+        (0..N).map(|_| ed25519::Keypair::generate()).collect()
+    }
+
+    /// A delivered block of `author` on top of `parent`
+    fn block(parent: &Block, author: Replica, cx: &mut Context) -> Arc<Block> {
+        let mut b = Block::with_tx(Vec::new());
+        b.header.author = author;
+        b.header.prev = parent.hash;
+        b.header.height = parent.header.height+1;
+        b.hash = b.compute_hash();
+        let b = Arc::new(b);
+        cx.storage.add_delivered_block(b.clone());
+        b
+    }
+
+    /// A certificate for `b` in `view`, whose signatures do not matter here
+    fn cert(view: View, b: &Block) -> Certificate {
+        let mut c = Certificate::empty_cert();
+        c.msg = CertType::Vote(view, b.hash);
+        c
+    }
+
+    /// A proposal for a block on top of the block that `cert` certifies
+    fn propose(parent: &Block, cert: Certificate) -> Propose {
+        let mut b = Block::with_tx(Vec::new());
+        b.header.prev = parent.hash;
+        b.header.height = parent.header.height+1;
+        b.hash = b.compute_hash();
+        let mut p = Propose::new();
+        p.block_hash = b.hash;
+        p.cert = cert;
+        p.block = Some(Arc::new(b));
+        p
+    }
+
+    #[test]
+    fn new_views_extend_the_lock_or_a_higher_chain() {
+        let mut cx = context(&keys());
+        let genesis = cx.last_seen_block.clone();
+        let b1 = block(&genesis, 0, &mut cx);
+        let b2 = block(&b1, 1, &mut cx);
+        let b2_fork = block(&b1, 2, &mut cx);
+        // We locked on b2, certified in view 1, before the view change
+        cx.last_seen_block = b2.clone();
+        cx.last_seen_cert = cert(1, &b2);
+        cx.phase = Phase::Wait;
+
+        // A certificate of a lower view or height ranks lower
+        assert!(!check_parent(&propose(&b1, cert(1, &b1)), &cx));
+        assert!(!check_parent(&propose(&b2_fork, cert(0, &b2_fork)), &cx));
+        // Our lock, or a higher certificate on another fork, will do
+        assert!(check_parent(&propose(&b2, cert(0, &b2)), &cx));
+        assert!(check_parent(&propose(&b2_fork, cert(2, &b2_fork)), &cx));
+
+        // In a view, a proposal must extend our last block
+        cx.phase = Phase::Normal;
+        assert!(!check_parent(&propose(&b2_fork, cert(2, &b2_fork)), &cx));
+        assert!(check_parent(&propose(&b2, cert(1, &b2)), &cx));
+    }
+
+    #[tokio::test]
+    async fn restarted_nodes_do_not_vote_twice() {
+        let dir = std::env::temp_dir().join(format!("optsync-wal-test-{}", std::process::id()));
+        let keys = keys();
+        let mut cx = context_in(&keys, Some(&dir));
+        let genesis = cx.last_seen_block.clone();
+        let p = Arc::new(propose(&genesis, Certificate::empty_cert()));
+        assert!(on_new_valid_proposal(p.clone(), &mut cx).await);
+        drop(cx);
+
+        // After a restart, we vote for the same block again, but not for
+        // another one at the same height
+        let mut cx = context_in(&keys, Some(&dir));
+        let mut b = Block::with_tx(Vec::new());
+        b.header.prev = genesis.hash;
+        b.header.author = 1;
+        b.header.height = 1;
+        b.hash = b.compute_hash();
+        let mut other = propose(&genesis, Certificate::empty_cert());
+        other.block_hash = b.hash;
+        other.block = Some(Arc::new(b));
+        assert!(!on_new_valid_proposal(Arc::new(other), &mut cx).await);
+        assert!(on_new_valid_proposal(p, &mut cx).await);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -9,11 +9,15 @@ use tokio::sync::mpsc::{
 };
 use types::optsync::{Replica, Transaction, ClientMsg, ProtocolMsg};
 use config::Node;
+use consensus::{CommitSink, SignKind};
 use crate::node::{
+    blame::on_timeout,
     commit::on_commit, 
     proposal::do_propose,
     process::process_msg,
     context::Context,
+    phase::{Event, Phase},
+    view_change::*,
 };
 use tokio_stream::StreamExt;
 use std::sync::Arc;
@@ -23,22 +27,30 @@ pub async fn reactor(
     net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
     cli_send: UnboundedSender<Arc<ClientMsg>>,
+    commits: CommitSink,
     mut cli_recv: UnboundedReceiver<Transaction>
 ) {
     log::debug!("Started timers");
-    let mut cx = Context::new(config, net_send, cli_send);
+    let mut cx = Context::new(config, net_send, cli_send, commits);
+    // Check the signatures on a pool of workers before they reach the reactor
+    if config.verify_workers > 0 {
+        let pk_map = cx.pub_key_map.clone();
+        net_recv = consensus::start_verifier(net_recv, config.verify_workers,
+            move |_, m: &ProtocolMsg| m.verify_sigs(&pk_map));
+    }
     let block_size = config.block_size;
     let myid = config.id;
+    cx.start_timer(std::time::Duration::ZERO);
     // Start event loop
     loop {
         tokio::select! {
             pmsg_opt = net_recv.recv() => {
                 // Received a protocol message
-                let protmsg = match pmsg_opt {
+                let (sender, protmsg) = match pmsg_opt {
                     None => break,
-                    Some((_, x)) => x,
+                    Some(x) => x,
                 };
-                process_msg(&mut cx, protmsg).await;
+                process_msg(&mut cx, sender, protmsg).await;
             },
             tx_opt = cli_recv.recv() => {
                 // We received a message from the client
@@ -67,13 +79,31 @@ pub async fn reactor(
                         continue;
                     }
                 }
+            },
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                let ev = match ev_opt {
+                    None => continue,
+                    Some(Ok(x)) => x.into_inner(),
+                    Some(Err(e)) => {
+                        log::warn!("Event queue misfired: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("Triggered event {:?}", ev);
+                match ev {
+                    Event::Timeout(v, ht) => on_timeout(v, ht, &mut cx).await,
+                    Event::Status(v) => on_status_event(v, &mut cx).await,
+                    Event::NewView(v) => on_new_view_event(v, &mut cx).await,
+                }
             }
         }
         // Do we have sufficient commands, and are we the next leader?
         // Also, do we have sufficient votes?
-        if cx.storage.get_tx_pool_size() >= block_size && 
+        if cx.phase == Phase::Normal &&
+            cx.storage.get_tx_pool_size() >= block_size && 
             cx.next_leader() == myid && 
-            cx.cert_map.contains_key(&cx.last_seen_block.hash)
+            cx.cert_map.contains_key(&cx.last_seen_block.hash) &&
+            cx.may_sign(SignKind::Proposal, cx.last_seen_block.header.height+1)
         {
             log::debug!("I {} am the leader and, I am proposing", cx.myid);
             let txs = cx.storage.cleave(block_size);
@@ -0,0 +1,85 @@
+use types::{CertKind, Threshold, optsync::{CertType, Certificate, GENESIS_BLOCK, ProtocolMsg, Replica, View}};
+use crate::node::{context::Context, phase::{Event, Phase}, process::process_msg};
+use std::sync::Arc;
+
+/// Called 2\Delta after we quit the view before `v`, once the certificates in
+/// flight have reached us.
+///
+/// We lock on our highest certified block, and send its certificate to the
+/// leader of `v`, whose first proposal must extend the highest certified
+/// block that it hears about. A block committed responsively has 3n/4 votes,
+/// so its certificate outranks any other one of the same height.
+pub async fn on_status_event(v: View, cx: &mut Context) {
+    if cx.phase != Phase::Quit || cx.view+1 != v {
+        return;
+    }
+    cx.set_view(v);
+    cx.blamed = false;
+    cx.equivocated = false;
+    cx.proposal_map.clear();
+    // Give the leader the time to collect the statuses before blaming it
+    cx.start_timer(cx.deltas(2));
+    if cx.leader_of_view() == cx.myid {
+        log::debug!("Collecting the statuses for view {}", v);
+        cx.phase = Phase::StatusWait;
+        let wait = cx.deltas(2);
+        cx.events.insert(Event::NewView(v), wait);
+        return;
+    }
+    cx.phase = Phase::Wait;
+    if let CertType::Vote(..) = cx.last_seen_cert.msg {
+        let msg = Arc::new(ProtocolMsg::StatusMsg(cx.last_seen_cert.clone()));
+        if let Err(e) = cx.net_send.send((cx.leader_of_view(), msg)) {
+            log::warn!("Failed to send the status to the next leader: {}", e);
+        }
+    }
+    // Handle the messages of this view that came before we started it
+    let (now, later) = std::mem::take(&mut cx.future_msgs)
+        .into_iter()
+        .filter(|(w, _, _)| *w >= v)
+        .partition(|(w, _, _)| *w == v);
+    cx.future_msgs = later;
+    for (_, sender, m) in now {
+        process_msg(cx, sender, m).await;
+    }
+}
+
+pub async fn on_receive_status(sender: Replica, c: Certificate, cx: &mut Context) {
+    if cx.phase != Phase::StatusWait {
+        log::debug!("Status from {} not meant for us", sender);
+        return;
+    }
+    let h = match &c.msg {
+        CertType::Vote(_, h) => *h,
+        _ => return,
+    };
+    // We can only extend the blocks that we have
+    if !cx.storage.is_delivered_by_hash(&h) {
+        log::warn!("Status from {} certifies an undelivered block", sender);
+        return;
+    }
+    if let Err(e) = cx.verify_cert(&c, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid certificate in the status from {}: {}", sender, e);
+        return;
+    }
+    cx.update_high_cert(&c);
+}
+
+/// Called when we, as the leader of `v`, finished collecting the statuses.
+///
+/// Our next proposal extends the highest certified block that we know of.
+pub async fn on_new_view_event(v: View, cx: &mut Context) {
+    if cx.view != v || cx.phase != Phase::StatusWait {
+        return;
+    }
+    let h = match &cx.last_seen_cert.msg {
+        CertType::Vote(_, h) => *h,
+        _ => GENESIS_BLOCK.hash,
+    };
+    let b = cx.storage.delivered_block_from_hash(&h)
+        .expect("the highest certified block must be delivered");
+    log::info!("Starting view {} on height {}", v, b.header.height);
+    cx.height = b.header.height;
+    cx.last_seen_block = b;
+    cx.phase = Phase::Normal;
+}
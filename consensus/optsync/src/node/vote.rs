@@ -1,12 +1,12 @@
 use log::debug;
-use types::optsync::{CertType, Certificate, Propose};
+use types::{CertKind, Threshold, optsync::{CertType, Certificate, Propose}};
 use crypto::hash::Hash;
-use crate::node::context::Context;
+use crate::node::{blame::check_equivocation, context::Context, phase::Phase};
 use std::sync::Arc;
 
 pub fn add_vote(mut c: Certificate, hash: Hash, cx: &mut Context) -> bool {
     let mut commit_decision = false;
-    debug!("Waiting for {} votes",(3*cx.num_nodes)/4 as usize);
+    debug!("Waiting for votes of weight {}", cx.quorum.needed(Threshold::Responsive));
 
     let mut cert = match cx.vote_map.remove(&hash) {
         None => {
@@ -17,19 +17,26 @@ pub fn add_vote(mut c: Certificate, hash: Hash, cx: &mut Context) -> bool {
         },
         Some(cert) => cert,
     };
-    // Add the vote to the certificate
-    cert.votes.push(c.votes.pop().unwrap());
-    // Promote it to a full certificate if it has f+1 signatures
-    if cert.votes.len() >= (3*cx.num_nodes)/4 {
+    // Add the vote to the certificate, once per node
+    let vote = c.votes.pop().unwrap();
+    if cert.votes.iter().any(|v| v.origin == vote.origin) {
+        debug!("Duplicate vote from {}", vote.origin);
+        cx.vote_map.insert(hash, cert);
+        return commit_decision;
+    }
+    cert.votes.push(vote);
+    // Promote it to a responsive certificate if it has 3n/4 signatures
+    if cx.meets(Threshold::Responsive, &cert) {
         cx.resp_cert.insert(hash, Arc::new(cert.clone()));
         // Responsive certificate found
         debug!("Responsive certificate formed");
         commit_decision = true;
     }// A weird case for n=3. Optimistic responsiveness requires 2 signatures and even normal certificates require 2 signatures, hence the separation of the two if conditions.
     // If n>3, we can save a nanocycles by combining this into 1 if-else if-else branch or even match on cert.votes.len() for case >3n/4, case >f default.
-    if cert.votes.len() > cx.num_faults {
-        cx.cert_map.insert(hash, cert.clone());
-        cx.last_seen_cert = cert.clone();
+    if cx.meets(Threshold::Honest, &cert) {
+        let agg_cert = cx.finalize_cert(cert.clone());
+        cx.cert_map.insert(hash, agg_cert.clone());
+        cx.update_high_cert(&agg_cert);
     }
     cx.vote_map.insert(hash, cert);
     return commit_decision;
@@ -38,23 +45,18 @@ pub fn add_vote(mut c: Certificate, hash: Hash, cx: &mut Context) -> bool {
 pub async fn on_vote(c: Certificate, p: &mut Propose, cx: &mut Context) -> bool {
     let decision = false;
 
-    if c.votes.len() != 1 {
-        log::warn!(
-            "Invalid number of votes in vote message");
-        return false;
-    }
     // Check if we have already processed the block for which we have the vote
     // and if not check if it is valid
-    let vote = &c.votes[0];
-    let pk = match cx.pub_key_map.get(&vote.origin) {
-        None => {
-            log::warn!("vote from an unknown origin");
+    if let Err(e) = cx.verify_vote(&c, CertKind::Vote) {
+        log::warn!("Invalid vote message: {}", e);
+        return decision;
+    }
+    let blk_hash = match &c.msg {
+        CertType::Vote(v, d) if *v == p.view => *d,
+        CertType::Vote(_, _) => {
+            log::warn!("Vote for a proposal of another view");
             return decision;
-        },
-        Some(x) => x,
-    };
-    let (sign_data, blk_hash) = match &c.msg {
-        CertType::Vote(_v, d) => (util::io::to_bytes(&c.msg), *d),
+        }
         _ => unreachable!("other vote types cant be here"),
     };
 
@@ -63,14 +65,10 @@ pub async fn on_vote(c: Certificate, p: &mut Propose, cx: &mut Context) -> bool
         return decision;
     }
 
-    if !pk.verify(&sign_data, &vote.auth) {
-        log::warn!("vote not correctly signed");
-        return decision;
-    }
-
     if !cx.storage.is_delivered_by_hash(&blk_hash) {
         log::debug!(
             "Received vote for an undelivered block");
+        on_undelivered_vote(Arc::new(p.clone()), cx).await;
         return decision;
     }
 
@@ -100,8 +98,28 @@ pub async fn on_vote(c: Certificate, p: &mut Propose, cx: &mut Context) -> bool
     // This is a vote for a new delivered block
     let commit_decision = add_vote(c, blk_hash, cx);
 
-    // Let the reactor know that we have to start the commit timers for this
-    // block, if this is a new proposal
-    // return on_receive_proposal(Arc::new(p), cx).await;
-    return commit_decision;
+    // Commit responsively only while the view is running, since a quit
+    // certificate may already have taken our lock to the next leader
+    let is_current = p.view == cx.view && cx.phase == Phase::Normal;
+    return commit_decision && is_current && !cx.equivocated;
+}
+
+/// The vote from another node is the only way to hear about the proposals
+/// that the leader did not send us. We check them for equivocations.
+async fn on_undelivered_vote(p: Arc<Propose>, cx: &mut Context) {
+    if p.view != cx.view {
+        return;
+    }
+    let is_signed = match cx.pub_key_map.get(&cx.leader_of_view()) {
+        None => false,
+        Some(pk) => pk.verify(&p.sign_data(), &p.proof),
+    };
+    if !is_signed {
+        log::warn!("Vote for a proposal that is not signed by the leader");
+        return;
+    }
+    let is_current = matches!(cx.phase, Phase::Normal | Phase::Wait);
+    if is_current && !cx.equivocated {
+        check_equivocation(&p, cx).await;
+    }
 }
\ No newline at end of file
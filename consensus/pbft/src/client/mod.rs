@@ -0,0 +1,2 @@
+mod start;
+pub use start::*;
\ No newline at end of file
@@ -0,0 +1,10 @@
+use config::Client;
+use types::{FaultModel, pbft::ClientMsg};
+
+pub async fn start(
+    c:&Client, 
+    metric: u64,
+    window: usize,
+) {
+    consensus::start_client::<ClientMsg>(c, FaultModel::PartiallySynchronous, metric, window).await
+}
@@ -0,0 +1,14 @@
+/// PBFT consensus reactors
+///
+/// The classic three-phase protocol of Castro and Liskov: the primary assigns
+/// blocks to sequence numbers in a pre-prepare, and every node broadcasts a
+/// prepare and then a commit for it, so every block costs a quadratic number
+/// of messages. It tolerates f < n/3 faults without relying on synchrony for
+/// safety. Checkpoints bound the log, and view changes replace a primary that
+/// does not make progress.
+
+pub mod node;
+pub mod client;
+
+mod protocol;
+pub use protocol::*;
@@ -0,0 +1,264 @@
+use tokio::sync::mpsc::UnboundedSender;
+use tokio_util::time::DelayQueue;
+use types::{EVERYONE, pbft::{Block, CertType, Certificate, ClientMsg, GENESIS_BLOCK, Height, Prepared, ProtocolMsg, Replica, Storage, View, ViewChange, Vote}};
+use types::{CertKind, FaultModel, QuorumPolicy, Threshold};
+use config::Node;
+use consensus::{CommitSink, SignKind, Wal};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use crypto::hash::{EMPTY_HASH, Hash};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use super::view_change::Event;
+
+/// A node takes a checkpoint after every this many sequence numbers
+pub const CHECKPOINT_PERIOD: Height = 16;
+/// The sequence numbers that a node accepts are at most this far above its
+/// last stable checkpoint
+pub const WINDOW: Height = 4*CHECKPOINT_PERIOD;
+
+pub struct Context {
+    /// Networking context
+    pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    pub cli_send: UnboundedSender<Arc<ClientMsg>>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
+
+    /// Data context
+    pub num_nodes: usize,
+    pub myid: Replica,
+    /// The matching messages in a certificate: n-f, so that two
+    /// certificates share an honest node
+    pub quorum: QuorumPolicy,
+    pub payload:usize,
+    pub block_size: usize,
+    pub delta: u64,
+
+    /// PKI
+    pub my_secret_key: Keypair,
+    pub pub_key_map:HashMap<Replica, PublicKey>,
+
+    /// State context
+    pub storage: Storage,
+    /// The transactions that went into a block, which the clients may still
+    /// send us late
+    pub seen_txs: HashSet<Hash>,
+
+    /// Normal case
+    pub view: View,
+    /// Whether we left `view - 1` and wait for the new view message
+    pub in_view_change: bool,
+    /// The next sequence number that we assign as the primary
+    pub next_seq: Height,
+    /// The blocks of the pre-prepares that we accepted in this view
+    pub log: BTreeMap<Height, Arc<Block>>,
+    /// The prepares and commits for a block at a sequence number in a view
+    pub prepares: HashMap<(View, Height, Hash), Certificate>,
+    pub commits: HashMap<(View, Height, Hash), Certificate>,
+    /// The block that prepared in the highest view, for every sequence number
+    /// after the stable checkpoint
+    pub prepared: BTreeMap<Height, Prepared>,
+    /// The blocks that committed, which wait for the blocks before them to
+    /// execute
+    pub committed: BTreeMap<Height, (Arc<Block>, Certificate)>,
+
+    /// Execution
+    /// The last sequence number that we executed
+    pub exec_seq: Height,
+    /// The digest of all the blocks that we executed
+    pub digest: Hash,
+    /// The commit certificates of the executed blocks, for the nodes that fall
+    /// behind
+    pub exec_certs: HashMap<Height, Certificate>,
+
+    /// Checkpoints
+    pub checkpoints: HashMap<(Height, Hash), Certificate>,
+    pub stable_seq: Height,
+    /// The certificate of the last stable checkpoint, empty before the first
+    pub stable_cert: Certificate,
+
+    /// View changes
+    pub view_changes: HashMap<View, HashMap<Replica, ViewChange>>,
+    pub events: DelayQueue<Event>,
+    /// The number of view changes in a row without executing anything, for
+    /// the backoff
+    pub timeouts: u32,
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
+}
+
+const EXTRA_SPACE:usize = 10;
+
+impl Context {
+    pub fn new(
+        config: &Node,
+        net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+        cli_send: UnboundedSender<Arc<ClientMsg>>,
+        commit_sink: CommitSink,
+    ) -> Self {
+        let genesis_arc = Arc::new(GENESIS_BLOCK);
+        let mut c = Context {
+            net_send,
+            cli_send,
+            commit_sink,
+            num_nodes: config.num_nodes,
+            my_secret_key: match config.crypto_alg {
+                crypto::Algorithm::ED25519 => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = ed25519::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Ed25519(kp)
+                },
+                crypto::Algorithm::SECP256K1 => {
+                    let sk_copy = config.secret_key_bytes.clone();
+                    let sk = secp256k1::SecretKey::from_bytes(sk_copy).expect("Failed to decode the secret key from the config");
+                    let kp = secp256k1::Keypair::from(sk);
+                    Keypair::Secp256k1(kp)
+                }
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Bls(kp)
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Rsa(kp)
+                }
+            },
+            pub_key_map: HashMap::default(),
+            myid: config.id,
+            quorum: config.quorum_policy(FaultModel::PartiallySynchronous),
+            payload: config.payload*config.block_size,
+            block_size: config.block_size,
+            delta: config.delta,
+            storage: Storage::new(EXTRA_SPACE*config.block_size),
+            seen_txs: HashSet::default(),
+            view: 0,
+            in_view_change: false,
+            next_seq: 1,
+            log: BTreeMap::new(),
+            prepares: HashMap::default(),
+            commits: HashMap::default(),
+            prepared: BTreeMap::new(),
+            committed: BTreeMap::new(),
+            exec_seq: 0,
+            digest: EMPTY_HASH,
+            exec_certs: HashMap::default(),
+            checkpoints: HashMap::default(),
+            stable_seq: 0,
+            stable_cert: Certificate::empty_cert(),
+            view_changes: HashMap::default(),
+            events: DelayQueue::new(),
+            timeouts: 0,
+            wal: Wal::from_node(config, "pbft"),
+        };
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            c.pub_key_map.insert(*id, pk);
+        }
+
+
+        // Initialize storage
+        c.storage.add_delivered_block(genesis_arc.clone());
+        c.storage.add_committed_block(genesis_arc);
+
+        // Come back in the view we signed in before a restart. We catch up
+        // with the others from their checkpoints.
+        if let Some(w) = &c.wal {
+            let highest = std::cmp::max(
+                w.highest(SignKind::Vote),
+                w.highest(SignKind::Proposal),
+            );
+            if let Some((view, _)) = highest {
+                c.view = view;
+            }
+        }
+        c
+    }
+
+    /// The primary of `view`, in turn
+    pub fn primary_of(&self, view: View) -> Replica {
+        view % self.num_nodes
+    }
+
+    pub fn deltas(&self, n: u64) -> Duration {
+        Duration::from_millis(n*self.delta)
+    }
+
+    /// Whether a sequence number is between the watermarks
+    pub fn in_window(&self, seq: Height) -> bool {
+        seq > self.stable_seq && seq <= self.stable_seq + WINDOW
+    }
+
+    /// Whether `c` is a certificate on a message of `kind` with n-f
+    /// correctly signed votes from distinct nodes
+    pub fn is_valid_cert(&self, c: &Certificate, kind: CertKind) -> bool {
+        c.verify(kind, &self.pub_key_map, &self.quorum, Threshold::Quorum).is_ok()
+    }
+
+    /// Whether `c` is the single vote of `sender` on a message of `kind`,
+    /// correctly signed
+    pub fn is_valid_vote(&self, sender: Replica, c: &Certificate, kind: CertKind) -> bool {
+        c.verify_vote(kind, &self.pub_key_map) == Ok(sender)
+    }
+
+    /// Our vote for `msg`
+    pub fn sign_vote(&self, msg: CertType) -> Certificate {
+        let auth = match self.my_secret_key.sign(&util::io::to_bytes(&msg)) {
+            Err(e) => {
+                panic!("Failed to sign a vote: {}", e);
+            },
+            Ok(sig) => sig,
+        };
+        let mut vote = Certificate::empty_cert();
+        vote.msg = msg;
+        vote.votes.push(Vote {
+            origin: self.myid,
+            auth,
+        });
+        vote
+    }
+
+    /// Returns the certificate that we ship to the others. With BLS keys the
+    /// votes are aggregated into a single signature.
+    pub fn finalize_cert(&self, cert: Certificate) -> Certificate {
+        if !matches!(self.my_secret_key, Keypair::Bls(_)) {
+            return cert;
+        }
+        match cert.aggregate() {
+            Ok(agg) => agg,
+            Err(e) => {
+                log::warn!("Failed to aggregate the certificate: {}", e);
+                cert
+            }
+        }
+    }
+
+    /// Send a message to all the other nodes
+    pub fn broadcast(&self, msg: ProtocolMsg) {
+        if let Err(e) = self.net_send.send((EVERYONE, Arc::new(msg))) {
+            log::warn!("Failed to send a protocol message: {}", e);
+        }
+    }
+
+    /// Whether something waits to be ordered: transactions, or pre-prepares
+    /// that did not execute
+    pub fn has_pending_work(&self) -> bool {
+        self.storage.get_tx_pool_size() > 0 ||
+            self.log.range(self.exec_seq+1..).next().is_some()
+    }
+
+    /// Record that we sign a message of `kind` for `hash` at a sequence
+    /// number in a view, and return whether we may
+    pub fn on_sign(&mut self, kind: SignKind, view: View, seq: Height, hash: &Hash) -> bool {
+        self.wal.as_mut()
+            .map_or(true, |w| w.record((kind, view, seq), hash))
+    }
+}
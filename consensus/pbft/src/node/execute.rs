@@ -0,0 +1,152 @@
+use types::{CertKind, CommittedBlock, Threshold, pbft::{Block, CertType, Certificate, ClientMsg, Height, Payload, ProtocolMsg, Replica}};
+use super::context::{CHECKPOINT_PERIOD, Context, WINDOW};
+use std::sync::Arc;
+
+/// Execute the committed blocks that are next in order
+pub fn try_execute(cx: &mut Context) {
+    while let Some((b, cert)) = cx.committed.remove(&(cx.exec_seq+1)) {
+        execute(b, cert, cx);
+    }
+}
+
+/// Execute the block at the next sequence number: ship it to the clients, and
+/// take a checkpoint if it is time to
+fn execute(b: Arc<Block>, cert: Certificate, cx: &mut Context) {
+    let seq = b.header.height;
+    log::debug!("Executing sequence number {}", seq);
+    cx.exec_seq = seq;
+    cx.digest = crypto::hash::ser_and_hash(&(cx.digest, b.hash));
+    cx.timeouts = 0;
+    if !cx.storage.is_delivered_by_hash(&b.hash) {
+        cx.storage.add_delivered_block(b.clone());
+    }
+    let txs = cx.storage.take_committed(&b.body.tx_hashes);
+    cx.seen_txs.extend(b.body.tx_hashes.iter().cloned());
+    // The null blocks of the view changes only fill the gaps
+    if !b.body.tx_hashes.is_empty() {
+        let payload = Payload::with_payload(cx.payload);
+        let msg = ClientMsg::RawNewBlock(b.as_ref().clone(), payload);
+        if let Err(e) = cx.cli_send.send(Arc::new(msg)) {
+            log::warn!("Error sending the block to the client: {}", e);
+        }
+    }
+    cx.commit_sink.publish(CommittedBlock{
+        block: b.clone(),
+        txs,
+        justification: Some(cert.clone()),
+    });
+    cx.storage.add_committed_block(b);
+    cx.exec_certs.insert(seq, cert);
+    cx.committed.retain(|s, _| *s > seq);
+
+    if seq % CHECKPOINT_PERIOD == 0 {
+        let vote = cx.sign_vote(CertType::Checkpoint(seq, cx.digest));
+        cx.broadcast(ProtocolMsg::Checkpoint(vote.clone()));
+        on_checkpoint(cx.myid, vote, cx);
+    }
+}
+
+/// A checkpoint vote from `sender`. With n-f matching votes, the checkpoint
+/// is stable.
+pub fn on_checkpoint(sender: Replica, mut c: Certificate, cx: &mut Context) {
+    let (seq, digest) = match c.msg {
+        CertType::Checkpoint(s, d) => (s, d),
+        _ => return,
+    };
+    if seq <= cx.stable_seq {
+        return;
+    }
+    if !cx.is_valid_vote(sender, &c, CertKind::Checkpoint) {
+        log::warn!("Invalid checkpoint from {}", sender);
+        return;
+    }
+    let vote = c.votes.pop().unwrap();
+    let cert = cx.checkpoints.entry((seq, digest)).or_insert_with(|| {
+        let mut cert = Certificate::empty_cert();
+        cert.msg = CertType::Checkpoint(seq, digest);
+        cert
+    });
+    if cert.votes.iter().any(|v| v.origin == vote.origin) {
+        return;
+    }
+    let was_met = cx.quorum.is_met(Threshold::Quorum, cert.votes.iter().map(|v| v.origin));
+    cert.votes.push(vote);
+    if was_met || !cx.quorum.is_met(Threshold::Quorum, cert.votes.iter().map(|v| v.origin)) {
+        return;
+    }
+    let cert = cert.clone();
+    let cert = cx.finalize_cert(cert);
+    make_stable(cert, cx);
+}
+
+/// Move the low watermark to a checkpoint with a certificate, and forget
+/// everything before it. If we did not execute up to it, ask the others for
+/// the blocks.
+pub fn make_stable(cert: Certificate, cx: &mut Context) {
+    let seq = match cert.msg {
+        CertType::Checkpoint(s, _) => s,
+        _ => return,
+    };
+    if seq <= cx.stable_seq {
+        return;
+    }
+    log::debug!("Checkpoint {} is stable", seq);
+    cx.stable_seq = seq;
+    cx.stable_cert = cert;
+    cx.log.retain(|s, _| *s > seq);
+    cx.prepares.retain(|(_, s, _), _| *s > seq);
+    cx.commits.retain(|(_, s, _), _| *s > seq);
+    cx.prepared.retain(|s, _| *s > seq);
+    cx.checkpoints.retain(|(s, _), _| *s > seq);
+    cx.next_seq = std::cmp::max(cx.next_seq, seq+1);
+    if cx.exec_seq < seq {
+        log::info!("Fell behind the stable checkpoint {}, fetching the blocks", seq);
+        cx.broadcast(ProtocolMsg::Fetch(cx.exec_seq+1));
+    }
+}
+
+/// A node that fell behind asks for the committed blocks from `from` on
+pub fn on_fetch(sender: Replica, from: Height, cx: &mut Context) {
+    let mut blocks = Vec::new();
+    for seq in from..std::cmp::min(from+WINDOW, cx.exec_seq+1) {
+        let (b, cert) = match (cx.storage.committed_block_from_ht(seq), cx.exec_certs.get(&seq)) {
+            (Some(b), Some(cert)) => (b, cert),
+            _ => continue,
+        };
+        blocks.push((b.as_ref().clone(), cert.clone()));
+    }
+    if blocks.is_empty() {
+        return;
+    }
+    if let Err(e) = cx.net_send.send((sender, Arc::new(ProtocolMsg::Blocks(blocks)))) {
+        log::warn!("Failed to send the blocks: {}", e);
+    }
+}
+
+/// Committed blocks that we asked for, which we execute if their commit
+/// certificates check out
+pub fn on_blocks(sender: Replica, blocks: Vec<(Block, Certificate)>, cx: &mut Context) {
+    let before = cx.exec_seq;
+    for (b, cert) in blocks {
+        let seq = b.header.height;
+        if seq != cx.exec_seq+1 {
+            continue;
+        }
+        let is_valid = matches!(cert.msg, CertType::Commit(_, s, h) if s == seq && h == b.hash) &&
+            cx.is_valid_cert(&cert, CertKind::Commit);
+        if !is_valid {
+            log::warn!("Invalid commit certificate from {} for sequence number {}", sender, seq);
+            return;
+        }
+        execute(Arc::new(b), cert, cx);
+    }
+    try_execute(cx);
+    if cx.exec_seq > before && cx.exec_seq < cx.stable_seq {
+        // There is more to fetch
+        if let Err(e) = cx.net_send.send((sender, Arc::new(ProtocolMsg::Fetch(cx.exec_seq+1)))) {
+            log::warn!("Failed to send a fetch request: {}", e);
+        }
+    } else if cx.exec_seq > before {
+        log::info!("Caught up to sequence number {}", cx.exec_seq);
+    }
+}
@@ -0,0 +1,8 @@
+mod reactor;
+pub use reactor::*;
+
+mod context;
+mod proposal;
+mod vote;
+mod execute;
+mod view_change;
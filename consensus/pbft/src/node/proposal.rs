@@ -0,0 +1,289 @@
+use types::pbft::{Block, CertType, Height, PrePrepare, ProtocolMsg, Transaction, View};
+use consensus::SignKind;
+use crypto::hash::{EMPTY_HASH, Hash};
+use super::{context::Context, vote::{check_prepared, on_prepare}};
+use std::sync::Arc;
+
+/// The view in the header of a block, so that the blocks of two views never
+/// share a hash
+pub fn view_tag(view: View) -> Vec<u8> {
+    (view as u64).to_le_bytes().to_vec()
+}
+
+/// The empty block with which the primary of `view` fills a sequence number
+/// that nothing prepared at in the view change
+pub fn null_block(view: View, seq: Height, cx: &Context) -> Block {
+    let mut b = Block::with_tx(Vec::new());
+    b.header.author = cx.primary_of(view);
+    b.header.height = seq;
+    b.header.extra = view_tag(view);
+    b.hash = b.compute_hash();
+    b
+}
+
+/// The block that we assigned to the next sequence number in this view
+/// before a restart, if any
+fn recorded_proposal(cx: &Context) -> Option<Hash> {
+    cx.wal.as_ref()?
+        .signed(&(SignKind::Proposal, cx.view, cx.next_seq))
+        .copied()
+}
+
+/// Whether we can assign the next sequence number: as the primary of the
+/// view, below the high watermark, and if we did not assign it before a
+/// restart
+pub fn can_propose(cx: &Context) -> bool {
+    cx.primary_of(cx.view) == cx.myid &&
+        !cx.in_view_change &&
+        cx.in_window(cx.next_seq) &&
+        recorded_proposal(cx).is_none()
+}
+
+/// Send the pre-prepares again for the next sequence numbers that we assigned
+/// in this view before a restart, as we cannot assign them other blocks.
+///
+/// If we no longer have the block, we wait at its sequence number; the view
+/// change that follows fills it.
+pub fn resend_recorded(cx: &mut Context) {
+    while cx.primary_of(cx.view) == cx.myid && !cx.in_view_change && cx.in_window(cx.next_seq) {
+        let hash = match recorded_proposal(cx) {
+            None => return,
+            Some(h) => h,
+        };
+        let seq = cx.next_seq;
+        let b = cx.log.get(&seq)
+            .filter(|b| b.hash == hash)
+            .cloned()
+            .or_else(|| cx.storage.delivered_block_from_hash(&hash));
+        let b = match b {
+            None => {
+                log::debug!("Lost the block that we assigned to sequence number {} in view {}",
+                    seq, cx.view);
+                return;
+            }
+            Some(b) => b,
+        };
+        log::info!("Sending the pre-prepare for sequence number {} in view {} again", seq, cx.view);
+        cx.next_seq += 1;
+        send_pre_prepare(b, cx);
+    }
+}
+
+/// Assign a block to the next sequence number. The caller must check that we
+/// can propose.
+pub fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) {
+    let seq = cx.next_seq;
+    // The order comes from the sequence numbers; the link to the block before
+    // is only informative
+    let prev = cx.log.get(&(seq-1))
+        .map(|b| b.hash)
+        .or_else(|| cx.storage.delivered_block_from_ht(seq-1).map(|b| b.hash))
+        .unwrap_or(EMPTY_HASH);
+    let mut new_block = Block::with_tx(txs);
+    new_block.header.author = cx.myid;
+    new_block.header.prev = prev;
+    new_block.header.height = seq;
+    new_block.header.extra = view_tag(cx.view);
+    new_block.hash = new_block.compute_hash();
+
+    // `can_propose` checked that the sequence number is free in the WAL
+    if !cx.on_sign(SignKind::Proposal, cx.view, seq, &new_block.hash) {
+        panic!("Failed to record the pre-prepare for sequence number {} in view {} in the WAL",
+            seq, cx.view);
+    }
+    cx.next_seq += 1;
+    send_pre_prepare(Arc::new(new_block), cx);
+}
+
+/// Sign and send the pre-prepare for a block that the WAL has, and accept it
+fn send_pre_prepare(b: Arc<Block>, cx: &mut Context) {
+    let seq = b.header.height;
+    let mut p = PrePrepare::new();
+    p.view = cx.view;
+    p.seq = seq;
+    p.block_hash = b.hash;
+    p.proof = match cx.my_secret_key.sign(&p.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the pre-prepare: {}", e);
+        },
+        Ok(sig) => sig,
+    };
+    log::debug!("Assigning a block to sequence number {} in view {}", seq, cx.view);
+    cx.broadcast(ProtocolMsg::RawPrePrepare(p, b.as_ref().clone()));
+    if !cx.log.contains_key(&seq) {
+        accept(cx.view, b, cx);
+    }
+}
+
+/// Check the pre-prepare on its own: the signature of the primary of its
+/// view, and the block
+fn check_pre_prepare(p: &PrePrepare, cx: &Context) -> bool {
+    let b = p.block.as_ref().unwrap();
+    let primary = cx.primary_of(p.view);
+    let pk = match cx.pub_key_map.get(&primary) {
+        None => return false,
+        Some(pk) => pk,
+    };
+    if b.header.author != primary ||
+        p.block_hash != b.hash ||
+        b.header.height != p.seq ||
+        b.header.extra != view_tag(p.view) ||
+        !pk.verify(&p.sign_data(), &p.proof)
+    {
+        log::warn!("Got an incorrectly signed pre-prepare for view {}", p.view);
+        return false;
+    }
+    true
+}
+
+/// A pre-prepare from the primary of our view
+pub fn on_pre_prepare(p: PrePrepare, cx: &mut Context) {
+    if p.view != cx.view || cx.in_view_change {
+        log::debug!("Pre-prepare for view {} in view {}", p.view, cx.view);
+        return;
+    }
+    if !cx.in_window(p.seq) || p.seq <= cx.exec_seq {
+        log::debug!("Pre-prepare for sequence number {} out of the window", p.seq);
+        return;
+    }
+    if !check_pre_prepare(&p, cx) {
+        return;
+    }
+    if let Some(b) = cx.log.get(&p.seq) {
+        if b.hash != p.block_hash {
+            log::warn!("The primary of view {} equivocated at sequence number {}", p.view, p.seq);
+        }
+        return;
+    }
+    accept(p.view, p.block.unwrap(), cx);
+}
+
+/// Accept a block for its sequence number in `view`, and prepare it
+pub fn accept(view: View, b: Arc<Block>, cx: &mut Context) {
+    let seq = b.header.height;
+    // Never prepare two blocks at a sequence number in a view, even across
+    // restarts
+    if !cx.on_sign(SignKind::Vote, view, seq, &b.hash) {
+        log::warn!("Already prepared at sequence number {} in view {}", seq, view);
+        return;
+    }
+    if !cx.storage.is_delivered_by_hash(&b.hash) {
+        cx.storage.add_delivered_block(b.clone());
+    }
+    cx.storage.clear(&b.body.tx_hashes);
+    cx.seen_txs.extend(b.body.tx_hashes.iter().cloned());
+    cx.log.insert(seq, b.clone());
+
+    let vote = cx.sign_vote(CertType::Prepare(view, seq, b.hash));
+    cx.broadcast(ProtocolMsg::Prepare(vote.clone()));
+    on_prepare(cx.myid, vote, cx);
+    // The prepares of the others may have arrived before the pre-prepare
+    check_prepared(view, seq, b.hash, cx);
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use config::Node;
+    use consensus::CommitSink;
+    use crypto::{Algorithm, ed25519};
+    use tokio::sync::mpsc::{UnboundedReceiver, unbounded_channel};
+    use types::{WireReady, pbft::{Certificate, Replica, Vote}};
+
+    const N: usize = 4;
+
+    pub(crate) struct Fixture {
+        pub keys: Vec<ed25519::Keypair>,
+        pub cx: Context,
+        net: UnboundedReceiver<(Replica, Arc<ProtocolMsg>)>,
+    }
+
+    impl Fixture {
+        /// Node `id` of four, which assigns blocks of one transaction
+        pub fn new(id: Replica, keys: Vec<ed25519::Keypair>, state_dir: Option<String>) -> Self {
+            let mut config = Node::new();
+            config.num_nodes = N;
+            config.num_faults = 1;
+            config.block_size = 1;
+            config.id = id;
+            config.crypto_alg = Algorithm::ED25519;
+            config.secret_key_bytes = keys[id].encode().to_vec();
+            config.pk_map = keys.iter().enumerate()
+                .map(|(i, k)| (i, k.public().encode().to_vec()))
+                .collect();
+            config.state_dir = state_dir;
+            let (net_send, net) = unbounded_channel();
+            let (cli_send, _) = unbounded_channel();
+            let cx = Context::new(&config, net_send, cli_send, CommitSink::new(None));
+            Fixture { keys, cx, net }
+        }
+
+        pub fn keys() -> Vec<ed25519::Keypair> {
+            (0..N).map(|_| ed25519::Keypair::generate()).collect()
+        }
+
+        /// A certificate on `msg` with the votes of all the nodes
+        pub fn cert(&self, msg: CertType) -> Certificate {
+            let mut c = Certificate::empty_cert();
+            for (origin, k) in self.keys.iter().enumerate() {
+                let auth = k.sign(&util::io::to_bytes(&msg));
+                c.votes.push(Vote { origin, auth });
+            }
+            c.msg = msg;
+            c
+        }
+
+        /// The messages that we sent since the last call, as the others get
+        /// them
+        pub fn sent(&mut self) -> Vec<ProtocolMsg> {
+            let mut sent = Vec::new();
+            while let Ok((_, m)) = self.net.try_recv() {
+                sent.push(ProtocolMsg::from_bytes(&m.to_bytes()));
+            }
+            sent
+        }
+    }
+
+    #[tokio::test]
+    async fn restarted_primary_keeps_its_sequence_numbers() {
+        let dir = std::env::temp_dir().join(format!("pbft-wal-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let state_dir = Some(dir.to_string_lossy().to_string());
+        let keys = Fixture::keys();
+
+        // Node 0 assigns a block to sequence number 1 of view 0, and crashes
+        let mut f = Fixture::new(0, keys.clone(), state_dir.clone());
+        f.cx.storage.add_transaction(Transaction::new_dummy_tx(1, 0));
+        assert!(can_propose(&f.cx));
+        let txs = f.cx.storage.cleave(1);
+        do_propose(txs, &mut f.cx);
+        let b1 = f.cx.log[&1].clone();
+        drop(f);
+
+        // After the restart, it cannot assign the sequence number to another
+        // block, so the transactions stay in the pool
+        let mut f = Fixture::new(0, keys, state_dir);
+        assert_eq!(f.cx.view, 0);
+        f.cx.storage.add_transaction(Transaction::new_dummy_tx(2, 0));
+        assert!(!can_propose(&f.cx));
+        resend_recorded(&mut f.cx);
+        assert_eq!(f.cx.next_seq, 1);
+        assert_eq!(f.cx.storage.get_tx_pool_size(), 1);
+        assert!(f.sent().is_empty());
+
+        // Once it has the block again, it sends the same pre-prepare
+        f.cx.storage.add_delivered_block(b1.clone());
+        resend_recorded(&mut f.cx);
+        assert_eq!(f.cx.next_seq, 2);
+        let p = f.sent().into_iter()
+            .find_map(|m| match m {
+                ProtocolMsg::PrePrepare(p) => Some(p),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!((p.view, p.seq, p.block_hash), (0, 1, b1.hash));
+        assert!(check_pre_prepare(&p, &f.cx));
+        assert!(can_propose(&f.cx));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,90 @@
+/// The core consensus module used for PBFT
+///
+/// The reactor reacts to all the messages from the network, and talks to the
+/// clients accordingly.
+
+use tokio::sync::mpsc::{
+    UnboundedSender,
+    UnboundedReceiver
+};
+use types::{TxTrait, pbft::{Replica, Transaction, ClientMsg, ProtocolMsg}};
+use config::Node;
+use consensus::CommitSink;
+use crate::node::{
+    context::Context,
+    execute::{on_blocks, on_checkpoint, on_fetch},
+    proposal::{can_propose, do_propose, on_pre_prepare, resend_recorded},
+    view_change::*,
+    vote::{on_commit, on_prepare},
+};
+use tokio_stream::StreamExt;
+use std::sync::Arc;
+
+pub async fn reactor(
+    config:&Node,
+    net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
+    cli_send: UnboundedSender<Arc<ClientMsg>>,
+    commits: CommitSink,
+    mut cli_recv: UnboundedReceiver<Transaction>
+) {
+    let mut cx = Context::new(config, net_send, cli_send, commits);
+    let block_size = config.block_size;
+    start_view(&mut cx);
+    loop {
+        tokio::select! {
+            pmsg_opt = net_recv.recv() => {
+                // Received a protocol message
+                let (sender, protmsg) = match pmsg_opt {
+                    None => break,
+                    Some(x) => x,
+                };
+                log::trace!("Received protocol message: {:?}", protmsg);
+                match protmsg {
+                    ProtocolMsg::PrePrepare(p) => on_pre_prepare(p, &mut cx),
+                    ProtocolMsg::Prepare(c) => on_prepare(sender, c, &mut cx),
+                    ProtocolMsg::Commit(c) => on_commit(sender, c, &mut cx),
+                    ProtocolMsg::Checkpoint(c) => on_checkpoint(sender, c, &mut cx),
+                    ProtocolMsg::ViewChange(vc) => on_view_change(sender, vc, &mut cx),
+                    ProtocolMsg::NewView(v, vcs) => on_new_view(sender, v, vcs, &mut cx),
+                    ProtocolMsg::Fetch(from) => on_fetch(sender, from, &mut cx),
+                    ProtocolMsg::Blocks(blocks) => on_blocks(sender, blocks, &mut cx),
+                    other => log::debug!("Not handling {:?}", other),
+                }
+            },
+            tx_opt = cli_recv.recv() => {
+                // We received a message from the client
+                let tx = match tx_opt {
+                    None => break,
+                    Some(x) => x,
+                };
+                if !cx.seen_txs.contains(&tx.get_hash()) {
+                    cx.storage.add_transaction(tx);
+                }
+            },
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                let ev = match ev_opt {
+                    None => continue,
+                    Some(Ok(x)) => x.into_inner(),
+                    Some(Err(e)) => {
+                        log::warn!("Event queue misfired: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("Triggered event {:?}", ev);
+                match ev {
+                    Event::Progress(v, seq) => on_progress(v, seq, &mut cx),
+                    Event::ViewChange(v) => on_view_change_timeout(v, &mut cx),
+                    Event::Propose(v) => on_propose_event(v, &mut cx),
+                }
+            }
+        }
+        // Assign full blocks while the window allows
+        resend_recorded(&mut cx);
+        while cx.storage.get_tx_pool_size() >= block_size && can_propose(&cx) {
+            log::debug!("I {} am the primary of view {}, proposing", cx.myid, cx.view);
+            let txs = cx.storage.cleave(block_size);
+            do_propose(txs, &mut cx);
+        }
+    }
+}
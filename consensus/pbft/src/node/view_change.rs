@@ -0,0 +1,342 @@
+/// View changes replace a primary that does not make progress.
+///
+/// A node that has work pending and executes nothing for a while moves to the
+/// next view, and sends the others its stable checkpoint and the blocks that
+/// prepared at it since. The primary of the new view starts it with n-f of
+/// these messages, from which every node derives the same pre-prepares: the
+/// block that prepared in the highest view for every sequence number, or an
+/// empty block. The timeout doubles with every view change in a row that does
+/// not execute anything.
+
+use types::{CertKind, Threshold, pbft::{CertType, Height, Prepared, ProtocolMsg, Replica, View, ViewChange}};
+use fnv::FnvHashSet as HashSet;
+use super::{context::{Context, WINDOW}, execute::make_stable, proposal::{accept, can_propose, do_propose, null_block}};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+/// The most times that the timeout doubles
+const MAX_BACKOFF: u32 = 6;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event {
+    /// Check that we executed something in the view since this sequence
+    /// number
+    Progress(View, Height),
+    /// The view change to this view did not complete in time
+    ViewChange(View),
+    /// As the primary of the view, propose what we have even if it is less
+    /// than a block
+    Propose(View),
+}
+
+/// How long we wait for progress in a view
+fn timeout(cx: &Context) -> Duration {
+    cx.deltas(8) * (1 << std::cmp::min(cx.timeouts, MAX_BACKOFF))
+}
+
+/// Start the timers of the view that we are in
+pub fn start_view(cx: &mut Context) {
+    cx.events.insert(Event::Progress(cx.view, cx.exec_seq), timeout(cx));
+    if cx.primary_of(cx.view) == cx.myid {
+        cx.events.insert(Event::Propose(cx.view), cx.deltas(2));
+    }
+}
+
+/// The view did not execute anything since `seq`: move to the next view if
+/// something is waiting
+pub fn on_progress(view: View, seq: Height, cx: &mut Context) {
+    if view != cx.view || cx.in_view_change {
+        return;
+    }
+    if cx.exec_seq > seq || !cx.has_pending_work() {
+        cx.events.insert(Event::Progress(view, cx.exec_seq), timeout(cx));
+        return;
+    }
+    log::info!("View {} made no progress", view);
+    start_view_change(view+1, cx);
+}
+
+/// The view change to `view` timed out: try the next one
+pub fn on_view_change_timeout(view: View, cx: &mut Context) {
+    if view != cx.view || !cx.in_view_change {
+        return;
+    }
+    log::info!("The view change to view {} timed out", view);
+    start_view_change(view+1, cx);
+}
+
+/// As the primary, propose a partial block if nothing is in flight
+pub fn on_propose_event(view: View, cx: &mut Context) {
+    if view != cx.view || cx.in_view_change || cx.primary_of(view) != cx.myid {
+        return;
+    }
+    cx.events.insert(Event::Propose(view), cx.deltas(2));
+    let pool = cx.storage.get_tx_pool_size();
+    let idle = cx.next_seq == cx.exec_seq+1;
+    if pool > 0 && pool < cx.block_size && idle && can_propose(cx) {
+        log::debug!("Proposing a partial block in view {}", view);
+        let txs = cx.storage.cleave(pool);
+        do_propose(txs, cx);
+    }
+}
+
+/// Leave our view for `view`, and send the others what prepared at us
+pub fn start_view_change(view: View, cx: &mut Context) {
+    if view <= cx.view {
+        return;
+    }
+    log::info!("Changing to view {}", view);
+    cx.view = view;
+    cx.in_view_change = true;
+    cx.timeouts += 1;
+    cx.log.clear();
+    cx.prepares.retain(|(v, _, _), _| *v >= view);
+    cx.commits.retain(|(v, _, _), _| *v >= view);
+    cx.view_changes.retain(|v, _| *v >= view);
+    cx.events.insert(Event::ViewChange(view), timeout(cx));
+
+    let mut vc = ViewChange {
+        view,
+        origin: cx.myid,
+        stable: cx.stable_cert.clone(),
+        prepared: cx.prepared.values().cloned().collect(),
+        proof: Vec::new(),
+    };
+    vc.proof = match cx.my_secret_key.sign(&vc.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the view change: {}", e);
+        },
+        Ok(sig) => sig,
+    };
+    cx.broadcast(ProtocolMsg::ViewChange(vc.clone()));
+    on_view_change(cx.myid, vc, cx);
+}
+
+/// The stable checkpoint of a view change message, if its certificate checks
+/// out. The empty certificate is the checkpoint before the first block.
+fn stable_seq(vc: &ViewChange, cx: &Context) -> Option<Height> {
+    match vc.stable.msg {
+        CertType::DEFAULT if vc.stable.num_votes() == 0 => Some(0),
+        CertType::Checkpoint(s, _) if cx.is_valid_cert(&vc.stable, CertKind::Checkpoint) => Some(s),
+        _ => None,
+    }
+}
+
+/// Check the signature of a view change message, its checkpoint and the
+/// certificates of its prepared blocks
+fn check_view_change(vc: &ViewChange, cx: &Context) -> bool {
+    let pk = match cx.pub_key_map.get(&vc.origin) {
+        None => return false,
+        Some(pk) => pk,
+    };
+    if !pk.verify(&vc.sign_data(), &vc.proof) {
+        return false;
+    }
+    let low = match stable_seq(vc, cx) {
+        None => return false,
+        Some(s) => s,
+    };
+    let mut seqs = HashSet::default();
+    vc.prepared.iter().all(|p| {
+        match p.cert.msg {
+            CertType::Prepare(v, s, h) => {
+                v < vc.view &&
+                    s > low && s <= low + WINDOW &&
+                    seqs.insert(s) &&
+                    p.block.hash == h &&
+                    p.block.header.height == s &&
+                    cx.is_valid_cert(&p.cert, CertKind::Prepare)
+            }
+            _ => false,
+        }
+    })
+}
+
+/// A view change message from `sender`
+pub fn on_view_change(sender: Replica, vc: ViewChange, cx: &mut Context) {
+    if vc.origin != sender || vc.view < cx.view {
+        return;
+    }
+    if cx.view_changes.get(&vc.view).map_or(false, |m| m.contains_key(&sender)) {
+        return;
+    }
+    if sender != cx.myid && !check_view_change(&vc, cx) {
+        log::warn!("Invalid view change message from {}", sender);
+        return;
+    }
+    let view = vc.view;
+    cx.view_changes.entry(view).or_default().insert(sender, vc);
+
+    // Join f+1 nodes that left our view: at least one of them is honest
+    let mut ahead = cx.view_changes.iter()
+        .filter(|(v, _)| **v > cx.view)
+        .flat_map(|(_, m)| m.keys().cloned())
+        .collect::<HashSet<_>>();
+    ahead.remove(&cx.myid);
+    if cx.quorum.is_met(Threshold::Honest, ahead) {
+        let next = *cx.view_changes.keys()
+            .filter(|v| **v > cx.view)
+            .min()
+            .unwrap();
+        start_view_change(next, cx);
+    }
+
+    // As the primary of the new view, start it with n-f view changes
+    if cx.view == view &&
+        cx.in_view_change &&
+        cx.primary_of(view) == cx.myid &&
+        cx.quorum.is_met(Threshold::Quorum, cx.view_changes[&view].keys().cloned())
+    {
+        let mut vcs: Vec<ViewChange> = Vec::new();
+        for vc in cx.view_changes[&view].values() {
+            if cx.quorum.is_met(Threshold::Quorum, vcs.iter().map(|vc| vc.origin)) {
+                break;
+            }
+            vcs.push(vc.clone());
+        }
+        log::info!("Starting view {}", view);
+        cx.broadcast(ProtocolMsg::NewView(view, vcs.clone()));
+        install_view(view, &vcs, cx);
+    }
+}
+
+/// The new view message of the primary of `view`
+pub fn on_new_view(sender: Replica, view: View, vcs: Vec<ViewChange>, cx: &mut Context) {
+    if sender != cx.primary_of(view) ||
+        view < cx.view ||
+        (view == cx.view && !cx.in_view_change)
+    {
+        return;
+    }
+    let mut origins = HashSet::default();
+    let is_valid = vcs.iter().all(|vc| {
+        vc.view == view &&
+            origins.insert(vc.origin) &&
+            check_view_change(vc, cx)
+    });
+    if !is_valid || !cx.quorum.is_met(Threshold::Quorum, origins) {
+        log::warn!("Invalid new view message from {}", sender);
+        return;
+    }
+    install_view(view, &vcs, cx);
+}
+
+/// Enter `view` with the pre-prepares that follow from its view change
+/// messages
+fn install_view(view: View, vcs: &[ViewChange], cx: &mut Context) {
+    // The highest stable checkpoint, and the block that prepared in the
+    // highest view for every sequence number after it
+    let stable = vcs.iter()
+        .max_by_key(|vc| stable_seq(vc, cx).unwrap_or(0))
+        .map(|vc| vc.stable.clone())
+        .unwrap();
+    let low = match stable.msg {
+        CertType::Checkpoint(s, _) => s,
+        _ => 0,
+    };
+    let mut chosen: BTreeMap<Height, &Prepared> = BTreeMap::new();
+    for p in vcs.iter().flat_map(|vc| vc.prepared.iter()) {
+        let (v, s) = match p.cert.msg {
+            CertType::Prepare(v, s, _) => (v, s),
+            _ => continue,
+        };
+        if s <= low {
+            continue;
+        }
+        let is_higher = chosen.get(&s).map_or(true, |q| match q.cert.msg {
+            CertType::Prepare(qv, _, _) => v > qv,
+            _ => true,
+        });
+        if is_higher {
+            chosen.insert(s, p);
+        }
+    }
+    let high = chosen.keys().next_back().cloned().unwrap_or(low);
+    let blocks: Vec<_> = (low+1..=high)
+        .map(|s| match chosen.get(&s) {
+            Some(p) => p.block.clone(),
+            None => null_block(view, s, cx),
+        })
+        .collect();
+
+    log::info!("Entering view {} with sequence numbers {} to {}", view, low+1, high);
+    cx.view = view;
+    cx.in_view_change = false;
+    cx.log.clear();
+    cx.prepares.retain(|(v, _, _), _| *v >= view);
+    cx.commits.retain(|(v, _, _), _| *v >= view);
+    cx.view_changes.retain(|v, _| *v > view);
+    make_stable(stable, cx);
+    cx.next_seq = std::cmp::max(high, cx.stable_seq)+1;
+    for b in blocks {
+        if b.header.height > cx.stable_seq {
+            accept(view, Arc::new(b), cx);
+        }
+    }
+    start_view(cx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::proposal::{tests::Fixture, view_tag};
+    use crypto::Keypair;
+    use types::pbft::Block;
+
+    /// A block of the primary of `view` at `seq`
+    fn block(view: View, seq: Height, cx: &Context) -> Block {
+        let mut b = Block::with_tx(Vec::new());
+        b.header.author = cx.primary_of(view);
+        b.header.height = seq;
+        b.header.extra = view_tag(view);
+        b.header.extra.push(1);
+        b.hash = b.compute_hash();
+        b
+    }
+
+    #[tokio::test]
+    async fn install_view_takes_the_highest_prepared_blocks() {
+        let mut f = Fixture::new(0, Fixture::keys(), None);
+        let prepared = |f: &Fixture, view: View, seq: Height| {
+            let block = block(view, seq, &f.cx);
+            let cert = f.cert(CertType::Prepare(view, seq, block.hash));
+            Prepared { block, cert }
+        };
+        let view_change = |f: &Fixture, origin: Replica, prepared: Vec<Prepared>| {
+            let mut vc = ViewChange {
+                view: 3,
+                origin,
+                stable: types::pbft::Certificate::empty_cert(),
+                prepared,
+                proof: Vec::new(),
+            };
+            vc.proof = Keypair::Ed25519(f.keys[origin].clone()).sign(&vc.sign_data()).unwrap();
+            vc
+        };
+        // Sequence number 1 prepared in views 0 and 1, 3 in views 0 and 2,
+        // and 2 nowhere
+        let vcs = vec![
+            view_change(&f, 1, vec![prepared(&f, 0, 1), prepared(&f, 2, 3)]),
+            view_change(&f, 2, vec![prepared(&f, 1, 1), prepared(&f, 0, 3)]),
+            view_change(&f, 3, Vec::new()),
+        ];
+        on_new_view(3, 3, vcs, &mut f.cx);
+
+        assert_eq!(f.cx.view, 3);
+        assert!(!f.cx.in_view_change);
+        assert_eq!(f.cx.next_seq, 4);
+        assert_eq!(f.cx.log[&1].hash, block(1, 1, &f.cx).hash);
+        assert_eq!(f.cx.log[&2].hash, null_block(3, 2, &f.cx).hash);
+        assert!(f.cx.log[&2].body.tx_hashes.is_empty());
+        assert_eq!(f.cx.log[&3].hash, block(2, 3, &f.cx).hash);
+        // We prepare them all in the new view
+        let prepares: Vec<_> = f.sent().into_iter()
+            .filter_map(|m| match m {
+                ProtocolMsg::Prepare(c) => Some(c.msg),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(prepares, (1..=3)
+            .map(|s| CertType::Prepare(3, s, f.cx.log[&s].hash))
+            .collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,119 @@
+use types::{CertKind, Threshold, pbft::{CertType, Certificate, Height, Prepared, ProtocolMsg, Replica, View}};
+use crypto::hash::Hash;
+use fnv::FnvHashMap as HashMap;
+use super::{context::Context, execute::try_execute};
+
+/// Add a single vote to the certificate of its message, and return the
+/// number of distinct votes in it
+fn add_vote(
+    map: &mut HashMap<(View, Height, Hash), Certificate>,
+    key: (View, Height, Hash),
+    mut c: Certificate,
+) -> usize {
+    let vote = c.votes.pop().unwrap();
+    let cert = map.entry(key).or_insert_with(|| {
+        let mut cert = Certificate::empty_cert();
+        cert.msg = c.msg.clone();
+        cert
+    });
+    if !cert.votes.iter().any(|v| v.origin == vote.origin) {
+        cert.votes.push(vote);
+    }
+    cert.votes.len()
+}
+
+/// Whether a vote for a sequence number in a view is worth keeping: votes for
+/// the next view may arrive before its new view message
+fn is_relevant(view: View, seq: Height, cx: &Context) -> bool {
+    view >= cx.view && cx.in_window(seq) && seq > cx.exec_seq
+}
+
+/// A prepare from `sender`
+pub fn on_prepare(sender: Replica, c: Certificate, cx: &mut Context) {
+    let (view, seq, hash) = match c.msg {
+        CertType::Prepare(v, s, h) => (v, s, h),
+        _ => return,
+    };
+    if !is_relevant(view, seq, cx) {
+        return;
+    }
+    if !cx.is_valid_vote(sender, &c, CertKind::Prepare) {
+        log::warn!("Invalid prepare from {}", sender);
+        return;
+    }
+    add_vote(&mut cx.prepares, (view, seq, hash), c);
+    check_prepared(view, seq, hash, cx);
+}
+
+/// Once the block that we accepted at `seq` has n-f prepares, it is prepared
+/// at us: remember it for the view changes, and commit it
+pub fn check_prepared(view: View, seq: Height, hash: Hash, cx: &mut Context) {
+    if view != cx.view || cx.in_view_change {
+        return;
+    }
+    let block = match cx.log.get(&seq) {
+        Some(b) if b.hash == hash => b.clone(),
+        _ => return,
+    };
+    let done = cx.prepared.get(&seq)
+        .map_or(false, |p| p.cert.msg == CertType::Prepare(view, seq, hash));
+    if done {
+        return;
+    }
+    let cert = match cx.prepares.get(&(view, seq, hash)) {
+        Some(c) if cx.quorum.is_met(Threshold::Quorum, c.votes.iter().map(|v| v.origin)) => c.clone(),
+        _ => return,
+    };
+    log::debug!("Prepared sequence number {} in view {}", seq, view);
+    let cert = cx.finalize_cert(cert);
+    cx.prepared.insert(seq, Prepared {
+        block: block.as_ref().clone(),
+        cert,
+    });
+
+    let vote = cx.sign_vote(CertType::Commit(view, seq, hash));
+    cx.broadcast(ProtocolMsg::Commit(vote.clone()));
+    on_commit(cx.myid, vote, cx);
+}
+
+/// A commit from `sender`
+pub fn on_commit(sender: Replica, c: Certificate, cx: &mut Context) {
+    let (view, seq, hash) = match c.msg {
+        CertType::Commit(v, s, h) => (v, s, h),
+        _ => return,
+    };
+    if !is_relevant(view, seq, cx) {
+        return;
+    }
+    if !cx.is_valid_vote(sender, &c, CertKind::Commit) {
+        log::warn!("Invalid commit from {}", sender);
+        return;
+    }
+    add_vote(&mut cx.commits, (view, seq, hash), c);
+    check_committed(view, seq, hash, cx);
+}
+
+/// Once the block that prepared at `seq` has n-f commits, it commits, and
+/// executes after the blocks before it
+fn check_committed(view: View, seq: Height, hash: Hash, cx: &mut Context) {
+    if seq <= cx.exec_seq || cx.committed.contains_key(&seq) {
+        return;
+    }
+    let block = match cx.prepared.get(&seq) {
+        Some(p) if p.cert.msg == CertType::Prepare(view, seq, hash) => {
+            match cx.log.get(&seq) {
+                Some(b) if b.hash == hash => b.clone(),
+                _ => return,
+            }
+        }
+        _ => return,
+    };
+    let cert = match cx.commits.get(&(view, seq, hash)) {
+        Some(c) if cx.quorum.is_met(Threshold::Quorum, c.votes.iter().map(|v| v.origin)) => c.clone(),
+        _ => return,
+    };
+    log::debug!("Committed sequence number {} in view {}", seq, view);
+    let cert = cx.finalize_cert(cert);
+    cx.committed.insert(seq, (block, cert));
+    try_execute(cx);
+}
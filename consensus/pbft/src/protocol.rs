@@ -0,0 +1,66 @@
+use config::Node;
+use consensus::{NodeOptions, Reactor};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::UnboundedReceiver;
+use types::pbft::{ClientMsg, ProtocolMsg, Transaction};
+
+/// The PBFT protocol, for `consensus::run`
+pub struct Pbft;
+
+impl consensus::Protocol for Pbft {
+    const NAME: &'static str = "pbft";
+    const FAULT_MODEL: types::FaultModel = types::FaultModel::PartiallySynchronous;
+
+    type ProtocolMsg = ProtocolMsg;
+    type ClientMsg = ClientMsg;
+    type Transaction = Transaction;
+
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor {
+        // Setup client network
+        let client_network = net::tokio_manager::Protocol::<Transaction, ClientMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (cli_send, cli_recv) = 
+        cli_net_rt.block_on(
+            client_network.client_setup(
+                config.client_ip(),
+                util::codec::EnCodec::new(),
+                util::codec::Decodec::new()
+            )
+        );
+        let cli_recv = consensus::merge_local_txs(cli_net_rt, cli_recv, local_txs);
+
+        // Setup the protocol network
+        let protocol_network = net::tokio_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+        let (net_send, net_recv) = 
+        prot_net_rt.block_on(
+            protocol_network.server_setup(
+                config.net_map.clone(), 
+                util::codec::EnCodec::new(), 
+                util::codec::Decodec::new()
+            )
+        );
+
+        // Measure the delay to the other nodes before starting
+        let (net_recv, _delays) = consensus::start_delay_monitor(config, prot_net_rt, net_send.clone(), net_recv);
+        // Misbehave if the config asks for it
+        let net_send = consensus::start_adversary(config, prot_net_rt, net_send);
+
+        let config = config.clone();
+        let commits = opts.commit_sink();
+        Box::pin(async move {
+            crate::node::reactor(
+                &config,
+                net_send,
+                net_recv,
+                cli_send,
+                commits,
+                cli_recv
+            ).await
+        })
+    }
+}
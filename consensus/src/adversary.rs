@@ -0,0 +1,190 @@
+use config::{Byzantine, Node};
+use crypto::{Keypair, bls, ed25519, rsa, secp256k1};
+use futures::{Stream, StreamExt};
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+use tokio::{runtime::Runtime, sync::mpsc::unbounded_channel, time::Instant};
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use types::{ByzantineMsg, MsgKind, Replica};
+
+/// How many messages later an old message is sent again
+const REPLAY_AFTER: usize = 100;
+
+fn keypair(config: &Node) -> Keypair {
+    match config.crypto_alg {
+        crypto::Algorithm::ED25519 => {
+            let mut sk_copy = config.secret_key_bytes.clone();
+            let kp = ed25519::Keypair::decode(&mut sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            Keypair::Ed25519(kp)
+        }
+        crypto::Algorithm::SECP256K1 => {
+            let sk_copy = config.secret_key_bytes.clone();
+            let sk = secp256k1::SecretKey::from_bytes(sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            Keypair::Secp256k1(secp256k1::Keypair::from(sk))
+        }
+        crypto::Algorithm::BLS => {
+            let mut sk_copy = config.secret_key_bytes.clone();
+            let kp = bls::Keypair::decode(&mut sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            Keypair::Bls(kp)
+        }
+        crypto::Algorithm::RSA => {
+            let mut sk_copy = config.secret_key_bytes.clone();
+            let kp = rsa::Keypair::from_pkcs8(&mut sk_copy)
+                .expect("Failed to decode the secret key from the config");
+            Keypair::Rsa(kp)
+        }
+    }
+}
+
+/// The outbound side of a Byzantine node: it gets the messages that the
+/// reactor sends and changes them according to `Byzantine`
+struct Adversary<M> {
+    b: Byzantine,
+    sk: Keypair,
+    num_nodes: usize,
+    /// The two halves of the other nodes, that get conflicting proposals
+    halves: (Vec<Replica>, Vec<Replica>),
+    /// The messages to send again
+    history: VecDeque<(Replica, Arc<M>)>,
+}
+
+impl<M: ByzantineMsg> Adversary<M> {
+    fn new(config: &Node) -> Self {
+        let mut others: Vec<_> = config.net_map.keys()
+            .copied()
+            .filter(|r| *r != config.id)
+            .collect();
+        others.sort_unstable();
+        let second = others.split_off(others.len() / 2);
+        Adversary {
+            b: config.byzantine.clone(),
+            sk: keypair(config),
+            num_nodes: config.num_nodes,
+            halves: (others, second),
+            history: VecDeque::new(),
+        }
+    }
+
+    /// The messages to send instead of `msg` to `to`
+    fn tamper(&mut self, to: Replica, msg: Arc<M>) -> Vec<(Replica, Arc<M>)> {
+        let mut out = Vec::new();
+        let kind = msg.kind();
+        if (kind == MsgKind::Proposal && self.b.withhold_proposals) ||
+            (kind == MsgKind::Vote && self.b.withhold_votes)
+        {
+            log::debug!("Withholding a {:?}", kind);
+            return out;
+        }
+        if self.b.malformed {
+            if let Some(bad) = msg.malformed() {
+                out.push((to, Arc::new(bad)));
+            }
+        }
+        if self.b.replay {
+            if self.history.len() == REPLAY_AFTER {
+                out.extend(self.history.pop_front());
+            }
+            self.history.push_back((to, msg.clone()));
+        }
+        let twin = if self.b.equivocate && to >= self.num_nodes {
+            msg.conflicting(&self.sk)
+        } else {
+            None
+        };
+        match twin {
+            None => out.push((to, msg)),
+            Some(twin) => {
+                log::debug!("Equivocating");
+                let twin = Arc::new(twin);
+                out.extend(self.halves.0.iter().map(|r| (*r, msg.clone())));
+                out.extend(self.halves.1.iter().map(|r| (*r, twin.clone())));
+            }
+        }
+        out
+    }
+}
+
+/// Tamper with the messages of `reactor_recv`, and hand them to `send`
+/// (after the delay of the config, if any)
+async fn tamper<M, R, S>(
+    mut adversary: Adversary<M>,
+    mut reactor_recv: R,
+    send: S,
+) where
+    M: ByzantineMsg + Send + Sync + 'static,
+    R: Stream<Item = (Replica, Arc<M>)> + Unpin,
+    S: Fn(Replica, Arc<M>) -> bool + Send + 'static,
+{
+    let delay = Duration::from_millis(adversary.b.delay);
+    let (delayed_send, mut delayed_recv) = unbounded_channel::<(Instant, Replica, Arc<M>)>();
+    // The messages are all delayed by the same amount, so they leave in order
+    tokio::spawn(async move {
+        while let Some((at, to, msg)) = delayed_recv.recv().await {
+            tokio::time::sleep_until(at).await;
+            if !send(to, msg) {
+                break;
+            }
+        }
+    });
+    while let Some((to, msg)) = reactor_recv.next().await {
+        let at = Instant::now() + delay;
+        for (to, msg) in adversary.tamper(to, msg) {
+            if delayed_send.send((at, to, msg)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn warn(b: &Byzantine) {
+    log::warn!("This node is Byzantine: {:?}", b);
+}
+
+/// Put the Byzantine behaviour of the config, if any, between the reactor
+/// and the tokio channel of the protocol network.
+///
+/// Returns the channel on which the reactor sends its messages.
+pub fn start_adversary<M>(
+    config: &Node,
+    rt: &Runtime,
+    net_send: tokio::sync::mpsc::UnboundedSender<(Replica, Arc<M>)>,
+) -> tokio::sync::mpsc::UnboundedSender<(Replica, Arc<M>)>
+where
+    M: ByzantineMsg + Send + Sync + 'static,
+{
+    if config.byzantine.is_honest() {
+        return net_send;
+    }
+    warn(&config.byzantine);
+    let (reactor_send, reactor_recv) = unbounded_channel();
+    rt.spawn(tamper(
+        Adversary::new(config),
+        UnboundedReceiverStream::new(reactor_recv),
+        move |to, msg| net_send.send((to, msg)).is_ok(),
+    ));
+    reactor_send
+}
+
+/// `start_adversary` for the futures channel of the protocol network
+pub fn start_adversary_futures<M>(
+    config: &Node,
+    rt: &Runtime,
+    net_send: futures::channel::mpsc::UnboundedSender<(Replica, Arc<M>)>,
+) -> futures::channel::mpsc::UnboundedSender<(Replica, Arc<M>)>
+where
+    M: ByzantineMsg + Send + Sync + 'static,
+{
+    if config.byzantine.is_honest() {
+        return net_send;
+    }
+    warn(&config.byzantine);
+    let (reactor_send, reactor_recv) = futures::channel::mpsc::unbounded();
+    rt.spawn(tamper(
+        Adversary::new(config),
+        reactor_recv,
+        move |to, msg| net_send.unbounded_send((to, msg)).is_ok(),
+    ));
+    reactor_send
+}
@@ -0,0 +1,126 @@
+use std::time::SystemTime;
+use std::collections::hash_map::Entry;
+use std::fmt::Debug;
+use config::Client;
+use types::{ClientBlockMsg, FaultModel, Replica, Threshold, WireReady, synchs::Transaction};
+use tokio::sync::mpsc::channel;
+use crypto::hash::Hash;
+use crate::{EvidenceChecker, statistics};
+use std::sync::Arc;
+use util::codec::{EnCodec, Decodec};
+use net::tokio_manager::TlsClient as NClient;
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+
+/// Run a client of a protocol whose replicas send it the blocks they
+/// propose as `M`: keep `window` transactions in flight, count a block as
+/// committed once replicas with more than the faulty weight under `model`
+/// sent it, and report the statistics after `metric` transactions.
+pub async fn start_client<M>(
+    c:&Client,
+    model: FaultModel,
+    metric: u64,
+    window: usize,
+)
+where M: ClientBlockMsg + WireReady + Debug + 'static + Unpin,
+{
+    let mut client_network = NClient::<M, Transaction>::new(c.root_cert.clone());
+    let servers = c.net_map.clone();
+    let send_id = c.num_nodes;
+    let (net_send, mut net_recv) =
+        client_network.setup(servers, EnCodec::new(), Decodec::new()).await;
+
+    // Start with the sink implementation
+    let (send, mut recv) = channel(util::CHANNEL_SIZE);
+    let m = metric;
+    let payload = c.payload;
+    tokio::spawn(async move{
+        let mut i = 0;
+        loop {
+            let tx = Transaction::new_dummy_tx(i,payload);
+            i += 1;
+            if let Err(e) = send.send(Arc::new(tx)).await {
+                log::info!("Closing tx producer channel: {}", e);
+                std::process::exit(0);
+            }
+        }
+    });
+    let mut pending = window;
+    let mut time_map = HashMap::default();
+    let quorum = c.quorum_policy(model);
+    let mut ack_map:HashMap<Hash, HashSet<Replica>> = HashMap::default();
+    let mut finished_map:HashSet<Hash> = HashSet::default();
+    let mut latency_map = HashMap::default();
+    let mut num_cmds:u128 = 0;
+    let mut evidence = EvidenceChecker::from_client(c);
+
+    let start = SystemTime::now();
+    loop {
+        tokio::select! {
+            tx_opt = recv.recv(), if pending > 0 => {
+                if let Some(x) = tx_opt {
+                    let hash = crypto::hash::ser_and_hash(x.as_ref());
+                    net_send.send((send_id, x))
+                        .expect("Failed to send to the client");
+                    time_map.insert(hash, SystemTime::now());
+                    pending -= 1;
+                    log::trace!(
+                        "Sending transaction to the leader");
+                } else {
+                    log::info!("Finished sending messages");
+                    std::process::exit(0);
+                }
+            },
+            block_opt = net_recv.recv() => {
+                log::debug!("Got {:?} from the network", block_opt);
+                // Got something from the network
+                let (sender, msg) = match block_opt {
+                    Some(x) => x,
+                    None => panic!("invalid content received from the server"),
+                };
+                let b = match msg.new_block() {
+                    Some(b) => b,
+                    None => continue,
+                };
+                log::debug!("got a block:{:?}",b);
+
+                // Check if the block is valid the first time we see it
+                let acks = match ack_map.entry(b.hash) {
+                    Entry::Vacant(e) => {
+                        if !evidence.check(&b.header.evidence) {
+                            log::warn!("Got a block with invalid evidence");
+                            continue;
+                        }
+                        e.insert(HashSet::default())
+                    },
+                    Entry::Occupied(e) => e.into_mut(),
+                };
+                acks.insert(sender);
+                // One of the replicas that sent it is honest
+                if !quorum.is_met(Threshold::Honest, acks.iter().cloned()) {
+                    continue;
+                }
+                if finished_map.contains(&b.hash) {
+                    continue;
+                }
+                let now = SystemTime::now();
+                pending += c.block_size;
+                num_cmds += c.block_size as u128;
+                for t in &b.body.tx_hashes {
+                    if let Some(old) = time_map.get(t) {
+                        latency_map.insert(*t, (*old, now));
+                    } else {
+                        log::warn!(
+                            "transaction not found in time map");
+                        num_cmds -= 1;
+                    }
+                }
+                finished_map.insert(b.hash);
+            }
+        }
+        if num_cmds > m as u128 {
+            let now = SystemTime::now();
+            statistics(now, start, latency_map);
+            return;
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use tokio::sync::mpsc::UnboundedSender;
+use types::CommittedBlock;
+
+/// Where a reactor publishes the blocks that it commits, for the application
+/// that embeds the node (see `NodeOptions::commits`)
+#[derive(Debug, Clone, Default)]
+pub struct CommitSink {
+    send: Option<UnboundedSender<CommittedBlock>>,
+}
+
+impl CommitSink {
+    pub fn new(send: Option<UnboundedSender<CommittedBlock>>) -> Self {
+        CommitSink { send }
+    }
+
+    /// Whether anyone listens to the committed blocks
+    pub fn is_subscribed(&self) -> bool {
+        self.send.is_some()
+    }
+
+    /// Publish the next committed block. Once the subscriber goes away, the
+    /// blocks are dropped.
+    pub fn publish(&mut self, b: CommittedBlock) {
+        let send = match &self.send {
+            None => return,
+            Some(s) => s,
+        };
+        if send.send(b).is_err() {
+            log::warn!("The subscriber of the committed blocks is gone");
+            self.send = None;
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use config::Node;
+use crypto::hash::Hash;
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use futures::{Stream, StreamExt};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::runtime::Runtime;
+use tokio_stream::wrappers::UnboundedReceiverStream;
+use types::{Heartbeat, HeartbeatMsg, Replica, genesis_hash};
+
+/// How often the nodes exchange heartbeats
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the delays are logged, in heartbeats
+const REPORT_EVERY: u64 = 30;
+/// How many samples are kept for every peer
+const WINDOW: usize = 1000;
+/// How many heartbeats are sent before the protocol starts, and how often
+const PROBE_PINGS: u64 = 10;
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+/// How long to wait for the replies of the probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+/// The fraction of delta above which the delay is too close to it
+const WARN_RATIO: f64 = 0.8;
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("time differencing errors")
+        .as_micros() as u64
+}
+
+/// Percentiles of the delay to a peer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// The delay measured to a peer.
+///
+/// The one-way delay compares the clocks of the two nodes, so it is only as
+/// accurate as their clocks are synchronized.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerDelay {
+    pub samples: usize,
+    pub rtt: Percentiles,
+    pub one_way: Percentiles,
+}
+
+impl PeerDelay {
+    /// The p-th percentile delay of a message to this peer, taking the worse
+    /// of the one-way delay and half the round trip
+    fn delay(&self, p: fn(&Percentiles) -> Duration) -> Duration {
+        std::cmp::max(p(&self.one_way), p(&self.rtt) / 2)
+    }
+}
+
+/// The last `WINDOW` samples, in microseconds
+#[derive(Default)]
+struct Samples(VecDeque<u64>);
+
+impl Samples {
+    fn push(&mut self, s: u64) {
+        if self.0.len() == WINDOW {
+            self.0.pop_front();
+        }
+        self.0.push_back(s);
+    }
+
+    fn percentiles(&self) -> Option<Percentiles> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.0.iter().copied().collect();
+        sorted.sort_unstable();
+        let at = |p: usize| {
+            let idx = (sorted.len() * p / 100).min(sorted.len() - 1);
+            Duration::from_micros(sorted[idx])
+        };
+        Some(Percentiles {
+            p50: at(50),
+            p90: at(90),
+            p99: at(99),
+            max: Duration::from_micros(sorted[sorted.len() - 1]),
+        })
+    }
+}
+
+/// How the delay to a peer compares to delta
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Level {
+    Ok,
+    Close,
+    Violated,
+}
+
+#[derive(Default)]
+struct Peer {
+    rtt: Samples,
+    one_way: Samples,
+    level: Option<Level>,
+}
+
+impl Peer {
+    fn delay(&self) -> Option<PeerDelay> {
+        Some(PeerDelay {
+            samples: self.rtt.0.len(),
+            rtt: self.rtt.percentiles()?,
+            one_way: self.one_way.percentiles()?,
+        })
+    }
+}
+
+struct Tracker {
+    delta: Duration,
+    peers: HashMap<Replica, Peer>,
+    /// Our genesis hash, and the peers that sent another one
+    genesis: Hash,
+    foreign: HashSet<Replica>,
+}
+
+impl Tracker {
+    fn level(&self, d: Duration) -> Level {
+        if d >= self.delta {
+            Level::Violated
+        } else if d.as_secs_f64() >= WARN_RATIO * self.delta.as_secs_f64() {
+            Level::Close
+        } else {
+            Level::Ok
+        }
+    }
+
+    /// Log when the p99 delay to `from` crosses to another level
+    fn check(&mut self, from: Replica) {
+        let d = match self.peers.get(&from).and_then(Peer::delay) {
+            None => return,
+            Some(d) => d.delay(|p| p.p99),
+        };
+        let level = self.level(d);
+        let peer = self.peers.get_mut(&from).unwrap();
+        let old = peer.level.replace(level);
+        if old == Some(level) || (old.is_none() && level == Level::Ok) {
+            return;
+        }
+        match level {
+            Level::Violated => log::error!(
+                "The p99 delay to {} is {:?}, above delta ({:?}): the protocol is not safe",
+                from, d, self.delta),
+            Level::Close => log::warn!(
+                "The p99 delay to {} is {:?}, close to delta ({:?})",
+                from, d, self.delta),
+            Level::Ok => log::info!(
+                "The p99 delay to {} is {:?}, below delta ({:?})",
+                from, d, self.delta),
+        }
+    }
+
+    /// Record a heartbeat from `from`. Returns false if `from` is on another
+    /// chain, whose messages must be dropped.
+    fn on_heartbeat(&mut self, from: Replica, hb: &Heartbeat, now: u64) -> bool {
+        if let Heartbeat::Ping{genesis, ..} = hb {
+            if *genesis != self.genesis && self.foreign.insert(from) {
+                log::error!("Node {} is on another chain (a different genesis block or hash function), ignoring it",
+                    from);
+            }
+        }
+        if self.foreign.contains(&from) {
+            return false;
+        }
+        let peer = self.peers.entry(from).or_default();
+        match *hb {
+            Heartbeat::Ping{sent, ..} => {
+                peer.one_way.push(now.saturating_sub(sent));
+            }
+            Heartbeat::Pong{sent, received, ..} => {
+                peer.rtt.push(now.saturating_sub(sent));
+                peer.one_way.push(received.saturating_sub(sent));
+            }
+        }
+        self.check(from);
+        true
+    }
+}
+
+/// Measures the delay to the other nodes with heartbeats on the connections
+/// of the protocol, and checks it against delta.
+///
+/// Clones share the measurements.
+#[derive(Clone)]
+pub struct DelayMonitor {
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+impl DelayMonitor {
+    fn new(config: &Node) -> Self {
+        DelayMonitor {
+            tracker: Arc::new(Mutex::new(Tracker {
+                delta: Duration::from_millis(config.delta),
+                peers: HashMap::default(),
+                genesis: genesis_hash(),
+                foreign: HashSet::default(),
+            })),
+        }
+    }
+
+    /// The delay measured to `peer`, if it replied to a heartbeat
+    pub fn peer(&self, peer: Replica) -> Option<PeerDelay> {
+        self.tracker.lock().unwrap().peers.get(&peer).and_then(Peer::delay)
+    }
+
+    /// The delay measured to all the peers that replied to a heartbeat
+    pub fn peers(&self) -> HashMap<Replica, PeerDelay> {
+        self.tracker.lock().unwrap().peers.iter()
+            .filter_map(|(r, p)| Some((*r, p.delay()?)))
+            .collect()
+    }
+
+    fn report(&self) {
+        let mut peers: Vec<_> = self.peers().into_iter().collect();
+        peers.sort_by_key(|(r, _)| *r);
+        for (r, d) in peers {
+            log::info!("DP[Delay] {}: rtt p50 {:?} p99 {:?}, one-way p50 {:?} p99 {:?} max {:?}",
+                r, d.rtt.p50, d.rtt.p99, d.one_way.p50, d.one_way.p99, d.one_way.max);
+        }
+    }
+
+    /// Wait for the replies to the first heartbeats, and check the median
+    /// delay to every peer against delta. Returns false if it is above delta.
+    async fn probe(&self, config: &Node) -> bool {
+        let start = tokio::time::Instant::now();
+        let peers = config.num_nodes - 1;
+        while start.elapsed() < PROBE_TIMEOUT {
+            let done = self.tracker.lock().unwrap().peers.values()
+                .filter(|p| p.rtt.0.len() as u64 >= PROBE_PINGS / 2)
+                .count();
+            if done >= peers {
+                break;
+            }
+            tokio::time::sleep(PROBE_INTERVAL).await;
+        }
+        let tracker = self.tracker.lock().unwrap();
+        let mut ok = true;
+        for r in config.net_map.keys().filter(|r| **r != config.id) {
+            let d = match tracker.peers.get(r).and_then(Peer::delay) {
+                None => {
+                    log::warn!("No reply to the heartbeats from {}, cannot check its delay", r);
+                    continue;
+                }
+                Some(d) => d.delay(|p| p.p50),
+            };
+            match tracker.level(d) {
+                Level::Violated => {
+                    log::error!("The delay to {} is {:?}, above delta ({:?})", r, d, tracker.delta);
+                    ok = false;
+                }
+                Level::Close => log::warn!("The delay to {} is {:?}, close to delta ({:?})",
+                    r, d, tracker.delta),
+                Level::Ok => log::debug!("The delay to {} is {:?}", r, d),
+            }
+        }
+        ok
+    }
+}
+
+/// Exchange heartbeats with the other nodes: a quick burst first, and then
+/// one every `HEARTBEAT_INTERVAL`. Every other message from `net_recv` is
+/// given to `forward`, unless its sender is on another chain, and the task
+/// stops when either side is closed.
+async fn exchange<M, R, S, F>(
+    monitor: DelayMonitor,
+    num_nodes: usize,
+    mut net_recv: R,
+    send: S,
+    forward: F,
+) where
+    M: HeartbeatMsg,
+    R: Stream<Item = (Replica, M)> + Unpin,
+    S: Fn(Replica, M) -> bool,
+    F: Fn(Replica, M) -> bool,
+{
+    let genesis = genesis_hash();
+    let mut seq = 0;
+    let mut next = tokio::time::Instant::now();
+    loop {
+        tokio::select! {
+            msg = net_recv.next() => {
+                let (from, m) = match msg {
+                    None => break,
+                    Some(x) => x,
+                };
+                let hb = match m.into_heartbeat() {
+                    Ok(hb) => hb,
+                    Err(m) => {
+                        if monitor.tracker.lock().unwrap().foreign.contains(&from) {
+                            continue;
+                        }
+                        if !forward(from, m) {
+                            break;
+                        }
+                        continue;
+                    }
+                };
+                let now = now();
+                if !monitor.tracker.lock().unwrap().on_heartbeat(from, &hb, now) {
+                    continue;
+                }
+                if let Heartbeat::Ping{seq, sent, ..} = hb {
+                    let pong = Heartbeat::Pong{seq, sent, received: now};
+                    if !send(from, M::heartbeat(pong)) {
+                        break;
+                    }
+                }
+            },
+            _ = tokio::time::sleep_until(next) => {
+                let ping = Heartbeat::Ping{seq, sent: now(), genesis};
+                if !send(num_nodes, M::heartbeat(ping)) {
+                    break;
+                }
+                seq += 1;
+                next += if seq < PROBE_PINGS { PROBE_INTERVAL } else { HEARTBEAT_INTERVAL };
+                if seq % REPORT_EVERY == 0 {
+                    monitor.report();
+                }
+            },
+        }
+    }
+}
+
+/// Check the first heartbeats, and refuse to start if a peer is on another
+/// chain, or if the delay is above delta and the config asks for it
+fn check_at_start(config: &Node, rt: &Runtime, monitor: &DelayMonitor) {
+    let delay_ok = rt.block_on(monitor.probe(config));
+    let mut foreign: Vec<_> = monitor.tracker.lock().unwrap().foreign.iter().copied().collect();
+    if !foreign.is_empty() {
+        foreign.sort_unstable();
+        panic!("Refusing to start: nodes {:?} are on another chain (a different genesis block or hash function)",
+            foreign);
+    }
+    if !delay_ok && config.strict_delta {
+        panic!("Refusing to start: the delay to the other nodes is above delta ({} ms)",
+            config.delta);
+    }
+}
+
+/// Start measuring the delay on the tokio channels of the protocol network,
+/// before the reactor starts.
+///
+/// Returns the channel on which the reactor gets the other messages.
+pub fn start_delay_monitor<M>(
+    config: &Node,
+    rt: &Runtime,
+    net_send: tokio::sync::mpsc::UnboundedSender<(Replica, Arc<M>)>,
+    net_recv: tokio::sync::mpsc::UnboundedReceiver<(Replica, M)>,
+) -> (tokio::sync::mpsc::UnboundedReceiver<(Replica, M)>, DelayMonitor)
+where
+    M: HeartbeatMsg + Send + Sync + 'static,
+{
+    let monitor = DelayMonitor::new(config);
+    let (out_send, out_recv) = tokio::sync::mpsc::unbounded_channel();
+    rt.spawn(exchange(
+        monitor.clone(),
+        config.num_nodes,
+        UnboundedReceiverStream::new(net_recv),
+        move |r, m| net_send.send((r, Arc::new(m))).is_ok(),
+        move |r, m| out_send.send((r, m)).is_ok(),
+    ));
+    check_at_start(config, rt, &monitor);
+    (out_recv, monitor)
+}
+
+/// `start_delay_monitor` for the futures channels of the protocol network
+pub fn start_delay_monitor_futures<M>(
+    config: &Node,
+    rt: &Runtime,
+    net_send: futures::channel::mpsc::UnboundedSender<(Replica, Arc<M>)>,
+    net_recv: futures::channel::mpsc::UnboundedReceiver<(Replica, M)>,
+) -> (futures::channel::mpsc::UnboundedReceiver<(Replica, M)>, DelayMonitor)
+where
+    M: HeartbeatMsg + Send + Sync + 'static,
+{
+    let monitor = DelayMonitor::new(config);
+    let (out_send, out_recv) = futures::channel::mpsc::unbounded();
+    rt.spawn(exchange(
+        monitor.clone(),
+        config.num_nodes,
+        net_recv,
+        move |r, m| net_send.unbounded_send((r, Arc::new(m))).is_ok(),
+        move |r, m| out_send.unbounded_send((r, m)).is_ok(),
+    ));
+    check_at_start(config, rt, &monitor);
+    (out_recv, monitor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peers_on_another_chain_are_ignored() {
+        let mut config = Node::new();
+        config.delta = 50;
+        let monitor = DelayMonitor::new(&config);
+        let mut tracker = monitor.tracker.lock().unwrap();
+        let genesis = genesis_hash();
+        let ours = Heartbeat::Ping{seq: 0, sent: 0, genesis};
+        assert!(tracker.on_heartbeat(1, &ours, 10));
+
+        let mut other = genesis;
+        other[0] ^= 1;
+        let theirs = Heartbeat::Ping{seq: 0, sent: 0, genesis: other};
+        assert!(!tracker.on_heartbeat(2, &theirs, 10));
+        // Even the pongs of a foreign peer are dropped from then on
+        let pong = Heartbeat::Pong{seq: 0, sent: 0, received: 5};
+        assert!(!tracker.on_heartbeat(2, &pong, 10));
+        assert!(tracker.foreign.contains(&2));
+        assert!(!tracker.peers.contains_key(&2));
+        assert!(tracker.on_heartbeat(1, &pong, 10));
+    }
+}
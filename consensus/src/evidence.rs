@@ -0,0 +1,98 @@
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use config::Client;
+use crypto::PublicKey;
+use types::{Evidence, Replica};
+
+/// The proofs of misbehaviour that a node heard of, one for every offender.
+///
+/// The pool does not check the proofs: verify them with `Evidence::verify`
+/// before adding them.
+#[derive(Default)]
+pub struct EvidencePool {
+    proofs: HashMap<Replica, Evidence>,
+    /// The offenders whose proof is already in a committed block
+    included: HashSet<Replica>,
+}
+
+impl EvidencePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a checked proof. Returns true if it is the first proof against
+    /// its offender, in which case it should be forwarded to the others.
+    pub fn add(&mut self, e: Evidence) -> bool {
+        let offender = e.offender();
+        if self.proofs.contains_key(&offender) {
+            return false;
+        }
+        log::warn!("Node {} misbehaved: {:?} and {:?}", offender,
+            e.first.statement, e.second.statement);
+        self.proofs.insert(offender, e);
+        true
+    }
+
+    /// Whether we have a proof that `r` misbehaved
+    pub fn is_faulty(&self, r: Replica) -> bool {
+        self.proofs.contains_key(&r)
+    }
+
+    /// The nodes that we know misbehaved
+    pub fn faulty(&self) -> impl Iterator<Item = &Replica> {
+        self.proofs.keys()
+    }
+
+    /// The proofs that are not in a committed block yet, to include in the
+    /// next block we propose
+    pub fn pending(&self) -> Vec<Evidence> {
+        self.proofs.iter()
+            .filter(|(r, _)| !self.included.contains(r))
+            .map(|(_, e)| e.clone())
+            .collect()
+    }
+
+    /// Called with the proofs of a committed block, which no longer need to be
+    /// included
+    pub fn on_included(&mut self, proofs: &[Evidence]) {
+        for e in proofs {
+            self.included.insert(e.offender());
+        }
+    }
+}
+
+/// Checks the proofs of misbehaviour in the blocks that a client gets, with
+/// the public keys of the nodes from its config
+pub struct EvidenceChecker {
+    pk_map: HashMap<Replica, PublicKey>,
+    pub pool: EvidencePool,
+}
+
+impl EvidenceChecker {
+    pub fn from_client(config: &Client) -> Self {
+        let pk_map = config.server_pk.iter().map(|(id, pk_data)| {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            (*id, pk)
+        }).collect();
+        EvidenceChecker {
+            pk_map,
+            pool: EvidencePool::new(),
+        }
+    }
+
+    /// Check the proofs of a block and store the valid ones. Returns false if
+    /// any of them is invalid, in which case the block must be rejected.
+    pub fn check(&mut self, proofs: &[Evidence]) -> bool {
+        for e in proofs {
+            if self.pool.is_faulty(e.offender()) {
+                continue;
+            }
+            if let Err(err) = e.verify(&self.pk_map) {
+                log::warn!("Invalid evidence against {}: {}", e.offender(), err);
+                return false;
+            }
+            self.pool.add(e.clone());
+        }
+        true
+    }
+}
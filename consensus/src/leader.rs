@@ -0,0 +1,115 @@
+use config::{Client, LeaderElection, Node};
+use crypto::{hash::{EMPTY_HASH, Hash}, vrf};
+use fnv::FnvHashMap as HashMap;
+use std::convert::TryInto;
+use types::Replica;
+
+/// A chain of random seeds from which the leaders are elected when the config
+/// uses `LeaderElection::VRF`.
+///
+/// The leader of round `r` evaluates its VRF on the current seed and `r`, and
+/// sends the proof along with its proposal. The next seed is the hash of the
+/// current seed and the VRF output. So the next leader is only known once the
+/// current leader has proposed, and cannot be chosen by it, and anyone with
+/// the VRF public keys (the nodes and the clients) can check the schedule.
+pub struct VrfBeacon {
+    seed: Hash,
+    my_key: Option<vrf::Keypair>,
+    pk_map: HashMap<Replica, vrf::PublicKey>,
+}
+
+impl VrfBeacon {
+    /// Returns the beacon for a node, or `None` if the config does not use VRF
+    /// leader election
+    pub fn from_node(config: &Node) -> Option<Self> {
+        if config.leader_election != LeaderElection::VRF {
+            return None;
+        }
+        let mut sk_copy = config.vrf_secret_key.clone();
+        let kp = vrf::Keypair::decode(&mut sk_copy)
+            .expect("Failed to decode the VRF secret key from the config");
+        Some(VrfBeacon {
+            seed: EMPTY_HASH,
+            my_key: Some(kp),
+            pk_map: decode_pks(&config.vrf_pk_map),
+        })
+    }
+
+    /// Returns the beacon for a client, or `None` if the config does not use
+    /// VRF leader election
+    pub fn from_client(config: &Client) -> Option<Self> {
+        if config.leader_election != LeaderElection::VRF {
+            return None;
+        }
+        Some(VrfBeacon {
+            seed: EMPTY_HASH,
+            my_key: None,
+            pk_map: decode_pks(&config.vrf_pk_map),
+        })
+    }
+
+    /// The current seed
+    pub fn seed(&self) -> &Hash {
+        &self.seed
+    }
+
+    /// Prove our VRF output for `round`. Only nodes can do this.
+    pub fn prove(&self, round: usize) -> vrf::Proof {
+        self.prove_at(&self.seed, round)
+    }
+
+    /// Prove our VRF output for `round` on `seed` instead of the current seed
+    pub fn prove_at(&self, seed: &Hash, round: usize) -> vrf::Proof {
+        let kp = self.my_key.as_ref()
+            .expect("Only nodes have a VRF key");
+        let (_, proof) = kp.prove(&input(seed, round));
+        proof
+    }
+
+    /// Check the VRF proof of the `leader` of `round`, and return the seed
+    /// that follows it if the proof is correct
+    pub fn verify(&self, leader: Replica, round: usize, proof: &vrf::Proof) -> Option<Hash> {
+        self.verify_at(&self.seed, leader, round, proof)
+    }
+
+    /// Same as `verify`, on `seed` instead of the current seed. Useful when
+    /// the seeds of several forks of a chain are tracked separately.
+    pub fn verify_at(&self, seed: &Hash, leader: Replica, round: usize, proof: &vrf::Proof) -> Option<Hash> {
+        let pk = self.pk_map.get(&leader)?;
+        let out = pk.verify(&input(seed, round), proof)?;
+        let mut data = seed.to_vec();
+        data.extend_from_slice(&out);
+        Some(crypto::hash::do_hash(&data))
+    }
+
+    /// Move to the seed returned by `verify`
+    pub fn advance(&mut self, seed: Hash) {
+        self.seed = seed;
+    }
+
+    /// Pick an index in `0..n` using the current seed
+    pub fn pick(&self, n: usize) -> usize {
+        Self::pick_at(&self.seed, n)
+    }
+
+    /// Pick an index in `0..n` using `seed`
+    pub fn pick_at(seed: &Hash, n: usize) -> usize {
+        let x = u64::from_be_bytes(seed[24..].try_into().unwrap());
+        (x % n as u64) as usize
+    }
+}
+
+/// The VRF input of `round` on `seed`
+fn input(seed: &Hash, round: usize) -> Vec<u8> {
+    let mut data = seed.to_vec();
+    data.extend_from_slice(&(round as u64).to_be_bytes());
+    data
+}
+
+fn decode_pks(vrf_pk_map: &HashMap<Replica, Vec<u8>>) -> HashMap<Replica, vrf::PublicKey> {
+    vrf_pk_map.iter().map(|(id, pk_data)| {
+        let pk = vrf::PublicKey::decode(pk_data)
+            .expect("Failed to decode the VRF public key from the config");
+        (*id, pk)
+    }).collect()
+}
@@ -1,3 +1,39 @@
+mod verifier;
+pub use verifier::*;
+
+mod leader;
+pub use leader::*;
+
+mod reputation;
+pub use reputation::*;
+
+mod evidence;
+pub use evidence::*;
+
+mod protocol;
+pub use protocol::*;
+
+mod delay;
+pub use delay::*;
+
+mod adversary;
+pub use adversary::*;
+
+mod reconfig;
+pub use reconfig::*;
+
+mod wal;
+pub use wal::*;
+
+mod commits;
+pub use commits::*;
+
+mod node;
+pub use node::*;
+
+mod client;
+pub use client::*;
+
 use std::time::SystemTime;
 use fnv::FnvHashMap as HashMap;
 use crypto::hash::Hash;
@@ -0,0 +1,381 @@
+use config::Node;
+use std::{fmt, future::Future, marker::PhantomData, ops::Deref, path::PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{oneshot, watch};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use tokio_stream::{StreamExt, wrappers::UnboundedReceiverStream};
+use types::{CommittedBlock, Height, Replica};
+use crate::{NodeOptions, Protocol};
+
+/// How long the runtimes of a node get to finish their tasks and close their
+/// sockets when it shuts down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Why a node did not start, or did not take a transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeError {
+    /// The config does not suit the protocol, or this process
+    InvalidConfig(String),
+    /// The keystore of the config could not be opened
+    Keystore(String),
+    /// The node has stopped
+    Stopped,
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::InvalidConfig(e) => write!(f, "invalid config: {}", e),
+            NodeError::Keystore(e) => write!(f, "failed to unlock the keystore: {}", e),
+            NodeError::Stopped => write!(f, "the node has stopped"),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+/// A snapshot of a running node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeStatus {
+    pub id: Replica,
+    pub protocol: &'static str,
+    /// Whether the reactor still runs
+    pub running: bool,
+    /// The height of the last block that we committed
+    pub committed_height: Height,
+    pub committed_blocks: u64,
+    pub committed_txs: u64,
+}
+
+/// Builds a node that runs the protocol `P` in the current process, e.g.
+/// inside a service or a test
+///
+/// ```ignore
+/// let node = NodeBuilder::<synchs::SyncHotStuff>::new(config).start()?;
+/// let commits = node.subscribe();
+/// node.submit(tx)?;
+/// ...
+/// node.shutdown();
+/// ```
+///
+/// The tests of the `hotstuff` crate start a whole chain this way.
+pub struct NodeBuilder<P: Protocol> {
+    config: Node,
+    opts: NodeOptions,
+    config_dir: PathBuf,
+    _protocol: PhantomData<P>,
+}
+
+impl<P: Protocol> NodeBuilder<P> {
+    pub fn new(config: Node) -> Self {
+        NodeBuilder {
+            config,
+            opts: NodeOptions::default(),
+            config_dir: PathBuf::from("."),
+            _protocol: PhantomData,
+        }
+    }
+
+    /// Use these options. The channel in `opts.commits`, if any, becomes the
+    /// first subscriber of the node.
+    pub fn options(mut self, opts: NodeOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// Whether the clients run the client side of the protocol (Apollo and
+    /// Artemis only)
+    pub fn special_client(mut self, special_client: bool) -> Self {
+        self.opts.special_client = special_client;
+        self
+    }
+
+    /// The directory of the config file, against which the keystore path in
+    /// the config is resolved. The default is the working directory.
+    pub fn config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config_dir = dir.into();
+        self
+    }
+
+    /// Connect to the other nodes and to the clients, and start the reactor.
+    ///
+    /// Unless the config already has its secret keys, they come from its
+    /// keystore, with the password in `config::KEYSTORE_PASSWORD_ENV`. The
+    /// hash function of the config becomes the one of the process, which
+    /// fails if the process already uses another one.
+    ///
+    /// This blocks until the protocol network is up, so call it outside of
+    /// async code.
+    pub fn start(self) -> Result<NodeHandle<P>, NodeError> {
+        let NodeBuilder { mut config, mut opts, config_dir, .. } = self;
+        if config.secret_key_bytes.is_empty() {
+            config.unlock_keystore(&config_dir)
+                .map_err(|e| NodeError::Keystore(e.to_string()))?;
+        }
+        config.validate_for(P::FAULT_MODEL)
+            .map_err(|e| NodeError::InvalidConfig(e.to_string()))?;
+        config.use_hash_algorithm()
+            .map_err(|e| NodeError::InvalidConfig(e.to_string()))?;
+
+        let cli_net_rt = Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let prot_net_rt = Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let core_rt = Builder::new_multi_thread()
+            .enable_all()
+            .worker_threads(2)
+            .build()
+            .unwrap();
+
+        let shared = Arc::new(Mutex::new(Shared {
+            status: NodeStatus {
+                id: config.id,
+                protocol: P::NAME,
+                running: true,
+                committed_height: 0,
+                committed_blocks: 0,
+                committed_txs: 0,
+            },
+            subscribers: opts.commits.take().into_iter().collect(),
+        }));
+        let (commit_send, commit_recv) = unbounded_channel();
+        opts.commits = Some(commit_send);
+        let (submit, local_txs) = unbounded_channel();
+        let reactor = P::start(&config, &opts, local_txs, &prot_net_rt, &cli_net_rt);
+        // The reactor holds the only sender, so the fan out ends with it
+        drop(opts);
+        let fan_out = core_rt.spawn(fan_out(commit_recv, shared.clone()));
+
+        let (stop, stop_recv) = oneshot::channel::<()>();
+        let (running_send, running) = watch::channel(true);
+        log::info!("Starting {} on node {}", P::NAME, config.id);
+        let reactor = core_rt.spawn(async move {
+            tokio::select! {
+                _ = reactor => log::info!("The {} reactor stopped", P::NAME),
+                _ = stop_recv => log::info!("Stopping {}", P::NAME),
+            }
+            let _ = running_send.send(false);
+        });
+
+        Ok(NodeHandle {
+            submit: Some(submit),
+            shared,
+            running,
+            stop: Some(stop),
+            reactor: Some(reactor),
+            fan_out: Some(fan_out),
+            runtimes: vec![core_rt, prot_net_rt, cli_net_rt],
+        })
+    }
+}
+
+/// The state that a handle shares with the task that fans the committed
+/// blocks out
+struct Shared {
+    status: NodeStatus,
+    subscribers: Vec<UnboundedSender<CommittedBlock>>,
+}
+
+/// Pass the committed blocks on to the subscribers, until the reactor stops
+async fn fan_out(mut commits: UnboundedReceiver<CommittedBlock>, shared: Arc<Mutex<Shared>>) {
+    while let Some(b) = commits.recv().await {
+        let mut shared = shared.lock().unwrap();
+        shared.status.committed_height = b.height();
+        shared.status.committed_blocks += 1;
+        shared.status.committed_txs += b.txs.len() as u64;
+        shared.subscribers.retain(|s| s.send(b.clone()).is_ok());
+    }
+}
+
+/// A node that runs in this process, from `NodeBuilder::start`
+///
+/// Dropping the handle stops the node without waiting for it; `shutdown`
+/// stops it cleanly.
+pub struct NodeHandle<P: Protocol> {
+    submit: Option<UnboundedSender<P::Transaction>>,
+    shared: Arc<Mutex<Shared>>,
+    running: watch::Receiver<bool>,
+    stop: Option<oneshot::Sender<()>>,
+    reactor: Option<JoinHandle<()>>,
+    fan_out: Option<JoinHandle<()>>,
+    /// The core, protocol network and client network runtimes
+    runtimes: Vec<Runtime>,
+}
+
+impl<P: Protocol> NodeHandle<P> {
+    /// Give a transaction to the node, as if a client had sent it
+    pub fn submit(&self, tx: P::Transaction) -> Result<(), NodeError> {
+        if !*self.running.borrow() {
+            return Err(NodeError::Stopped);
+        }
+        match &self.submit {
+            Some(s) if s.send(tx).is_ok() => Ok(()),
+            _ => Err(NodeError::Stopped),
+        }
+    }
+
+    /// The blocks that the node commits from now on, in order. The channel
+    /// closes once the node stops and the last blocks are out.
+    pub fn subscribe(&self) -> UnboundedReceiver<CommittedBlock> {
+        let (send, recv) = unbounded_channel();
+        self.shared.lock().unwrap().subscribers.push(send);
+        recv
+    }
+
+    pub fn status(&self) -> NodeStatus {
+        let mut status = self.shared.lock().unwrap().status.clone();
+        status.running = *self.running.borrow();
+        status
+    }
+
+    /// Completes when the reactor stops, on its own or on `shutdown`
+    pub async fn stopped(&self) {
+        let mut running = self.running.clone();
+        while *running.borrow_and_update() {
+            if running.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Stop the reactor, hand the last committed blocks to the subscribers,
+    /// and close the connections to the other nodes and to the clients.
+    ///
+    /// This blocks until the node is down, so call it outside of async code.
+    pub fn shutdown(mut self) {
+        let stop = self.stop.take();
+        let reactor = self.reactor.take();
+        let fan_out = self.fan_out.take();
+        self.submit = None;
+        let core_rt = &self.runtimes[0];
+        core_rt.block_on(async move {
+            if let Some(stop) = stop {
+                let _ = stop.send(());
+            }
+            for task in reactor.into_iter().chain(fan_out) {
+                if let Err(e) = task.await {
+                    log::warn!("A task of the node failed: {}", e);
+                }
+            }
+        });
+        self.shared.lock().unwrap().subscribers.clear();
+        for rt in self.runtimes.drain(..) {
+            rt.shutdown_timeout(SHUTDOWN_TIMEOUT);
+        }
+        log::info!("Node {} is down", self.shared.lock().unwrap().status.id);
+    }
+}
+
+impl<P: Protocol> Drop for NodeHandle<P> {
+    fn drop(&mut self) {
+        // The runtimes cannot block here, as we may be in async code
+        for rt in self.runtimes.drain(..) {
+            rt.shutdown_background();
+        }
+    }
+}
+
+/// Merge the transactions that the application submits (see
+/// `NodeHandle::submit`) with the ones from the tokio channels of the client
+/// network.
+///
+/// Returns the channel on which the reactor gets both.
+pub fn merge_local_txs<T>(
+    rt: &Runtime,
+    cli_recv: UnboundedReceiver<T>,
+    local_txs: UnboundedReceiver<T>,
+) -> UnboundedReceiver<T>
+where
+    T: Send + 'static,
+{
+    let (out_send, out_recv) = unbounded_channel();
+    let mut txs = UnboundedReceiverStream::new(cli_recv)
+        .merge(UnboundedReceiverStream::new(local_txs));
+    rt.spawn(async move {
+        while let Some(tx) = StreamExt::next(&mut txs).await {
+            if out_send.send(tx).is_err() {
+                break;
+            }
+        }
+    });
+    out_recv
+}
+
+/// `merge_local_txs` for the futures channels of the client network
+pub fn merge_local_txs_futures<T>(
+    rt: &Runtime,
+    cli_recv: futures::channel::mpsc::UnboundedReceiver<T>,
+    local_txs: UnboundedReceiver<T>,
+) -> futures::channel::mpsc::UnboundedReceiver<T>
+where
+    T: Send + 'static,
+{
+    let (out_send, out_recv) = futures::channel::mpsc::unbounded();
+    let mut txs = futures::stream::select(cli_recv, UnboundedReceiverStream::new(local_txs));
+    rt.spawn(async move {
+        while let Some(tx) = futures::StreamExt::next(&mut txs).await {
+            if out_send.unbounded_send(tx).is_err() {
+                break;
+            }
+        }
+    });
+    out_recv
+}
+
+/// A runtime that a reactor owns, e.g. for its helper tasks. It shuts down in
+/// the background when the reactor stops, as a runtime cannot drop in async
+/// code.
+pub struct ReactorRuntime(Option<Runtime>);
+
+impl ReactorRuntime {
+    pub fn new(rt: Runtime) -> Self {
+        ReactorRuntime(Some(rt))
+    }
+}
+
+impl Deref for ReactorRuntime {
+    type Target = Runtime;
+
+    fn deref(&self) -> &Runtime {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl Drop for ReactorRuntime {
+    fn drop(&mut self) {
+        if let Some(rt) = self.0.take() {
+            rt.shutdown_background();
+        }
+    }
+}
+
+/// Run the protocol `P` until it stops or `stop` completes, whichever comes
+/// first
+pub fn run<P, S>(config: &Node, opts: &NodeOptions, stop: S)
+where
+    P: Protocol,
+    S: Future<Output = ()>,
+{
+    let node = NodeBuilder::<P>::new(config.clone())
+        .options(opts.clone())
+        .start()
+        .unwrap_or_else(|e| panic!("Failed to start {}: {}", P::NAME, e));
+    let waiter = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    waiter.block_on(async {
+        tokio::select! {
+            _ = node.stopped() => {},
+            _ = stop => {},
+        }
+    });
+    node.shutdown();
+}
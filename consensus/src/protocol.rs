@@ -0,0 +1,64 @@
+use config::Node;
+use std::{future::Future, pin::Pin};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use types::{CommittedBlock, FaultModel, WireReady};
+use crate::CommitSink;
+
+/// The options of a node that are not part of its config
+#[derive(Debug, Clone, Default)]
+pub struct NodeOptions {
+    /// Whether the clients run the client side of the protocol (Apollo and
+    /// Artemis only)
+    pub special_client: bool,
+    /// Where the reactor publishes the blocks that it commits, in order
+    pub commits: Option<UnboundedSender<CommittedBlock>>,
+}
+
+impl NodeOptions {
+    /// The sink of the committed blocks, for the reactor
+    pub fn commit_sink(&self) -> CommitSink {
+        CommitSink::new(self.commits.clone())
+    }
+}
+
+/// A running reactor, which completes when the protocol stops
+pub type Reactor = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// A consensus protocol that a node can run
+pub trait Protocol {
+    /// The name of the protocol, as given to `node --protocol`
+    const NAME: &'static str;
+    /// What the protocol assumes about the network, which decides how many
+    /// faults it tolerates
+    const FAULT_MODEL: FaultModel;
+
+    /// The messages that the nodes exchange
+    type ProtocolMsg: WireReady + 'static;
+    /// The messages that the nodes send to the clients
+    type ClientMsg: WireReady + 'static;
+    /// The messages that the clients send to the nodes
+    type Transaction: WireReady + 'static;
+
+    /// Connect to the other nodes on `prot_net_rt` and to the clients on
+    /// `cli_net_rt`, and return the reactor. The connections live as long as
+    /// the runtimes, and the reactor stops when it is dropped.
+    ///
+    /// The reactor takes the transactions in `local_txs` like the ones from
+    /// the clients (see `merge_local_txs`).
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Self::Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor;
+}
+
+/// A `stop` for `run` that completes on Ctrl-C
+pub async fn ctrl_c() {
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        log::warn!("Failed to listen for Ctrl-C: {}", e);
+        std::future::pending::<()>().await
+    }
+}
@@ -0,0 +1,211 @@
+use config::Node;
+use crypto::{Algorithm, PublicKey};
+use fnv::FnvHashMap as HashMap;
+use net::tokio_manager::PeerChange;
+use std::collections::BTreeMap;
+use tokio::sync::mpsc::UnboundedSender;
+use types::{FaultModel, Height, QuorumPolicy, Reconfig, Replica};
+
+/// The replica set that runs the protocol
+#[derive(Clone)]
+pub struct Membership {
+    pub net_map: HashMap<Replica, String>,
+    pub pk_map: HashMap<Replica, PublicKey>,
+    pub num_faults: usize,
+    /// The nodes, in the order in which they lead
+    order: Vec<Replica>,
+}
+
+impl Membership {
+    /// The replica set of the config
+    pub fn from_node(config: &Node) -> Self {
+        let pk_map = config.pk_map.iter()
+            .map(|(id, pk)| {
+                let pk = PublicKey::decode(&config.crypto_alg, pk)
+                    .expect("Failed to decode the public key from the config");
+                (*id, pk)
+            })
+            .collect();
+        Self::new(config.net_map.clone(), pk_map, config.num_faults)
+    }
+
+    fn new(
+        net_map: HashMap<Replica, String>,
+        pk_map: HashMap<Replica, PublicKey>,
+        num_faults: usize,
+    ) -> Self {
+        let mut order: Vec<_> = net_map.keys().copied().collect();
+        order.sort_unstable();
+        Membership { net_map, pk_map, num_faults, order }
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn contains(&self, r: Replica) -> bool {
+        self.net_map.contains_key(&r)
+    }
+
+    /// The leader of `turn` in the round-robin schedule
+    pub fn rotation(&self, turn: usize) -> Replica {
+        self.order[turn % self.order.len()]
+    }
+
+    /// The replica set after the change `r`, if it is valid: the new nodes
+    /// are not members yet and have valid keys, the removed ones are members,
+    /// and the new replica set has more than `2*num_faults` nodes
+    pub fn apply(&self, r: &Reconfig, alg: &Algorithm) -> Result<Membership, String> {
+        let mut net_map = self.net_map.clone();
+        let mut pk_map = self.pk_map.clone();
+        for id in &r.remove {
+            if net_map.remove(id).is_none() {
+                return Err(format!("node {} is not a member", id));
+            }
+            pk_map.remove(id);
+        }
+        for m in &r.add {
+            if self.contains(m.id) || net_map.contains_key(&m.id) {
+                return Err(format!("node {} is already a member", m.id));
+            }
+            let pk = PublicKey::decode(alg, &m.pk)
+                .map_err(|e| format!("invalid public key for node {}: {}", m.id, e))?;
+            net_map.insert(m.id, m.addr.clone());
+            pk_map.insert(m.id, pk);
+        }
+        let policy = QuorumPolicy::new(FaultModel::Synchronous, net_map.keys().cloned(), r.num_faults, HashMap::default());
+        if !policy.is_tolerated() {
+            return Err(format!("{} nodes cannot tolerate {} faults", net_map.len(), r.num_faults));
+        }
+        Ok(Self::new(net_map, pk_map, r.num_faults))
+    }
+}
+
+/// Follows the configuration transactions that change the replica set, for
+/// configs that are `reconfigurable`.
+///
+/// A change is included in a block below its `from_height`, and the new
+/// replica set takes over once the block before `from_height` commits. Since
+/// everyone commits the same blocks in the same order, everyone switches at
+/// the same height.
+pub struct Reconfiguration {
+    pub members: Membership,
+    alg: Algorithm,
+    /// The committed changes that are not used yet, by their first height
+    pending: BTreeMap<Height, Vec<Reconfig>>,
+    /// Connects to the new nodes and drops the removed ones
+    peers: UnboundedSender<PeerChange>,
+}
+
+impl Reconfiguration {
+    /// Returns the reconfiguration of a node, or `None` if its config is not
+    /// `reconfigurable`
+    pub fn from_node(config: &Node, peers: UnboundedSender<PeerChange>) -> Option<Self> {
+        if !config.reconfigurable {
+            return None;
+        }
+        Some(Reconfiguration {
+            members: Membership::from_node(config),
+            alg: config.crypto_alg.clone(),
+            pending: BTreeMap::new(),
+            peers,
+        })
+    }
+
+    /// Whether the change `r` can go in a block at `height`
+    pub fn is_valid(&self, r: &Reconfig, height: Height) -> bool {
+        r.from_height > height && self.members.apply(r, &self.alg).is_ok()
+    }
+
+    /// The changes of `changes` that can go in a block at `height`
+    pub fn proposable(&self, changes: impl Iterator<Item = Reconfig>, height: Height) -> Vec<Reconfig> {
+        changes.filter(|r| self.is_valid(r, height)).collect()
+    }
+
+    /// Called when the block at `height`, with the changes `changes`, is
+    /// committed. Returns the new replica set if it takes over at the next
+    /// height.
+    pub fn on_commit(&mut self, height: Height, changes: &[Reconfig]) -> Option<&Membership> {
+        for r in changes {
+            self.pending.entry(r.from_height).or_default().push(r.clone());
+        }
+        let mut changed = false;
+        while let Some(entry) = self.pending.first_entry() {
+            if *entry.key() > height+1 {
+                break;
+            }
+            for r in entry.remove() {
+                // An earlier change can make a later one invalid
+                match self.members.apply(&r, &self.alg) {
+                    Err(e) => log::warn!("Skipping the reconfiguration {:?}: {}", r, e),
+                    Ok(m) => {
+                        self.update_peers(&m);
+                        self.members = m;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        if !changed {
+            return None;
+        }
+        log::info!("The nodes {:?} run the protocol from height {}", self.members.order, height+1);
+        Some(&self.members)
+    }
+
+    /// Connect to the nodes of `next` that are new, and drop the ones that
+    /// are gone
+    fn update_peers(&self, next: &Membership) {
+        let removed = self.members.order.iter()
+            .filter(|r| !next.contains(**r))
+            .map(|r| PeerChange::Remove(*r));
+        let added = next.net_map.iter()
+            .filter(|(r, _)| !self.members.contains(**r))
+            .map(|(r, addr)| PeerChange::Add(*r, addr.clone()));
+        for change in removed.chain(added) {
+            if self.peers.send(change).is_err() {
+                log::warn!("The protocol network is down");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::ed25519;
+    use types::Member;
+
+    fn members(n: usize) -> Membership {
+        let pk = ed25519::Keypair::generate().public();
+        let net_map = (0..n).map(|i| (i, format!("127.0.0.1:{}", 4000+i))).collect();
+        let pk_map = (0..n).map(|i| (i, PublicKey::Ed25519(pk.clone()))).collect();
+        Membership::new(net_map, pk_map, (n-1)/2)
+    }
+
+    #[test]
+    fn apply_reconfig() {
+        let m = members(3);
+        let pk = ed25519::Keypair::generate().public().encode().to_vec();
+        let add = Reconfig {
+            from_height: 10,
+            add: vec![Member { id: 5, addr: "127.0.0.1:4005".to_string(), pk }],
+            remove: vec![1],
+            num_faults: 1,
+        };
+        let next = m.apply(&add, &Algorithm::ED25519).unwrap();
+        assert_eq!(next.order, vec![0, 2, 5]);
+        assert_eq!(next.rotation(1), 2);
+        assert_eq!(next.rotation(5), 5);
+
+        // Removing a node that is gone, adding one that is there, or
+        // tolerating too many faults are invalid
+        assert!(next.apply(&add, &Algorithm::ED25519).is_err());
+        let mut too_many = add.clone();
+        too_many.num_faults = 2;
+        assert!(m.apply(&too_many, &Algorithm::ED25519).is_err());
+        let mut bad_key = add;
+        bad_key.add[0].pk = vec![0; 3];
+        assert!(m.apply(&bad_key, &Algorithm::ED25519).is_err());
+    }
+}
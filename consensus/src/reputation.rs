@@ -0,0 +1,172 @@
+use config::{Client, LeaderElection, Node};
+use fnv::FnvHashSet as HashSet;
+use types::Replica;
+
+/// The number of passes over all the nodes that we look back at
+const WINDOW_PASSES: usize = 2;
+/// A demoted node gets its turn back once every this many passes, so that it
+/// can earn its reputation again after it recovers
+const PROBATION_PASSES: usize = 4;
+
+/// Leader selection from the recent history of the chain, used when the
+/// config uses `LeaderElection::Reputation`.
+///
+/// The leaders take turns (rounds or views) as usual, but a node that did not
+/// get a block into the chain in the last `window()` turns is demoted, and its
+/// turns go to the next node that did. So a crashed node stops costing a
+/// timeout every time its turn comes.
+///
+/// The schedule only depends on the turn and on the chain before it, so
+/// everyone who agrees on the chain agrees on the leaders. The callers must
+/// therefore pass a chain that every honest node agrees on, e.g. the
+/// committed blocks, and not the blocks that they happened to receive.
+pub struct Reputation {
+    num_nodes: usize,
+}
+
+impl Reputation {
+    pub fn new(num_nodes: usize) -> Self {
+        Reputation { num_nodes }
+    }
+
+    /// Returns the policy for a node, or `None` if the config does not use
+    /// reputation-based leader election
+    pub fn from_node(config: &Node) -> Option<Self> {
+        if config.leader_election != LeaderElection::Reputation {
+            return None;
+        }
+        Some(Self::new(config.num_nodes))
+    }
+
+    /// Returns the policy for a client, or `None` if the config does not use
+    /// reputation-based leader election
+    pub fn from_client(config: &Client) -> Option<Self> {
+        if config.leader_election != LeaderElection::Reputation {
+            return None;
+        }
+        Some(Self::new(config.num_nodes))
+    }
+
+    /// The number of turns before the current one that decide who is active
+    pub fn window(&self) -> usize {
+        WINDOW_PASSES*self.num_nodes
+    }
+
+    /// Returns the nodes that can lead `turn`: the authors of the blocks of
+    /// the chain in the window before it. Returns `None` if every node can.
+    ///
+    /// `chain` yields the turn and the author of the blocks of the chain
+    /// before `turn`, the latest first. It is only read as far back as the
+    /// window.
+    pub fn active<I>(&self, turn: usize, chain: I) -> Option<HashSet<Replica>>
+        where I: IntoIterator<Item = (usize, Replica)>,
+    {
+        // Everyone starts with a good reputation
+        if turn < self.window() {
+            return None;
+        }
+        // Demoted nodes are on probation in some passes
+        if (turn / self.num_nodes).is_multiple_of(PROBATION_PASSES) {
+            return None;
+        }
+        let start = turn - self.window();
+        let active: HashSet<Replica> = chain.into_iter()
+            .skip_while(|(t, _)| *t >= turn)
+            .take_while(|(t, _)| *t >= start)
+            .map(|(_, author)| author)
+            .collect();
+        if active.is_empty() {
+            return None;
+        }
+        Some(active)
+    }
+
+    /// Returns the leader of `turn`, whose leader is `default` in the usual
+    /// schedule. See `active` for `chain`.
+    pub fn leader<I>(&self, turn: usize, default: Replica, chain: I) -> Replica
+        where I: IntoIterator<Item = (usize, Replica)>,
+    {
+        let active = match self.active(turn, chain) {
+            Some(x) if !x.contains(&default) => x,
+            _ => return default,
+        };
+        // Give the turn to the next active node
+        (1..self.num_nodes)
+            .map(|i| (default+i) % self.num_nodes)
+            .find(|r| active.contains(r))
+            .unwrap_or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const N: usize = 4;
+
+    /// A chain in which the default leader of every turn before `turn`
+    /// proposed, except in the turns `missed`
+    fn chain(turn: usize, missed: &[usize]) -> Vec<(usize, Replica)> {
+        (1..turn).rev()
+            .filter(|t| !missed.contains(t))
+            .map(|t| (t, t % N))
+            .collect()
+    }
+
+    #[test]
+    fn everyone_leads_at_first() {
+        let rep = Reputation::new(N);
+        assert_eq!(rep.window(), WINDOW_PASSES*N);
+        for turn in 0..rep.window() {
+            assert_eq!(rep.active(turn, chain(turn, &[3, 7])), None);
+            assert_eq!(rep.leader(turn, turn % N, chain(turn, &[3, 7])), turn % N);
+        }
+    }
+
+    #[test]
+    fn demotes_after_a_window_of_missed_turns() {
+        let rep = Reputation::new(N);
+        // Node 3 missed its turns 3 and 7, the whole window before turn 11
+        let active = rep.active(11, chain(11, &[3, 7])).unwrap();
+        assert!(!active.contains(&3));
+        assert_eq!(rep.leader(11, 3, chain(11, &[3, 7])), 0);
+        // The others keep their turns
+        assert_eq!(rep.leader(9, 1, chain(9, &[3, 7])), 1);
+        // Missing only one of them is not enough
+        assert_eq!(rep.leader(11, 3, chain(11, &[7])), 3);
+    }
+
+    #[test]
+    fn demoted_nodes_return_after_probation() {
+        let rep = Reputation::new(N);
+        let crashed: Vec<usize> = (0..30).filter(|t| t % N == 3).collect();
+        for turn in (WINDOW_PASSES*N..PROBATION_PASSES*N).filter(|t| t % N == 3) {
+            assert_ne!(rep.leader(turn, 3, chain(turn, &crashed)), 3);
+        }
+        // Every PROBATION_PASSES passes, everyone gets a turn
+        let probation = PROBATION_PASSES*N + 3;
+        assert_eq!(rep.active(probation, chain(probation, &crashed)), None);
+        assert_eq!(rep.leader(probation, 3, chain(probation, &crashed)), 3);
+        // Once it proposes again, it keeps its turns
+        let recovered: Vec<usize> = crashed.iter().copied().filter(|t| *t < probation).collect();
+        let next = probation + N;
+        assert_eq!(rep.leader(next, 3, chain(next, &recovered)), 3);
+        // Otherwise it is demoted again after the probation
+        assert_ne!(rep.leader(next, 3, chain(next, &crashed)), 3);
+    }
+
+    #[test]
+    fn same_schedule_for_the_same_chain() {
+        let rep = Reputation::new(N);
+        let committed = chain(11, &[3, 7]);
+        // Blocks from the turn on, or older than the window, do not matter
+        let mut other = vec![(12, 1), (11, 3)];
+        other.extend(committed.iter().copied().filter(|(t, _)| *t >= 11 - rep.window()));
+        assert_eq!(rep.active(11, committed.clone()), rep.active(11, other.clone()));
+        assert_eq!(rep.leader(11, 3, committed.clone()), rep.leader(11, 3, other));
+        // But a block of node 3 in the window does
+        let mut proposed = committed.clone();
+        proposed.insert(3, (7, 3));
+        assert_ne!(rep.leader(11, 3, committed), rep.leader(11, 3, proposed));
+    }
+}
@@ -0,0 +1,116 @@
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use types::Replica;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// Starts a pool of `workers` threads between the network and the reactor.
+/// Every message from `net_recv` is checked with `verify` and forwarded on the
+/// returned channel only if it passes, so the reactor receives messages whose
+/// signatures are already checked.
+///
+/// The messages are verified in parallel, but they reach the reactor in the
+/// order they were received from the network. This keeps the per-sender
+/// ordering, and also the order across senders that the reactors rely on
+/// (e.g., a proposal is seen before the quit view messages that follow it).
+pub fn start_verifier<M, F>(
+    mut net_recv: UnboundedReceiver<(Replica, M)>,
+    workers: usize,
+    verify: F,
+) -> UnboundedReceiver<(Replica, M)>
+where
+    M: Send + 'static,
+    F: Fn(Replica, &M) -> bool + Send + Sync + 'static,
+{
+    let workers = std::cmp::max(workers, 1);
+    let verify = Arc::new(verify);
+    let (out_send, out_recv) = unbounded_channel();
+    let (done_send, mut done_recv) = unbounded_channel::<(u64, Option<(Replica, M)>)>();
+    let mut worker_send: Vec<UnboundedSender<(u64, Replica, M)>> = Vec::with_capacity(workers);
+    for i in 0..workers {
+        let (send, mut recv) = unbounded_channel::<(u64, Replica, M)>();
+        worker_send.push(send);
+        let done = done_send.clone();
+        let verify = verify.clone();
+        std::thread::Builder::new()
+            .name(format!("verifier-{}", i))
+            .spawn(move || {
+                while let Some((seq, from, msg)) = recv.blocking_recv() {
+                    let res = if verify(from, &msg) {
+                        Some((from, msg))
+                    } else {
+                        log::warn!("Dropping an incorrectly signed message from {}", from);
+                        None
+                    };
+                    if done.send((seq, res)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to start a verification worker");
+    }
+    drop(done_send);
+
+    // Number the messages and spread them over the workers
+    std::thread::Builder::new()
+        .name("verifier-dispatch".to_string())
+        .spawn(move || {
+            let mut seq: u64 = 0;
+            while let Some((from, msg)) = net_recv.blocking_recv() {
+                if worker_send[(seq as usize) % workers].send((seq, from, msg)).is_err() {
+                    break;
+                }
+                seq += 1;
+            }
+        })
+        .expect("failed to start the verification dispatcher");
+
+    // Put the verified messages back in order for the reactor
+    std::thread::Builder::new()
+        .name("verifier-sequencer".to_string())
+        .spawn(move || {
+            let mut next: u64 = 0;
+            let mut pending = BTreeMap::new();
+            while let Some((seq, res)) = done_recv.blocking_recv() {
+                pending.insert(seq, res);
+                while let Some(res) = pending.remove(&next) {
+                    next += 1;
+                    if let Some(msg) = res {
+                        if out_send.send(msg).is_err() {
+                            // The reactor is gone
+                            return;
+                        }
+                    }
+                }
+            }
+        })
+        .expect("failed to start the verification sequencer");
+    out_recv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn keeps_the_order_and_drops_invalid_messages() {
+        let (send, recv) = unbounded_channel();
+        // Every third message is invalid, and the messages take longer to
+        // check the lower they are, so the workers finish out of order
+        let mut out = start_verifier(recv, 4, |_, m: &u64| {
+            std::thread::sleep(Duration::from_millis(10 - m % 10));
+            m % 3 != 0
+        });
+        for m in 0..100u64 {
+            send.send(((m % 4) as Replica, m)).unwrap();
+        }
+        drop(send);
+
+        let mut got = Vec::new();
+        while let Some((from, m)) = out.blocking_recv() {
+            assert_eq!(from, (m % 4) as Replica);
+            got.push(m);
+        }
+        let expected: Vec<u64> = (0..100).filter(|m| m % 3 != 0).collect();
+        assert_eq!(got, expected);
+    }
+}
@@ -0,0 +1,182 @@
+use config::Node;
+use crypto::hash::{HASH_SIZE, Hash};
+use fnv::FnvHashMap as HashMap;
+use std::{convert::TryInto, fs::{self, File, OpenOptions}, io::{self, Read, Write}, path::{Path, PathBuf}};
+
+/// The kinds of messages whose signatures go through the WAL
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SignKind {
+    /// An Apollo proposal for a round, a Sync HotStuff, Opt Sync or HotStuff
+    /// proposal for a height in a view, or a PBFT pre-prepare for a sequence
+    /// number in a view
+    Proposal,
+    /// A Sync HotStuff, Opt Sync or HotStuff vote for a height in a view, or a
+    /// PBFT prepare for a sequence number in a view
+    Vote,
+    /// An Artemis UCR vote for a round in a view
+    UcrVote,
+}
+
+impl SignKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            SignKind::Proposal => 0,
+            SignKind::Vote => 1,
+            SignKind::UcrVote => 2,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        match b {
+            0 => Some(SignKind::Proposal),
+            1 => Some(SignKind::Vote),
+            2 => Some(SignKind::UcrVote),
+            _ => None,
+        }
+    }
+}
+
+/// Where a node signs at most one hash: the kind of message, the view (0 for
+/// the protocols without views), and the round or height
+pub type Slot = (SignKind, usize, usize);
+
+/// kind, view, round, hash
+const RECORD_SIZE: usize = 1 + 8 + 8 + HASH_SIZE;
+
+/// A write-ahead log of everything that a node signed, which it checks before
+/// signing so that it never signs two hashes for the same slot, even across
+/// restarts.
+///
+/// Every record is fsync'd before the signed message leaves the node. The log
+/// lives in `Node::state_dir`, and is reloaded when the node starts again.
+pub struct Wal {
+    path: PathBuf,
+    file: File,
+    signed: HashMap<Slot, Hash>,
+}
+
+impl Wal {
+    /// Opens the log of `protocol` for a node, or returns `None` if the
+    /// config has no `state_dir`
+    pub fn from_node(config: &Node, protocol: &str) -> Option<Self> {
+        let dir = PathBuf::from(config.state_dir.as_ref()?);
+        fs::create_dir_all(&dir)
+            .expect("Failed to create the state directory");
+        let path = dir.join(format!("{}-{}.wal", protocol, config.id));
+        Some(Wal::recover(&path).expect("Failed to open the WAL"))
+    }
+
+    /// Opens the log at `path`, and reloads the records in it.
+    ///
+    /// A crash while appending leaves a partial record at the end, which was
+    /// never acknowledged and is dropped.
+    pub fn recover(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let mut signed = HashMap::default();
+        let mut valid = 0;
+        for rec in bytes.chunks_exact(RECORD_SIZE) {
+            let kind = match SignKind::from_byte(rec[0]) {
+                None => break,
+                Some(k) => k,
+            };
+            let view = u64::from_le_bytes(rec[1..9].try_into().unwrap()) as usize;
+            let round = u64::from_le_bytes(rec[9..17].try_into().unwrap()) as usize;
+            let hash: Hash = rec[17..].try_into().unwrap();
+            signed.insert((kind, view, round), hash);
+            valid += RECORD_SIZE;
+        }
+        if valid < bytes.len() {
+            log::warn!("Dropping {} bytes at the end of the WAL {:?}", bytes.len()-valid, path);
+            file.set_len(valid as u64)?;
+            file.sync_all()?;
+        }
+        if !signed.is_empty() {
+            log::info!("Recovered {} signed messages from {:?}", signed.len(), path);
+        }
+        Ok(Wal {
+            path: path.to_path_buf(),
+            file,
+            signed,
+        })
+    }
+
+    /// Whether the log has any record, i.e. the node signed something before
+    /// it restarted
+    pub fn is_empty(&self) -> bool {
+        self.signed.is_empty()
+    }
+
+    /// The hash that we signed for a slot, if any
+    pub fn signed(&self, slot: &Slot) -> Option<&Hash> {
+        self.signed.get(slot)
+    }
+
+    /// The highest view and round in which we signed a message of `kind`
+    pub fn highest(&self, kind: SignKind) -> Option<(usize, usize)> {
+        self.signed.keys()
+            .filter(|(k, _, _)| *k == kind)
+            .map(|(_, view, round)| (*view, *round))
+            .max()
+    }
+
+    /// Records that we are about to sign `hash` for `slot`, and returns
+    /// whether we may: signing a hash again is fine, but not another hash for
+    /// the same slot.
+    ///
+    /// Returns only once the record is on the disk. If it cannot be written,
+    /// we do not sign.
+    pub fn record(&mut self, slot: Slot, hash: &Hash) -> bool {
+        if let Some(old) = self.signed.get(&slot) {
+            return old == hash;
+        }
+        let (kind, view, round) = slot;
+        let mut rec = Vec::with_capacity(RECORD_SIZE);
+        rec.push(kind.to_byte());
+        rec.extend_from_slice(&(view as u64).to_le_bytes());
+        rec.extend_from_slice(&(round as u64).to_le_bytes());
+        rec.extend_from_slice(hash);
+        let res = self.file.write_all(&rec)
+            .and_then(|_| self.file.sync_data());
+        if let Err(e) = res {
+            log::error!("Failed to write to the WAL {:?}: {}", self.path, e);
+            return false;
+        }
+        self.signed.insert(slot, *hash);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recover_signed() {
+        let dir = std::env::temp_dir().join(format!("wal-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("test.wal");
+        let _ = fs::remove_file(&path);
+        let slot = (SignKind::Vote, 2, 5);
+        {
+            let mut wal = Wal::recover(&path).unwrap();
+            assert!(wal.record(slot, &[1; HASH_SIZE]));
+            assert!(wal.record(slot, &[1; HASH_SIZE]));
+            assert!(wal.record((SignKind::Proposal, 2, 5), &[2; HASH_SIZE]));
+        }
+        // A torn record at the end is dropped
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(&[1, 0, 0]).unwrap();
+        let mut wal = Wal::recover(&path).unwrap();
+        assert!(!wal.record(slot, &[3; HASH_SIZE]));
+        assert_eq!(wal.highest(SignKind::Vote), Some((2, 5)));
+        assert_eq!(wal.highest(SignKind::UcrVote), None);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 2*RECORD_SIZE as u64);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
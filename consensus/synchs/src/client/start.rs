@@ -1,10 +1,11 @@
 use std::time::SystemTime;
+use std::collections::hash_map::Entry;
 use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
 use config::Client;
-use types::synchs::{ClientMsg, Transaction};
+use types::{FaultModel, Reconfig, Replica, Threshold, synchs::{ClientMsg, Transaction}};
 use tokio::sync::mpsc::channel;
 use crypto::hash::Hash;
-use consensus::statistics;
+use consensus::{EvidenceChecker, statistics};
 use std::sync::Arc;
 use util::codec::{EnCodec, Decodec};
 use net::tokio_manager::TlsClient as NClient;
@@ -13,6 +14,7 @@ pub async fn start(
     c:&Client, 
     metric: u64,
     window: usize,
+    reconfig: Option<Reconfig>,
 ) {
     let mut client_network = NClient::<ClientMsg, Transaction>::new(c.root_cert.clone());
     let servers = c.net_map.clone();
@@ -25,6 +27,13 @@ pub async fn start(
     let m = metric;
     let payload = c.payload;
     tokio::spawn(async move{
+        // The change of the replica set goes first
+        if let Some(r) = reconfig {
+            log::info!("Submitting the reconfiguration {:?}", r);
+            if send.send(Arc::new(r.to_transaction())).await.is_err() {
+                return;
+            }
+        }
         let mut i = 0;
         loop {
             let tx = Transaction::new_dummy_tx(i,payload);
@@ -37,10 +46,12 @@ pub async fn start(
     });
     let mut pending = window;
     let mut time_map = HashMap::default();
-    let mut count_map:HashMap<Hash, usize> = HashMap::default();
+    let quorum = c.quorum_policy(FaultModel::Synchronous);
+    let mut ack_map:HashMap<Hash, HashSet<Replica>> = HashMap::default();
     let mut finished_map:HashSet<Hash> = HashSet::default();
     let mut latency_map = HashMap::default();
     let mut num_cmds:u128 = 0;
+    let mut evidence = EvidenceChecker::from_client(c);
 
     let start = SystemTime::now();
     loop {
@@ -62,7 +73,7 @@ pub async fn start(
             block_opt = net_recv.recv() => {
                 log::debug!("Got {:?} from the network", block_opt);
                 // Got something from the network
-                if let Some((_, b)) = block_opt {
+                if let Some((sender, b)) = block_opt {
                     let b = match b {
                         ClientMsg::NewBlock(b, _) => {
                             b
@@ -71,14 +82,20 @@ pub async fn start(
                     };
                     log::debug!("got a block:{:?}",b);
                     
-                    // Check if the block is valid?
-                    if !count_map.contains_key(&b.hash) {
-                        count_map.insert(b.hash, 1);
-                        continue;
-                    }
-                    let ct = count_map.get(&b.hash).unwrap().clone();
-                    if ct < c.num_faults {
-                        count_map.insert(b.hash, ct+1);
+                    // Check if the block is valid the first time we see it
+                    let acks = match ack_map.entry(b.hash) {
+                        Entry::Vacant(e) => {
+                            if !evidence.check(&b.header.evidence) {
+                                log::warn!("Got a block with invalid evidence");
+                                continue;
+                            }
+                            e.insert(HashSet::default())
+                        },
+                        Entry::Occupied(e) => e.into_mut(),
+                    };
+                    acks.insert(sender);
+                    // One of the replicas that sent it is honest
+                    if !quorum.is_met(Threshold::Honest, acks.iter().cloned()) {
                         continue;
                     }
                     let now = SystemTime::now();
@@ -89,7 +106,7 @@ pub async fn start(
                     num_cmds += c.block_size as u128;
                     for t in &b.body.tx_hashes {
                         if let Some(old) = time_map.get(t) {
-                            latency_map.insert(t.clone(), (old.clone(),now));
+                            latency_map.insert(*t, (*old, now));
                         } else {
                             log::warn!(
                                 "transaction not found in time map");
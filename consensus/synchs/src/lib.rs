@@ -1,4 +1,7 @@
 /// Sync HotStuff consensus reactors
 
 pub mod node;
-pub mod client;
\ No newline at end of file
+pub mod client;
+
+mod protocol;
+pub use protocol::*;
\ No newline at end of file
@@ -1,6 +1,223 @@
-use types::synchs::Vote;
-use super::context::Context;
+use types::{CertKind, EVERYONE, Statement, Threshold, synchs::{CertType, Certificate, Evidence, Height, Propose, ProtocolMsg, View, Vote}};
+use super::{context::Context, phase::{Event, Phase}};
+use std::sync::Arc;
 
-pub async fn _on_receive_blame(_v: &Vote,_cx:&mut Context) {
-    panic!("Unimplemented");
-}
\ No newline at end of file
+/// Called when the view has not seen a proposal after height `ht` in time
+pub async fn on_timeout(view: View, ht: Height, cx: &mut Context) {
+    if view != cx.view || ht != cx.height || cx.blamed {
+        return;
+    }
+    match cx.phase {
+        Phase::Normal | Phase::Wait if !cx.expect_work || !cx.has_work() => {
+            // The leader had nothing to propose for a whole timeout, which is
+            // not its fault
+            cx.start_timer(std::time::Duration::ZERO);
+        }
+        // The leader did not propose, or did not start its view
+        Phase::Normal | Phase::Wait => do_blame(cx).await,
+        Phase::Quit | Phase::StatusWait => {}
+    }
+}
+
+/// Sign a blame for the leader of this view
+fn sign_blame(cx: &Context) -> Certificate {
+    let msg = CertType::Blame(cx.leader_of_view(), cx.view);
+    let auth = cx.my_secret_key.sign(&util::io::to_bytes(&msg))
+        .expect("Failed to sign a blame");
+    Certificate {
+        msg,
+        votes: vec![Vote { origin: cx.myid, auth }],
+        agg: None,
+    }
+}
+
+fn multicast(msg: ProtocolMsg, cx: &Context) {
+    if let Err(e) = cx.net_send.send((EVERYONE, Arc::new(msg))) {
+        log::warn!("Failed to send a view change message: {}", e);
+    }
+}
+
+/// Blame the leader of this view for not making progress
+pub async fn do_blame(cx: &mut Context) {
+    if cx.blamed {
+        return;
+    }
+    cx.blamed = true;
+    log::info!("Blaming the leader {} of view {} at height {}", cx.leader_of_view(), cx.view, cx.height);
+    let cert = sign_blame(cx);
+    multicast(ProtocolMsg::NoProgressBlameMsg(cert.clone()), cx);
+    on_receive_blame(cert, cx).await;
+}
+
+/// Record the proposal `p` of the leader of this view. If the leader made
+/// another proposal on the same parent, it equivocated, and we blame it.
+///
+/// Returns true on an equivocation.
+pub async fn check_equivocation(p: &Arc<Propose>, cx: &mut Context) -> bool {
+    let old = match cx.proposal_map.get(&p.parent()) {
+        None => {
+            cx.proposal_map.insert(p.parent(), p.clone());
+            return false;
+        }
+        Some(old) if old.block_hash == p.block_hash => return false,
+        Some(old) => old.clone(),
+    };
+    let leader = cx.leader_of_view();
+    on_equivocation(Evidence::new(old.signed(leader), p.signed(leader)), cx).await;
+    true
+}
+
+/// Called when the leader of this view made the conflicting proposals in
+/// `e`. We stop voting and committing in this view, and send the proof to
+/// the others along with our blame.
+async fn on_equivocation(e: Evidence, cx: &mut Context) {
+    if cx.equivocated {
+        return;
+    }
+    log::warn!("The leader {} of view {} equivocated", cx.leader_of_view(), cx.view);
+    cx.equivocated = true;
+    cx.blamed = true;
+    cx.commit_queue.clear();
+    cx.evidence.add(e.clone());
+    let cert = sign_blame(cx);
+    multicast(ProtocolMsg::EquivcationBlameMsg(e, cert.clone()), cx);
+    on_receive_blame(cert, cx).await;
+}
+
+pub async fn on_receive_equivocation(e: Evidence, c: Certificate, cx: &mut Context) {
+    let (leader, view) = match c.msg {
+        CertType::Blame(l, v) => (l, v),
+        _ => return,
+    };
+    let is_valid = e.offender() == leader &&
+        matches!(e.first.statement, Statement::ViewProposal{view: v, ..} if v == view) &&
+        (cx.pre_verified || e.verify(&cx.pub_key_map).is_ok());
+    if !is_valid {
+        log::warn!("Invalid equivocation proof for view {}", view);
+        return;
+    }
+    if view == cx.view && !has_quit(view, cx) {
+        on_equivocation(e, cx).await;
+    } else {
+        cx.evidence.add(e);
+    }
+    on_receive_blame(c, cx).await;
+}
+
+pub async fn on_receive_blame(c: Certificate, cx: &mut Context) {
+    let view = match c.msg {
+        CertType::Blame(l, v) if cx.leader_of(v) == Some(l) => v,
+        _ => {
+            log::warn!("Blame message for {:?}", c.msg);
+            return;
+        }
+    };
+    let origin = match cx.verify_vote(&c, CertKind::Blame) {
+        Ok(r) => r,
+        Err(e) => {
+            log::warn!("Invalid blame message: {}", e);
+            return;
+        }
+    };
+    if has_quit(view, cx) {
+        log::debug!("Blame for an old view {}", view);
+        return;
+    }
+    let votes = cx.blame_map.entry(view).or_default();
+    votes.insert(origin, c.votes[0].clone());
+    // Promote it to a certificate if it has f+1 blames
+    if !cx.quorum.is_met(Threshold::Honest, votes.keys().cloned()) {
+        return;
+    }
+    let votes = cx.blame_map.remove(&view).unwrap();
+    let cert = Certificate {
+        msg: c.msg,
+        votes: votes.into_values().collect(),
+        agg: None,
+    };
+    on_quit_view(cert, cx).await;
+}
+
+pub async fn on_receive_quit(v: View, c: Certificate, cx: &mut Context) {
+    let is_valid = match c.msg {
+        CertType::Blame(l, w) => v == w && cx.leader_of(w).is_none_or(|x| x == l),
+        _ => false,
+    };
+    if !is_valid {
+        log::warn!("Invalid blame certificate {:?}", c.msg);
+        return;
+    }
+    if let Err(e) = cx.verify_cert(&c, CertKind::Blame, Threshold::Honest) {
+        log::warn!("Invalid blame certificate {:?}: {}", c.msg, e);
+        return;
+    }
+    if has_quit(v, cx) {
+        log::debug!("Already quit view {}", v);
+        return;
+    }
+    on_quit_view(c, cx).await;
+}
+
+/// Whether we already quit `view`
+pub(crate) fn has_quit(view: View, cx: &Context) -> bool {
+    view < cx.view || (view == cx.view && cx.phase == Phase::Quit)
+}
+
+/// Quit the view blamed by the certificate `c`
+///
+/// We forward the certificate so that everyone quits within \Delta, stop
+/// committing in the view, and wait 2\Delta for the certificates still in
+/// flight before sending our highest certificate to the next leader (see
+/// `on_status_event`).
+pub async fn on_quit_view(c: Certificate, cx: &mut Context) {
+    let view = match c.msg {
+        CertType::Blame(_, v) => v,
+        _ => unreachable!("only blame certificates are quit certificates"),
+    };
+    log::info!("Quitting view {} at height {} with the blames of {:?}", view, cx.height,
+        c.votes.iter().map(|v| v.origin).collect::<Vec<_>>());
+    multicast(ProtocolMsg::QuitViewMsg(view, c), cx);
+
+    cx.blame_map.retain(|v, _| *v > view);
+    cx.set_view(view);
+    cx.phase = Phase::Quit;
+    cx.commit_queue.clear();
+    let wait = cx.deltas(2);
+    cx.events.insert(Event::Status(view+1), wait);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::proposal::tests::{context, keys};
+    use types::Replica;
+
+    /// The blame of `origin` for `leader` in `view`
+    fn blame(keys: &[crypto::ed25519::Keypair], origin: Replica, leader: Replica, view: View) -> Certificate {
+        let mut c = Certificate::empty_cert();
+        c.msg = CertType::Blame(leader, view);
+        let auth = keys[origin].sign(&util::io::to_bytes(&c.msg));
+        c.votes.push(Vote { origin, auth });
+        c
+    }
+
+    #[tokio::test]
+    async fn blames_only_count_for_the_leader() {
+        let keys = keys();
+        let mut cx = context(&keys);
+        assert_eq!(cx.leader_of(0), Some(0));
+
+        // Node 1 is not the leader of view 0
+        on_receive_blame(blame(&keys, 2, 1, 0), &mut cx).await;
+        assert!(cx.blame_map.is_empty());
+        // A blame of the leader with a bad signature does not count either
+        let mut forged = blame(&keys, 2, 0, 0);
+        forged.votes[0].origin = 1;
+        on_receive_blame(forged, &mut cx).await;
+        assert!(cx.blame_map.is_empty());
+
+        on_receive_blame(blame(&keys, 2, 0, 0), &mut cx).await;
+        assert_eq!(cx.blame_map[&0].keys().collect::<Vec<_>>(), vec![&2]);
+        assert_eq!(cx.phase, Phase::Normal);
+    }
+}
@@ -1,34 +1,63 @@
-use types::synchs::{ClientMsg, Payload, Propose};
+use types::{CommittedBlock, synchs::{Block, ClientMsg, Payload, Propose}};
 
 use super::context::Context;
 use std::sync::Arc;
 
-/// Commit this block and all its ancestors
+/// Commit the block of this proposal and all its ancestors
 pub async fn on_commit(p: Arc<Propose>, cx:&mut Context) {
-    let b = p.block.as_ref().unwrap();
+    commit_block(p.block.clone().unwrap(), cx);
+}
+
+/// Commit this block and all its ancestors
+pub fn commit_block(b: Arc<Block>, cx: &mut Context) {
     // Check if we have already committed this block and its ancestors
     if cx.storage.is_committed_by_hash(&b.hash) {
         return;
     }
 
-    // Ship the block to the clients
-    let ship = cx.cli_send.clone();
-    let payload = cx.payload;
-    let ship_b = b.clone();
-    let ship_block = tokio::spawn(async move {
-        let payload = Payload::with_payload(payload);
-        let msg = ClientMsg::RawNewBlock(
-            ship_b.as_ref().clone(), payload);
+    // The ancestors from the previous views may not be committed yet
+    let mut to_commit = vec![b.clone()];
+    let mut prev = b.header.prev;
+    while !cx.storage.is_committed_by_hash(&prev) {
+        let parent = match cx.storage.delivered_block_from_hash(&prev) {
+            None => {
+                log::warn!("Committing a block with an undelivered ancestor {:?}", prev);
+                break;
+            }
+            Some(x) => x,
+        };
+        prev = parent.header.prev;
+        to_commit.push(parent);
+    }
+
+    // A certificate on the top block also certifies its ancestors
+    let top_cert = cx.cert_map.get(&b.hash).cloned();
+
+    // Ship the blocks to the clients, in order
+    for b in to_commit.into_iter().rev() {
+        let payload = Payload::with_payload(cx.payload);
+        let msg = ClientMsg::RawNewBlock(b.as_ref().clone(), payload);
         log::debug!(
             "sending msg: {:?} to the client", msg);
-        if let Err(e) = ship.send(Arc::new(msg)) {
+        if let Err(e) = cx.cli_send.send(Arc::new(msg)) {
             println!("Error sending the block to the client: {}", e);
-            ()
         }
         log::debug!(
             "Committed block and sending it to the client now");
-    });
-    cx.last_committed_block_ht = b.header.height;
-    cx.storage.add_committed_block(b.clone());
-    ship_block.await.unwrap();
-}
\ No newline at end of file
+        let txs = cx.storage.take_committed(&b.body.tx_hashes);
+        cx.commit_sink.publish(CommittedBlock{
+            block: b.clone(),
+            txs,
+            justification: cx.cert_map.get(&b.hash).cloned().or_else(|| top_cert.clone()),
+        });
+        cx.last_committed_block_ht = b.header.height;
+        cx.evidence.on_included(&b.header.evidence);
+        // The replica set may change at the next height
+        let members = cx.reconfig.as_mut()
+            .and_then(|rc| rc.on_commit(b.header.height, &b.header.reconfig).cloned());
+        if let Some(m) = members {
+            cx.use_members(m);
+        }
+        cx.storage.add_committed_block(b);
+    }
+}
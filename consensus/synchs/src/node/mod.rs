@@ -10,6 +10,12 @@ mod proposal;
 
 mod blame;
 
+mod phase;
+
+mod view_change;
+
 mod vote;
 
-mod commit;
\ No newline at end of file
+mod commit;
+
+mod recovery;
\ No newline at end of file
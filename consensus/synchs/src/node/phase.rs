@@ -0,0 +1,26 @@
+use types::synchs::{Height, View};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    /// The leader proposes blocks, and everyone votes for them
+    Normal,
+    /// We quit a view on a blame certificate, and are waiting for the votes
+    /// still in flight before sending our status to the next leader
+    Quit,
+    /// We sent our status, and are waiting for the first proposal of the next
+    /// leader
+    Wait,
+    /// We are the next leader, and are collecting the status of the others
+    StatusWait,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Event {
+    /// The view has not seen a proposal after the given height in time
+    Timeout(View, Height),
+    /// We finished waiting after quitting the view before the given view
+    Status(View),
+    /// We, as the leader of the given view, finished collecting the status
+    /// messages
+    NewView(View),
+}
@@ -1,6 +1,7 @@
-use std::collections::HashSet;
-use super::context::Context;
+use types::{CertKind, EVERYONE, Reconfig, Threshold};
+use super::{blame::check_equivocation, context::Context, phase::Phase, recovery::request_sync};
 use crypto::hash::EMPTY_HASH;
+use consensus::SignKind;
 use types::synchs::{Block, CertType, Certificate, Transaction, Vote, 
         Propose, ProtocolMsg};
 use std::sync::Arc;
@@ -23,24 +24,38 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return false;
     }
 
-    // Check if the block has sufficient votes
-    if new_block.header.height > 1 && p.cert.votes.len() <= cx.num_faults {
-        log::warn!(
-            "Insufficient votes in the proposal, rejecting the proposal");
-        return false;
-    }
-
     // Check signature for the proposal
     let pk = cx.pub_key_map.get(&new_block.header.author).unwrap();
-    if !pk.verify(&new_block.hash, &p.proof) {
+    if p.block_hash != new_block.hash ||
+        p.parent() != new_block.header.prev ||
+        (!cx.pre_verified && !pk.verify(&p.sign_data(), &p.proof)) 
+    {
         log::warn!(
             "Got an incorrectly signed block");
         return false;
     }
 
+    // Check the proofs of misbehaviour in the block
+    if !cx.pre_verified {
+        if let Some(e) = new_block.header.evidence.iter().find(|e| e.verify(&cx.pub_key_map).is_err()) {
+            log::warn!("Invalid evidence against {} in the proposal", e.offender());
+            return false;
+        }
+    }
+
+    // Check the changes of the replica set in the block
+    if let Some(rc) = &cx.reconfig {
+        if let Some(r) = new_block.header.reconfig.iter().find(|r| !rc.is_valid(r, new_block.header.height)) {
+            log::warn!("Invalid reconfiguration {:?} in the proposal", r);
+            return false;
+        }
+    }
+
     // Check if the view is correct
     if cx.view != p.view {
-        panic!("This view check should be unreachable");
+        log::warn!(
+            "Got a proposal for view {} in view {}", p.view, cx.view);
+        return false;
     }
 
     // If the parent is genesis block, then the parent is correctly certified
@@ -48,7 +63,6 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return true;
     }
     // Otherwise check if all the parent certificates are correctly signed
-    let mut uniq_votes = HashSet::with_capacity(cx.num_faults+1);
     if let CertType::Vote(_v, h) = &p.cert.msg {
         // Check if vote message is the same as that in the proposal
         if *h != new_block.header.prev {
@@ -62,26 +76,22 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
     }
 
     log::debug!("Checking certificate: {:?}", p.cert);
-    let data = util::io::to_bytes(&p.cert.msg);
-    for v in &p.cert.votes {
-        // check signature
-        let pk = match cx.pub_key_map.get(&v.origin) {
-            None => {
-                log::warn!("Invalid vote origin");
-                return false;
-            }
-            Some(x) => x,
-        };
-        if !pk.verify(&data, &v.auth) {
-            log::warn!("Invalid vote signature: {:?}", v);
-            return false;
-        }
-        // Add this unique vote
-        uniq_votes.insert(v.origin);
-    }
-    if uniq_votes.len() < cx.num_faults {
+    if let Err(e) = cx.verify_cert(&p.cert, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid certificate in the proposal: {}", e);
         return false;
     }
+    true
+}
+
+/// Check if the proposal extends the last block that we voted for. The first
+/// proposal of a view must instead extend a block certified at least as high
+/// as the one we locked on before the view change.
+pub fn check_parent(p: &Propose, cx: &Context) -> bool {
+    let new_block = p.block.as_ref().unwrap();
+    if cx.phase == Phase::Wait {
+        return cx.storage.is_delivered_by_hash(&new_block.header.prev) &&
+            cx.rank(&p.cert) >= cx.rank(&cx.last_seen_cert);
+    }
     // Is it extending the last known parent?
     if new_block.header.prev != cx.last_seen_block.hash {
         log::warn!("Parent undelivered");
@@ -109,6 +119,21 @@ pub async fn on_receive_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
         log::warn!("Proposal checking failed");
         return decision;
     }
+    for e in &new_block.header.evidence {
+        cx.evidence.add(e.clone());
+    }
+    // Do not vote for the leader again once it equivocates
+    if cx.equivocated || check_equivocation(&p, cx).await {
+        return decision;
+    }
+    if !check_parent(&p, cx) {
+        log::warn!("Proposal does not extend our chain");
+        // We may have missed some blocks
+        if !cx.storage.is_delivered_by_hash(&new_block.header.prev) {
+            request_sync(cx);
+        }
+        return decision;
+    }
     return on_new_valid_proposal(p, cx).await;
 }
     
@@ -121,7 +146,13 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
     {
         log::warn!(
             "We do not have the parent for this block");
-        // TODO: Request and Deliver blocks
+        request_sync(cx);
+        return decision;
+    }
+    // Do not vote for a second block at this height, which we may have done
+    // before a restart
+    if !cx.on_sign(SignKind::Vote, new_block.header.height, &new_block.hash) {
+        log::warn!("Already voted at height {} in view {}", new_block.header.height, cx.view);
         return decision;
     }
     // Everything looks fine, initiate voting and continue to process this
@@ -146,7 +177,7 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
 
     // Create a scope and send the proposal to other nodes
     let ship = cx.net_send.clone();
-    let ship_nodes = cx.num_nodes;
+    let ship_nodes = EVERYONE;
     let ship_v = ProtocolMsg::VoteMsg(my_vote, p.as_ref().clone());
     let vote_ship = tokio::spawn(async move {
         let msg = Arc::new(ship_v);
@@ -162,8 +193,14 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
     cx.storage.add_delivered_block(new_block.clone());
     cx.storage.clear(&new_block.body.tx_hashes);
     cx.height = new_block.header.height;
+    cx.view_of.insert(new_block.hash, p.view);
     cx.last_seen_block = new_block.clone();
-    cx.last_seen_cert = p.cert.clone();
+    cx.update_high_cert(&p.cert);
+    if cx.phase == Phase::Wait {
+        log::info!("Started view {} on height {}", cx.view, new_block.header.height);
+        cx.phase = Phase::Normal;
+    }
+    cx.start_timer(std::time::Duration::ZERO);
 
     // wait for voting to finish?
     if let Err(e) = vote_ship.await {
@@ -178,25 +215,29 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
 
 pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Propose> {
     // Build the proposal
-    let parent = &cx.last_seen_block;
+    let parent = cx.last_seen_block.clone();
+    let reconfig = match &cx.reconfig {
+        None => Vec::new(),
+        Some(rc) => rc.proposable(
+            txs.iter().filter_map(|tx| Reconfig::from_transaction(tx)),
+            parent.header.height+1),
+    };
     let mut new_block = Block::with_tx(txs);
 
     // Update block contents here
     new_block.header.author = cx.myid;
     new_block.header.prev = parent.hash;
     new_block.header.height = parent.header.height+1;
+    new_block.header.evidence = cx.evidence.pending();
+    new_block.header.reconfig = reconfig;
     
     // Update the hash at the end
     new_block.hash = new_block.compute_hash();
+    // The reactor checked that we did not propose at this height yet
+    if !cx.on_sign(SignKind::Proposal, new_block.header.height, &new_block.hash) {
+        panic!("Failed to record the proposal at height {} in the WAL", new_block.header.height);
+    }
     
-    // Sign the block hash 
-    let proof = match cx.my_secret_key.sign(&new_block.hash) {
-        Err(e) => {
-            panic!("Failed to sign the new proposal: {}", e);
-        },
-        Ok(sig) => sig,
-    };
-
     let mut new_block_cert = Certificate::empty_cert();
     new_block_cert.msg = CertType::Vote(cx.view, new_block.hash);
     let sign_data = util::io::to_bytes(&new_block_cert.msg);
@@ -219,7 +260,6 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
     // The block is ready, build proposal
     let new_block_ref = Arc::new(new_block.clone());
     let mut p = Propose::new();
-    p.proof = proof;
     p.block = Some(new_block_ref.clone());
     p.block_hash = new_block_ref.hash;
     p.cert = match cx.cert_map.get(&parent.hash) {
@@ -229,10 +269,17 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
         Some(x) => x.clone(),
     };
     p.view = cx.view;
+    // Sign the block hash in this view
+    p.proof = match cx.my_secret_key.sign(&p.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the new proposal: {}", e);
+        },
+        Ok(sig) => sig,
+    };
 
     // Ship the proposal
     let ship = cx.net_send.clone();
-    let ship_num = cx.num_nodes;
+    let ship_num = EVERYONE;
     let ship_p = ProtocolMsg::RawNewProposal(p.clone(), new_block_ref.as_ref().clone());
     let broadcast = tokio::spawn(async move {
         if let Err(e) = ship.send(
@@ -251,11 +298,103 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
     // Commit normally, and tell the client after 2\Delta
     cx.vote_map.insert(new_block_ref.hash, new_block_cert);
     cx.height = new_block_ref.header.height;
+    cx.view_of.insert(new_block_ref.hash, cx.view);
     // The leader remains the same
     cx.last_seen_block = new_block_ref.clone();
     cx.last_committed_block_ht = cx.height;
+    cx.start_timer(std::time::Duration::ZERO);
     // The view remains the same
     broadcast.await.expect("failed to broadcast the proposal");
 
     Arc::new(p)
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use config::Node;
+    use consensus::CommitSink;
+    use crypto::{Algorithm, ed25519};
+    use tokio::sync::mpsc::unbounded_channel;
+    use types::{Replica, View};
+
+    pub(crate) const N: usize = 4;
+
+    /// Node 3 of four, in view 0
+    pub(crate) fn context(keys: &[ed25519::Keypair]) -> Context {
+        let mut config = Node::new();
+        config.num_nodes = N;
+        config.num_faults = 1;
+        config.block_size = 1;
+        config.id = 3;
+        config.crypto_alg = Algorithm::ED25519;
+        config.secret_key_bytes = keys[3].encode().to_vec();
+        config.pk_map = keys.iter().enumerate()
+            .map(|(i, k)| (i, k.public().encode().to_vec()))
+            .collect();
+        let (net_send, _) = unbounded_channel();
+        let (cli_send, _) = unbounded_channel();
+        let (peers, _) = unbounded_channel();
+        Context::new(&config, net_send, cli_send, CommitSink::new(None), peers)
+    }
+
+    pub(crate) fn keys() -> Vec<ed25519::Keypair> {
+        (0..N).map(|_| ed25519::Keypair::generate()).collect()
+    }
+
+    /// A delivered block of `author` on top of `parent`
+    fn block(parent: &Block, author: Replica, cx: &mut Context) -> Arc<Block> {
+        let mut b = Block::with_tx(Vec::new());
+        b.header.author = author;
+        b.header.prev = parent.hash;
+        b.header.height = parent.header.height+1;
+        b.hash = b.compute_hash();
+        let b = Arc::new(b);
+        cx.storage.add_delivered_block(b.clone());
+        b
+    }
+
+    /// A certificate for `b` in `view`, whose signatures do not matter here
+    fn cert(view: View, b: &Block) -> Certificate {
+        let mut c = Certificate::empty_cert();
+        c.msg = CertType::Vote(view, b.hash);
+        c
+    }
+
+    /// A proposal for a block on top of the block that `cert` certifies
+    fn propose(parent: &Block, cert: Certificate) -> Propose {
+        let mut b = Block::with_tx(Vec::new());
+        b.header.prev = parent.hash;
+        b.header.height = parent.header.height+1;
+        b.hash = b.compute_hash();
+        let mut p = Propose::new();
+        p.block_hash = b.hash;
+        p.cert = cert;
+        p.block = Some(Arc::new(b));
+        p
+    }
+
+    #[test]
+    fn new_views_extend_a_chain_as_high_as_the_lock() {
+        let mut cx = context(&keys());
+        let genesis = cx.last_seen_block.clone();
+        let b1 = block(&genesis, 0, &mut cx);
+        let b2 = block(&b1, 1, &mut cx);
+        let b2_fork = block(&b1, 2, &mut cx);
+        // We locked on b2, certified in view 1, before the view change
+        cx.last_seen_block = b2.clone();
+        cx.last_seen_cert = cert(1, &b2);
+        cx.phase = Phase::Wait;
+
+        // A certificate of a lower view or height ranks lower
+        assert!(!check_parent(&propose(&b1, cert(1, &b1)), &cx));
+        assert!(!check_parent(&propose(&b2_fork, cert(0, &b2_fork)), &cx));
+        // The same certificate, or a higher one on another fork, will do
+        assert!(check_parent(&propose(&b2, cert(1, &b2)), &cx));
+        assert!(check_parent(&propose(&b2_fork, cert(2, &b2_fork)), &cx));
+
+        // In a view, a proposal must extend our last block
+        cx.phase = Phase::Normal;
+        assert!(!check_parent(&propose(&b2_fork, cert(2, &b2_fork)), &cx));
+        assert!(check_parent(&propose(&b2, cert(1, &b2)), &cx));
+    }
+}
@@ -10,12 +10,18 @@ use tokio::sync::mpsc::{
 use types::synchs::{Replica, Transaction,ProtocolMsg,ClientMsg};
 use config::Node;
 use super::{
+    blame::*,
     commit::on_commit, 
     proposal::*, 
     vote::on_vote,
     context::Context,
+    phase::{Event, Phase},
+    view_change::*,
+    recovery::*,
 };
 use tokio_stream::StreamExt;
+use net::tokio_manager::PeerChange;
+use consensus::{CommitSink, SignKind};
 use std::sync::Arc;
 
 pub async fn reactor(
@@ -23,39 +29,38 @@ pub async fn reactor(
     net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
     cli_send: UnboundedSender<Arc<ClientMsg>>,
-    mut cli_recv: UnboundedReceiver<Transaction>
+    commits: CommitSink,
+    mut cli_recv: UnboundedReceiver<Transaction>,
+    peers: UnboundedSender<PeerChange>,
 ) {
     let d2 = std::time::Duration::from_millis(2*config.delta);
     log::debug!("Started timers");
-    let mut cx = Context::new(config, net_send, cli_send);
+    let mut cx = Context::new(config, net_send, cli_send, commits, peers);
+    // Check the signatures on a pool of workers before they reach the reactor
+    if config.verify_workers > 0 {
+        let pk_map = cx.pub_key_map.clone();
+        net_recv = consensus::start_verifier(net_recv, config.verify_workers,
+            move |_, m: &ProtocolMsg| m.verify_sigs(&pk_map));
+    }
     let block_size = config.block_size;
     let myid = config.id;
+    cx.start_timer(std::time::Duration::ZERO);
+    // Catch up with the others after a restart
+    if cx.is_recovering() {
+        request_sync(&mut cx);
+    }
     // Start event loop
     loop {
         tokio::select! {
             pmsg_opt = net_recv.recv() => {
                 // Received a protocol message
-                let protmsg = match pmsg_opt {
+                let (sender, protmsg) = match pmsg_opt {
                     None => break,
-                    Some((_, x)) => x,
+                    Some(x) => x,
                 };
                 log::debug!(
                     "Received protocol message: {:?}", protmsg);
-                if let ProtocolMsg::NewProposal(p) = protmsg {
-                    log::debug!("Received a proposal: {:?}", p);
-                    let p = Arc::new(p);
-                    let decision = on_receive_proposal(p.clone(), &mut cx).await;
-                    log::debug!(
-                        "Decision for the incoming proposal is {}", decision);
-                    if decision {
-                        cx.commit_queue.insert(p, d2);
-                    }
-                }
-                else if let ProtocolMsg::VoteMsg(v,p) = protmsg {
-                    log::debug!(
-                        "Received a vote for a proposal: {:?}", v);
-                    on_vote(v, p, &mut cx).await;
-                }
+                on_protocol_msg(sender, protmsg, &mut cx).await;
             },
             tx_opt = cli_recv.recv() => {
                 // We received a message from the client
@@ -78,19 +83,41 @@ pub async fn reactor(
                     Some(Ok(b)) => {
                         log::debug!("Timer fired");
                         on_commit(b.into_inner(), &mut cx).await;
+                        if cx.removed {
+                            log::info!("This node is no longer a replica");
+                            break;
+                        }
                     },
                     Some(Err(e)) => {
                         log::warn!("Timer misfired: {}", e);
                         continue;
                     }
                 }
+            },
+            ev_opt = cx.events.next(), if !cx.events.is_empty() => {
+                let ev = match ev_opt {
+                    None => continue,
+                    Some(Ok(x)) => x.into_inner(),
+                    Some(Err(e)) => {
+                        log::warn!("Event queue misfired: {}", e);
+                        continue;
+                    }
+                };
+                log::debug!("Triggered event {:?}", ev);
+                match ev {
+                    Event::Timeout(v, ht) => on_timeout(v, ht, &mut cx).await,
+                    Event::Status(v) => on_status_event(v, &mut cx).await,
+                    Event::NewView(v) => on_new_view_event(v, &mut cx).await,
+                }
             }
         }
         // Do we have sufficient commands, and are we the next leader?
         // Also, do we have sufficient votes?
-        if cx.storage.get_tx_pool_size() >= block_size && 
+        if cx.phase == Phase::Normal &&
+            cx.storage.get_tx_pool_size() >= block_size && 
             cx.next_leader() == myid && 
-            cx.cert_map.contains_key(&cx.last_seen_block.hash)
+            cx.cert_map.contains_key(&cx.last_seen_block.hash) &&
+            cx.may_sign(SignKind::Proposal, cx.last_seen_block.header.height+1)
         {
             log::debug!("I {} am the leader and, I am proposing", cx.myid);
             let txs = cx.storage.cleave(block_size);
@@ -99,4 +126,58 @@ pub async fn reactor(
             cx.commit_queue.insert(p, d2);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Handle a protocol message from the node `sender`
+pub(crate) async fn on_protocol_msg(sender: Replica, protmsg: ProtocolMsg, cx: &mut Context) {
+    match protmsg {
+        ProtocolMsg::NewProposal(p) => {
+            log::debug!("Received a proposal: {:?}", p);
+            if p.view == cx.view+1 {
+                // The next leader can start its view before we do
+                cx.future_msgs.push((p.view, sender, ProtocolMsg::NewProposal(p)));
+                return;
+            }
+            if p.view > cx.view {
+                // We fell behind the others
+                log::debug!("Proposal for view {} in view {}", p.view, cx.view);
+                request_sync(cx);
+                return;
+            }
+            if p.view != cx.view || !matches!(cx.phase, Phase::Normal | Phase::Wait) {
+                log::debug!("Proposal for view {} in view {}", p.view, cx.view);
+                return;
+            }
+            let p = Arc::new(p);
+            let decision = on_receive_proposal(p.clone(), cx).await;
+            log::debug!(
+                "Decision for the incoming proposal is {}", decision);
+            if decision {
+                let d2 = cx.deltas(2);
+                cx.commit_queue.insert(p, d2);
+            }
+        }
+        ProtocolMsg::VoteMsg(v,p) => {
+            log::debug!(
+                "Received a vote for a proposal: {:?}", v);
+            if p.view == cx.view+1 {
+                cx.future_msgs.push((p.view, sender, ProtocolMsg::VoteMsg(v, p)));
+                return;
+            }
+            on_vote(v, p, cx).await;
+        }
+        ProtocolMsg::NoProgressBlameMsg(c) => on_receive_blame(c, cx).await,
+        ProtocolMsg::EquivcationBlameMsg(e, c) => {
+            on_receive_equivocation(e, c, cx).await
+        }
+        ProtocolMsg::QuitViewMsg(v, c) => on_receive_quit(v, c, cx).await,
+        ProtocolMsg::StatusMsg(c) => on_receive_status(sender, c, cx).await,
+        ProtocolMsg::SyncRequest(h) => on_sync_request(sender, h, cx),
+        ProtocolMsg::SyncResponse(blocks, c) => {
+            on_sync_response(sender, blocks, c, cx).await
+        }
+        other => {
+            log::debug!("Not handling {:?}", other);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use types::synchs_rr::{ClientMsg, Payload, Propose};
+use types::{CommittedBlock, synchs_rr::{ClientMsg, Payload, Propose}};
 
 use super::context::Context;
 use std::sync::Arc;
@@ -28,6 +28,12 @@ pub async fn on_commit(p: Arc<Propose>, cx:&mut Context) {
         log::debug!(
             "Committed block and sending it to the client now");
     });
+    let txs = cx.storage.take_committed(&b.body.tx_hashes);
+    cx.commit_sink.publish(CommittedBlock{
+        block: b.clone(),
+        txs,
+        justification: cx.cert_map.get(&b.hash).map(|c| c.as_ref().clone()),
+    });
     cx.last_committed_block_ht = b.header.height;
     cx.storage.add_committed_block(b.clone());
     ship_block.await.unwrap();
@@ -2,8 +2,10 @@ use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::time::DelayQueue;
 // use futures::channel::mpsc::UnboundedSender;
 use types::synchs_rr::{Block, Certificate, GENESIS_BLOCK, Height, Replica, Storage, View, ClientMsg, ProtocolMsg, Propose};
+use types::{CertError, CertKind, FaultModel, QuorumPolicy, Threshold};
 use config::Node;
-use crypto::{Keypair, PublicKey, ed25519, secp256k1};
+use consensus::{CommitSink, SignKind, Wal};
+use crypto::{Keypair, PublicKey, bls, ed25519, rsa, secp256k1};
 use fnv::FnvHashMap as HashMap;
 use crypto::hash::Hash;
 use std::sync::Arc;
@@ -14,16 +16,22 @@ pub struct Context {
     /// Networking context
     pub net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     pub cli_send: UnboundedSender<Arc<ClientMsg>>,
+    /// The blocks that we commit, for the application
+    pub commit_sink: CommitSink,
 
     /// Data context
     pub num_nodes: usize,
     pub myid: Replica,
-    pub num_faults: usize,
+    /// The votes that the certificates need
+    pub quorum: QuorumPolicy,
     pub payload:usize,
 
     /// PKI
     pub my_secret_key: Keypair,
     pub pub_key_map:HashMap<Replica, PublicKey>,
+    /// Set if the signatures in the network messages are already checked by
+    /// the verification workers
+    pub pre_verified: bool,
 
     /// State context
     pub storage: Storage,
@@ -40,6 +48,8 @@ pub struct Context {
     pub event_queue: DelayQueue<Phase>,
     pub phase: Phase,
     pub delay: u64,
+    /// Everything we signed, which we keep across restarts
+    pub wal: Option<Wal>,
 }
 
 const EXTRA_SPACE:usize = 10;
@@ -49,12 +59,14 @@ impl Context {
         config: &Node,
         net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
         cli_send: UnboundedSender<Arc<ClientMsg>>,
+        commit_sink: CommitSink,
     ) -> Self {
         let genesis_arc = Arc::new(GENESIS_BLOCK);
         let mut c = Context {
             net_send,
             num_nodes: config.num_nodes,
             cli_send,
+            commit_sink,
             my_secret_key: match config.crypto_alg {
                 crypto::Algorithm::ED25519 => {
                     let mut sk_copy = config.secret_key_bytes.clone();
@@ -69,11 +81,25 @@ impl Context {
                     let kp = secp256k1::Keypair::from(sk);
                     Keypair::Secp256k1(kp)
                 }
-                _ => panic!("Unimplemented algorithm"),
+                crypto::Algorithm::BLS => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = bls::Keypair::decode(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Bls(kp)
+                }
+                crypto::Algorithm::RSA => {
+                    let mut sk_copy = config.secret_key_bytes.clone();
+                    let kp = rsa::Keypair::from_pkcs8(
+                        &mut sk_copy
+                    ).expect("Failed to decode the secret key from the config");
+                    Keypair::Rsa(kp)
+                }
             },
             pub_key_map: HashMap::default(),
+            pre_verified: config.verify_workers > 0,
             myid: config.id,
-            num_faults: config.num_faults,
+            quorum: config.quorum_policy(FaultModel::Synchronous),
             storage: Storage::new(EXTRA_SPACE*config.block_size),
             height: 0,
             last_leader: 0,
@@ -88,22 +114,12 @@ impl Context {
             phase: if config.id == 1 { Phase::Propose } else { Phase::ProposeWait },
             event_queue: DelayQueue::new(),
             delay: config.delta,
+            wal: Wal::from_node(config, "synchs-rr"),
         };
-        for (id,mut pk_data) in config.pk_map.clone() {
-            let pk = match config.crypto_alg {
-                crypto::Algorithm::ED25519 => {
-                    let kp = ed25519::PublicKey::decode(
-                        &mut pk_data
-                    ).expect("Failed to decode the secret key from the config");
-                    PublicKey::Ed25519(kp)
-                },
-                crypto::Algorithm::SECP256K1 => {
-                    let sk = secp256k1::PublicKey::decode(&pk_data).expect("Failed to decode the secret key from the config");
-                    PublicKey::Secp256k1(sk)
-                }
-                _ => panic!("Unimplemented algorithm"),
-            };
-            c.pub_key_map.insert(id, pk);
+        for (id, pk_data) in &config.pk_map {
+            let pk = PublicKey::decode(&config.crypto_alg, pk_data)
+                .expect("Failed to decode the public key from the config");
+            c.pub_key_map.insert(*id, pk);
         }
 
         // Initialize storage
@@ -113,6 +129,21 @@ impl Context {
         c
     }
 
+    /// Whether we can still sign a message of `kind` at `height` in this view,
+    /// which we may have done before a restart
+    pub fn may_sign(&self, kind: SignKind, height: Height) -> bool {
+        self.wal.as_ref()
+            .is_none_or(|w| w.signed(&(kind, self.view, height)).is_none())
+    }
+
+    /// Record that we sign `hash` at `height` in this view, and return
+    /// whether we may, i.e. we did not sign another hash there before
+    pub fn on_sign(&mut self, kind: SignKind, height: Height, hash: &Hash) -> bool {
+        let view = self.view;
+        self.wal.as_mut()
+            .is_none_or(|w| w.record((kind, view, height), hash))
+    }
+
     /// For sync hotstuff, the next leader is the current leader
     pub fn next_leader(&self) -> Replica {
         // Apparently this is faster than % operator which is expensive in terms
@@ -128,5 +159,40 @@ impl Context {
     pub fn change_leader(&mut self) {
         self.last_leader = self.next_leader();
     }
-}
 
+    /// Check that `c` is a certificate on a message of `kind` whose votes
+    /// meet `t`. The signatures are checked unless the verification workers
+    /// already did.
+    pub fn verify_cert(&self, c: &Certificate, kind: CertKind, t: Threshold) -> Result<Vec<Replica>, CertError> {
+        if self.pre_verified {
+            c.check_signers(kind, &self.pub_key_map, &self.quorum, t)
+        } else {
+            c.verify(kind, &self.pub_key_map, &self.quorum, t)
+        }
+    }
+
+    /// Check that `c` is a single vote on a message of `kind`, and return
+    /// its origin
+    pub fn verify_vote(&self, c: &Certificate, kind: CertKind) -> Result<Replica, CertError> {
+        if self.pre_verified {
+            c.check_vote(kind, &self.pub_key_map)
+        } else {
+            c.verify_vote(kind, &self.pub_key_map)
+        }
+    }
+
+    /// Returns the certificate that we ship in proposals. With BLS keys the
+    /// votes are aggregated into a single signature.
+    pub fn finalize_cert(&self, cert: Certificate) -> Certificate {
+        if !matches!(self.my_secret_key, Keypair::Bls(_)) {
+            return cert;
+        }
+        match cert.aggregate() {
+            Ok(agg) => agg,
+            Err(e) => {
+                log::warn!("Failed to aggregate the certificate: {}", e);
+                cert
+            }
+        }
+    }
+}
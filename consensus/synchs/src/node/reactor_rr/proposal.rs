@@ -1,10 +1,11 @@
-use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use fnv::FnvHashMap as HashMap;
 use super::{context::Context, phase::Phase};
+use consensus::SignKind;
 use crypto::hash::EMPTY_HASH;
-use types::synchs_rr::{
+use types::{CertKind, Threshold, synchs_rr::{
     Block, CertType, Certificate, Transaction, Vote, 
     Propose, ProtocolMsg
-};
+}};
 use std::sync::Arc;
 
 pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
@@ -25,16 +26,11 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return false;
     }
 
-    // Check if the block has sufficient votes
-    if new_block.header.height > 1 && p.cert.votes.len() <= cx.num_faults {
-        log::warn!(
-            "Insufficient votes in the proposal, rejecting the proposal");
-        return false;
-    }
-
     // Check signature for the proposal
     let pk = cx.pub_key_map.get(&new_block.header.author).unwrap();
-    if !pk.verify(&new_block.hash, &p.proof) {
+    if p.block_hash != new_block.hash ||
+        (!cx.pre_verified && !pk.verify(&p.sign_data(), &p.proof)) 
+    {
         log::warn!(
             "Got an incorrectly signed block");
         return false;
@@ -50,7 +46,6 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
         return true;
     }
     // Otherwise check if all the parent certificates are correctly signed
-    let mut uniq_votes = HashSet::with_capacity_and_hasher(cx.num_faults+1, Default::default());
     if let CertType::Vote(v, h) = &p.cert.msg {
         // Check if vote message is the same as that in the proposal
         if *h != new_block.header.prev {
@@ -68,25 +63,8 @@ pub fn check_proposal(p: Arc<Propose>, cx:&Context) -> bool {
     }
 
     log::debug!("Checking certificate: {:?}", p.cert);
-    let data = util::io::to_bytes(&p.cert.msg);
-    for v in &p.cert.votes {
-        // check signature
-        let pk = match cx.pub_key_map.get(&v.origin) {
-            None => {
-                log::warn!("Invalid vote origin");
-                return false;
-            }
-            Some(x) => x,
-        };
-        if !pk.verify(&data, &v.auth) {
-            log::warn!("Invalid vote signature: {:?}", v);
-            return false;
-        }
-        // Add this unique vote
-        uniq_votes.insert(v.origin);
-    }
-    if uniq_votes.len() < cx.num_faults {
-        log::warn!("Insufficient unique votes in certificate: {:?}", p.cert);
+    if let Err(e) = cx.verify_cert(&p.cert, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid certificate in the proposal: {}", e);
         return false;
     }
     // Is it extending the last known parent?
@@ -131,6 +109,12 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
         // TODO: Request and Deliver blocks
         return decision;
     }
+    // Do not vote for a second block at this height, which we may have done
+    // before a restart
+    if !cx.on_sign(SignKind::Vote, new_block.header.height, &new_block.hash) {
+        log::warn!("Already voted at height {} in view {}", new_block.header.height, cx.view);
+        return decision;
+    }
 
     cx.phase = Phase::CollectVote;
 
@@ -195,7 +179,7 @@ pub async fn on_new_valid_proposal(p: Arc<Propose>, cx: &mut Context) -> bool {
 
 pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Propose> {
     // Build the proposal
-    let parent = &cx.last_seen_block;
+    let parent = cx.last_seen_block.clone();
     let mut new_block = Block::with_tx(txs);
 
     // Update block contents here
@@ -205,15 +189,11 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
     
     // Update the hash at the end
     new_block.hash = new_block.compute_hash();
+    // The reactor checked that we did not propose at this height yet
+    if !cx.on_sign(SignKind::Proposal, new_block.header.height, &new_block.hash) {
+        panic!("Failed to record the proposal at height {} in the WAL", new_block.header.height);
+    }
     
-    // Sign the block hash 
-    let proof = match cx.my_secret_key.sign(&new_block.hash) {
-        Err(e) => {
-            panic!("Failed to sign the new proposal: {}", e);
-        },
-        Ok(sig) => sig,
-    };
-
     let mut new_block_cert = Certificate::empty_cert();
     new_block_cert.msg = CertType::Vote(cx.view, new_block.hash);
     let sign_data = util::io::to_bytes(&new_block_cert.msg);
@@ -236,7 +216,6 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
     // The block is ready, build proposal
     let new_block_ref = Arc::new(new_block.clone());
     let mut p = Propose::new();
-    p.proof = proof;
     p.block = Some(new_block_ref.clone());
     p.block_hash = new_block_ref.hash;
     p.cert = match cx.cert_map.get(&parent.hash) {
@@ -247,6 +226,13 @@ pub async fn do_propose(txs: Vec<Arc<Transaction>>, cx: &mut Context) -> Arc<Pro
     };
     log::debug!("Proposing with view: {}", cx.view);
     p.view = cx.view;
+    // Sign the block hash in this view
+    p.proof = match cx.my_secret_key.sign(&p.sign_data()) {
+        Err(e) => {
+            panic!("Failed to sign the new proposal: {}", e);
+        },
+        Ok(sig) => sig,
+    };
 
     // Ship the proposal
     let ship = cx.net_send.clone();
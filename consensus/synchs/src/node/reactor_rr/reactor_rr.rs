@@ -9,6 +9,7 @@ use tokio::sync::mpsc::{
 };
 use types::synchs_rr::{Replica, Transaction, ClientMsg, ProtocolMsg};
 use config::Node;
+use consensus::{CommitSink, SignKind};
 use super::{commit::on_commit, context::Context, phase::Phase, proposal::*, status::{do_status, on_recv_status}, vote::*};
 use tokio_stream::StreamExt;
 use std::{sync::Arc, time::Duration};
@@ -18,11 +19,18 @@ pub async fn reactor_rr(
     net_send: UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
     mut net_recv: UnboundedReceiver<(Replica, ProtocolMsg)>,
     cli_send: UnboundedSender<Arc<ClientMsg>>,
+    commits: CommitSink,
     mut cli_recv: UnboundedReceiver<Transaction>
 ) {
     let d2 = std::time::Duration::from_millis(2*config.delta);
     log::debug!("Started timers");
-    let mut cx = Context::new(config, net_send, cli_send);
+    let mut cx = Context::new(config, net_send, cli_send, commits);
+    // Check the signatures on a pool of workers before they reach the reactor
+    if config.verify_workers > 0 {
+        let pk_map = cx.pub_key_map.clone();
+        net_recv = consensus::start_verifier(net_recv, config.verify_workers,
+            move |_, m: &ProtocolMsg| m.verify_sigs(&pk_map));
+    }
     // Start event loop
     loop {
         tokio::select! {
@@ -124,11 +132,12 @@ async fn try_propose(c: &Node, cx: &mut Context, d2: &Duration) {
     let am_i_leader = cx.next_leader() == c.id;
     let is_no_cert = cx.cert_map.contains_key(&cx.last_seen_block.hash);
     let is_wrong_phase = cx.phase == Phase::Propose;
+    let is_not_signed = cx.may_sign(SignKind::Proposal, cx.last_seen_block.header.height+1);
 
     // log::debug!("Not proposing because: {} {} {} {}", is_pool_suff, am_i_leader, is_no_cert, is_wrong_phase);
 
     if is_pool_suff && am_i_leader && 
-        is_no_cert && is_wrong_phase
+        is_no_cert && is_wrong_phase && is_not_signed
     {
         log::debug!("I {} am the leader and, I am proposing", cx.myid);
         let txs = cx.storage.cleave(c.block_size);
@@ -1,5 +1,5 @@
-use types::synchs_rr::{CertType, Certificate, Replica, ProtocolMsg};
-use std::{collections::HashSet, sync::Arc};
+use types::{CertKind, Threshold, synchs_rr::{CertType, Certificate, Replica, ProtocolMsg}};
+use std::sync::Arc;
 use super::{context::Context, phase::Phase};
 
 /// We have decided to switch the view
@@ -32,44 +32,18 @@ pub async fn on_recv_status(cert: Certificate, cx: &mut Context) {
     // Check if the certificate is valid
     // We may seen a few votes from different nodes, but we are not sure if all
     // of the votes in this are valid
-    let (sign_data, block_hash) = match &cert.msg {
-        CertType::Vote(ref _v, ref h) => (util::io::to_bytes(&cert.msg),*h),
+    let block_hash = match &cert.msg {
+        CertType::Vote(ref _v, ref h) => *h,
         _ => panic!("Quit view code unreachable"),
     };
 
-    if cert.votes.len() <= cx.num_faults {
-        log::debug!("Insufficient votes in a quit view certificate");
+    // The certificate must have f+1 correctly signed votes from distinct
+    // nodes
+    if let Err(e) = cx.verify_cert(&cert, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid status certificate: {}", e);
         return;
     }
 
-    let mut unique_votes = HashSet::new();
-    for vote in &cert.votes {
-        if cx.vote_map.contains_key(&block_hash) {
-            continue;
-        }
-        let pk = match cx.pub_key_map.get(&vote.origin) {
-            None => {
-                log::warn!("vote from an unknown origin");
-                return;
-            },
-            Some(x) => x,
-        };
-        if !pk.verify(&sign_data, &vote.auth) {
-            log::warn!("quit view vote not correctly signed");
-            return;
-        }
-        if unique_votes.contains(&vote.origin) {
-            log::debug!("Duplicate vote in a quit view certificate");
-            return;
-        } else {
-            unique_votes.insert(vote.origin);
-        }
-    }
-    // The certificate:
-    // 1) Contain f+1 signatures
-    // 2) Contain f+1 valid votes
-    // 3) Contain f+1 unique votes
-
     let block = cx.storage.delivered_block_from_hash(&block_hash).unwrap();
     if block.header.height > cx.last_seen_block.header.height {
         cx.last_seen_cert = Arc::new(cert);
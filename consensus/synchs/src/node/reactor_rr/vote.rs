@@ -1,10 +1,10 @@
-use types::synchs_rr::{CertType, Certificate, View, Propose, ProtocolMsg};
+use types::{CertKind, Threshold, synchs_rr::{CertType, Certificate, View, Propose, ProtocolMsg}};
 use crypto::hash::Hash;
 use super::{context::Context, phase::Phase, proposal::{
         on_receive_proposal
     }};
 use std::sync::Arc;
-use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+use fnv::FnvHashMap as HashMap;
 
 pub async fn add_vote(c: Certificate, hash: Hash, cx: &mut Context) {
     debug_assert!(c.votes.len() == 1);
@@ -29,13 +29,13 @@ pub async fn add_vote(c: Certificate, hash: Hash, cx: &mut Context) {
     log::debug!("Not first vote: {:?}", c);
     cert_map.insert(c.votes[0].origin, c);
     // Promote it to a full certificate if it has f+1 signatures
-    if cert_map.len() > cx.num_faults {
+    if cx.quorum.is_met(Threshold::Honest, cert_map.keys().cloned()) {
         let mut new_cert = Certificate::empty_cert();
         for (_o,mut v) in cert_map {
             new_cert.votes.push(v.votes.pop().unwrap());
             new_cert.msg = v.msg;
         }
-        let cert_arc = Arc::new(new_cert);
+        let cert_arc = Arc::new(cx.finalize_cert(new_cert));
         log::debug!("Promoting and updating last seen certificate {:?}", cert_arc);
 
         cx.cert_map.insert(hash, cert_arc.clone());
@@ -56,41 +56,18 @@ pub async fn on_recv_quit_view(v: View, cert: Certificate, cx: &mut Context) {
 
     // We may seen a few votes from different nodes, but we are not sure if all
     // of the votes in this are valid
-    let (sign_data, block_hash) = match &cert.msg {
-        CertType::Vote(ref _v, ref h) => (util::io::to_bytes(&cert.msg),*h),
+    let block_hash = match &cert.msg {
+        CertType::Vote(ref _v, ref h) => *h,
         _ => panic!("Quit view code unreachable"),
     };
 
-    if cert.votes.len() <= cx.num_faults {
-        log::debug!("Insufficient votes in a quit view certificate");
+    // The certificate must have f+1 correctly signed votes from distinct
+    // nodes
+    if let Err(e) = cx.verify_cert(&cert, CertKind::Vote, Threshold::Honest) {
+        log::warn!("Invalid quit view certificate: {}", e);
         return;
     }
 
-    let mut unique_votes = HashSet::default();
-    for vote in &cert.votes {
-        let pk = match cx.pub_key_map.get(&vote.origin) {
-            None => {
-                log::warn!("vote from an unknown origin");
-                return;
-            },
-            Some(x) => x,
-        };
-        if !pk.verify(&sign_data, &vote.auth) {
-            log::warn!("quit view vote not correctly signed");
-            return;
-        }
-        if unique_votes.contains(&vote.origin) {
-            log::debug!("Duplicate vote in a quit view certificate");
-            return;
-        } else {
-            unique_votes.insert(vote.origin);
-        }
-    }
-    // The certificate:
-    // 1) Contain f+1 signatures
-    // 2) Contain f+1 valid votes
-    // 3) Contain f+1 unique votes
-
     // Update the last seen cert, if this is the first time we are observing this certificate
     let cert_arc = Arc::new(cert);
     if !cx.cert_map.contains_key(&block_hash) {
@@ -121,23 +98,14 @@ pub async fn on_vote(c: Certificate, mut p: Propose, cx: &mut Context) -> bool {
     let decision = false;
     log::debug!("Received a vote message: {:?}", c);
 
-    if c.votes.len() != 1 {
-        log::warn!(
-            "Invalid number of votes in vote message");
-        return false;
-    }
     // Check if we have already processed the block for which we have the vote
     // and if not check if it is valid
-    let vote = &c.votes[0];
-    let pk = match cx.pub_key_map.get(&vote.origin) {
-        None => {
-            log::warn!("vote from an unknown origin");
-            return decision;
-        },
-        Some(x) => x,
-    };
-    let (sign_data, blk_hash) = match &c.msg {
-        CertType::Vote(_v, d) => (util::io::to_bytes(&c.msg), *d),
+    if let Err(e) = cx.verify_vote(&c, CertKind::Vote) {
+        log::warn!("Invalid vote message: {}", e);
+        return decision;
+    }
+    let blk_hash = match &c.msg {
+        CertType::Vote(_v, d) => *d,
         _ => unreachable!("other vote types cant be here"),
     };
 
@@ -146,11 +114,6 @@ pub async fn on_vote(c: Certificate, mut p: Propose, cx: &mut Context) -> bool {
         return decision;
     }
 
-    if !pk.verify(&sign_data, &vote.auth) {
-        log::warn!("vote not correctly signed");
-        return decision;
-    }
-
     if !cx.storage.is_delivered_by_hash(&blk_hash) {
         log::debug!(
             "Received vote for an undelivered block");
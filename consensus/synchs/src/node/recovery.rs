@@ -0,0 +1,129 @@
+/// Recovery of a node that restarted, or that fell behind
+///
+/// The node keeps everything it signs in a WAL in the state directory of its
+/// config, so that it does not sign anything that conflicts with what it
+/// signed before the restart. After a restart, it asks the others for the chain that it missed and
+/// resumes from the highest certified block that they send.
+
+use types::{CertKind, EVERYONE, Threshold, synchs::{Block, CertType, Certificate, Height, ProtocolMsg, Replica, View}};
+use super::{commit::commit_block, context::Context, phase::Phase};
+use std::sync::Arc;
+
+/// The most blocks that we send in a sync response
+const MAX_SYNC_BLOCKS: usize = 1000;
+
+/// Ask the others for the chain above our last block, unless we already did
+pub fn request_sync(cx: &mut Context) {
+    let from = cx.last_seen_block.header.height;
+    if cx.sync_requested == Some(from) {
+        return;
+    }
+    log::info!("Requesting the chain above height {}", from);
+    cx.sync_requested = Some(from);
+    if let Err(e) = cx.net_send.send((EVERYONE, Arc::new(ProtocolMsg::SyncRequest(from)))) {
+        log::warn!("Failed to send a sync request: {}", e);
+    }
+}
+
+/// Send `sender` the chain above `from` that ends with our highest certified
+/// block
+pub fn on_sync_request(sender: Replica, from: Height, cx: &Context) {
+    let tip = match &cx.last_seen_cert.msg {
+        CertType::Vote(_, h) => *h,
+        _ => return,
+    };
+    let mut blocks: Vec<_> = std::iter::successors(
+            cx.storage.delivered_block_from_hash(&tip),
+            |b| cx.storage.delivered_block_from_hash(&b.header.prev))
+        .take_while(|b| b.header.height > from)
+        .collect();
+    blocks.reverse();
+    // Send the lowest blocks first if there are too many, up to the last one
+    // that we have a certificate for
+    blocks.truncate(MAX_SYNC_BLOCKS);
+    let last = match blocks.iter().rposition(|b| cx.cert_map.contains_key(&b.hash)) {
+        None => return,
+        Some(x) => x,
+    };
+    blocks.truncate(last+1);
+    let cert = cx.cert_map[&blocks[last].hash].clone();
+    log::debug!("Sending {} blocks above height {} to {}", blocks.len(), from, sender);
+    let blocks = blocks.iter().map(|b| b.as_ref().clone()).collect();
+    if let Err(e) = cx.net_send.send((sender, Arc::new(ProtocolMsg::SyncResponse(blocks, cert)))) {
+        log::warn!("Failed to send a sync response: {}", e);
+    }
+}
+
+/// Check that `blocks` extend a block that we have, one height at a time,
+/// and that `cert` certifies the last one
+fn check_sync(blocks: &[Block], cert: &Certificate, cx: &Context) -> bool {
+    let (first, last) = match (blocks.first(), blocks.last()) {
+        (Some(f), Some(l)) => (f, l),
+        _ => return false,
+    };
+    let parent = match cx.storage.delivered_block_from_hash(&first.header.prev) {
+        None => return false,
+        Some(p) => p,
+    };
+    let is_chain = first.header.height == parent.header.height+1 &&
+        blocks.windows(2).all(|w| {
+            w[1].header.prev == w[0].hash && w[1].header.height == w[0].header.height+1
+        });
+    matches!(cert.msg, CertType::Vote(_, h) if h == last.hash) &&
+        is_chain &&
+        cx.verify_cert(cert, CertKind::Vote, Threshold::Honest).is_ok()
+}
+
+/// Deliver the chain that another node sent us, commit the blocks below its
+/// certified tip, and resume from the tip
+pub async fn on_sync_response(sender: Replica, blocks: Vec<Block>, cert: Certificate, cx: &mut Context) {
+    let view = match cert.msg {
+        CertType::Vote(v, _) => v,
+        _ => return,
+    };
+    let ahead = blocks.last()
+        .map(|b| b.header.height > cx.last_seen_block.header.height)
+        .unwrap_or(false);
+    if !ahead || cx.storage.is_delivered_by_hash(&blocks[blocks.len()-1].hash) {
+        log::debug!("Stale sync response from {}", sender);
+        return;
+    }
+    if !check_sync(&blocks, &cert, cx) {
+        log::warn!("Invalid sync response from {}", sender);
+        return;
+    }
+    let full = blocks.len() == MAX_SYNC_BLOCKS;
+    let blocks: Vec<_> = blocks.into_iter().map(Arc::new).collect();
+    for b in &blocks {
+        cx.storage.add_delivered_block(b.clone());
+        cx.storage.clear(&b.body.tx_hashes);
+        cx.view_of.insert(b.hash, view);
+    }
+    let tip = blocks[blocks.len()-1].clone();
+    if blocks.len() > 1 {
+        commit_block(blocks[blocks.len()-2].clone(), cx);
+    }
+    cx.cert_map.insert(tip.hash, cert.clone());
+    cx.height = tip.header.height;
+    cx.last_seen_block = tip;
+    cx.update_high_cert(&cert);
+    if view > cx.view {
+        enter_view(view, cx);
+    }
+    log::info!("Caught up to height {} in view {} with {}", cx.height, cx.view, sender);
+    cx.sync_requested = None;
+    if full {
+        request_sync(cx);
+    }
+}
+
+/// Join `view`, which the others are already in
+pub fn enter_view(view: View, cx: &mut Context) {
+    cx.set_view(view);
+    cx.blamed = false;
+    cx.equivocated = false;
+    cx.proposal_map.clear();
+    cx.commit_queue.clear();
+    cx.phase = Phase::Normal;
+    cx.start_timer(std::time::Duration::ZERO);
+}
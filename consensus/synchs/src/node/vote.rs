@@ -1,7 +1,9 @@
-use types::synchs::{CertType, Certificate, Propose};
+use types::{CertKind, Threshold, synchs::{CertType, Certificate, Propose}};
 use crypto::hash::Hash;
 use super::{
+    blame::check_equivocation,
     context::Context, 
+    phase::Phase,
     proposal::{
         on_receive_proposal
     }
@@ -21,12 +23,19 @@ pub fn add_vote(mut c: Certificate, hash: Hash, cx: &mut Context) {
         },
         Some(cert) => cert,
     };
-    // Add the vote to the certificate
-    cert.votes.push(c.votes.pop().unwrap());
+    // Add the vote to the certificate, once per node
+    let vote = c.votes.pop().unwrap();
+    if cert.votes.iter().any(|v| v.origin == vote.origin) {
+        log::debug!("Duplicate vote from {}", vote.origin);
+        cx.vote_map.insert(hash, cert);
+        return;
+    }
+    cert.votes.push(vote);
     // Promote it to a full certificate if it has f+1 signatures
-    if cert.votes.len() > cx.num_faults {
+    if cx.meets(Threshold::Honest, &cert) {
+        let cert = cx.finalize_cert(cert);
         cx.cert_map.insert(hash, cert.clone());
-        cx.last_seen_cert = cert;
+        cx.update_high_cert(&cert);
     } else {
         cx.vote_map.insert(hash, cert);
     }
@@ -37,23 +46,18 @@ pub async fn on_vote(c: Certificate, mut p: Propose, cx: &mut Context) -> bool {
     log::debug!(
         "Received a vote message: {:?}", c);
 
-    if c.votes.len() != 1 {
-        log::warn!(
-            "Invalid number of votes in vote message");
-        return false;
-    }
     // Check if we have already processed the block for which we have the vote
     // and if not check if it is valid
-    let vote = &c.votes[0];
-    let pk = match cx.pub_key_map.get(&vote.origin) {
-        None => {
-            log::warn!("vote from an unknown origin");
+    if let Err(e) = cx.verify_vote(&c, CertKind::Vote) {
+        log::warn!("Invalid vote message: {}", e);
+        return decision;
+    }
+    let blk_hash = match &c.msg {
+        CertType::Vote(v, d) if *v == p.view => *d,
+        CertType::Vote(_, _) => {
+            log::warn!("Vote for a proposal of another view");
             return decision;
-        },
-        Some(x) => x,
-    };
-    let (sign_data, blk_hash) = match &c.msg {
-        CertType::Vote(_v, d) => (util::io::to_bytes(&c.msg), *d),
+        }
         _ => unreachable!("other vote types cant be here"),
     };
 
@@ -62,14 +66,10 @@ pub async fn on_vote(c: Certificate, mut p: Propose, cx: &mut Context) -> bool {
         return decision;
     }
 
-    if !pk.verify(&sign_data, &vote.auth) {
-        log::warn!("vote not correctly signed");
-        return decision;
-    }
-
     if !cx.storage.is_delivered_by_hash(&blk_hash) {
         log::debug!(
             "Received vote for an undelivered block");
+        on_undelivered_vote(Arc::new(p), cx).await;
         return decision;
     }
 
@@ -100,4 +100,24 @@ pub async fn on_vote(c: Certificate, mut p: Propose, cx: &mut Context) -> bool {
     // Let the reactor know that we have to start the commit timers for this
     // block, if this is a new proposal
     return on_receive_proposal(Arc::new(p), cx).await;
-}
\ No newline at end of file
+}
+
+/// The vote from another node is the only way to hear about the proposals
+/// that the leader did not send us. We check them for equivocations.
+async fn on_undelivered_vote(p: Arc<Propose>, cx: &mut Context) {
+    if p.view != cx.view {
+        return;
+    }
+    let is_signed = match cx.pub_key_map.get(&cx.leader_of_view()) {
+        None => false,
+        Some(pk) => pk.verify(&p.sign_data(), &p.proof),
+    };
+    if !is_signed {
+        log::warn!("Vote for a proposal that is not signed by the leader");
+        return;
+    }
+    let is_current = matches!(cx.phase, Phase::Normal | Phase::Wait);
+    if is_current && !cx.equivocated {
+        check_equivocation(&p, cx).await;
+    }
+}
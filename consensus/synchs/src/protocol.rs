@@ -0,0 +1,122 @@
+use config::Node;
+use consensus::{NodeOptions, Reactor};
+use net::tokio_manager::PeerChange;
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use types::synchs::{ClientMsg, Replica, ProtocolMsg, Transaction};
+use std::sync::Arc;
+
+type Channels = (
+    UnboundedSender<(Replica, Arc<ProtocolMsg>)>,
+    UnboundedReceiver<(Replica, ProtocolMsg)>,
+    UnboundedSender<Arc<ClientMsg>>,
+    UnboundedReceiver<Transaction>,
+    UnboundedSender<PeerChange>,
+);
+
+/// Connect to the other nodes and to the clients, for both variants of Sync
+/// HotStuff
+fn setup(
+    config: &Node,
+    local_txs: UnboundedReceiver<Transaction>,
+    prot_net_rt: &Runtime,
+    cli_net_rt: &Runtime,
+) -> Channels {
+    // Setup client network
+    let client_network = net::tokio_manager::Protocol::<Transaction, ClientMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+    let (cli_send, cli_recv) = 
+    cli_net_rt.block_on(
+        client_network.client_setup(
+            config.client_ip(),
+            util::codec::EnCodec::new(),
+            util::codec::Decodec::new()
+        )
+    );
+    let cli_recv = consensus::merge_local_txs(cli_net_rt, cli_recv, local_txs);
+
+    // Setup the protocol network
+    let protocol_network = net::tokio_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
+    let (net_send, net_recv, peers) = 
+    prot_net_rt.block_on(
+        protocol_network.reconfigurable_server_setup(
+            config.net_map.clone(), 
+            util::codec::EnCodec::new(), 
+            util::codec::Decodec::new()
+        )
+    );
+
+    // Measure the delay to the other nodes before starting
+    let (net_recv, _delays) = consensus::start_delay_monitor(config, prot_net_rt, net_send.clone(), net_recv);
+    // Misbehave if the config asks for it
+    let net_send = consensus::start_adversary(config, prot_net_rt, net_send);
+    (net_send, net_recv, cli_send, cli_recv, peers)
+}
+
+/// The Sync HotStuff protocol, for `consensus::run`
+pub struct SyncHotStuff;
+
+impl consensus::Protocol for SyncHotStuff {
+    const NAME: &'static str = "synchs";
+    const FAULT_MODEL: types::FaultModel = types::FaultModel::Synchronous;
+
+    type ProtocolMsg = ProtocolMsg;
+    type ClientMsg = ClientMsg;
+    type Transaction = Transaction;
+
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor {
+        let (net_send, net_recv, cli_send, cli_recv, peers) = setup(config, local_txs, prot_net_rt, cli_net_rt);
+        let config = config.clone();
+        let commits = opts.commit_sink();
+        Box::pin(async move {
+            crate::node::reactor(
+                &config,
+                net_send,
+                net_recv,
+                cli_send,
+                commits,
+                cli_recv,
+                peers
+            ).await
+        })
+    }
+}
+
+/// The round-robin variant of Sync HotStuff, for `consensus::run`
+pub struct SyncHotStuffRR;
+
+impl consensus::Protocol for SyncHotStuffRR {
+    const NAME: &'static str = "synchs-rr";
+    const FAULT_MODEL: types::FaultModel = types::FaultModel::Synchronous;
+
+    type ProtocolMsg = ProtocolMsg;
+    type ClientMsg = ClientMsg;
+    type Transaction = Transaction;
+
+    fn start(
+        config: &Node,
+        opts: &NodeOptions,
+        local_txs: UnboundedReceiver<Transaction>,
+        prot_net_rt: &Runtime,
+        cli_net_rt: &Runtime,
+    ) -> Reactor {
+        let (net_send, net_recv, cli_send, cli_recv, _peers) = setup(config, local_txs, prot_net_rt, cli_net_rt);
+        let config = config.clone();
+        let commits = opts.commit_sink();
+        Box::pin(async move {
+            crate::node::reactor_rr(
+                &config,
+                net_send,
+                net_recv,
+                cli_send,
+                commits,
+                cli_recv
+            ).await
+        })
+    }
+}
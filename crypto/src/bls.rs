@@ -0,0 +1,233 @@
+//! BLS12-381 keys with aggregatable signatures.
+//!
+//! Public keys live in G1 (48 bytes compressed) and signatures in G2 (96
+//! bytes compressed). Signatures by different keys on the same message can be
+//! aggregated into one signature that is checked with a single pairing check.
+//!
+//! Aggregate verification uses the proof of possession ciphersuite, but no
+//! proofs of possession are made or checked here. It is therefore only safe if
+//! every public key in the config is known to belong to its owner. A node that
+//! picks its key after seeing the others' (a rogue key) can forge aggregate
+//! signatures that seem to include honest nodes. So only use BLS with configs
+//! from a trusted source, e.g. all generated by one run of `genconfig`, and
+//! never with keys submitted by the nodes themselves.
+
+use blst::min_pk as bls;
+use blst::BLST_ERROR;
+use rand::RngCore;
+use super::error::{DecodingError, SigningError};
+use zeroize::Zeroize;
+use core::fmt;
+
+/// The domain separation tag for the proof of possession scheme
+const DST: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// A BLS keypair.
+#[derive(Clone)]
+pub struct Keypair {
+    secret: SecretKey,
+    public: PublicKey,
+}
+
+impl Keypair {
+    /// Generate a new BLS keypair.
+    pub fn generate() -> Keypair {
+        Keypair::from(SecretKey::generate())
+    }
+
+    /// Encode the keypair as the bytes of its secret key. The public key is
+    /// derived again when decoding.
+    pub fn encode(&self) -> [u8; 32] {
+        self.secret.to_bytes()
+    }
+
+    /// Decode a keypair from the format produced by `encode`,
+    /// zeroing the input on success.
+    pub fn decode(kp: &mut [u8]) -> Result<Keypair, DecodingError> {
+        SecretKey::from_bytes(kp).map(Keypair::from)
+    }
+
+    /// Sign a message using the private key of this keypair.
+    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        self.secret.0.sign(msg, DST, &[]).compress().to_vec()
+    }
+
+    /// Get the public key of this keypair.
+    pub fn public(&self) -> PublicKey {
+        self.public.clone()
+    }
+
+    /// Get the secret key of this keypair.
+    pub fn secret(&self) -> &SecretKey {
+        &self.secret
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keypair").field("public", &self.public).finish()
+    }
+}
+
+/// Promote a BLS secret key into a keypair.
+impl From<SecretKey> for Keypair {
+    fn from(sk: SecretKey) -> Keypair {
+        let public = PublicKey(sk.0.sk_to_pk());
+        Keypair { secret: sk, public }
+    }
+}
+
+/// A BLS public key.
+#[derive(PartialEq, Eq, Clone)]
+pub struct PublicKey(bls::PublicKey);
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PublicKey(compressed): ")?;
+        for byte in self.0.compress().iter() {
+            write!(f, "{:x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl PublicKey {
+    /// Verify the BLS signature on a message using the public key.
+    pub fn verify(&self, msg: &[u8], sig: &[u8]) -> bool {
+        match bls::Signature::uncompress(sig) {
+            Ok(s) => s.verify(true, msg, DST, &[], &self.0, false) == BLST_ERROR::BLST_SUCCESS,
+            Err(_) => false,
+        }
+    }
+
+    /// Verify an aggregate signature, as produced by `aggregate`, on a message
+    /// signed by all of `pks`.
+    pub fn verify_aggregate(pks: &[&PublicKey], msg: &[u8], sig: &[u8]) -> bool {
+        if pks.is_empty() {
+            return false;
+        }
+        let sig = match bls::Signature::uncompress(sig) {
+            Ok(s) => s,
+            Err(_) => return false,
+        };
+        let pks: Vec<&bls::PublicKey> = pks.iter().map(|pk| &pk.0).collect();
+        sig.fast_aggregate_verify(true, msg, DST, &pks) == BLST_ERROR::BLST_SUCCESS
+    }
+
+    /// Encode the public key into a byte array in compressed form.
+    pub fn encode(&self) -> [u8; 48] {
+        self.0.compress()
+    }
+
+    /// Decode a public key from a byte array as produced by `encode`. The key
+    /// is also checked to be in the correct subgroup.
+    pub fn decode(k: &[u8]) -> Result<PublicKey, DecodingError> {
+        bls::PublicKey::key_validate(k)
+            .map_err(|e| DecodingError::new(format!("BLS public key: {:?}", e)))
+            .map(PublicKey)
+    }
+}
+
+/// Aggregate the signatures of several keys on the same message into one
+/// signature.
+pub fn aggregate(sigs: &[&[u8]]) -> Result<Vec<u8>, SigningError> {
+    let mut parsed = Vec::with_capacity(sigs.len());
+    for s in sigs {
+        let sig = bls::Signature::uncompress(s)
+            .map_err(|e| SigningError::new(format!("BLS signature: {:?}", e)))?;
+        parsed.push(sig);
+    }
+    let refs: Vec<&bls::Signature> = parsed.iter().collect();
+    bls::AggregateSignature::aggregate(&refs, true)
+        .map(|agg| agg.to_signature().compress().to_vec())
+        .map_err(|e| SigningError::new(format!("BLS aggregation: {:?}", e)))
+}
+
+/// A BLS secret key.
+#[derive(Clone)]
+pub struct SecretKey(bls::SecretKey);
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey")
+    }
+}
+
+impl SecretKey {
+    /// Generate a new BLS secret key.
+    pub fn generate() -> SecretKey {
+        let mut ikm = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut ikm);
+        let sk = bls::SecretKey::key_gen(&ikm, &[])
+            .expect("this returns `Err` only if the ikm is shorter than 32 bytes; qed");
+        ikm.zeroize();
+        SecretKey(sk)
+    }
+
+    /// Create a BLS secret key from a byte slice, zeroing the input on
+    /// success.
+    pub fn from_bytes(mut sk_bytes: impl AsMut<[u8]>) -> Result<SecretKey, DecodingError> {
+        let sk_bytes = sk_bytes.as_mut();
+        let secret = bls::SecretKey::from_bytes(&*sk_bytes)
+            .map_err(|e| DecodingError::new(format!("BLS secret key: {:?}", e)))?;
+        sk_bytes.zeroize();
+        Ok(SecretKey(secret))
+    }
+
+    /// Encode the secret key into a byte array.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::*;
+
+    #[test]
+    fn bls_keypair_encode_decode() {
+        fn prop() -> bool {
+            let kp1 = Keypair::generate();
+            let mut kp1_enc = kp1.encode();
+            let kp2 = Keypair::decode(&mut kp1_enc).unwrap();
+            kp1.public() == kp2.public()
+                &&
+            kp1_enc.iter().all(|b| *b == 0)
+        }
+        QuickCheck::new().tests(10).quickcheck(prop as fn() -> _);
+    }
+
+    #[test]
+    fn bls_signature() {
+        let kp = Keypair::generate();
+        let pk = kp.public();
+        let msg = "hello world".as_bytes();
+        let sig = kp.sign(msg);
+        assert!(pk.verify(msg, &sig));
+
+        let mut invalid_sig = sig.clone();
+        invalid_sig[3..6].copy_from_slice(&[10, 23, 42]);
+        assert!(!pk.verify(msg, &invalid_sig));
+
+        let invalid_msg = "h3ll0 w0rld".as_bytes();
+        assert!(!pk.verify(invalid_msg, &sig));
+    }
+
+    #[test]
+    fn bls_aggregate() {
+        let kps: Vec<Keypair> = (0..4).map(|_| Keypair::generate()).collect();
+        let msg = "hello world".as_bytes();
+        let sigs: Vec<Vec<u8>> = kps.iter().map(|kp| kp.sign(msg)).collect();
+        let sig_refs: Vec<&[u8]> = sigs.iter().map(|s| s.as_slice()).collect();
+        let agg = aggregate(&sig_refs).unwrap();
+
+        let pks: Vec<PublicKey> = kps.iter().map(|kp| kp.public()).collect();
+        let pk_refs: Vec<&PublicKey> = pks.iter().collect();
+        assert!(PublicKey::verify_aggregate(&pk_refs, msg, &agg));
+        // Missing signer
+        assert!(!PublicKey::verify_aggregate(&pk_refs[1..], msg, &agg));
+        // Wrong message
+        assert!(!PublicKey::verify_aggregate(&pk_refs, "h3ll0".as_bytes(), &agg));
+    }
+}
@@ -6,6 +6,7 @@ pub enum Algorithm {
     RSA,
     ED25519,
     SECP256K1,
+    BLS,
 }
 
 impl FromStr for Algorithm {
@@ -16,6 +17,7 @@ impl FromStr for Algorithm {
             "RSA" => Ok(Algorithm::RSA),
             "ED25519" => Ok(Algorithm::ED25519),
             "SECP256K1" => Ok(Algorithm::SECP256K1),
+            "BLS" => Ok(Algorithm::BLS),
             _ => Err("no match"),
         }
     }
@@ -31,9 +33,16 @@ impl FromStr for Algorithm {
 // let keypair = Keypair::rsa_from_pkcs8(&mut bytes);
 // pk = keypair.public()
 // pk.verify for verification
-pub const RSA_PK_SIZE:usize = 64;
+// use pk.encode_pkcs1() to serialize
+// use crypto::rsa::PublicKey::decode_pkcs1 to deserialize
 // Codec
 // keypair.sign()
+// The PKCS#8 encoding of the private key does not have a fixed size (it
+// depends on the leading zeros of the key components), so instead of a size
+// check, the secret key is validated by decoding it with
+// crypto::rsa::Keypair::from_pkcs8
+pub const RSA_BITS:usize = 2048;
+#[deprecated(note = "RSA secret keys have no fixed size; decode them with crypto::rsa::Keypair::from_pkcs8 to check them")]
 pub const RSA_PVT_SIZE:usize = 64;
 
 // Generate Keys 
@@ -58,4 +67,16 @@ pub const SECP256K1_PK_SIZE:usize = 33;
 // Codec
 // use self.to_bytes() to serialize
 // use crypto::secp256k1::SecretKey::from_bytes to deserialize
-pub const SECP256K1_PVT_SIZE:usize = 32;
\ No newline at end of file
+pub const SECP256K1_PVT_SIZE:usize = 32;
+
+// Generate Keys
+// use crypto::bls::Keypair::generate().{public(), secret()} to
+// generate keys
+// Codec
+// use self.encode() to serialize
+// use crypto::bls::PublicKey::decode to deserialize
+pub const BLS_PK_SIZE:usize = 48;
+// Codec
+// use self.to_bytes() to serialize
+// use crypto::bls::SecretKey::from_bytes to deserialize
+pub const BLS_PVT_SIZE:usize = 32;
\ No newline at end of file
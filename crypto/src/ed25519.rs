@@ -121,6 +121,21 @@ impl PublicKey {
         ed25519::Signature::try_from(sig).and_then(|s| self.0.verify(msg, &s)).is_ok()
     }
 
+    /// Verify many signatures at once, where `sigs[i]` is the signature of
+    /// `pks[i]` on `msgs[i]`. This is faster than checking them one by one,
+    /// but does not tell which signature is invalid.
+    pub fn verify_batch(pks: &[&PublicKey], msgs: &[&[u8]], sigs: &[&[u8]]) -> bool {
+        let mut parsed = Vec::with_capacity(sigs.len());
+        for sig in sigs {
+            match ed25519::Signature::try_from(*sig) {
+                Ok(s) => parsed.push(s),
+                Err(_) => return false,
+            }
+        }
+        let pks: Vec<ed25519::PublicKey> = pks.iter().map(|pk| pk.0).collect();
+        ed25519::verify_batch(msgs, &parsed, &pks).is_ok()
+    }
+
     /// Encode the public key into a byte array in compressed form, i.e.
     /// where one coordinate is represented by a single bit.
     pub fn encode(&self) -> [u8; 32] {
@@ -183,6 +198,7 @@ impl SecretKey {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::error::BatchError;
     use quickcheck::*;
 
     fn eq_keypairs(kp1: &Keypair, kp2: &Keypair) -> bool {
@@ -233,4 +249,24 @@ mod tests {
         let invalid_msg = "h3ll0 w0rld".as_bytes();
         assert!(!pk.verify(invalid_msg, &sig));
     }
+
+    #[test]
+    fn ed25519_batch_signature() {
+        let kps: Vec<Keypair> = (0..8).map(|_| Keypair::generate()).collect();
+        let msg = "hello world".as_bytes();
+        let mut sigs: Vec<Vec<u8>> = kps.iter().map(|kp| kp.sign(msg)).collect();
+        let pks: Vec<crate::PublicKey> = kps.iter()
+            .map(|kp| crate::PublicKey::Ed25519(kp.public()))
+            .collect();
+        let pk_refs: Vec<&crate::PublicKey> = pks.iter().collect();
+        let msgs = vec![msg; kps.len()];
+
+        let sig_refs: Vec<&[u8]> = sigs.iter().map(|s| s.as_slice()).collect();
+        assert_eq!(crate::PublicKey::verify_batch(&pk_refs, &msgs, &sig_refs), Ok(()));
+
+        sigs[5][3..6].copy_from_slice(&[10, 23, 42]);
+        let sig_refs: Vec<&[u8]> = sigs.iter().map(|s| s.as_slice()).collect();
+        assert_eq!(crate::PublicKey::verify_batch(&pk_refs, &msgs, &sig_refs), Err(BatchError::Invalid(5)));
+        assert_eq!(crate::PublicKey::verify_batch(&pk_refs, &msgs[1..], &sig_refs), Err(BatchError::LengthMismatch));
+    }
 }
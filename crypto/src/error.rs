@@ -82,3 +82,23 @@ impl Error for SigningError {
     }
 }
 
+
+/// An error during the verification of a batch of signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchError {
+    /// The keys, messages and signatures are not as many
+    LengthMismatch,
+    /// The signature at this index is invalid
+    Invalid(usize),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::LengthMismatch => write!(f, "Batch verification error: the keys, messages and signatures are not as many"),
+            BatchError::Invalid(i) => write!(f, "Batch verification error: invalid signature at index {}", i),
+        }
+    }
+}
+
+impl Error for BatchError {}
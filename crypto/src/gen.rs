@@ -29,7 +29,7 @@
 // pub mod error;
 
 use crate::error::*;
-use crate::{ed25519, rsa, secp256k1};
+use crate::{Algorithm, bls, ed25519, rsa, secp256k1};
 
 /// Identity keypair of a node.
 ///
@@ -55,7 +55,9 @@ pub enum Keypair {
     /// An RSA keypair.
     Rsa(rsa::Keypair),
     /// A Secp256k1 keypair.
-    Secp256k1(secp256k1::Keypair)
+    Secp256k1(secp256k1::Keypair),
+    /// A BLS keypair.
+    Bls(bls::Keypair),
 }
 
 impl Keypair {
@@ -69,6 +71,11 @@ impl Keypair {
         Keypair::Secp256k1(secp256k1::Keypair::generate())
     }
 
+    /// Generate a new BLS keypair.
+    pub fn generate_bls() -> Keypair {
+        Keypair::Bls(bls::Keypair::generate())
+    }
+
     /// Decode an keypair from a DER-encoded secret key in PKCS#8 PrivateKeyInfo
     /// format (i.e. unencrypted) as defined in [RFC5208].
     ///
@@ -93,7 +100,8 @@ impl Keypair {
         match self {
             Ed25519(ref pair) => Ok(pair.sign(msg)),
             Rsa(ref pair) => pair.sign(msg),
-            Secp256k1(ref pair) => pair.secret().sign(msg)
+            Secp256k1(ref pair) => pair.secret().sign(msg),
+            Bls(ref pair) => Ok(pair.sign(msg)),
         }
     }
 
@@ -104,6 +112,7 @@ impl Keypair {
             Ed25519(pair) => PublicKey::Ed25519(pair.public()),
             Rsa(pair) => PublicKey::Rsa(pair.public()),
             Secp256k1(pair) => PublicKey::Secp256k1(pair.public().clone()),
+            Bls(pair) => PublicKey::Bls(pair.public()),
         }
     }
 }
@@ -116,10 +125,24 @@ pub enum PublicKey {
     /// A public RSA key.
     Rsa(rsa::PublicKey),
     /// A public Secp256k1 key.
-    Secp256k1(secp256k1::PublicKey)
+    Secp256k1(secp256k1::PublicKey),
+    /// A public BLS key.
+    Bls(bls::PublicKey),
 }
 
 impl PublicKey {
+    /// Decode a public key of the algorithm `alg`, in the format of the
+    /// configs: as produced by `encode`, or PKCS#1 for RSA keys.
+    pub fn decode(alg: &Algorithm, k: &[u8]) -> Result<PublicKey, DecodingError> {
+        let pk = match alg {
+            Algorithm::ED25519 => PublicKey::Ed25519(ed25519::PublicKey::decode(k)?),
+            Algorithm::SECP256K1 => PublicKey::Secp256k1(secp256k1::PublicKey::decode(k)?),
+            Algorithm::BLS => PublicKey::Bls(bls::PublicKey::decode(k)?),
+            Algorithm::RSA => PublicKey::Rsa(rsa::PublicKey::decode_pkcs1(k)?),
+        };
+        Ok(pk)
+    }
+
     /// Verify a signature for a message using this public key, i.e. check
     /// that the signature has been produced by the corresponding
     /// private key (authenticity), and that the message has not been
@@ -129,8 +152,75 @@ impl PublicKey {
         match self {
             Ed25519(pk) => pk.verify(msg, sig),
             Rsa(pk) => pk.verify(msg, sig),
-            Secp256k1(pk) => pk.verify(msg, sig)
+            Secp256k1(pk) => pk.verify(msg, sig),
+            Bls(pk) => pk.verify(msg, sig),
+        }
+    }
+
+    /// Verify a batch of signatures, where `sigs[i]` is the signature of
+    /// `pks[i]` on `msgs[i]`. Ed25519 signatures are checked together, other
+    /// algorithms fall back to checking them one by one. On failure, returns
+    /// the index of an invalid signature, or `LengthMismatch` if the slices
+    /// are not as long.
+    pub fn verify_batch(pks: &[&PublicKey], msgs: &[&[u8]], sigs: &[&[u8]]) -> Result<(), BatchError> {
+        let len = pks.len();
+        if msgs.len() != len || sigs.len() != len {
+            return Err(BatchError::LengthMismatch);
+        }
+        let ed_pks: Option<Vec<&ed25519::PublicKey>> = pks.iter()
+            .map(|pk| match pk {
+                PublicKey::Ed25519(pk) => Some(pk),
+                _ => None,
+            })
+            .collect();
+        if let Some(ed_pks) = ed_pks {
+            if ed25519::PublicKey::verify_batch(&ed_pks, msgs, sigs) {
+                return Ok(());
+            }
+        }
+        // The batch failed (or cannot be batched), find the offending signature
+        for i in 0..len {
+            if !pks[i].verify(msgs[i], sigs[i]) {
+                return Err(BatchError::Invalid(i));
+            }
         }
+        Ok(())
+    }
+
+    /// Verify an aggregate signature for a message signed by all of `pks`.
+    /// Only BLS signatures can be aggregated, so this fails if any of the keys
+    /// is not a BLS key.
+    pub fn verify_aggregate(pks: &[&PublicKey], msg: &[u8], sig: &[u8]) -> bool {
+        let mut bls_pks = Vec::with_capacity(pks.len());
+        for pk in pks {
+            match pk {
+                PublicKey::Bls(pk) => bls_pks.push(pk),
+                _ => return false,
+            }
+        }
+        bls::PublicKey::verify_aggregate(&bls_pks, msg, sig)
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_key_decode() {
+        let ed = ed25519::Keypair::generate().public();
+        let pk = PublicKey::decode(&Algorithm::ED25519, &ed.encode()).unwrap();
+        assert_eq!(pk, PublicKey::Ed25519(ed));
+        let secp = secp256k1::Keypair::generate().public().clone();
+        let pk = PublicKey::decode(&Algorithm::SECP256K1, &secp.encode()).unwrap();
+        assert_eq!(pk, PublicKey::Secp256k1(secp));
+        let bls = bls::Keypair::generate().public();
+        let pk = PublicKey::decode(&Algorithm::BLS, &bls.encode()).unwrap();
+        assert_eq!(pk, PublicKey::Bls(bls.clone()));
+
+        // The key of another algorithm
+        assert!(PublicKey::decode(&Algorithm::ED25519, &bls.encode()).is_err());
+        assert!(PublicKey::decode(&Algorithm::RSA, &[0u8; 32]).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use sha2::{Digest, Sha256};
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
+use std::str::FromStr;
+use std::sync::OnceLock;
 
 pub const HASH_SIZE:usize = 32;
 
@@ -7,12 +9,89 @@ pub type Hash = [u8; HASH_SIZE];
 
 pub const EMPTY_HASH:Hash = [0 as u8; 32];
 
+/// The hash function used for all the blocks, transactions and proposals of a
+/// chain. Both produce a `HASH_SIZE` output, so `Hash` and `EMPTY_HASH` are
+/// the same for either choice.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum HashAlgorithm {
+    #[default]
+    SHA256,
+    BLAKE3,
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "SHA256" => Ok(HashAlgorithm::SHA256),
+            "BLAKE3" => Ok(HashAlgorithm::BLAKE3),
+            _ => Err("no match"),
+        }
+    }
+}
+
+/// The hash function used by `do_hash`. It is a chain-wide parameter, so it
+/// is fixed once per process: by `set_hash_algorithm`, or by the first hash
+/// that is computed, which uses the default.
+static HASH_ALG: OnceLock<HashAlgorithm> = OnceLock::new();
+
+/// Set the hash function used by `do_hash` and `ser_and_hash`.
+///
+/// This must be called before any hash is computed, and every node and client
+/// of a chain must use the same function. Setting the function in use again
+/// is fine; a different one is rejected with the function in use, as the
+/// hashes computed so far would not match.
+pub fn set_hash_algorithm(alg: HashAlgorithm) -> Result<(), HashAlgorithm> {
+    let current = *HASH_ALG.get_or_init(|| alg);
+    if current == alg {
+        Ok(())
+    } else {
+        Err(current)
+    }
+}
+
+/// Get the hash function used by `do_hash`
+pub fn hash_algorithm() -> HashAlgorithm {
+    *HASH_ALG.get_or_init(HashAlgorithm::default)
+}
+
+/// Hash the bytes with a specific hash function
+pub fn do_hash_with(alg: HashAlgorithm, bytes: &[u8]) -> Hash {
+    match alg {
+        HashAlgorithm::SHA256 => Sha256::digest(bytes).into(),
+        HashAlgorithm::BLAKE3 => blake3::hash(bytes).into(),
+    }
+}
+
 pub fn do_hash(bytes: &[u8]) -> Hash {
-    let hash = Sha256::digest(bytes);
-    return hash.into();
-} 
+    do_hash_with(hash_algorithm(), bytes)
+}
 
 pub fn ser_and_hash(obj: &impl Serialize) -> Hash {
     let serialized_bytes = bincode::serialize(obj).unwrap();
     return do_hash(&serialized_bytes);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hash_algorithms() {
+        let sha = do_hash_with(HashAlgorithm::SHA256, b"abc");
+        assert_eq!(sha[..4], [0xba, 0x78, 0x16, 0xbf]);
+        let blake = do_hash_with(HashAlgorithm::BLAKE3, b"abc");
+        assert_eq!(blake[..4], [0x64, 0x37, 0xb3, 0xac]);
+        assert_eq!(do_hash(b"abc"), sha);
+    }
+
+    #[test]
+    fn hash_algorithm_is_set_once() {
+        // The first hash fixes the default for the rest of the process
+        assert_eq!(do_hash(b"abc"), do_hash_with(HashAlgorithm::SHA256, b"abc"));
+        assert_eq!(set_hash_algorithm(HashAlgorithm::SHA256), Ok(()));
+        assert_eq!(set_hash_algorithm(HashAlgorithm::BLAKE3), Err(HashAlgorithm::SHA256));
+        assert_eq!(hash_algorithm(), HashAlgorithm::SHA256);
+    }
+}
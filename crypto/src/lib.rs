@@ -14,7 +14,9 @@ pub use crypto::*;
 mod gen;
 pub use gen::*;
 
+pub mod bls;
 pub mod ed25519;
 pub mod error;
 pub mod rsa;
-pub mod secp256k1;
\ No newline at end of file
+pub mod secp256k1;
+pub mod vrf;
\ No newline at end of file
@@ -20,7 +20,7 @@
 
 //! RSA keys.
 
-use asn1_der::typed::{DerEncodable, DerDecodable, DerTypeView, Sequence};
+use asn1_der::typed::{DerEncodable, DerDecodable, DerTypeView, Integer, Sequence};
 use asn1_der::{DerObject, Asn1DerError, Asn1DerErrorVariant, Sink, VecBacking};
 use super::error::*;
 use ring::rand::SystemRandom;
@@ -81,6 +81,14 @@ impl PublicKey {
         self.0.clone()
     }
 
+    /// Decode an RSA public key from a DER-encoded PKCS#1 RSAPublicKey
+    /// structure. See also `encode_pkcs1`.
+    pub fn decode_pkcs1(pk: &[u8]) -> Result<PublicKey, DecodingError> {
+        Asn1RsaPublicKey::decode(pk)
+            .map_err(|e| DecodingError::new("RSA PKCS#1").source(e))
+            .map(|_| PublicKey(pk.to_vec()))
+    }
+
     /// Encode the RSA public key in DER as a X.509 SubjectPublicKeyInfo structure,
     /// as defined in [RFC5280].
     ///
@@ -260,6 +268,24 @@ impl DerDecodable<'_> for Asn1SubjectPublicKey {
     }
 }
 
+/// ASN.1 RSAPublicKey, i.e. the modulus and the public exponent. Only used to
+/// check the structure of PKCS#1 encoded keys.
+struct Asn1RsaPublicKey;
+
+impl DerDecodable<'_> for Asn1RsaPublicKey {
+    fn load(object: DerObject<'_>) -> Result<Self, Asn1DerError> {
+        let seq: Sequence = Sequence::load(object)?;
+        if seq.len() != 2 {
+            return Err(Asn1DerError::new(Asn1DerErrorVariant::InvalidData(
+                "DER object is not a (modulus, publicExponent) sequence.",
+            )));
+        }
+        let _modulus: Integer = seq.get_as(0)?;
+        let _exponent: Integer = seq.get_as(1)?;
+        Ok(Self)
+    }
+}
+
 /// ASN.1 SubjectPublicKeyInfo
 #[allow(non_snake_case)]
 struct Asn1SubjectPublicKeyInfo {
@@ -290,56 +316,67 @@ impl DerDecodable<'_> for Asn1SubjectPublicKeyInfo {
     }
 }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-//     use quickcheck::*;
-//     use rand::seq::SliceRandom;
-//     use std::fmt;
-
-//     const KEY1: &'static [u8] = include_bytes!("test/rsa-2048.pk8");
-//     const KEY2: &'static [u8] = include_bytes!("test/rsa-3072.pk8");
-//     const KEY3: &'static [u8] = include_bytes!("test/rsa-4096.pk8");
-
-//     #[derive(Clone)]
-//     struct SomeKeypair(Keypair);
-
-//     impl fmt::Debug for SomeKeypair {
-//         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//             write!(f, "SomeKeypair")
-//         }
-//     }
-
-//     impl Arbitrary for SomeKeypair {
-//         fn arbitrary(g: &mut Gen) -> SomeKeypair {
-//             let mut key = [KEY1, KEY2, KEY3].choose(g).unwrap().to_vec();
-//             SomeKeypair(Keypair::from_pkcs8(&mut key).unwrap())
-//         }
-//     }
-
-//     #[test]
-//     fn rsa_from_pkcs8() {
-//         assert!(Keypair::from_pkcs8(&mut KEY1.to_vec()).is_ok());
-//         assert!(Keypair::from_pkcs8(&mut KEY2.to_vec()).is_ok());
-//         assert!(Keypair::from_pkcs8(&mut KEY3.to_vec()).is_ok());
-//     }
-
-//     #[test]
-//     fn rsa_x509_encode_decode() {
-//         fn prop(SomeKeypair(kp): SomeKeypair) -> Result<bool, String> {
-//             let pk = kp.public();
-//             PublicKey::decode_x509(&pk.encode_x509())
-//                 .map_err(|e| e.to_string())
-//                 .map(|pk2| pk2 == pk)
-//         }
-//         QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _);
-//     }
-
-//     #[test]
-//     fn rsa_sign_verify() {
-//         fn prop(SomeKeypair(kp): SomeKeypair, msg: Vec<u8>) -> Result<bool, SigningError> {
-//             kp.sign(&msg).map(|s| kp.public().verify(&msg, &s))
-//         }
-//         QuickCheck::new().tests(10).quickcheck(prop as fn(_,_) -> _);
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::*;
+    use std::fmt;
+
+    const KEY1: &[u8] = include_bytes!("test/rsa-2048.pk8");
+    const KEY2: &[u8] = include_bytes!("test/rsa-3072.pk8");
+    const KEY3: &[u8] = include_bytes!("test/rsa-4096.pk8");
+
+    #[derive(Clone)]
+    struct SomeKeypair(Keypair);
+
+    impl fmt::Debug for SomeKeypair {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "SomeKeypair")
+        }
+    }
+
+    impl Arbitrary for SomeKeypair {
+        fn arbitrary(g: &mut Gen) -> SomeKeypair {
+            let mut key = g.choose(&[KEY1, KEY2, KEY3]).unwrap().to_vec();
+            SomeKeypair(Keypair::from_pkcs8(&mut key).unwrap())
+        }
+    }
+
+    #[test]
+    fn rsa_from_pkcs8() {
+        assert!(Keypair::from_pkcs8(&mut KEY1.to_vec()).is_ok());
+        assert!(Keypair::from_pkcs8(&mut KEY2.to_vec()).is_ok());
+        assert!(Keypair::from_pkcs8(&mut KEY3.to_vec()).is_ok());
+    }
+
+    #[test]
+    fn rsa_x509_encode_decode() {
+        fn prop(SomeKeypair(kp): SomeKeypair) -> Result<bool, String> {
+            let pk = kp.public();
+            PublicKey::decode_x509(&pk.encode_x509())
+                .map_err(|e| e.to_string())
+                .map(|pk2| pk2 == pk)
+        }
+        QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _);
+    }
+
+    #[test]
+    fn rsa_pkcs1_encode_decode() {
+        fn prop(SomeKeypair(kp): SomeKeypair) -> Result<bool, String> {
+            let pk = kp.public();
+            PublicKey::decode_pkcs1(&pk.encode_pkcs1())
+                .map_err(|e| e.to_string())
+                .map(|pk2| pk2 == pk)
+        }
+        QuickCheck::new().tests(10).quickcheck(prop as fn(_) -> _);
+        assert!(PublicKey::decode_pkcs1(&[0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn rsa_sign_verify() {
+        fn prop(SomeKeypair(kp): SomeKeypair, msg: Vec<u8>) -> Result<bool, SigningError> {
+            kp.sign(&msg).map(|s| kp.public().verify(&msg, &s))
+        }
+        QuickCheck::new().tests(10).quickcheck(prop as fn(_,_) -> _);
+    }
+}
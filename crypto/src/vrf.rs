@@ -0,0 +1,169 @@
+//! A verifiable random function (VRF) over Ristretto255 (sr25519).
+//!
+//! The holder of a secret key evaluates the VRF on an input to get a random
+//! output along with a proof. Anyone with the public key can check the proof
+//! and recompute the same output, but nobody can predict the output without
+//! the secret key. There is exactly one valid output for every key and input,
+//! so the output cannot be ground by the key holder.
+//!
+//! These keys are only used for leader election, and are separate from the
+//! signing keys of the nodes.
+
+use schnorrkel::{
+    signing_context, ExpansionMode, MiniSecretKey,
+    vrf::{VRFPreOut, VRFProof},
+};
+use serde::{Serialize, Deserialize};
+use super::error::DecodingError;
+use zeroize::Zeroize;
+use core::fmt;
+
+/// The size of an encoded VRF public key
+pub const VRF_PK_SIZE: usize = 32;
+/// The size of an encoded VRF secret key
+pub const VRF_PVT_SIZE: usize = 32;
+/// The size of a VRF output
+pub const VRF_OUTPUT_SIZE: usize = 32;
+
+/// The signing context that separates these VRF evaluations from any other
+/// use of the keys
+const CONTEXT: &[u8] = b"libchatter-vrf";
+/// The label used to derive the output bytes
+const OUTPUT_LABEL: &[u8] = b"libchatter-vrf-output";
+
+/// A VRF output along with the proof that it was computed correctly.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Proof {
+    pub preout: Vec<u8>,
+    pub proof: Vec<u8>,
+}
+
+/// A VRF keypair.
+#[derive(Clone)]
+pub struct Keypair {
+    secret: MiniSecretKey,
+    inner: schnorrkel::Keypair,
+}
+
+impl Keypair {
+    /// Generate a new VRF keypair.
+    pub fn generate() -> Keypair {
+        Keypair::from(MiniSecretKey::generate())
+    }
+
+    /// Encode the keypair as the bytes of its secret key. The public key is
+    /// derived again when decoding.
+    pub fn encode(&self) -> [u8; VRF_PVT_SIZE] {
+        self.secret.to_bytes()
+    }
+
+    /// Decode a keypair from the format produced by `encode`,
+    /// zeroing the input on success.
+    pub fn decode(kp: &mut [u8]) -> Result<Keypair, DecodingError> {
+        let secret = MiniSecretKey::from_bytes(kp)
+            .map_err(|e| DecodingError::new(format!("VRF secret key: {}", e)))?;
+        kp.zeroize();
+        Ok(Keypair::from(secret))
+    }
+
+    /// Evaluate the VRF on `input`, returning the output and its proof.
+    pub fn prove(&self, input: &[u8]) -> ([u8; VRF_OUTPUT_SIZE], Proof) {
+        let ctx = signing_context(CONTEXT);
+        let (io, proof, _) = self.inner.vrf_sign(ctx.bytes(input));
+        let out = io.make_bytes::<[u8; VRF_OUTPUT_SIZE]>(OUTPUT_LABEL);
+        let proof = Proof {
+            preout: io.to_preout().to_bytes().to_vec(),
+            proof: proof.to_bytes().to_vec(),
+        };
+        (out, proof)
+    }
+
+    /// Get the public key of this keypair.
+    pub fn public(&self) -> PublicKey {
+        PublicKey(self.inner.public)
+    }
+}
+
+impl fmt::Debug for Keypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keypair").field("public", &self.public()).finish()
+    }
+}
+
+impl From<MiniSecretKey> for Keypair {
+    fn from(secret: MiniSecretKey) -> Keypair {
+        let inner = secret.expand_to_keypair(ExpansionMode::Ed25519);
+        Keypair { secret, inner }
+    }
+}
+
+/// A VRF public key.
+#[derive(PartialEq, Eq, Clone)]
+pub struct PublicKey(schnorrkel::PublicKey);
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PublicKey(compressed): ")?;
+        for byte in self.0.to_bytes().iter() {
+            write!(f, "{:x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl PublicKey {
+    /// Check the proof of a VRF evaluation on `input`, and return the output
+    /// if it is correct.
+    pub fn verify(&self, input: &[u8], proof: &Proof) -> Option<[u8; VRF_OUTPUT_SIZE]> {
+        let preout = VRFPreOut::from_bytes(&proof.preout).ok()?;
+        let p = VRFProof::from_bytes(&proof.proof).ok()?;
+        let ctx = signing_context(CONTEXT);
+        let (io, _) = self.0.vrf_verify(ctx.bytes(input), &preout, &p).ok()?;
+        Some(io.make_bytes::<[u8; VRF_OUTPUT_SIZE]>(OUTPUT_LABEL))
+    }
+
+    /// Encode the public key into a byte array in compressed form.
+    pub fn encode(&self) -> [u8; VRF_PK_SIZE] {
+        self.0.to_bytes()
+    }
+
+    /// Decode a public key from a byte array as produced by `encode`.
+    pub fn decode(k: &[u8]) -> Result<PublicKey, DecodingError> {
+        schnorrkel::PublicKey::from_bytes(k)
+            .map_err(|e| DecodingError::new(format!("VRF public key: {}", e)))
+            .map(PublicKey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vrf_keypair_encode_decode() {
+        let kp1 = Keypair::generate();
+        let mut kp1_enc = kp1.encode();
+        let kp2 = Keypair::decode(&mut kp1_enc).unwrap();
+        assert_eq!(kp1.public(), kp2.public());
+        assert!(kp1_enc.iter().all(|b| *b == 0));
+        let pk = PublicKey::decode(&kp1.public().encode()).unwrap();
+        assert_eq!(pk, kp1.public());
+    }
+
+    #[test]
+    fn vrf_prove_verify() {
+        let kp = Keypair::generate();
+        let pk = kp.public();
+        let (out, proof) = kp.prove(b"round 1");
+        assert_eq!(pk.verify(b"round 1", &proof), Some(out));
+        // The output is unique
+        assert_eq!(kp.prove(b"round 1").0, out);
+
+        assert_eq!(pk.verify(b"round 2", &proof), None);
+        assert_eq!(Keypair::generate().public().verify(b"round 1", &proof), None);
+
+        let mut invalid = proof.clone();
+        invalid.proof[3] ^= 1;
+        assert_eq!(pk.verify(b"round 1", &invalid), None);
+    }
+}
@@ -26,6 +26,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
     config
         .validate()
         .expect("The decoded config is not valid");
+    config
+        .use_hash_algorithm()
+        .expect("Failed to set the hash function of the chain");
     if let Some(f) = m.value_of("ip") {
         config.update_config(util::io::file_to_ips(f.to_string()));
     }
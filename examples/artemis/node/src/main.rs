@@ -1,7 +1,7 @@
 // use futures::prelude::*;
 use clap::{load_yaml, App};
 use config::Node;
-use types::artemis::{ClientMsg, ProtocolMsg, Transaction};
+use consensus::NodeOptions;
 use std::error::Error;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -28,6 +28,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         config.delta = v.parse().expect("unexpected delta value provided");
     }
 
+    if let Some(v) = m.value_of("state_dir") {
+        config.state_dir = Some(v.to_string());
+    }
+
     if let Some(v) = m.value_of("sleep") {
         unsafe {
             config::SLEEP_TIME = v.parse().expect("unexpected sleep time");
@@ -38,17 +42,23 @@ fn main() -> Result<(), Box<dyn Error>> {
         }
     }
 
+    config
+        .unlock_keystore(conf_file.parent().unwrap_or(std::path::Path::new(".")))
+        .expect("Failed to unlock the keystore");
     config
         .validate()
         .expect("The decoded config is not valid");
+    config
+        .use_hash_algorithm()
+        .expect("Failed to set the hash function of the chain");
     if let Some(f) = m.value_of("ip") {
         config.update_config(util::io::file_to_ips(f.to_string()));
     }
     let config = config;
-    let mut is_client_apollo_enabled = false;
-    if m.is_present("special_client") {
-        is_client_apollo_enabled = true;
-    } 
+    let opts = NodeOptions {
+        special_client: m.is_present("special_client"),
+        ..NodeOptions::default()
+    };
 
     simple_logger::SimpleLogger::new().init().unwrap();
     let x = m.occurrences_of("debug");
@@ -60,58 +70,7 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     log::info!("Successfully decoded the config file");
 
-    let cli_net_rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
-    
-    // Setup client network
-    let cli_network = net::futures_manager::Protocol::<Transaction, ClientMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
-    let (cli_send, cli_recv) = 
-    cli_net_rt.block_on(
-        cli_network.client_setup(
-            config.client_ip(),
-            util::codec::EnCodec::new(),
-            util::codec::Decodec::new(),
-        )
-    );
-
-    let prot_net_rt = tokio::runtime::Builder::new_multi_thread()
-    .enable_all()
-    .build()
-    .unwrap();
-
-    // Setup networking
-    let protocol_network = net::futures_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
-
-    // Setup the protocol network
-    let (net_send, net_recv) = 
-    prot_net_rt.block_on(
-        protocol_network.server_setup(
-            config.net_map.clone(), 
-            util::codec::EnCodec::new(), 
-            util::codec::Decodec::new()
-        )
-    );
-
-    let core_rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .worker_threads(2)
-        .build()
-        .unwrap();
-    
-    log::info!("Using special artemis client: {}", is_client_apollo_enabled);
-    // Start the Apollo consensus protocol
-    core_rt.block_on(
-        artemis::node::reactor(
-            &config,
-            is_client_apollo_enabled,
-            net_send,
-            net_recv,
-            cli_send,
-            cli_recv
-        )
-    );
+    consensus::run::<artemis::Artemis, _>(&config, &opts, consensus::ctrl_c());
     Ok(())
 }
 
@@ -0,0 +1,72 @@
+use clap::{
+    load_yaml, 
+    App
+};
+use config::Node;
+use consensus::NodeOptions;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let yaml = load_yaml!("cli.yml");
+    let m = App::from_yaml(yaml).get_matches();
+
+    let conf_str = m.value_of("config")
+        .expect("unable to convert config file into a string");
+    let conf_file = std::path::Path::new(conf_str);
+    let str = String::from(conf_str);
+    let mut config = match conf_file
+        .extension()
+        .expect("Unable to get file extension")
+        .to_str()
+        .expect("Failed to convert the extension into ascii string") 
+    {
+        "json" => Node::from_json(str),
+        "dat" => Node::from_bin(str),
+        "toml" => Node::from_toml(str),
+        "yaml" => Node::from_yaml(str),
+        _ => panic!("Invalid config file extension"),
+    };
+    if let Some(v) = m.value_of("delta") {
+        config.delta = v.parse().expect("unexpected delta value provided");
+    }
+
+    if let Some(v) = m.value_of("state_dir") {
+        config.state_dir = Some(v.to_string());
+    }
+
+    if let Some(v) = m.value_of("sleep") {
+        unsafe {
+            config::SLEEP_TIME = v.parse().expect("unexpected sleep time");
+        }
+    } else {
+        unsafe {
+            config::SLEEP_TIME = (5 + config.num_nodes) as u64;
+        }
+    }
+
+    simple_logger::SimpleLogger::new().init().unwrap();
+    match m.occurrences_of("debug") {
+        0 => log::set_max_level(log::LevelFilter::Info),
+        1 => log::set_max_level(log::LevelFilter::Debug),
+        2 | _ => log::set_max_level(log::LevelFilter::Trace),
+    }
+
+    config
+        .unlock_keystore(conf_file.parent().unwrap_or(std::path::Path::new(".")))
+        .expect("Failed to unlock the keystore");
+    config
+        .validate()
+        .expect("The decoded config is not valid");
+    config
+        .use_hash_algorithm()
+        .expect("Failed to set the hash function of the chain");
+    if let Some(f) = m.value_of("ip") {
+        config.update_config(util::io::file_to_ips(f.to_string()));
+    }
+    let config = config;
+    let opts = NodeOptions::default();
+
+    consensus::run::<hotstuff::HotStuff, _>(&config, &opts, consensus::ctrl_c());
+    Ok(())
+}
+
@@ -38,6 +38,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
     config
         .validate()
         .expect("The decoded config is not valid");
+    config
+        .use_hash_algorithm()
+        .expect("Failed to set the hash function of the chain");
     if let Some(f) = m.value_of("ip") {
         config.update_config(util::io::file_to_ips(f.to_string()));
     }
@@ -46,8 +49,14 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .parse().unwrap();
     let window:usize = m.value_of("window").unwrap_or("1000")
         .parse().unwrap();
+    let reconfig = m.value_of("reconfig").map(|f| {
+        let f = std::fs::File::open(f)
+            .expect("Failed to open the reconfiguration file");
+        serde_json::from_reader(f)
+            .expect("Failed to decode the reconfiguration file")
+    });
     
     synchs::client::start(
-        &config, metrics, window).await;
+        &config, metrics, window, reconfig).await;
     Ok(())
 }
@@ -3,8 +3,8 @@ use clap::{
     App
 };
 use config::Node;
+use consensus::NodeOptions;
 use std::error::Error;
-use types::synchs_rr::{ClientMsg, Transaction, ProtocolMsg};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let yaml = load_yaml!("cli.yml");
@@ -30,6 +30,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         config.delta = v.parse().expect("unexpected delta value provided");
     }
 
+    if let Some(v) = m.value_of("verify_workers") {
+        config.verify_workers = v.parse().expect("unexpected number of verification workers");
+    }
+
     if let Some(v) = m.value_of("sleep") {
         unsafe {
             config::SLEEP_TIME = v.parse().expect("unexpected sleep time");
@@ -47,64 +51,22 @@ fn main() -> Result<(), Box<dyn Error>> {
         2 | _ => log::set_max_level(log::LevelFilter::Trace),
     }
 
+    config
+        .unlock_keystore(conf_file.parent().unwrap_or(std::path::Path::new(".")))
+        .expect("Failed to unlock the keystore");
     config
         .validate()
         .expect("The decoded config is not valid");
+    config
+        .use_hash_algorithm()
+        .expect("Failed to set the hash function of the chain");
     if let Some(f) = m.value_of("ip") {
         config.update_config(util::io::file_to_ips(f.to_string()));
     }
     let config = config;
+    let opts = NodeOptions::default();
 
-    let cli_net_rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap();
-    
-    // Setup client network
-    let client_network = net::tokio_manager::Protocol::<Transaction, ClientMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
-    let (cli_send, cli_recv) = 
-    cli_net_rt.block_on(
-        client_network.client_setup(
-            config.client_ip(),
-            util::codec::EnCodec::new(),
-            util::codec::Decodec::new()
-        )
-    );
-
-    let prot_net_rt = tokio::runtime::Builder::new_multi_thread()
-    .enable_all()
-    .build()
-    .unwrap();
-
-    // Setup networking
-    let protocol_network = net::tokio_manager::Protocol::<ProtocolMsg, ProtocolMsg>::new(config.id, config.num_nodes, config.root_cert.clone(), config.my_cert.clone(), config.my_cert_key.clone());
-
-    // Setup the protocol network
-    let (net_send, net_recv) = 
-    prot_net_rt.block_on(
-        protocol_network.server_setup(
-            config.net_map.clone(), 
-            util::codec::EnCodec::new(), 
-            util::codec::Decodec::new()
-        )
-    );
-
-    let core_rt = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .worker_threads(2)
-        .build()
-        .unwrap();
-    
-    // Start the Sync HotStuff RR consensus protocol
-    core_rt.block_on(
-        synchs::node::reactor_rr(
-            &config,
-            net_send,
-            net_recv,
-            cli_send,
-            cli_recv
-        )
-    );
+    consensus::run::<synchs::SyncHotStuffRR, _>(&config, &opts, consensus::ctrl_c());
     Ok(())
 }
 
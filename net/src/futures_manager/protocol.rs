@@ -230,47 +230,55 @@ async fn protocol_event_loop<I,O>(
     mut in_send: UnboundedSender<(Replica, I)>,
     mut out_recv: UnboundedReceiver<(Replica, Arc<O>)>,
     mut reading_net: impl Stream<Item=(Replica, I)>+Unpin,
-    writers: HashMap<Replica, UnboundedSender<Arc<O>>>
+    mut writers: HashMap<Replica, UnboundedSender<Arc<O>>>
 ) where I: WireReady
 {
+    let mut to_remove = Vec::new();
     loop {
         tokio::select!{
             opt_in = reading_net.next() => {
                 if let None = opt_in {
                     log::error!(
                         "Failed to read a protocol message from a peer");
-                    std::process::exit(0);
+                    return;
                 }
                 let (id, msg) = opt_in.unwrap();
                 if let Err(e) = in_send.send((id, msg.init())).await {
                     log::error!(
                         "Failed to send a protocol message outside the network, with error {}", e);
-                    std::process::exit(0);
+                    return;
                 }
             },
             opt_out = out_recv.next() => {
                 if let None = opt_out {
                     log::error!(
                         "Failed to read a protocol message to send outside the network");
-                    std::process::exit(0);
+                    return;
                 }
                 let (to, msg) = opt_out.unwrap();
                 if to < num_nodes {
-                    if let Err(_e) = writers[&to].clone().send(msg).await {
-                        log::error!("Failed to send msg to peer {}", to);
-                        std::process::exit(0);
+                    // A crashed peer stays disconnected
+                    if let Some(writer) = writers.get(&to) {
+                        if let Err(e) = writer.clone().send(msg).await {
+                            log::warn!("Disconnected from peer {} with error {}", to, e);
+                            to_remove.push(to);
+                        }
                     }
                 } else {
                     for (id, writer) in &writers {
                         if let Err(e) = writer.clone().send(msg.clone()).await {
-                            log::error!("Failed to send msg to peer {} with error {}", id, e);
-                            std::process::exit(0);
-                            // TODO Handle disconnection from peer
+                            log::warn!("Disconnected from peer {} with error {}", id, e);
+                            to_remove.push(*id);
                         }
                     }
                 }
             },
         }
+        // Remove disconnected peers
+        for id in &to_remove {
+            writers.remove(id);
+        }
+        to_remove.clear();
     }
 }
 
@@ -301,7 +309,7 @@ async fn cli_manager(addr: String) -> UnboundedReceiver<TcpStream> {
             };
             if let Err(e) = conn_ch_send.send(conn).await {
                 log::error!("Failed to send out new client connection: {}", e);
-                std::process::exit(0);
+                return;
             }
         }
     });
@@ -328,20 +336,20 @@ O: WireReady + Clone+Unpin+Sync + 'static,
             in_opt = read_stream.next(), if read_stream.len() > 0 => {
                 if let None = in_opt {
                     log::warn!("Read stream closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let (_id, msg) = in_opt.unwrap();
                 let msg = msg.init();
                 if let Err(e) = new_in_ch.send(msg).await {
                     log::error!("Failed to send an incoming client message outside, with error {}", e);
-                    std::process::exit(0);
+                    return;
                 }
             },
             // We have a new client
             conn_opt = new_conn_ch.next() => {
                 if let None = conn_opt {
                     log::warn!("New connection channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let conn = conn_opt.unwrap();
                 let new_acceptor = cli_acceptor.clone();
@@ -366,7 +374,7 @@ O: WireReady + Clone+Unpin+Sync + 'static,
             out_opt = send_out_ch.next() => {
                 if let None = out_opt {
                     log::warn!("Send out channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let msg = out_opt.unwrap();
                 for (id, writer) in &writers {
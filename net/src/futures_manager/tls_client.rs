@@ -96,9 +96,10 @@ O:WireReady + Clone + Sync + 'static + Unpin,
             = unbounded_channel();
         // I hope no new peers will be added later
         let n = self.peers.len();
-        let peers = self.peers.clone();
+        let mut peers = self.peers.clone();
         // log::trace!("Using peers: {:?}", peers);
         tokio::spawn(async move {
+            let mut to_remove = Vec::new();
             loop {
                 tokio::select! {
                     to_send_opt = in_recv.next() => {
@@ -110,15 +111,19 @@ O:WireReady + Clone + Sync + 'static + Unpin,
                         }
                         let (to, msg) = to_send_opt.unwrap();
                         if (to as usize) < n {
-                            let opt = peers[&to].clone().send(msg).await;
-                            if let Err(e) = opt {
-                                panic!("failed to send a message out to peer {} with error {}", to, e);
+                            // A crashed server stays disconnected
+                            if let Some(sender) = peers.get(&to) {
+                                if let Err(e) = sender.clone().send(msg).await {
+                                    log::warn!("Disconnected from server {} with error {}", to, e);
+                                    to_remove.push(to);
+                                }
                             }
                         } else {
-                            for (_i, sender) in &peers {
+                            for (i, sender) in &peers {
                                 let opt = sender.clone().send(msg.clone()).await;
                                 if let Err(e) = opt {
-                                    panic!("failed to send a message out to peer {} with error {}", to, e);
+                                    log::warn!("Disconnected from server {} with error {}", i, e);
+                                    to_remove.push(*i);
                                 }
                             }
                         }
@@ -136,6 +141,11 @@ O:WireReady + Clone + Sync + 'static + Unpin,
                         }
                     },
                 }
+                // Remove disconnected servers
+                for id in &to_remove {
+                    peers.remove(id);
+                }
+                to_remove.clear();
             }
         });
         (in_send, out_recv)
@@ -80,14 +80,14 @@ O: WireReady+'static + Clone+Sync,
                     let mut s = stream::iter(to_send.into_iter().map(Ok));
                     if let Err(_e) = writer.send_all(&mut s).await {
                         log::error!("Failed to write a message to a peer");
-                        std::process::exit(0);
+                        return;
                     }
                     if let Err(_e) = internal_ch_in_send.send(InternalInMsg::Ready) {
                         log::error!("Failed to send a message to the internal channel");
                     }
                 } else {
                     log::error!("Internal message channel closed");
-                    std::process::exit(0);
+                    return;
                 }
             }
         });
@@ -100,19 +100,19 @@ O: WireReady+'static + Clone+Sync,
                     in_opt = reader.next() => {
                         if let None = in_opt {
                             log::warn!("Disconnected from peer");
-                            std::process::exit(0);
+                            return;
                         }
                         if let Some(Ok(x)) = in_opt {
                             if let Err(_e) = send_in.send(x) {
                                 log::warn!("Error in sending out");
-                                std::process::exit(0);
+                                return;
                             }
                         }
                     },
                     out_opt = recv_out.recv() => {
                         if let None = out_opt {
                             log::warn!("Error in receiving message");
-                            std::process::exit(0);
+                            return;
                         }
                         if let Some(x) = out_opt {
                             // Write if not already writing, otherwise
@@ -121,7 +121,7 @@ O: WireReady+'static + Clone+Sync,
                                 buffers.push_back(x);
                                 if let Err(_e) = internal_ch_out_send.send(InternalOutMsg::Batch(buffers)) {
                                     log::warn!("Error in sending message out");
-                                    std::process::exit(0);
+                                    return;
                                 }
                                 buffers = VecDeque::new();
                             } else {
@@ -134,7 +134,7 @@ O: WireReady+'static + Clone+Sync,
                             ready = true;                                
                         } else {
                             log::warn!("Error in getting message from int channel");
-                            std::process::exit(0);
+                            return;
                         }
                     }
                 }
@@ -2,7 +2,7 @@ use std::{
     sync::Arc,
     pin::Pin,
 };
-use fnv::FnvHashMap as HashMap;
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
 
 use log::info;
 use tokio::{
@@ -36,11 +36,22 @@ use super::peer::Peer;
 use super::Protocol;
 
 const ID_BYTE_SIZE:usize = std::mem::size_of::<Replica>();
+/// How long to wait before connecting again to a node that is not up yet
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
 
 type Err = std::io::Error;
 type Reader = tokio::net::tcp::OwnedReadHalf;
 type Writer = tokio::net::tcp::OwnedWriteHalf;
 
+/// A change to the set of nodes that the protocol network talks to
+#[derive(Debug, Clone)]
+pub enum PeerChange {
+    /// Connect to a new node at this address
+    Add(Replica, String),
+    /// Disconnect from a node and ignore it from now on
+    Remove(Replica),
+}
+
 impl<I,O> Protocol<I,O>
 where I:WireReady + Send + Sync + 'static + Unpin,
 O:WireReady + Clone + Sync + 'static + Unpin, 
@@ -51,6 +62,22 @@ O:WireReady + Clone + Sync + 'static + Unpin,
         enc: impl Encoder<Arc<O>> + Clone + Send + Sync + 'static, 
         dec: impl Decoder<Item=I, Error=Err> + Clone + Send + Sync + 'static
     ) -> (UnboundedSender<(Replica, Arc<O>)>, UnboundedReceiver<(Replica, I)>)
+    {
+        let (net_send, net_recv, _changes) = self.reconfigurable_server_setup(node_addr, enc, dec).await;
+        (net_send, net_recv)
+    }
+
+    /// `server_setup` for a network whose nodes can change: the nodes of
+    /// `node_addr` are connected first, and the returned `PeerChange` channel
+    /// adds and removes nodes later.
+    ///
+    /// A message to a node that is not in the network is sent to everyone.
+    pub async fn reconfigurable_server_setup(
+        &self,
+        node_addr: HashMap<Replica, String>, 
+        enc: impl Encoder<Arc<O>> + Clone + Send + Sync + 'static, 
+        dec: impl Decoder<Item=I, Error=Err> + Clone + Send + Sync + 'static
+    ) -> (UnboundedSender<(Replica, Arc<O>)>, UnboundedReceiver<(Replica, I)>, UnboundedSender<PeerChange>)
     {
         // Task that receives connections from everyone
         let incoming_conn_task = 
@@ -67,7 +94,7 @@ O:WireReady + Clone + Sync + 'static + Unpin,
         // Start connecting to other nodes
         let mut writers = outgoing_conn(self.my_id, &node_addr)
             .await;
-        let mut readers = incoming_conn_task
+        let (mut readers, listener) = incoming_conn_task
             .await
             .expect("Failed to collect all readers");
         
@@ -77,46 +104,49 @@ O:WireReady + Clone + Sync + 'static + Unpin,
         let mut unified_stream = StreamMap::new();
 
         // Create write end points for peers
-        let mut writer_end_points = HashMap::default();
-        for i in 0..self.num_nodes {
+        let mut writer_end_points = HashMap::with_capacity_and_hasher(self.num_nodes, Default::default());
+        for i in node_addr.keys().copied() {
             if i == self.my_id {
                 continue;
             }
             let read = readers.remove(&i).unwrap();
             let write = writers.remove(&i).unwrap();
-            let enc = enc.clone();
-            let dec = dec.clone();
-            let peer = Peer::new(read, write, dec, enc);
-            let mut recv_ch = peer.recv;
-            unified_stream.insert(
-                i as Replica, 
-                Box::pin(async_stream::stream!{
-                    while let Some(item) = recv_ch.recv().await {
-                        yield(item);
-                    }
-                }) as Pin<Box<dyn Stream<Item=I>+Send>>
-                // recv_ch
-            );
-            writer_end_points.insert(i as Replica, peer.send);
+            add_peer(i, read, write, &enc, &dec, &mut unified_stream, &mut writer_end_points);
         }
 
         // Create channels so that the outside world can communicate with the
         // network
         let (in_send, in_recv) = unbounded_channel::<(Replica, I)>();
         let (out_send, out_recv) = unbounded_channel();
+        let (change_send, change_recv) = unbounded_channel();
+
+        // Keep accepting the nodes that join later
+        let (conn_send, conn_recv) = unbounded_channel();
+        tokio::spawn(accept_later(listener, conn_send.clone()));
 
         // Start the event loop that processes network messages
         tokio::spawn(
             protocol_event_loop(
-                self.num_nodes, 
+                NetState {
+                    my_id: self.my_id,
+                    members: node_addr.clone(),
+                    connecting: HashSet::default(),
+                    readers: unified_stream,
+                    writers: writer_end_points,
+                    pending_readers: HashMap::default(),
+                    pending_writers: HashMap::default(),
+                    enc,
+                    dec,
+                },
                 in_send, 
                 out_recv, 
-                unified_stream, 
-                writer_end_points
+                change_recv,
+                conn_send,
+                conn_recv,
             )
         );
     
-        (out_send, in_recv)
+        (out_send, in_recv, change_send)
     }
 
     pub async fn client_setup(
@@ -140,7 +170,7 @@ O:WireReady + Clone + Sync + 'static + Unpin,
 async fn start_conn_all(
     node_addr: HashMap<Replica, String>,
     my_id: Replica,
-) -> HashMap<Replica, Reader>
+) -> (HashMap<Replica, Reader>, TcpListener)
 {
     // Start listening to incoming connections
     let listener = TcpListener::bind(
@@ -152,25 +182,9 @@ async fn start_conn_all(
     let mut readers = HashMap::with_capacity_and_hasher(n,Default::default());
     for _i in 1..n {
         // Connect to a new node
-        let (mut conn, from) = listener.accept()
+        let (id, read) = accept_one(&listener)
             .await
             .expect("Failed to listen to incoming connections");
-        
-        // Set nodelay
-        conn.set_nodelay(true).expect("Failed to set nodelay");
-        
-        info!("New incoming connection from {}", from);
-        
-        // Get the ID of the connector
-        let mut id_buf = [0 as u8; ID_BYTE_SIZE];
-        conn
-            .read_exact(&mut id_buf)
-            .await
-            .expect("Failed to read ID bytes");
-        let id = Replica::from_be_bytes(id_buf);
-        
-        // Split the connection and drop the writing part
-        let (read, _write) = conn.into_split();
 
         // Add this reader
         readers.insert(
@@ -178,7 +192,77 @@ async fn start_conn_all(
             read
         );
     }
-    readers
+    (readers, listener)
+}
+
+/// Accept a connection from a node, and return its ID and the reading part
+async fn accept_one(listener: &TcpListener) -> Result<(Replica, Reader), Err> {
+    let (mut conn, from) = listener.accept().await?;
+    
+    // Set nodelay
+    conn.set_nodelay(true)?;
+    
+    info!("New incoming connection from {}", from);
+    
+    // Get the ID of the connector
+    let mut id_buf = [0 as u8; ID_BYTE_SIZE];
+    conn
+        .read_exact(&mut id_buf)
+        .await?;
+    let id = Replica::from_be_bytes(id_buf);
+    
+    // Split the connection and drop the writing part
+    let (read, _write) = conn.into_split();
+    Ok((id, read))
+}
+
+/// Accept the connections of the nodes that join after the setup
+async fn accept_later(listener: TcpListener, conn_send: UnboundedSender<Conn>) {
+    loop {
+        match accept_one(&listener).await {
+            Err(e) => log::warn!("Failed to accept a connection from a node: {}", e),
+            Ok((id, read)) => {
+                if conn_send.send(Conn::Reader(id, read)).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Connect to a node that joined after the setup, until it is up
+async fn connect_later(my_id: Replica, id: Replica, addr: String, conn_send: UnboundedSender<Conn>) {
+    loop {
+        match connect_one(my_id, &addr).await {
+            Ok(write) => {
+                let _ = conn_send.send(Conn::Writer(id, write));
+                return;
+            }
+            Err(e) => {
+                log::debug!("Failed to connect to node {} at {}: {}", id, addr, e);
+                if conn_send.is_closed() {
+                    return;
+                }
+                tokio::time::sleep(RETRY_INTERVAL).await;
+            }
+        }
+    }
+}
+
+/// Connect to a node, and return the writing part
+async fn connect_one(my_id: Replica, addr: &str) -> Result<Writer, Err> {
+    let conn = TcpStream::connect(addr).await?;
+    // Enbale high speed connection
+    conn.set_nodelay(true)?;
+    
+    // Split the socket into seperate RW components
+    let (_read, mut write) = conn.into_split();
+    
+    // Send id of self on the connection
+    write
+        .write_all(&my_id.to_be_bytes())
+        .await?;
+    Ok(write)
 }
 
 async fn outgoing_conn(
@@ -221,49 +305,211 @@ async fn outgoing_conn(
     writers
 }
 
-async fn protocol_event_loop<I,O>(
-    num_nodes: Replica, 
+type ReadStream<I> = StreamMap<Replica, Pin<Box<dyn Stream<Item=I>+Send>>>;
+
+/// A connection that a node made after the setup
+enum Conn {
+    Reader(Replica, Reader),
+    Writer(Replica, Writer),
+}
+
+/// Create a peer from both parts of its connections
+fn add_peer<I,O,E,D>(
+    id: Replica,
+    read: Reader,
+    write: Writer,
+    enc: &E,
+    dec: &D,
+    readers: &mut ReadStream<I>,
+    writers: &mut HashMap<Replica, UnboundedSender<Arc<O>>>,
+) where I:WireReady + Send + Sync + 'static + Unpin,
+O:WireReady + Clone + Sync + 'static + Unpin,
+E: Encoder<Arc<O>> + Clone + Send + Sync + 'static,
+D: Decoder<Item=I, Error=Err> + Clone + Send + Sync + 'static,
+{
+    let peer = Peer::new(read, write, dec.clone(), enc.clone());
+    let mut recv_ch = peer.recv;
+    readers.insert(
+        id, 
+        Box::pin(async_stream::stream!{
+            while let Some(item) = recv_ch.recv().await {
+                yield(item);
+            }
+        }) as Pin<Box<dyn Stream<Item=I>+Send>>
+    );
+    writers.insert(id, peer.send);
+}
+
+/// The peers of the protocol network
+struct NetState<I,O,E,D> {
+    my_id: Replica,
+    /// The addresses of the nodes in the network, including us and the ones
+    /// that we are still connecting to
+    members: HashMap<Replica, String>,
+    /// The nodes that we are connecting to
+    connecting: HashSet<Replica>,
+    readers: ReadStream<I>,
+    writers: HashMap<Replica, UnboundedSender<Arc<O>>>,
+    /// The halves of the connections to the new nodes, until we have both
+    pending_readers: HashMap<Replica, Reader>,
+    pending_writers: HashMap<Replica, Writer>,
+    enc: E,
+    dec: D,
+}
+
+impl<I,O,E,D> NetState<I,O,E,D>
+where I:WireReady + Send + Sync + 'static + Unpin,
+O:WireReady + Clone + Sync + 'static + Unpin,
+E: Encoder<Arc<O>> + Clone + Send + Sync + 'static,
+D: Decoder<Item=I, Error=Err> + Clone + Send + Sync + 'static,
+{
+    fn on_conn(&mut self, conn: Conn, conn_send: &UnboundedSender<Conn>) {
+        match conn {
+            Conn::Reader(id, read) => {
+                // The node may connect before we know that it joined, so keep
+                // the connection until then
+                if self.writers.contains_key(&id) {
+                    // A node that connects again restarted, and its old
+                    // connections are dead
+                    info!("Node {} reconnected", id);
+                    self.readers.remove(&id);
+                    self.writers.remove(&id);
+                }
+                self.pending_readers.insert(id, read);
+                self.connect(id, conn_send);
+            }
+            Conn::Writer(id, write) => {
+                self.connecting.remove(&id);
+                if !self.members.contains_key(&id) {
+                    return;
+                }
+                self.pending_writers.insert(id, write);
+            }
+        }
+        self.try_add_peer();
+    }
+
+    /// Connect to the member `id`, unless we are already connecting to it
+    fn connect(&mut self, id: Replica, conn_send: &UnboundedSender<Conn>) {
+        let addr = match self.members.get(&id) {
+            Some(addr) if id != self.my_id => addr.clone(),
+            _ => return,
+        };
+        if self.connecting.insert(id) {
+            tokio::spawn(connect_later(self.my_id, id, addr, conn_send.clone()));
+        }
+    }
+
+    /// Create the peers of the members for which we have both connections
+    fn try_add_peer(&mut self) {
+        let ready: Vec<_> = self.pending_writers.keys()
+            .filter(|id| self.pending_readers.contains_key(id))
+            .copied()
+            .collect();
+        for id in ready {
+            let read = self.pending_readers.remove(&id).unwrap();
+            let write = self.pending_writers.remove(&id).unwrap();
+            add_peer(id, read, write, &self.enc, &self.dec, &mut self.readers, &mut self.writers);
+            info!("Connected to node {}", id);
+        }
+    }
+
+    fn on_change(&mut self, change: PeerChange, conn_send: &UnboundedSender<Conn>) {
+        match change {
+            PeerChange::Add(id, addr) => {
+                if self.members.contains_key(&id) {
+                    return;
+                }
+                info!("Connecting to the new node {} at {}", id, addr);
+                self.members.insert(id, addr);
+                self.connect(id, conn_send);
+            }
+            PeerChange::Remove(id) => {
+                if self.members.remove(&id).is_none() {
+                    return;
+                }
+                info!("Disconnecting from the removed node {}", id);
+                self.readers.remove(&id);
+                self.writers.remove(&id);
+                self.pending_readers.remove(&id);
+                self.pending_writers.remove(&id);
+            }
+        }
+    }
+
+    /// Send `msg` to `to`, or to everyone if `to` is not in the network
+    fn send(&mut self, to: Replica, msg: Arc<O>) {
+        let mut to_remove = Vec::new();
+        if self.members.contains_key(&to) {
+            // A crashed peer stays disconnected
+            if let Some(writer) = self.writers.get(&to) {
+                if let Err(e) = writer.send(msg) {
+                    log::warn!("Disconnected from peer {} with error {}", to, e);
+                    to_remove.push(to);
+                }
+            }
+        } else {
+            for (id, writer) in &self.writers {
+                if let Err(e) = writer.send(msg.clone()) {
+                    log::warn!("Disconnected from peer {} with error {}", id, e);
+                    to_remove.push(*id);
+                }
+            }
+        }
+        // Remove disconnected peers
+        for id in &to_remove {
+            self.writers.remove(id);
+        }
+    }
+}
+
+async fn protocol_event_loop<I,O,E,D>(
+    mut net: NetState<I,O,E,D>,
     in_send: UnboundedSender<(Replica, I)>,
     mut out_recv: UnboundedReceiver<(Replica, Arc<O>)>,
-    mut reading_net: impl Stream<Item=(Replica, I)>+Unpin,
-    writers: HashMap<Replica, UnboundedSender<Arc<O>>>
-) where I: WireReady
+    mut change_recv: UnboundedReceiver<PeerChange>,
+    conn_send: UnboundedSender<Conn>,
+    mut conn_recv: UnboundedReceiver<Conn>,
+) where I:WireReady + Send + Sync + 'static + Unpin,
+O:WireReady + Clone + Sync + 'static + Unpin,
+E: Encoder<Arc<O>> + Clone + Send + Sync + 'static,
+D: Decoder<Item=I, Error=Err> + Clone + Send + Sync + 'static,
 {
+    let mut changes_open = true;
     loop {
         tokio::select!{
-            opt_in = reading_net.next() => {
+            opt_in = net.readers.next(), if !net.readers.is_empty() => {
                 if let None = opt_in {
                     log::error!(
                         "Failed to read a protocol message from a peer");
-                    std::process::exit(0);
+                    return;
                 }
                 let (id, msg) = opt_in.unwrap();
                 if let Err(e) = in_send.send((id, msg.init())) {
                     log::error!(
                         "Failed to send a protocol message outside the network, with error {}", e);
-                    std::process::exit(0);
+                    return;
                 }
             },
             opt_out = out_recv.recv() => {
                 if let None = opt_out {
                     log::error!(
                         "Failed to read a protocol message to send outside the network");
-                    std::process::exit(0);
+                    return;
                 }
                 let (to, msg) = opt_out.unwrap();
-                if to < num_nodes {
-                    if let Err(_e) = writers[&to].send(msg) {
-                        log::error!("Failed to send msg to peer {}", to);
-                        std::process::exit(0);
-                    }
-                } else {
-                    for (id, writer) in &writers {
-                        if let Err(e) = writer.send(msg.clone()) {
-                            log::error!("Failed to send msg to peer {} with error {}", id, e);
-                            std::process::exit(0);
-                            // TODO Handle disconnection from peer
-                        }
-                    }
+                net.send(to, msg);
+            },
+            change_opt = change_recv.recv(), if changes_open => {
+                match change_opt {
+                    // The network does not change any more
+                    None => changes_open = false,
+                    Some(change) => net.on_change(change, &conn_send),
+                }
+            },
+            conn_opt = conn_recv.recv() => {
+                if let Some(conn) = conn_opt {
+                    net.on_conn(conn, &conn_send);
                 }
             },
         }
@@ -297,7 +543,7 @@ async fn cli_manager(addr: String) -> UnboundedReceiver<TcpStream> {
             };
             if let Err(e) = conn_ch_send.send(conn) {
                 log::error!("Failed to send out new client connection: {}", e);
-                std::process::exit(0);
+                return;
             }
         }
     });
@@ -324,20 +570,20 @@ O: WireReady + Clone+Unpin+Sync + 'static,
             in_opt = read_stream.next(), if read_stream.len() > 0 => {
                 if let None = in_opt {
                     log::warn!("Read stream closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let (_id, msg) = in_opt.unwrap();
                 let msg = msg.init();
                 if let Err(e) = new_in_ch.send(msg) {
                     log::error!("Failed to send an incoming client message outside, with error {}", e);
-                    std::process::exit(0);
+                    return;
                 }
             },
             // We have a new client
             conn_opt = new_conn_ch.recv() => {
                 if let None = conn_opt {
                     log::warn!("New connection channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let conn = conn_opt.unwrap();
                 let new_acceptor = cli_acceptor.clone();
@@ -362,7 +608,7 @@ O: WireReady + Clone+Unpin+Sync + 'static,
             out_opt = send_out_ch.recv() => {
                 if let None = out_opt {
                     log::warn!("Send out channel closed");
-                    std::process::exit(0);
+                    return;
                 }
                 let msg = out_opt.unwrap();
                 for (id, writer) in &writers {
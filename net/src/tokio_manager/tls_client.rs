@@ -104,9 +104,10 @@ O:WireReady + Clone + Sync + 'static + Unpin,
             = unbounded_channel();
         // I hope no new peers will be added later
         let n = self.peers.len();
-        let peers = self.peers.clone();
+        let mut peers = self.peers.clone();
         // log::trace!("Using peers: {:?}", peers);
         tokio::spawn(async move {
+            let mut to_remove = Vec::new();
             loop {
                 tokio::select! {
                     to_send_opt = in_recv.recv() => {
@@ -118,15 +119,19 @@ O:WireReady + Clone + Sync + 'static + Unpin,
                         }
                         let (to, msg) = to_send_opt.unwrap();
                         if (to as usize) < n {
-                            let opt = peers[&to].send(msg);
-                            if let Err(e) = opt {
-                                panic!("failed to send a message out to peer {} with error {}", to, e);
+                            // A crashed server stays disconnected
+                            if let Some(sender) = peers.get(&to) {
+                                if let Err(e) = sender.send(msg) {
+                                    log::warn!("Disconnected from server {} with error {}", to, e);
+                                    to_remove.push(to);
+                                }
                             }
                         } else {
-                            for (_i, sender) in &peers {
+                            for (i, sender) in &peers {
                                 let opt = sender.send(msg.clone());
                                 if let Err(e) = opt {
-                                    panic!("failed to send a message out to peer {} with error {}", to, e);
+                                    log::warn!("Disconnected from server {} with error {}", i, e);
+                                    to_remove.push(*i);
                                 }
                             }
                         }
@@ -144,6 +149,11 @@ O:WireReady + Clone + Sync + 'static + Unpin,
                         }
                     },
                 }
+                // Remove disconnected servers
+                for id in &to_remove {
+                    peers.remove(id);
+                }
+                to_remove.clear();
             }
         });
         (in_send, out_recv)
@@ -1,5 +1,5 @@
-// Baseline stress test for the four consensus protocols shipped in
-// libchatter-rs: Apollo, Artemis, Sync HotStuff, and Opt Sync.
+// Baseline stress test for the consensus protocols shipped in
+// libchatter-rs: Apollo, Artemis, Sync HotStuff, Opt Sync, HotStuff, and PBFT.
 //
 // For each protocol, the harness:
 //   1. Shells out to `genconfig` to produce a fresh Node/Client config set
@@ -10,6 +10,10 @@
 //      `consensus::statistics` on the client's stderr (simple_logger at INFO)
 //   6. Kills nodes, reports result, moves on
 //
+// The restart scenario also kills the last node while the client runs, starts
+// it again from its state directory, and checks that it caught up with the
+// others.
+//
 // The output format mirrors libnet-rs's stress-test so the two baselines
 // can sit side-by-side in README / CV material. The canonical run is
 // captured in `baseline_results.txt` at the repo root.
@@ -32,6 +36,8 @@ enum Protocol {
     Artemis,
     Synchs,
     Optsync,
+    Hotstuff,
+    Pbft,
 }
 
 impl Protocol {
@@ -41,6 +47,8 @@ impl Protocol {
             Protocol::Artemis => "artemis",
             Protocol::Synchs => "synchs",
             Protocol::Optsync => "optsync",
+            Protocol::Hotstuff => "hotstuff",
+            Protocol::Pbft => "pbft",
         }
     }
     fn label(&self) -> &'static str {
@@ -49,11 +57,13 @@ impl Protocol {
             Protocol::Artemis => "Artemis",
             Protocol::Synchs => "Sync HotStuff",
             Protocol::Optsync => "Opt Sync",
+            Protocol::Hotstuff => "HotStuff",
+            Protocol::Pbft => "PBFT",
         }
     }
     // Seconds the nodes wait at startup before entering the protocol loop.
-    // Apollo and Artemis gate protocol entry on the `--sleep` arg; Sync HotStuff
-    // and Opt Sync gate on `config::SLEEP_TIME`, which the binaries compute from
+    // Apollo and Artemis gate protocol entry on the `--sleep` arg; the other
+    // protocols gate on `config::SLEEP_TIME`, which the binaries compute from
     // `--sleep` when present. A larger N needs more slack.
     fn bootstrap_secs(num_nodes: usize) -> u64 {
         5 + (num_nodes as u64).max(3)
@@ -81,12 +91,30 @@ struct BenchConfig {
     payload: usize,
     total_txs: u64,
     window: usize,
+    // Kill and restart a node mid-run (Sync HotStuff only)
+    restart: bool,
 }
 
+impl BenchConfig {
+    fn label(&self) -> String {
+        if self.restart {
+            format!("{} (restart)", self.protocol.label())
+        } else {
+            self.protocol.label().to_string()
+        }
+    }
+}
+
+// When the restarted node goes down after the client starts, and for how long
+const RESTART_AFTER: Duration = Duration::from_secs(2);
+const RESTART_DOWNTIME: Duration = Duration::from_secs(2);
+
 struct BenchResult {
     throughput: f64, // tx / sec
     latency_ms: f64, // avg ms per tx
     wall_elapsed: Duration,
+    // Whether the restarted node caught up, in the restart scenario
+    recovered: Option<bool>,
 }
 
 struct Harness {
@@ -136,14 +164,30 @@ impl Harness {
         let bootstrap = Protocol::bootstrap_secs(cfg.num_nodes);
         let mut nodes: Vec<Child> = Vec::with_capacity(cfg.num_nodes);
         for i in 0..cfg.num_nodes {
-            nodes.push(spawn_node(&self.repo_root, &run_dir, cfg, i, bootstrap).await?);
+            nodes.push(spawn_node(&self.repo_root, &run_dir, cfg, i, bootstrap, "node").await?);
         }
 
         sleep(Duration::from_secs(bootstrap)).await;
 
         let started = Instant::now();
-        let client_out = spawn_client_and_parse(&self.repo_root, &run_dir, cfg).await;
+        let client = spawn_client_and_parse(&self.repo_root, &run_dir, cfg);
+        let restart = async {
+            if cfg.restart {
+                restart_node(&self.repo_root, &run_dir, cfg, &mut nodes).await
+            } else {
+                Ok(())
+            }
+        };
+        let (client_out, restart_out) = tokio::join!(client, restart);
         let wall_elapsed = started.elapsed();
+        restart_out?;
+        let recovered = if cfg.restart {
+            let log = fs::read_to_string(run_dir.join(format!("restarted-{}.log", cfg.num_nodes - 1)))
+                .unwrap_or_default();
+            Some(log.contains("Caught up to height"))
+        } else {
+            None
+        };
 
         for mut n in nodes {
             let _ = n.kill().await;
@@ -156,6 +200,7 @@ impl Harness {
             throughput,
             latency_ms,
             wall_elapsed,
+            recovered,
         })
     }
 }
@@ -213,17 +258,34 @@ fn write_ip_files(
     Ok(())
 }
 
+// Kill the last node a while after the client starts, and start it again
+async fn restart_node(
+    repo_root: &Path,
+    run_dir: &Path,
+    cfg: &BenchConfig,
+    nodes: &mut [Child],
+) -> Result<(), BoxErr> {
+    let i = cfg.num_nodes - 1;
+    sleep(RESTART_AFTER).await;
+    let _ = nodes[i].kill().await;
+    sleep(RESTART_DOWNTIME).await;
+    // The others are up, so there is no need to wait for them
+    nodes[i] = spawn_node(repo_root, run_dir, cfg, i, 1, "restarted").await?;
+    Ok(())
+}
+
 async fn spawn_node(
     repo_root: &Path,
     run_dir: &Path,
     cfg: &BenchConfig,
     i: usize,
     bootstrap_secs: u64,
+    log_name: &str,
 ) -> Result<Child, BoxErr> {
     let bin = repo_root.join(format!("target/release/node-{}", cfg.protocol.short()));
     let config_file = run_dir.join(format!("nodes-{}.json", i));
     let ip_file = run_dir.join("ip_file");
-    let log_path = run_dir.join(format!("node-{}.log", i));
+    let log_path = run_dir.join(format!("{}-{}.log", log_name, i));
     let stdout_fd = std::fs::File::create(&log_path)?;
     let stderr_fd = stdout_fd.try_clone()?;
 
@@ -239,6 +301,9 @@ async fn spawn_node(
     if cfg.protocol.wants_special_client() {
         cmd.arg("-s");
     }
+    if cfg.restart {
+        cmd.arg("--state-dir").arg(run_dir.join("state"));
+    }
     let child = cmd
         .stdout(Stdio::from(stdout_fd))
         .stderr(Stdio::from(stderr_fd))
@@ -371,7 +436,7 @@ fn print_header(cfg: &BenchConfig) {
     println!("┌{}", box_line());
     println!(
         "│ {} (n={}, f={}, blk={}, payload={}B, window={}, txs={})",
-        cfg.protocol.label(),
+        cfg.label(),
         cfg.num_nodes,
         cfg.num_faults,
         cfg.block_size,
@@ -388,6 +453,9 @@ fn print_result(r: &BenchResult) {
     println!("│ Throughput     : {:>12.2} tx/s", r.throughput);
     println!("│ Avg Latency    : {:>12.2} ms/tx", r.latency_ms);
     println!("│ Wall elapsed   : {:>12.2} s", r.wall_elapsed.as_secs_f64());
+    if let Some(recovered) = r.recovered {
+        println!("│ Restarted node : {:>12}", if recovered { "caught up" } else { "STUCK" });
+    }
     println!("└{}", box_line());
 }
 
@@ -397,20 +465,20 @@ fn print_failure(err: &(dyn Error + Send + Sync)) {
 }
 
 fn print_summary(results: &[(BenchConfig, Option<BenchResult>)]) {
-    let w = 90;
+    let w = 100;
     let line = "─".repeat(w);
     println!();
     println!("┌{}", line);
     println!(
-        "│ {:<16} {:>4} {:>4} {:>5} {:>7} {:>16} {:>16}",
+        "│ {:<26} {:>4} {:>4} {:>5} {:>7} {:>16} {:>16}",
         "Protocol", "N", "f", "blk", "txs", "Throughput", "Latency"
     );
     println!("├{}", line);
     for (c, r) in results {
         match r {
             Some(r) => println!(
-                "│ {:<16} {:>4} {:>4} {:>5} {:>7} {:>10.2} tx/s {:>11.2} ms",
-                c.protocol.label(),
+                "│ {:<26} {:>4} {:>4} {:>5} {:>7} {:>10.2} tx/s {:>11.2} ms",
+                c.label(),
                 c.num_nodes,
                 c.num_faults,
                 c.block_size,
@@ -419,8 +487,8 @@ fn print_summary(results: &[(BenchConfig, Option<BenchResult>)]) {
                 r.latency_ms
             ),
             None => println!(
-                "│ {:<16} {:>4} {:>4} {:>5} {:>7} {:>16} {:>16}",
-                c.protocol.label(),
+                "│ {:<26} {:>4} {:>4} {:>5} {:>7} {:>16} {:>16}",
+                c.label(),
                 c.num_nodes,
                 c.num_faults,
                 c.block_size,
@@ -452,9 +520,37 @@ fn build_matrix() -> Vec<BenchConfig> {
                 payload: 0,
                 total_txs: 50_000,
                 window: 10_000,
+                restart: false,
+            });
+        }
+    }
+    // HotStuff and PBFT tolerate fewer faults: they need n >= 3f+1
+    for &protocol in &[Protocol::Hotstuff, Protocol::Pbft] {
+        for &(n, f) in &[(4usize, 1usize), (7, 2)] {
+            v.push(BenchConfig {
+                protocol,
+                num_nodes: n,
+                num_faults: f,
+                block_size: 400,
+                payload: 0,
+                total_txs: 50_000,
+                window: 10_000,
+                restart: false,
             });
         }
     }
+    // Crash recovery: a node goes down mid-run and catches up when it is back
+    v.push(BenchConfig {
+        protocol: Protocol::Synchs,
+        num_nodes: 3,
+        num_faults: 1,
+        block_size: 400,
+        payload: 0,
+        // Long enough for the node to come back while the client runs
+        total_txs: 300_000,
+        window: 10_000,
+        restart: true,
+    });
     v
 }
 
@@ -473,6 +569,10 @@ async fn main() -> Result<(), BoxErr> {
         "client-synchs",
         "node-optsync",
         "client-optsync",
+        "node-hotstuff",
+        "client-hotstuff",
+        "node-pbft",
+        "client-pbft",
     ] {
         let p = repo_root.join(format!("target/release/{}", bin));
         if !p.exists() {
@@ -489,7 +589,7 @@ async fn main() -> Result<(), BoxErr> {
 
     println!("{:=^63}", " libchatter-rs baseline stress test ");
     println!(
-        "Protocols: Apollo, Artemis, Sync HotStuff, Opt Sync.     Loopback (127.0.0.1), release build."
+        "Protocols: Apollo, Artemis, Sync HotStuff, Opt Sync, HotStuff, PBFT.     Loopback (127.0.0.1), release build."
     );
 
     let mut results: Vec<(BenchConfig, Option<BenchResult>)> = Vec::new();
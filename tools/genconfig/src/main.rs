@@ -1,139 +1,17 @@
 // A tool that builds config files for all the nodes and the clients for the
 // protocol.
 
-use crypto::{ed25519, secp256k1};
-use config::{Node, Client};
+use crypto::{bls, ed25519, secp256k1, vrf};
+use config::{Node, Client, LeaderElection};
 use clap::{load_yaml, App};
 use types::Replica;
-use crypto::Algorithm;
+use crypto::{Algorithm, hash::HashAlgorithm};
 use std::error::Error;
 use util::io::*;
-use openssl::{asn1::Asn1Time, bn::{BigNum, MsbOption}, error::ErrorStack, hash::MessageDigest, pkey::{PKey, PKeyRef, Private}, rsa::Rsa, x509::{X509, X509NameBuilder, X509Ref, X509Req, X509ReqBuilder, extension::{AuthorityKeyIdentifier, BasicConstraints, KeyUsage, SubjectAlternativeName, SubjectKeyIdentifier}}};
+use openssl::{pkey::PKey, rsa::Rsa};
+use genconfig::{get_signed_cert, new_root_cert};
 use fnv::FnvHashMap as HashMap;
 
-fn new_root_cert() -> Result<(X509, PKey<Private>), ErrorStack> {
-    let rsa = Rsa::generate(2048)?;
-    let privkey = PKey::from_rsa(rsa)?;
-
-    let mut x509_name = X509NameBuilder::new()?;
-    x509_name.append_entry_by_text("C", "US")?;
-    x509_name.append_entry_by_text("ST", "IN")?;
-    x509_name.append_entry_by_text("O", "Libchatter Test")?;
-    x509_name.append_entry_by_text("CN", "Root")?;
-    let x509_name = x509_name.build();
-
-    let mut cert_builder = X509::builder()?;
-    cert_builder.set_version(2)?;
-    let serial_number = {
-        let mut serial = BigNum::new()?;
-        serial.rand(159, MsbOption::MAYBE_ZERO, false)?;
-        serial.to_asn1_integer()?
-    };
-    cert_builder.set_serial_number(&serial_number)?;
-    cert_builder.set_subject_name(&x509_name)?;
-    cert_builder.set_issuer_name(&x509_name)?;
-    cert_builder.set_pubkey(&privkey)?;
-    let not_before = Asn1Time::days_from_now(0)?;
-    cert_builder.set_not_before(&not_before)?;
-    let not_after = Asn1Time::days_from_now(365)?;
-    cert_builder.set_not_after(&not_after)?;
-
-    cert_builder.append_extension(BasicConstraints::new().critical().ca().build()?)?;
-    cert_builder.append_extension(
-        KeyUsage::new()
-            .critical()
-            .key_cert_sign()
-            .crl_sign()
-            .build()?,
-    )?;
-
-    let subject_key_identifier =
-        SubjectKeyIdentifier::new().build(&cert_builder.x509v3_context(None, None))?;
-    cert_builder.append_extension(subject_key_identifier)?;
-
-    cert_builder.sign(&privkey, MessageDigest::sha256())?;
-    let cert = cert_builder.build();
-
-    Ok((cert, privkey))
-}
-
-/// Make a X509 request with the given private key
-fn mk_request(privkey: &PKey<Private>) -> Result<X509Req, ErrorStack> {
-    let mut req_builder = X509ReqBuilder::new()?;
-    req_builder.set_pubkey(&privkey)?;
-
-    let mut x509_name = X509NameBuilder::new()?;
-    x509_name.append_entry_by_text("C", "US")?;
-    x509_name.append_entry_by_text("ST", "IN")?;
-    x509_name.append_entry_by_text("O", "Nodes")?;
-    x509_name.append_entry_by_text("CN", "nodes.com")?;
-    let x509_name = x509_name.build();
-    req_builder.set_subject_name(&x509_name)?;
-
-    req_builder.sign(&privkey, MessageDigest::sha256())?;
-    let req = req_builder.build();
-    Ok(req)
-}
-
-/// Make a certificate and private key signed by the given CA cert and private key
-fn get_signed_cert(
-    ca_cert: &X509Ref,
-    ca_privkey: &PKeyRef<Private>,
-) -> Result<(X509, PKey<Private>), ErrorStack> {
-    let rsa = Rsa::generate(2048)?;
-    let privkey = PKey::from_rsa(rsa)?;
-
-    let req = mk_request(&privkey)?;
-
-    let mut cert_builder = X509::builder()?;
-    cert_builder.set_version(2)?;
-    let serial_number = {
-        let mut serial = BigNum::new()?;
-        serial.rand(159, MsbOption::MAYBE_ZERO, false)?;
-        serial.to_asn1_integer()?
-    };
-    cert_builder.set_serial_number(&serial_number)?;
-    cert_builder.set_subject_name(req.subject_name())?;
-    cert_builder.set_issuer_name(ca_cert.subject_name())?;
-    cert_builder.set_pubkey(&privkey)?;
-    let not_before = Asn1Time::days_from_now(0)?;
-    cert_builder.set_not_before(&not_before)?;
-    let not_after = Asn1Time::days_from_now(365)?;
-    cert_builder.set_not_after(&not_after)?;
-
-    cert_builder.append_extension(BasicConstraints::new().build()?)?;
-
-    cert_builder.append_extension(
-        KeyUsage::new()
-            .critical()
-            .non_repudiation()
-            .digital_signature()
-            .key_encipherment()
-            .build()?,
-    )?;
-
-    let subject_key_identifier =
-        SubjectKeyIdentifier::new().build(&cert_builder.x509v3_context(Some(ca_cert), None))?;
-    cert_builder.append_extension(subject_key_identifier)?;
-
-    let auth_key_identifier = AuthorityKeyIdentifier::new()
-        .keyid(false)
-        .issuer(false)
-        .build(&cert_builder.x509v3_context(Some(ca_cert), None))?;
-    cert_builder.append_extension(auth_key_identifier)?;
-
-    let subject_alt_name = SubjectAlternativeName::new()
-    //     .dns("*.example.com")
-        .dns("nodes.com")
-        .build(&cert_builder.x509v3_context(Some(ca_cert), None))?;
-    cert_builder.append_extension(subject_alt_name)?;
-
-    cert_builder.sign(&ca_privkey, MessageDigest::sha256())?;
-    let cert = cert_builder.build();
-
-    Ok((cert, privkey))
-}
-
 fn main() -> Result<(), Box<dyn Error>> {
     let yaml = load_yaml!("cli.yml");
     let m = App::from_yaml(yaml).get_matches();
@@ -162,10 +40,18 @@ fn main() -> Result<(), Box<dyn Error>> {
         .expect("no client_base_port specified")
         .parse::<u16>()
         .expect("unable to parse client_base_port into an integer");
-    let t:Algorithm = m.value_of("algorithm")
+    let t:Algorithm = m.value_of("crypto")
         .unwrap_or("ED25519")
         .parse::<Algorithm>()
         .unwrap_or(Algorithm::ED25519);
+    let hash_alg:HashAlgorithm = m.value_of("hash")
+        .unwrap_or("SHA256")
+        .parse::<HashAlgorithm>()
+        .unwrap_or(HashAlgorithm::SHA256);
+    let leader:LeaderElection = m.value_of("leader")
+        .unwrap_or("Deterministic")
+        .parse::<LeaderElection>()
+        .unwrap_or(LeaderElection::Deterministic);
     let out = m.value_of("out_type")
         .unwrap_or("json");
     let target = m.value_of("target")
@@ -174,15 +60,23 @@ fn main() -> Result<(), Box<dyn Error>> {
         .unwrap_or("0")
         .parse()
         .unwrap();
+    let password = if m.is_present("keystore") {
+        Some(config::password_from_env(config::KEYSTORE_PASSWORD_ENV)?)
+    } else {
+        None
+    };
     let mut client = Client::new();
     client.block_size = blocksize;
     client.crypto_alg = t.clone();
+    client.hash_alg = hash_alg;
+    client.leader_election = leader;
     client.num_nodes = num_nodes;
     client.num_faults = num_faults;
 
     let mut node:Vec<Node> = Vec::with_capacity(num_nodes);
 
     let mut pk = HashMap::default();
+    let mut vrf_pk = HashMap::default();
     let mut ip = HashMap::default();
 
     let (cert, privkey) = new_root_cert()?;
@@ -199,6 +93,13 @@ fn main() -> Result<(), Box<dyn Error>> {
         node[i].client_port = client_base_port+(i as u16);
 
         node[i].crypto_alg = t.clone();
+        node[i].hash_alg = hash_alg;
+        node[i].leader_election = leader;
+        if leader == LeaderElection::VRF {
+            let kp = vrf::Keypair::generate();
+            vrf_pk.insert(i as Replica, kp.public().encode().to_vec());
+            node[i].vrf_secret_key = kp.encode().to_vec();
+        }
         match t {
             Algorithm::ED25519 => {
                 let kp = ed25519::Keypair::generate();
@@ -211,7 +112,17 @@ fn main() -> Result<(), Box<dyn Error>> {
                 pk.insert(i as Replica, kp.public().encode().to_vec());
                 node[i].secret_key_bytes = kp.secret().to_bytes().to_vec();
             }
-            _ => (),
+            Algorithm::BLS => {
+                let kp = bls::Keypair::generate();
+                pk.insert(i as Replica, kp.public().encode().to_vec());
+                node[i].secret_key_bytes = kp.encode().to_vec();
+            }
+            Algorithm::RSA => {
+                let rsa = Rsa::generate(crypto::RSA_BITS as u32)?;
+                pk.insert(i as Replica, rsa.public_key_to_der_pkcs1()?);
+                node[i].secret_key_bytes = PKey::from_rsa(rsa)?
+                    .private_key_to_pkcs8()?;
+            }
         };
         ip.insert(i as Replica, 
         format!("{}:{}", "127.0.0.1", base_port+(i as u16))
@@ -231,13 +142,22 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     for i in 0..num_nodes {
         node[i].pk_map = pk.clone();
+        node[i].vrf_pk_map = vrf_pk.clone();
         node[i].net_map = ip.clone();
     }
 
     client.server_pk = pk;
+    client.vrf_pk_map = vrf_pk;
 
     // Write all the files
     for i in 0..num_nodes {
+        node[i].validate()
+            .expect("failed to validate node config");
+        if let Some(password) = &password {
+            let ks_file = format!("keystore-{}.json", i);
+            node[i].seal_keystore(ks_file.clone(), password)?
+                .to_file(format!("{}/{}", target, ks_file))?;
+        }
         match out {
             "json" => {
                 let filename = format!("{}/nodes-{}.json",target,i);
@@ -257,8 +177,6 @@ fn main() -> Result<(), Box<dyn Error>> {
             },
             _ => (),
         }
-        node[i].validate()
-            .expect("failed to validate node config");
     }
 
     // Write the client file
@@ -286,15 +204,3 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
-
-#[test]
-fn test_codec() -> Result<(), Box<dyn Error>>{
-    use rustls::{Certificate, ClientConfig};
-
-    let (cert, _key) = new_root_cert()?;
-    let data = cert.to_der()?;
-    let ok = Certificate(data);
-    let mut config = ClientConfig::new();
-    config.root_store.add(&ok)?;
-    Ok(())
-}
\ No newline at end of file
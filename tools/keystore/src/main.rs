@@ -0,0 +1,96 @@
+// A tool to manage the encrypted keystores that hold the secret keys of the
+// nodes.
+
+use clap::{load_yaml, App, ArgMatches};
+use config::{Keystore, Node, KEYSTORE_PASSWORD_ENV, password_from_env};
+use std::error::Error;
+use std::path::Path;
+use util::io::*;
+
+/// The environment variable with the new password when re-encrypting
+const KEYSTORE_NEW_PASSWORD_ENV: &str = "LIBCHATTER_KEYSTORE_NEW_PASSWORD";
+
+fn create(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let conf_str = m.value_of("config")
+        .expect("no config file specified");
+    let conf_file = Path::new(conf_str);
+    let ext = conf_file
+        .extension()
+        .expect("Unable to get file extension")
+        .to_str()
+        .expect("Failed to convert the extension into ascii string");
+    let mut config = match ext {
+        "json" => Node::from_json(conf_str.to_string()),
+        "dat" => Node::from_bin(conf_str.to_string()),
+        "toml" => Node::from_toml(conf_str.to_string()),
+        "yaml" | "yml" => Node::from_yaml(conf_str.to_string()),
+        _ => panic!("Invalid config file extension"),
+    };
+    if config.keystore.is_some() {
+        return Err(format!("{} already uses a keystore", conf_str).into());
+    }
+    config.validate()?;
+    let ks_file = match m.value_of("out") {
+        Some(x) => x.to_string(),
+        None => format!("keystore-{}.json", config.id),
+    };
+    let dir = conf_file.parent().unwrap_or_else(|| Path::new("."));
+    let password = password_from_env(KEYSTORE_PASSWORD_ENV)?;
+    let ks = config.seal_keystore(ks_file.clone(), &password)?;
+    // Write the keystore before removing the keys from the config
+    ks.to_file(dir.join(&ks_file))?;
+    let conf_out = conf_str.to_string();
+    match ext {
+        "json" => write_json(conf_out, &config),
+        "dat" => write_bin(conf_out, &config),
+        "toml" => write_toml(conf_out, &config),
+        _ => write_yaml(conf_out, &config),
+    }
+    println!("Moved the secret keys of node {} into {}", config.id, dir.join(&ks_file).display());
+    Ok(())
+}
+
+fn inspect(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let file = m.value_of("keystore")
+        .expect("no keystore file specified");
+    let ks = Keystore::from_file(file)?;
+    println!("version:    {}", ks.version);
+    println!("node:       {}", ks.id);
+    println!("algorithm:  {:?}", ks.crypto_alg);
+    println!("kdf:        argon2id (m_cost={} KiB, t_cost={}, p_cost={})",
+        ks.kdf.m_cost, ks.kdf.t_cost, ks.kdf.p_cost);
+    println!("cipher:     xchacha20-poly1305 ({} bytes)", ks.ciphertext.len());
+    match password_from_env(KEYSTORE_PASSWORD_ENV) {
+        Ok(password) => {
+            let secrets = ks.open(&password)?;
+            println!("password:   ok (secret key: {} bytes, certificate key: {} bytes)",
+                secrets.secret_key_bytes.len(), secrets.my_cert_key.len());
+        }
+        Err(_) => println!("password:   not checked ({} is not set)", KEYSTORE_PASSWORD_ENV),
+    }
+    Ok(())
+}
+
+fn reencrypt(m: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    let file = m.value_of("keystore")
+        .expect("no keystore file specified");
+    let ks = Keystore::from_file(file)?;
+    let old = password_from_env(KEYSTORE_PASSWORD_ENV)?;
+    let new = password_from_env(KEYSTORE_NEW_PASSWORD_ENV)?;
+    let ks = ks.reencrypt(&old, &new)?;
+    // Replaces the file only once the new keystore is fully written
+    ks.to_file(file)?;
+    println!("Re-encrypted {}", file);
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let yaml = load_yaml!("cli.yml");
+    let m = App::from_yaml(yaml).get_matches();
+    match m.subcommand() {
+        ("create", Some(sub)) => create(sub),
+        ("inspect", Some(sub)) => inspect(sub),
+        ("reencrypt", Some(sub)) => reencrypt(sub),
+        _ => unreachable!("clap requires a subcommand"),
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{Block, Certificate, Height, Transaction};
+use crypto::hash::Hash;
+use std::sync::Arc;
+
+/// A block that committed at a node, as the node publishes it to the
+/// application that embeds it. A node publishes its committed blocks once
+/// each, in the order of the chain.
+#[derive(Debug, Clone)]
+pub struct CommittedBlock {
+    pub block: Arc<Block>,
+    /// The transactions of the block in order, or `None` for the ones that
+    /// the node never received, e.g. for blocks that it caught up on
+    pub txs: Vec<Option<Arc<Transaction>>>,
+    /// A certificate for the block or for a block that extends it, if the
+    /// commit rule of the protocol has one
+    pub justification: Option<Certificate>,
+}
+
+impl CommittedBlock {
+    pub fn height(&self) -> Height {
+        self.block.header.height
+    }
+
+    pub fn hash(&self) -> Hash {
+        self.block.hash
+    }
+}
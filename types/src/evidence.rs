@@ -0,0 +1,258 @@
+use serde::{Serialize, Deserialize};
+use crypto::{PublicKey, hash::{Hash, ser_and_hash}};
+use std::{collections::HashMap, hash::BuildHasher};
+use crate::{Replica, View, Vote};
+
+/// A statement that a node signs in one of the protocols, and which it must
+/// sign at most once for every slot (see `Statement::conflicts_with`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// An Apollo proposal: one block per round
+    RoundProposal {
+        round: usize,
+        block_hash: Hash,
+    },
+    /// A Sync HotStuff or Opt Sync proposal: one block per view and parent
+    ViewProposal {
+        view: View,
+        parent: Hash,
+        block_hash: Hash,
+    },
+    /// An Artemis UCR vote: one block per view and round
+    UCRVote {
+        view: View,
+        round: usize,
+        hash: Hash,
+    },
+    /// A PBFT pre-prepare: one block per view and sequence number
+    SeqProposal {
+        view: View,
+        seq: usize,
+        block_hash: Hash,
+    },
+}
+
+impl Statement {
+    /// The data that the node signs for this statement
+    pub fn sign_data(&self) -> Vec<u8> {
+        match self {
+            Statement::RoundProposal{round, block_hash} => {
+                ser_and_hash(&(round, block_hash)).to_vec()
+            }
+            Statement::ViewProposal{view, parent, block_hash} => {
+                bincode::serialize(&(view, parent, block_hash))
+                    .expect("failed to serialize the proposal")
+            }
+            Statement::UCRVote{view, round, hash} => {
+                ser_and_hash(&(hash, round, view)).to_vec()
+            }
+            Statement::SeqProposal{view, seq, block_hash} => {
+                bincode::serialize(&(view, seq, block_hash))
+                    .expect("failed to serialize the pre-prepare")
+            }
+        }
+    }
+
+    /// Whether the two statements are for the same slot but with different
+    /// values, which an honest node never signs
+    pub fn conflicts_with(&self, other: &Statement) -> bool {
+        match (self, other) {
+            (Statement::RoundProposal{round: r1, block_hash: h1},
+                Statement::RoundProposal{round: r2, block_hash: h2}) => {
+                r1 == r2 && h1 != h2
+            }
+            (Statement::ViewProposal{view: v1, parent: p1, block_hash: h1},
+                Statement::ViewProposal{view: v2, parent: p2, block_hash: h2}) => {
+                v1 == v2 && p1 == p2 && h1 != h2
+            }
+            (Statement::UCRVote{view: v1, round: r1, hash: h1},
+                Statement::UCRVote{view: v2, round: r2, hash: h2}) => {
+                v1 == v2 && r1 == r2 && h1 != h2
+            }
+            (Statement::SeqProposal{view: v1, seq: s1, block_hash: h1},
+                Statement::SeqProposal{view: v2, seq: s2, block_hash: h2}) => {
+                v1 == v2 && s1 == s2 && h1 != h2
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A statement along with the signature of its origin
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Signed {
+    pub statement: Statement,
+    pub sig: Vote,
+}
+
+impl Signed {
+    pub fn new(statement: Statement, origin: Replica, auth: Vec<u8>) -> Self {
+        Signed {
+            statement,
+            sig: Vote { origin, auth },
+        }
+    }
+
+    pub fn is_correctly_signed(&self, pk: &PublicKey) -> bool {
+        pk.verify(&self.statement.sign_data(), &self.sig.auth)
+    }
+}
+
+/// A proof that a node misbehaved: two conflicting statements signed by it.
+///
+/// Anyone with the public keys of the nodes can check the proof, without
+/// any protocol state.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Evidence {
+    pub first: Signed,
+    pub second: Signed,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EvidenceError {
+    // origins of the two statements
+    DifferentOrigins(Replica, Replica),
+    // the statements are not for the same slot, or are the same
+    NoConflict,
+    // origin whose public key is unknown
+    UnknownOrigin(Replica),
+    // origin whose signature does not verify
+    InvalidSignature(Replica),
+}
+
+impl std::fmt::Display for EvidenceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            EvidenceError::DifferentOrigins(r1, r2) =>
+            write!(f, "statements from different nodes {} and {}", r1, r2),
+            EvidenceError::NoConflict =>
+            write!(f, "the statements do not conflict"),
+            EvidenceError::UnknownOrigin(r) =>
+            write!(f, "no public key for {} replica", r),
+            EvidenceError::InvalidSignature(r) =>
+            write!(f, "invalid signature from {} replica", r),
+        }
+    }
+}
+
+impl std::error::Error for EvidenceError {}
+
+impl Evidence {
+    pub fn new(first: Signed, second: Signed) -> Self {
+        Evidence { first, second }
+    }
+
+    /// The node that misbehaved, if the evidence is valid
+    pub fn offender(&self) -> Replica {
+        self.first.sig.origin
+    }
+
+    /// Check that the evidence proves that `offender()` misbehaved
+    pub fn verify<S: BuildHasher>(&self, pk_map: &HashMap<Replica, PublicKey, S>) -> Result<(), EvidenceError> {
+        let origin = self.first.sig.origin;
+        if self.second.sig.origin != origin {
+            return Err(EvidenceError::DifferentOrigins(origin, self.second.sig.origin));
+        }
+        if !self.first.statement.conflicts_with(&self.second.statement) {
+            return Err(EvidenceError::NoConflict);
+        }
+        let pk = pk_map.get(&origin)
+            .ok_or(EvidenceError::UnknownOrigin(origin))?;
+        if !self.first.is_correctly_signed(pk) || !self.second.is_correctly_signed(pk) {
+            return Err(EvidenceError::InvalidSignature(origin));
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crypto::{Keypair, hash::EMPTY_HASH};
+
+    fn keys() -> Vec<Keypair> {
+        (0..4).map(|_| Keypair::generate_ed25519()).collect()
+    }
+
+    fn pk_map(keys: &[Keypair]) -> HashMap<Replica, PublicKey> {
+        keys.iter().enumerate().map(|(i, k)| (i, k.public())).collect()
+    }
+
+    fn sign(keys: &[Keypair], origin: Replica, statement: Statement) -> Signed {
+        let auth = keys[origin].sign(&statement.sign_data()).unwrap();
+        Signed::new(statement, origin, auth)
+    }
+
+    fn proposal(round: usize, seed: u8) -> Statement {
+        Statement::RoundProposal { round, block_hash: [seed; 32] }
+    }
+
+    #[test]
+    fn conflicting_statements_are_evidence() {
+        let keys = keys();
+        let e = Evidence::new(sign(&keys, 1, proposal(3, 1)), sign(&keys, 1, proposal(3, 2)));
+        assert_eq!(e.verify(&pk_map(&keys)), Ok(()));
+        assert_eq!(e.offender(), 1);
+
+        let vote = |view, round, seed| Statement::UCRVote { view, round, hash: [seed; 32] };
+        let e = Evidence::new(sign(&keys, 2, vote(1, 4, 1)), sign(&keys, 2, vote(1, 4, 2)));
+        assert_eq!(e.verify(&pk_map(&keys)), Ok(()));
+    }
+
+    #[test]
+    fn the_same_statement_twice_is_not_evidence() {
+        let keys = keys();
+        let s = sign(&keys, 1, proposal(3, 1));
+        let e = Evidence::new(s.clone(), s);
+        assert_eq!(e.verify(&pk_map(&keys)), Err(EvidenceError::NoConflict));
+    }
+
+    #[test]
+    fn statements_for_different_slots_are_not_evidence() {
+        let keys = keys();
+        let pks = pk_map(&keys);
+        // Different rounds
+        let e = Evidence::new(sign(&keys, 1, proposal(3, 1)), sign(&keys, 1, proposal(4, 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::NoConflict));
+        // Different heights (sequence numbers)
+        let pre_prepare = |seq, seed| Statement::SeqProposal { view: 1, seq, block_hash: [seed; 32] };
+        let e = Evidence::new(sign(&keys, 1, pre_prepare(5, 1)), sign(&keys, 1, pre_prepare(6, 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::NoConflict));
+        // Different parents, i.e. blocks at different heights
+        let view = |parent, seed| Statement::ViewProposal { view: 2, parent, block_hash: [seed; 32] };
+        let e = Evidence::new(sign(&keys, 1, view(EMPTY_HASH, 1)), sign(&keys, 1, view([1; 32], 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::NoConflict));
+        // Different kinds of statements
+        let e = Evidence::new(sign(&keys, 1, proposal(2, 1)), sign(&keys, 1, view(EMPTY_HASH, 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::NoConflict));
+        // Different nodes
+        let e = Evidence::new(sign(&keys, 1, proposal(3, 1)), sign(&keys, 2, proposal(3, 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::DifferentOrigins(1, 2)));
+    }
+
+    #[test]
+    fn evidence_must_be_signed_by_the_offender() {
+        let keys = keys();
+        let pks = pk_map(&keys);
+        // Signed by node 2 in the name of node 1
+        let mut forged = sign(&keys, 2, proposal(3, 2));
+        forged.sig.origin = 1;
+        let e = Evidence::new(sign(&keys, 1, proposal(3, 1)), forged);
+        assert_eq!(e.verify(&pks), Err(EvidenceError::InvalidSignature(1)));
+        // A signature of another statement
+        let mut replayed = sign(&keys, 1, proposal(3, 1));
+        replayed.statement = proposal(3, 2);
+        let e = Evidence::new(sign(&keys, 1, proposal(3, 1)), replayed);
+        assert_eq!(e.verify(&pks), Err(EvidenceError::InvalidSignature(1)));
+    }
+
+    #[test]
+    fn evidence_against_unknown_nodes_is_rejected() {
+        let keys = keys();
+        let mut pks = pk_map(&keys);
+        pks.remove(&3);
+        let e = Evidence::new(sign(&keys, 3, proposal(3, 1)), sign(&keys, 3, proposal(3, 2)));
+        assert_eq!(e.verify(&pks), Err(EvidenceError::UnknownOrigin(3)));
+    }
+}
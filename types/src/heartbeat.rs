@@ -0,0 +1,22 @@
+use serde::{Serialize, Deserialize};
+use crypto::hash::Hash;
+
+/// A timestamped heartbeat, which the nodes exchange to measure the delay of
+/// the network. The times are in microseconds since the UNIX epoch, on the
+/// clock of the node that took them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Heartbeat {
+    /// Sent by a node at `sent`, on the chain with the `genesis` hash (see
+    /// `crate::genesis_hash`)
+    Ping {
+        seq: u64,
+        sent: u64,
+        genesis: Hash,
+    },
+    /// The reply to a ping, which the replier received at `received`
+    Pong {
+        seq: u64,
+        sent: u64,
+        received: u64,
+    },
+}
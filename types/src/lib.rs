@@ -7,4 +7,18 @@ pub use msg::*;
 mod traits;
 pub use traits::*;
 
-pub type View = usize;
\ No newline at end of file
+mod evidence;
+pub use evidence::*;
+
+mod heartbeat;
+pub use heartbeat::*;
+
+mod reconfig;
+pub use reconfig::*;
+
+mod commit;
+pub use commit::*;
+
+pub type View = usize;
+mod quorum;
+pub use quorum::*;
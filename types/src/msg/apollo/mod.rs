@@ -11,6 +11,10 @@ pub type Block = super::Block;
 pub const GENESIS_BLOCK: Block = super::GENESIS_BLOCK;
 
 pub type Vote = crate::Vote;
+pub type Certificate = super::Certificate;
+pub type Evidence = crate::Evidence;
+pub type Heartbeat = crate::Heartbeat;
+pub type CertType = super::CertType;
 pub type Height = crate::Height;
 pub type Transaction = super::Transaction;
 pub type Replica = crate::Replica;
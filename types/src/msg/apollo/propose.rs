@@ -1,13 +1,17 @@
 use serde::{Serialize, Deserialize};
-use crypto::{Keypair, PublicKey, hash::Hash};
+use crypto::{Keypair, PublicKey, hash::Hash, vrf};
 use std::sync::Arc;
 use super::*;
+use crate::{Signed, Statement};
 
 #[derive(Serialize, Deserialize, Debug,Clone)]
 pub struct Propose {
     pub sig: Vote,
     pub round: Round,
     pub block_hash: Hash,
+    /// The VRF proof of the leader for this round, when the leaders are
+    /// elected with a VRF
+    pub vrf: Option<vrf::Proof>,
 
     #[serde(skip)]
     pub block: Option<Arc<Block>>,
@@ -22,19 +26,39 @@ impl Propose {
                 origin: 0,
             },
             block_hash,
+            vrf: None,
             block:None,
         }
     }
 
+    /// The statement that the leader signs for this proposal
+    pub fn statement(&self) -> Statement {
+        Statement::RoundProposal {
+            round: self.round,
+            block_hash: self.block_hash,
+        }
+    }
+
     /// How to generate a signature for the proposal
+    ///
+    /// The signature covers the round along with the block, so that the block
+    /// cannot be replayed in another round of the same leader. Two signed
+    /// proposals for the same round are a proof of equivocation.
     pub fn sign_block(&mut self, b: &Block, sk: &Keypair) {
-        let auth = sk.sign(&crypto::hash::ser_and_hash(b))
+        self.block_hash = b.hash;
+        let auth = sk.sign(&self.statement().sign_data())
             .expect("Failed to sign a block");
         self.sig.auth = auth;
     }
 
     /// Check the signature of this proposal on the block
     pub fn check_sig(&self, b:&Block, pk: &PublicKey) -> bool {
-        pk.verify(&crypto::hash::ser_and_hash(b), &self.sig.auth)
+        self.block_hash == b.hash &&
+            pk.verify(&self.statement().sign_data(), &self.sig.auth)
+    }
+
+    /// The signed statement of this proposal, to use as evidence
+    pub fn signed(&self) -> Signed {
+        Signed::new(self.statement(), self.sig.origin, self.sig.auth.clone())
     }
 }
\ No newline at end of file
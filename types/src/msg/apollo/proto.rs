@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
-use super::{Block, Propose, Vote};
-use crate::WireReady;
+use super::{Block, Certificate, Evidence, Heartbeat, Propose, Round};
+use crate::{ByzantineMsg, HeartbeatMsg, MsgKind, WireReady};
+use crypto::Keypair;
 use crypto::hash::Hash;
 use std::sync::Arc;
 
@@ -19,8 +20,77 @@ pub enum ProtocolMsg {
     RawResponse(u64, Propose, Block),
     Response(u64, Propose),
 
-    // Blame a node
-    Blame(Vote),
+    /// Blame the leader of a round: a certificate with the vote of the sender
+    /// on `CertType::Blame(leader, round)`
+    Blame(Certificate),
+    /// f+1 blames for a round, on which the nodes quit the round
+    QuitView(Certificate),
+
+    /// The highest proposal of a node, sent to the leader of the round that
+    /// follows a quit round
+    RawStatus(Round, Propose, Block),
+    Status(Round, Propose),
+
+    /// A proof that a node misbehaved, forwarded by everyone the first time
+    /// they hear about its offender
+    Evidence(Evidence),
+
+    /// A heartbeat, to measure the delay of the network. It never reaches
+    /// the reactor.
+    Heartbeat(Heartbeat),
+}
+
+impl HeartbeatMsg for ProtocolMsg {
+    fn heartbeat(hb: Heartbeat) -> Self {
+        ProtocolMsg::Heartbeat(hb)
+    }
+
+    fn into_heartbeat(self) -> Result<Heartbeat, Self> {
+        match self {
+            ProtocolMsg::Heartbeat(hb) => Ok(hb),
+            x => Err(x),
+        }
+    }
+}
+
+impl ByzantineMsg for ProtocolMsg {
+    fn kind(&self) -> MsgKind {
+        match self {
+            ProtocolMsg::RawNewProposal(..) => MsgKind::Proposal,
+            // Apollo has no votes
+            _ => MsgKind::Other,
+        }
+    }
+
+    fn conflicting(&self, sk: &Keypair) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let b = b.twin();
+                let mut p = p.clone();
+                p.sign_block(&b, sk);
+                Some(ProtocolMsg::RawNewProposal(p, b))
+            }
+            _ => None,
+        }
+    }
+
+    fn malformed(&self) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let mut p = p.clone();
+                p.sig.auth = crate::broken(&p.sig.auth);
+                Some(ProtocolMsg::RawNewProposal(p, b.clone()))
+            }
+            ProtocolMsg::Relay(p) => {
+                let mut p = p.clone();
+                p.sig.auth = crate::broken(&p.sig.auth);
+                Some(ProtocolMsg::Relay(p))
+            }
+            ProtocolMsg::Blame(c) => c.malformed().map(ProtocolMsg::Blame),
+            ProtocolMsg::QuitView(c) => c.malformed().map(ProtocolMsg::QuitView),
+            _ => None,
+        }
+    }
 }
 
 impl WireReady for ProtocolMsg {
@@ -42,6 +112,11 @@ impl WireReady for ProtocolMsg {
                 prop.block = Some(Arc::new(block));
                 ProtocolMsg::Response(_i, prop)
             },
+            ProtocolMsg::RawStatus(r, mut prop, mut block) => {
+                block.hash = block.compute_hash();
+                prop.block = Some(Arc::new(block));
+                ProtocolMsg::Status(r, prop)
+            },
             _x => _x,
         }
     }
@@ -11,6 +11,12 @@ pub enum ClientMsg {
     RawNewBlock(UCRVote, Vec<(Block, Payload)>),
     /// A processed message
     NewBlock(UCRVote, Vec<(Block, Payload)>),
+    /// RawVoteChain contains the UCR votes of a node since its last commit,
+    /// and the blocks since then without payloads. A new view leader starts
+    /// its view with one, and the round leaders resend theirs in case the
+    /// client missed a vote.
+    RawVoteChain(Vec<UCRVote>, Vec<(Block, Payload)>),
+    VoteChain(Vec<UCRVote>, Vec<(Block, Payload)>),
     /// Request a block with Hash
     RequestBlock(Hash),
     /// Respond with an object with Hash
@@ -45,6 +51,16 @@ impl WireReady for ClientMsg {
                 }
                 ClientMsg::NewBlock(vote, block_vec)
             },
+            ClientMsg::RawVoteChain(votes, block_vec) => {
+                if votes.is_empty() {
+                    log::warn!("Got a vote chain with no votes");
+                    return ClientMsg::Invalid;
+                }
+                let block_vec = block_vec.into_iter().map(|(block, pl)| {
+                    (block.init(), pl)
+                }).collect();
+                ClientMsg::VoteChain(votes, block_vec)
+            },
             ClientMsg::RawResponseBlock(h, block) => {
                 let block = block.init();
                 if block.get_hash() == h {
@@ -15,6 +15,10 @@ pub use client::*;
 pub type Height = crate::Height;
 pub type Transaction = super::Transaction;
 pub type Vote = super::Vote;
+pub type Certificate = super::Certificate;
+pub type Evidence = crate::Evidence;
+pub type Heartbeat = crate::Heartbeat;
+pub type CertType = super::CertType;
 pub type Replica = crate::Replica;
 pub type Storage = super::Storage<Block, Transaction>;
 pub type Payload = super::Payload;
@@ -1,7 +1,8 @@
 use serde::{Serialize, Deserialize};
-use super::{Block, UCRVote, Vote};
-use crate::WireReady;
-use crypto::hash::Hash;
+use super::{Block, Certificate, Evidence, Heartbeat, UCRVote, View};
+use crate::{ByzantineMsg, HeartbeatMsg, MsgKind, WireReady};
+use crypto::Keypair;
+use crypto::hash::{Hash, ser_and_hash};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[repr(u8)]
@@ -19,7 +20,25 @@ pub enum ProtocolMsg {
     /// Forward a vote from the Round leader
     Relay(UCRVote),
 
-    Blame(Vote),
+    /// Blame the leader of a view: a certificate with the vote of the sender
+    /// on `CertType::Blame(view leader, view)`
+    Blame(Certificate),
+    /// f+1 blames for a view, on which the nodes quit the view
+    QuitView(Certificate),
+
+    /// The UCR votes of a node since its last commit, and the blocks they
+    /// vote for, sent to the leader of the next view
+    RawStatus(View, Vec<UCRVote>, Vec<Block>),
+    Status(View, Vec<UCRVote>, Vec<Block>),
+
+    /// The start of a view. It contains:
+    /// - The quit certificate of the previous view
+    /// - The highest UCR vote chain, ending with the vote of the new view
+    ///   leader
+    /// - The blocks that the votes vote for
+    RawNewView(Certificate, Vec<UCRVote>, Vec<Block>),
+    NewView(Certificate, Vec<UCRVote>, Vec<Block>),
+
     /// Request contains
     /// - Request ID
     /// - Hash of the block
@@ -33,10 +52,87 @@ pub enum ProtocolMsg {
     /// - Block
     Response(u64, Block),
 
+    /// A proof that a node misbehaved, forwarded by everyone the first time
+    /// they hear about its offender
+    Evidence(Evidence),
+
+    /// A heartbeat, to measure the delay of the network. It never reaches
+    /// the reactor.
+    Heartbeat(Heartbeat),
+
     /// Invalid messages
     Invalid
 }
 
+impl HeartbeatMsg for ProtocolMsg {
+    fn heartbeat(hb: Heartbeat) -> Self {
+        ProtocolMsg::Heartbeat(hb)
+    }
+
+    fn into_heartbeat(self) -> Result<Heartbeat, Self> {
+        match self {
+            ProtocolMsg::Heartbeat(hb) => Ok(hb),
+            x => Err(x),
+        }
+    }
+}
+
+impl ByzantineMsg for ProtocolMsg {
+    fn kind(&self) -> MsgKind {
+        match self {
+            // The blocks of the view leader and the votes of the round
+            // leaders both propose blocks
+            ProtocolMsg::RawNewBlock(..) |
+            ProtocolMsg::RawUCRVote(..) => MsgKind::Proposal,
+            _ => MsgKind::Other,
+        }
+    }
+
+    fn conflicting(&self, sk: &Keypair) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewBlock(b) => {
+                let mut b = Block {
+                    blk: b.blk.twin(),
+                    sig: b.sig.clone(),
+                };
+                b.sign(sk);
+                Some(ProtocolMsg::RawNewBlock(b))
+            }
+            ProtocolMsg::RawUCRVote(v) => {
+                let mut v = v.clone();
+                v.hash = ser_and_hash(&v.hash);
+                v.compute_sig(sk);
+                Some(ProtocolMsg::RawUCRVote(v))
+            }
+            _ => None,
+        }
+    }
+
+    fn malformed(&self) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewBlock(b) => {
+                let mut b = b.clone();
+                b.sig.auth = crate::broken(&b.sig.auth);
+                Some(ProtocolMsg::RawNewBlock(b))
+            }
+            // The vote no longer matches its signature
+            ProtocolMsg::RawUCRVote(v) => {
+                let mut v = v.clone();
+                v.hash = ser_and_hash(&v.hash);
+                Some(ProtocolMsg::RawUCRVote(v))
+            }
+            ProtocolMsg::Relay(v) => {
+                let mut v = v.clone();
+                v.hash = ser_and_hash(&v.hash);
+                Some(ProtocolMsg::Relay(v))
+            }
+            ProtocolMsg::Blame(c) => c.malformed().map(ProtocolMsg::Blame),
+            ProtocolMsg::QuitView(c) => c.malformed().map(ProtocolMsg::QuitView),
+            _ => None,
+        }
+    }
+}
+
 impl WireReady for ProtocolMsg {
     fn from_bytes(bytes:&[u8]) -> Self {
         let c:Self = bincode::deserialize(bytes)
@@ -56,6 +152,14 @@ impl WireReady for ProtocolMsg {
             ProtocolMsg::RawUCRVote(v) => {
                 ProtocolMsg::UCRVote(v)
             }
+            ProtocolMsg::RawStatus(view, votes, blocks) => {
+                let blocks = blocks.into_iter().map(|b| b.init()).collect();
+                ProtocolMsg::Status(view, votes, blocks)
+            }
+            ProtocolMsg::RawNewView(c, votes, blocks) => {
+                let blocks = blocks.into_iter().map(|b| b.init()).collect();
+                ProtocolMsg::NewView(c, votes, blocks)
+            }
             _x => _x,
         }
     }
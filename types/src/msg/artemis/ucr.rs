@@ -1,7 +1,8 @@
-use crypto::{Keypair, PublicKey, hash::{EMPTY_HASH, Hash, ser_and_hash}};
+use crypto::{Keypair, PublicKey, hash::{EMPTY_HASH, Hash}, vrf};
 use serde::{Serialize, Deserialize};
 
-use super::{Round, View, Vote};
+use super::{Replica, Round, View, Vote};
+use crate::{Signed, Statement};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 /// UCRVote message is sent by the round leader
@@ -10,52 +11,56 @@ use super::{Round, View, Vote};
 /// - View: The view number for voting
 /// - Round: The UCR round for this vote
 /// - Vote: A vote on this message 
+/// - VRF: The VRF proof of the round leader (only with VRF leader election)
 pub struct UCRVote {
     pub hash: Hash,
     pub round: Round,
     pub view: View,
+    pub vrf: Option<vrf::Proof>,
     // Private so you are forced to use `compute_sig`
     vote: Vote,
 }
 
-#[derive(Serialize, Deserialize)]
-struct InternalUCRVote {
-    hash: Hash,
-    round: Round, 
-    view: View,
-}
-
 impl UCRVote {
 
     /// Compute and update the signature on this vote
     /// Ensure that the `hash`, `view` and `round` are set
     /// If you change any of the above, don't forget to update the signature
     pub fn compute_sig(&mut self, sk:&Keypair) {
-        let ser = ser_and_hash(&self.to_internal());
+        let ser = self.statement().sign_data();
         self.vote.auth = sk.sign(&ser)
             .expect("Failed to sign a ucr message");
     }
 
     /// Check the signature on this message
     pub fn check_sig(&self, pk: &PublicKey) -> bool {
-        let ser = ser_and_hash(&self.to_internal());
+        let ser = self.statement().sign_data();
         pk.verify(&ser, &self.vote.auth)
     }
 
-    fn to_internal(&self) -> InternalUCRVote {
-        InternalUCRVote{
-            hash: self.hash,
-            round: self.round,
+    /// The statement that the round leader signs. Two votes for the same view
+    /// and round but different blocks are an equivocation.
+    pub fn statement(&self) -> Statement {
+        Statement::UCRVote {
             view: self.view,
+            round: self.round,
+            hash: self.hash,
         }
     }
 
+    /// The signed statement of this vote by the round leader `origin`, to use
+    /// as evidence
+    pub fn signed(&self, origin: Replica) -> Signed {
+        Signed::new(self.statement(), origin, self.vote.auth.clone())
+    }
+
     /// Get an empty vote instance with defaults
     pub fn new() -> Self {
         Self {
             hash: EMPTY_HASH,
             round: 0,
             view: 0,
+            vrf: None,
             vote: Vote{
                 auth: Vec::new(),
                 origin: 0,
@@ -1,6 +1,6 @@
 use serde::{Serialize, Deserialize};
 use super::{Transaction, Certificate};
-use crate::{BlockTrait, WireReady, protocol::{Replica, Height}};
+use crate::{BlockTrait, Evidence, Reconfig, WireReady, protocol::{Replica, Height}};
 use crypto::hash::{EMPTY_HASH, Hash};
 use std::sync::Arc;
 
@@ -26,6 +26,15 @@ impl Block {
     pub fn compute_hash(&self) -> Hash {
         crypto::hash::ser_and_hash(self)
     }
+
+    /// A block that differs from this one only in its hash, for the nodes
+    /// that equivocate on purpose
+    pub(crate) fn twin(&self) -> Block {
+        let mut b = self.clone();
+        b.header.extra.push(0);
+        b.hash = b.compute_hash();
+        b
+    }
 }
 
 pub const GENESIS_BLOCK: Block = Block{
@@ -35,6 +44,8 @@ pub const GENESIS_BLOCK: Block = Block{
         author: 0,
         height: 0,
         blame_certificates: Vec::new(),
+        evidence: Vec::new(),
+        reconfig: Vec::new(),
     },
     body: Body{
         tx_hashes: Vec::new(),
@@ -42,6 +53,13 @@ pub const GENESIS_BLOCK: Block = Block{
     hash: EMPTY_HASH,
 };
 
+/// The hash of the genesis block together with the hash function of the
+/// chain, which the nodes compare when they connect: nodes with different
+/// genesis hashes are not on the same chain.
+pub fn genesis_hash() -> Hash {
+    crypto::hash::ser_and_hash(&(crypto::hash::hash_algorithm(), &GENESIS_BLOCK))
+}
+
 impl WireReady for Block {
     fn from_bytes(data: &[u8]) -> Self {
         let c:Self = bincode::deserialize(data)
@@ -98,6 +116,11 @@ pub struct Header {
     pub author: Replica,
     pub height: Height,
     pub blame_certificates: Vec<Certificate>,
+    /// Proofs of misbehaviour that the author heard of
+    pub evidence: Vec<Evidence>,
+    /// The changes of the replica set in the configuration transactions of
+    /// this block
+    pub reconfig: Vec<Reconfig>,
 }
 
 impl std::fmt::Debug for Header {
@@ -134,6 +157,8 @@ impl Header {
             author: 0,
             height: 0,
             blame_certificates: Vec::new(),
+            evidence: Vec::new(),
+            reconfig: Vec::new(),
         }
     }
 }
@@ -1,19 +1,95 @@
 use serde::{Serialize, Deserialize};
-use crate::{Replica, Vote, View};
-use crypto::hash::Hash;
+use crate::{AggregateVote, Height, QuorumPolicy, Replica, Threshold, Vote, View};
+use crypto::{PublicKey, error::{BatchError, SigningError}, hash::Hash};
+use std::collections::{HashMap, HashSet};
+use std::hash::BuildHasher;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum CertType {
     Blame(Replica, View),
     Vote(View, Hash),
     QuitView(View, Hash),
+    /// A PBFT prepare or commit for a block at a sequence number in a view
+    Prepare(View, Height, Hash),
+    Commit(View, Height, Hash),
+    /// A PBFT checkpoint of the state after a sequence number
+    Checkpoint(Height, Hash),
     DEFAULT,
 }
 
+/// The kind of message of a certificate, without its contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertKind {
+    Blame,
+    Vote,
+    QuitView,
+    Prepare,
+    Commit,
+    Checkpoint,
+}
+
+impl CertType {
+    /// The kind of the message, if it is one that nodes vote on
+    pub fn kind(&self) -> Option<CertKind> {
+        match self {
+            CertType::Blame(_, _) => Some(CertKind::Blame),
+            CertType::Vote(_, _) => Some(CertKind::Vote),
+            CertType::QuitView(_, _) => Some(CertKind::QuitView),
+            CertType::Prepare(_, _, _) => Some(CertKind::Prepare),
+            CertType::Commit(_, _, _) => Some(CertKind::Commit),
+            CertType::Checkpoint(_, _) => Some(CertKind::Checkpoint),
+            CertType::DEFAULT => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CertError {
+    // expected kind, actual message
+    WrongMessage(CertKind, CertType),
+    // number of votes in a message that must hold exactly one
+    NotSingleVote(usize),
+    // origin that signed more than once
+    DuplicateSigner(Replica),
+    // origin whose public key is unknown
+    UnknownSigner(Replica),
+    // origin whose signature does not verify
+    InvalidSignature(Replica),
+    // the aggregate signature does not verify
+    InvalidAggregate,
+    // weight of the signers, weight needed
+    InsufficientVotes(u64, u64),
+}
+
+impl std::fmt::Display for CertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CertError::WrongMessage(kind, msg) =>
+            write!(f, "expected a {:?} message, got {:?}", kind, msg),
+            CertError::NotSingleVote(n) =>
+            write!(f, "expected a single vote, got {}", n),
+            CertError::DuplicateSigner(r) =>
+            write!(f, "more than one vote from {} replica", r),
+            CertError::UnknownSigner(r) =>
+            write!(f, "no public key for {} replica", r),
+            CertError::InvalidSignature(r) =>
+            write!(f, "invalid signature from {} replica", r),
+            CertError::InvalidAggregate =>
+            write!(f, "invalid aggregate signature"),
+            CertError::InsufficientVotes(w, needed) =>
+            write!(f, "votes of weight {} where {} is needed", w, needed),
+        }
+    }
+}
+
+impl std::error::Error for CertError {}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Certificate {
     pub msg: CertType,
     pub votes: Vec<Vote>,
+    /// With BLS, the votes are folded into this aggregate and `votes` is empty
+    pub agg: Option<AggregateVote>,
 }
 
 impl Certificate {
@@ -21,7 +97,196 @@ impl Certificate {
         Certificate {
             votes: Vec::new(),
             msg:CertType::DEFAULT,
+            agg: None,
+        }
+    }
+
+    /// A copy of this certificate with a vote that does not verify, if it
+    /// has any vote
+    pub(crate) fn malformed(&self) -> Option<Certificate> {
+        let mut c = self.clone();
+        if let Some(agg) = c.agg.as_mut() {
+            agg.auth = crate::broken(&agg.auth);
+            return Some(c);
+        }
+        let v = c.votes.first_mut()?;
+        v.auth = crate::broken(&v.auth);
+        Some(c)
+    }
+
+    /// Returns the number of votes in the certificate, aggregated or not
+    pub fn num_votes(&self) -> usize {
+        match &self.agg {
+            None => self.votes.len(),
+            Some(agg) => agg.num_signers(),
+        }
+    }
+
+    /// Fold all the votes into a single aggregate signature and a signer
+    /// bitmap. Duplicate votes from the same origin are dropped. Only works for
+    /// BLS votes.
+    pub fn aggregate(&self) -> Result<Certificate, SigningError> {
+        if self.agg.is_some() {
+            return Ok(self.clone());
+        }
+        let mut agg = AggregateVote::default();
+        let mut sigs = Vec::with_capacity(self.votes.len());
+        for v in &self.votes {
+            if agg.is_signer(v.origin) {
+                continue;
+            }
+            agg.add_signer(v.origin);
+            sigs.push(v.auth.as_slice());
+        }
+        agg.auth = crypto::bls::aggregate(&sigs)?;
+        Ok(Certificate {
+            msg: self.msg.clone(),
+            votes: Vec::new(),
+            agg: Some(agg),
+        })
+    }
+
+    /// Check that the certificate is for a message of `kind`, and that its
+    /// votes come from distinct nodes with known keys and meet `t`. Returns
+    /// the signers. The signatures are not checked, for callers that already
+    /// did.
+    pub fn check_signers<S: BuildHasher>(
+        &self,
+        kind: CertKind,
+        keys: &HashMap<Replica, PublicKey, S>,
+        quorum: &QuorumPolicy,
+        t: Threshold,
+    ) -> Result<Vec<Replica>, CertError> {
+        if self.msg.kind() != Some(kind) {
+            return Err(CertError::WrongMessage(kind, self.msg.clone()));
+        }
+        let mut signers = match &self.agg {
+            None => Vec::with_capacity(self.votes.len()),
+            Some(agg) => agg.signers(),
+        };
+        signers.extend(self.votes.iter().map(|v| v.origin));
+        let mut seen = HashSet::with_capacity(signers.len());
+        for r in &signers {
+            if !seen.insert(*r) {
+                return Err(CertError::DuplicateSigner(*r));
+            }
+            if !keys.contains_key(r) {
+                return Err(CertError::UnknownSigner(*r));
+            }
+        }
+        let weight = quorum.weight_of(signers.iter().cloned());
+        if weight < quorum.needed(t) {
+            return Err(CertError::InsufficientVotes(weight, quorum.needed(t)));
         }
+        Ok(signers)
+    }
+
+    /// Check the certificate like `check_signers`, and then all its
+    /// signatures. Returns the signers.
+    pub fn verify<S: BuildHasher>(
+        &self,
+        kind: CertKind,
+        keys: &HashMap<Replica, PublicKey, S>,
+        quorum: &QuorumPolicy,
+        t: Threshold,
+    ) -> Result<Vec<Replica>, CertError> {
+        let signers = self.check_signers(kind, keys, quorum, t)?;
+        if self.agg.is_some() && !self.verify_aggregate(keys) {
+            return Err(CertError::InvalidAggregate);
+        }
+        self.verify_votes(keys)
+            .map_err(CertError::InvalidSignature)?;
+        Ok(signers)
+    }
+
+    /// Check that the certificate is the single vote of a known node on a
+    /// message of `kind`, without checking the signature. Returns the origin
+    /// of the vote.
+    pub fn check_vote<S: BuildHasher>(
+        &self,
+        kind: CertKind,
+        keys: &HashMap<Replica, PublicKey, S>,
+    ) -> Result<Replica, CertError> {
+        if self.msg.kind() != Some(kind) {
+            return Err(CertError::WrongMessage(kind, self.msg.clone()));
+        }
+        if self.agg.is_some() || self.votes.len() != 1 {
+            return Err(CertError::NotSingleVote(self.num_votes()));
+        }
+        let origin = self.votes[0].origin;
+        if !keys.contains_key(&origin) {
+            return Err(CertError::UnknownSigner(origin));
+        }
+        Ok(origin)
+    }
+
+    /// Check the vote like `check_vote`, and then its signature. Returns the
+    /// origin of the vote.
+    pub fn verify_vote<S: BuildHasher>(
+        &self,
+        kind: CertKind,
+        keys: &HashMap<Replica, PublicKey, S>,
+    ) -> Result<Replica, CertError> {
+        let origin = self.check_vote(kind, keys)?;
+        let vote = &self.votes[0];
+        let pk = &keys[&origin];
+        let data = bincode::serialize(&self.msg)
+            .expect("failed to serialize the certificate message");
+        if !pk.verify(&data, &vote.auth) {
+            return Err(CertError::InvalidSignature(vote.origin));
+        }
+        Ok(vote.origin)
+    }
+
+    /// Check all the signatures in the certificate, aggregated or not
+    pub fn is_correctly_signed<S: BuildHasher>(&self, pk_map: &HashMap<Replica, PublicKey, S>) -> bool {
+        if self.agg.is_some() && !self.verify_aggregate(pk_map) {
+            return false;
+        }
+        self.verify_votes(pk_map).is_ok()
+    }
+
+    /// Check the signatures of all the individual votes in one batch. On
+    /// failure, returns the origin of an offending vote, which is either
+    /// incorrectly signed or from an unknown replica.
+    pub fn verify_votes<S: BuildHasher>(&self, pk_map: &HashMap<Replica, PublicKey, S>) -> Result<(), Replica> {
+        let mut pks = Vec::with_capacity(self.votes.len());
+        for v in &self.votes {
+            match pk_map.get(&v.origin) {
+                None => return Err(v.origin),
+                Some(pk) => pks.push(pk),
+            }
+        }
+        let data = bincode::serialize(&self.msg)
+            .expect("failed to serialize the certificate message");
+        let msgs = vec![data.as_slice(); self.votes.len()];
+        let sigs: Vec<&[u8]> = self.votes.iter()
+            .map(|v| v.auth.as_slice())
+            .collect();
+        PublicKey::verify_batch(&pks, &msgs, &sigs).map_err(|e| match e {
+            BatchError::Invalid(i) => self.votes[i].origin,
+            BatchError::LengthMismatch => unreachable!("the keys, messages and signatures are built together"),
+        })
+    }
+
+    /// Check the aggregate signature against the public keys of all the
+    /// signers in one pairing check. Returns false if the certificate is not
+    /// aggregated or has an unknown signer.
+    pub fn verify_aggregate<S: BuildHasher>(&self, pk_map: &HashMap<Replica, PublicKey, S>) -> bool {
+        let agg = match &self.agg {
+            None => return false,
+            Some(agg) => agg,
+        };
+        let mut pks = Vec::with_capacity(agg.num_signers());
+        for r in agg.signers() {
+            match pk_map.get(&r) {
+                None => return false,
+                Some(pk) => pks.push(pk),
+            }
+        }
+        let data = bincode::serialize(&self.msg)
+            .expect("failed to serialize the certificate message");
+        PublicKey::verify_aggregate(&pks, &data, &agg.auth)
     }
 }
 
@@ -29,4 +294,46 @@ impl std::default::Default for Certificate {
     fn default() -> Self {
         Certificate::empty_cert()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FaultModel;
+    use crypto::{Keypair, hash::EMPTY_HASH};
+
+    #[test]
+    fn verify_certificates() {
+        let kps: Vec<Keypair> = (0..3).map(|_| Keypair::generate_ed25519()).collect();
+        let keys: HashMap<Replica, PublicKey> = kps.iter().enumerate()
+            .map(|(i, kp)| (i, kp.public()))
+            .collect();
+        let quorum = QuorumPolicy::uniform(FaultModel::Synchronous, 3, 1);
+        let msg = CertType::Vote(1, EMPTY_HASH);
+        let vote = |r: Replica| Vote {
+            origin: r,
+            auth: kps[r].sign(&bincode::serialize(&msg).unwrap()).unwrap(),
+        };
+        let mut c = Certificate { msg: msg.clone(), votes: vec![vote(0)], agg: None };
+        assert_eq!(c.verify_vote(CertKind::Vote, &keys), Ok(0));
+        assert_eq!(c.verify(CertKind::Vote, &keys, &quorum, Threshold::Honest),
+            Err(CertError::InsufficientVotes(1, 2)));
+
+        c.votes.push(vote(2));
+        assert_eq!(c.verify(CertKind::Vote, &keys, &quorum, Threshold::Honest), Ok(vec![0, 2]));
+        assert!(matches!(c.verify(CertKind::Blame, &keys, &quorum, Threshold::Honest),
+            Err(CertError::WrongMessage(CertKind::Blame, _))));
+
+        c.votes.push(vote(2));
+        assert_eq!(c.verify(CertKind::Vote, &keys, &quorum, Threshold::Honest),
+            Err(CertError::DuplicateSigner(2)));
+
+        c.votes.pop();
+        c.votes[1].origin = 1;
+        assert_eq!(c.verify(CertKind::Vote, &keys, &quorum, Threshold::Honest),
+            Err(CertError::InvalidSignature(1)));
+        c.votes[1].origin = 3;
+        assert_eq!(c.verify(CertKind::Vote, &keys, &quorum, Threshold::Honest),
+            Err(CertError::UnknownSigner(3)));
+    }
+}
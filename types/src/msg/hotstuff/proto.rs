@@ -0,0 +1,135 @@
+use serde::{
+    Serialize, 
+    Deserialize
+};
+use crypto::{Keypair, hash::Hash};
+use std::sync::Arc;
+use super::{CertType, Certificate, Heartbeat, View, Block, Propose};
+use crate::{ByzantineMsg, HeartbeatMsg, MsgKind, WireReady};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ProtocolMsg {
+    /// A proposal for a view, with the certificate of the block it extends
+    RawNewProposal(Propose, Block),
+    NewProposal(Propose),
+    /// A vote for the proposal of a view, sent to the leader of the next view
+    VoteMsg(Certificate),
+    /// Sent to the leader of the view when we time out in the view before,
+    /// with the certificate of our highest certified block
+    NewView(View, Certificate),
+    /// A request for the proposal of a block, from a node that got a child
+    /// of it first
+    Fetch(Hash),
+    /// The proposals of the block asked for and of its ancestors, oldest
+    /// first, each with its block
+    RawProposals(Vec<(Propose, Block)>),
+    Proposals(Vec<Propose>),
+    /// A heartbeat, to measure the delay of the network. It never reaches
+    /// the reactor.
+    Heartbeat(Heartbeat),
+    /// Invalid message
+    INVALID,
+}
+
+impl HeartbeatMsg for ProtocolMsg {
+    fn heartbeat(hb: Heartbeat) -> Self {
+        ProtocolMsg::Heartbeat(hb)
+    }
+
+    fn into_heartbeat(self) -> Result<Heartbeat, Self> {
+        match self {
+            ProtocolMsg::Heartbeat(hb) => Ok(hb),
+            x => Err(x),
+        }
+    }
+}
+
+impl ByzantineMsg for ProtocolMsg {
+    fn kind(&self) -> MsgKind {
+        match self {
+            ProtocolMsg::RawNewProposal(..) => MsgKind::Proposal,
+            ProtocolMsg::VoteMsg(..) => MsgKind::Vote,
+            _ => MsgKind::Other,
+        }
+    }
+
+    fn conflicting(&self, sk: &Keypair) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let b = b.twin();
+                let mut p = p.clone();
+                p.block_hash = b.hash;
+                p.proof = sk.sign(&p.sign_data())
+                    .expect("Failed to sign a proposal");
+                p.block = None;
+                Some(ProtocolMsg::RawNewProposal(p, b))
+            }
+            _ => None,
+        }
+    }
+
+    fn malformed(&self) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let mut p = p.clone();
+                p.proof = crate::broken(&p.proof);
+                Some(ProtocolMsg::RawNewProposal(p, b.clone()))
+            }
+            ProtocolMsg::VoteMsg(c) => c.malformed().map(ProtocolMsg::VoteMsg),
+            ProtocolMsg::NewView(v, c) => {
+                c.malformed().map(|c| ProtocolMsg::NewView(*v, c))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl WireReady for ProtocolMsg {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let c:Self = bincode::deserialize(bytes)
+            .expect("failed to decode the protocol message");
+        c.init()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let bytes = bincode::serialize(self).expect("Failed to serialize protocol message");
+        bytes
+    }
+
+    fn init(self) -> Self {
+        match self {
+            ProtocolMsg::RawNewProposal(mut p, b) => {
+                let b = b.init();
+                p.block = Some(Arc::new(b));
+                ProtocolMsg::NewProposal(p)
+            },
+            ProtocolMsg::VoteMsg(ref c) => {
+                if let CertType::Vote(_,_) = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            },
+            ProtocolMsg::RawProposals(ps) => {
+                let ps = ps.into_iter()
+                    .map(|(mut p, b)| {
+                        p.block = Some(Arc::new(b.init()));
+                        p
+                    })
+                    .collect();
+                ProtocolMsg::Proposals(ps)
+            },
+            // The certificate of the genesis block is empty
+            ProtocolMsg::NewView(_, ref c) => {
+                if let CertType::Vote(_,_) | CertType::DEFAULT = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            }
+            _x => _x,
+        }
+    }
+}
@@ -3,6 +3,7 @@ pub(crate) use proto::*;
 
 mod block;
 pub(crate) use block::*;
+pub use block::genesis_hash;
 
 mod vote;
 pub(crate) use vote::*;
@@ -21,10 +22,13 @@ pub(crate) use payload::*;
 
 mod cert;
 pub(crate) use cert::*;
+pub use cert::{CertError, CertKind};
 
 pub mod synchs;
 pub mod synchs_rr;
 pub mod optsync;
+pub mod hotstuff;
+pub mod pbft;
 pub mod artemis;
 pub mod apollo;
 pub mod dummy;
@@ -0,0 +1,61 @@
+use crypto::hash::{EMPTY_HASH, Hash};
+use serde::{Serialize, Deserialize};
+use super::{Block, Height, Replica, View};
+use crate::{Signed, Statement};
+use std::sync::Arc;
+
+/// The block that the primary of a view assigns to a sequence number
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PrePrepare {
+    /// Signature by the primary
+    pub proof: Vec<u8>,
+    pub view: View,
+    /// The sequence number, which is also the height of the block
+    pub seq: Height,
+    /// Hash of the block being proposed
+    pub block_hash: Hash,
+
+    /// Cache
+    #[serde(skip_serializing, skip_deserializing)]
+    pub block: Option<Arc<Block>>,
+}
+
+impl PrePrepare {
+    pub fn new() -> Self {
+        Self{
+            proof: Vec::new(),
+            view: 0,
+            seq: 0,
+            block_hash: EMPTY_HASH,
+            block: None,
+        }
+    }
+
+    /// The statement that the primary signs in `proof`. Two pre-prepares with
+    /// the same view and sequence number but different blocks are an
+    /// equivocation.
+    pub fn statement(&self) -> Statement {
+        Statement::SeqProposal {
+            view: self.view,
+            seq: self.seq,
+            block_hash: self.block_hash,
+        }
+    }
+
+    /// The data that the primary signs in `proof`
+    pub fn sign_data(&self) -> Vec<u8> {
+        self.statement().sign_data()
+    }
+
+    /// The signed statement of this pre-prepare by the primary `origin`, to
+    /// use as evidence
+    pub fn signed(&self, origin: Replica) -> Signed {
+        Signed::new(self.statement(), origin, self.proof.clone())
+    }
+}
+
+impl std::default::Default for PrePrepare {
+    fn default() -> Self {
+        PrePrepare::new()
+    }
+}
@@ -0,0 +1,145 @@
+use serde::{
+    Serialize, 
+    Deserialize
+};
+use crypto::Keypair;
+use std::sync::Arc;
+use super::{Block, CertType, Certificate, Heartbeat, Height, PrePrepare, View, ViewChange};
+use crate::{ByzantineMsg, HeartbeatMsg, MsgKind, WireReady};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum ProtocolMsg {
+    /// The block of the primary for a sequence number
+    RawPrePrepare(PrePrepare, Block),
+    PrePrepare(PrePrepare),
+    /// A prepare for a pre-prepare, sent to everyone
+    Prepare(Certificate),
+    /// A commit for a prepared block, sent to everyone
+    Commit(Certificate),
+    /// A checkpoint vote for the state after a sequence number
+    Checkpoint(Certificate),
+    /// A request to move to the next view
+    ViewChange(ViewChange),
+    /// The view change messages with which the primary of a view starts it
+    NewView(View, Vec<ViewChange>),
+    /// A request for the committed blocks from this sequence number on, from
+    /// a node that fell behind the stable checkpoint
+    Fetch(Height),
+    /// Committed blocks in order, each with its commit certificate
+    Blocks(Vec<(Block, Certificate)>),
+    /// A heartbeat, to measure the delay of the network. It never reaches
+    /// the reactor.
+    Heartbeat(Heartbeat),
+    /// Invalid message
+    INVALID,
+}
+
+impl HeartbeatMsg for ProtocolMsg {
+    fn heartbeat(hb: Heartbeat) -> Self {
+        ProtocolMsg::Heartbeat(hb)
+    }
+
+    fn into_heartbeat(self) -> Result<Heartbeat, Self> {
+        match self {
+            ProtocolMsg::Heartbeat(hb) => Ok(hb),
+            x => Err(x),
+        }
+    }
+}
+
+impl ByzantineMsg for ProtocolMsg {
+    fn kind(&self) -> MsgKind {
+        match self {
+            ProtocolMsg::RawPrePrepare(..) => MsgKind::Proposal,
+            ProtocolMsg::Prepare(..) | ProtocolMsg::Commit(..) => MsgKind::Vote,
+            _ => MsgKind::Other,
+        }
+    }
+
+    fn conflicting(&self, sk: &Keypair) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawPrePrepare(p, b) => {
+                let b = b.twin();
+                let mut p = p.clone();
+                p.block_hash = b.hash;
+                p.proof = sk.sign(&p.sign_data())
+                    .expect("Failed to sign a pre-prepare");
+                p.block = None;
+                Some(ProtocolMsg::RawPrePrepare(p, b))
+            }
+            _ => None,
+        }
+    }
+
+    fn malformed(&self) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawPrePrepare(p, b) => {
+                let mut p = p.clone();
+                p.proof = crate::broken(&p.proof);
+                Some(ProtocolMsg::RawPrePrepare(p, b.clone()))
+            }
+            ProtocolMsg::Prepare(c) => c.malformed().map(ProtocolMsg::Prepare),
+            ProtocolMsg::Commit(c) => c.malformed().map(ProtocolMsg::Commit),
+            _ => None,
+        }
+    }
+}
+
+impl WireReady for ProtocolMsg {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let c:Self = bincode::deserialize(bytes)
+            .expect("failed to decode the protocol message");
+        c.init()
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let bytes = bincode::serialize(self).expect("Failed to serialize protocol message");
+        bytes
+    }
+
+    fn init(self) -> Self {
+        match self {
+            ProtocolMsg::RawPrePrepare(mut p, b) => {
+                let b = b.init();
+                p.block = Some(Arc::new(b));
+                ProtocolMsg::PrePrepare(p)
+            },
+            ProtocolMsg::Prepare(ref c) => {
+                if let CertType::Prepare(..) = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            },
+            ProtocolMsg::Commit(ref c) => {
+                if let CertType::Commit(..) = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            },
+            ProtocolMsg::Checkpoint(ref c) => {
+                if let CertType::Checkpoint(..) = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            },
+            ProtocolMsg::ViewChange(vc) => ProtocolMsg::ViewChange(vc.init()),
+            ProtocolMsg::NewView(v, vcs) => {
+                let vcs = vcs.into_iter().map(ViewChange::init).collect();
+                ProtocolMsg::NewView(v, vcs)
+            },
+            ProtocolMsg::Blocks(blocks) => {
+                let blocks = blocks.into_iter()
+                    .map(|(b, c)| (b.init(), c))
+                    .collect();
+                ProtocolMsg::Blocks(blocks)
+            },
+            _x => _x,
+        }
+    }
+}
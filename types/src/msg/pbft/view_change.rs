@@ -0,0 +1,48 @@
+use serde::{Serialize, Deserialize};
+use super::{Block, CertType, Certificate, Replica, View};
+use crate::WireReady;
+
+/// A block that prepared in some view, with the n-f prepares for it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Prepared {
+    pub block: Block,
+    pub cert: Certificate,
+}
+
+/// The request of a node to move to `view`, with its last stable checkpoint
+/// and the blocks that prepared at it after the checkpoint
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ViewChange {
+    pub view: View,
+    pub origin: Replica,
+    /// The certificate of the last stable checkpoint, empty before the first
+    pub stable: Certificate,
+    /// The highest prepared block for every sequence number after the
+    /// checkpoint
+    pub prepared: Vec<Prepared>,
+    /// Signature by the origin
+    pub proof: Vec<u8>,
+}
+
+impl ViewChange {
+    /// The data that the origin signs in `proof`. The blocks are bound by
+    /// the hashes in the certificates.
+    pub fn sign_data(&self) -> Vec<u8> {
+        let certs: Vec<&CertType> = self.prepared.iter()
+            .map(|p| &p.cert.msg)
+            .collect();
+        crypto::hash::ser_and_hash(&(self.view, self.origin, &self.stable.msg, certs))
+            .to_vec()
+    }
+
+    /// Compute the hashes of the blocks that came over the wire
+    pub fn init(mut self) -> Self {
+        self.prepared = self.prepared.into_iter()
+            .map(|p| Prepared {
+                block: p.block.init(),
+                cert: p.cert,
+            })
+            .collect();
+        self
+    }
+}
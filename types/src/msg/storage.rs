@@ -14,6 +14,10 @@ pub struct Storage<B,T> {
     committed_blocks_by_ht: HashSet<Height>,
     #[cfg(feature="mempool")]
     pending_tx: LinkedHashMap<Hash,Arc<T>>,
+    /// The transactions of the blocks that we proposed or delivered, until
+    /// the blocks commit
+    #[cfg(feature="mempool")]
+    inflight_tx: HashMap<Hash,Arc<T>>,
 }
 
 impl<B,T> Storage<B,T> 
@@ -29,6 +33,8 @@ T: TxTrait,
             committed_blocks_by_ht: HashSet::default(),
             #[cfg(feature="mempool")]
             pending_tx: LinkedHashMap::with_capacity(space),
+            #[cfg(feature="mempool")]
+            inflight_tx: HashMap::default(),
         }
     }
 
@@ -123,7 +129,10 @@ T: TxTrait,
         let mut txs = Vec::with_capacity(block_size);
         for _i in 0..block_size {
             let tx = match self.pending_tx.pop_front() {
-                Some((_hash, trans)) => trans,
+                Some((hash, trans)) => {
+                    self.inflight_tx.insert(hash, trans.clone());
+                    trans
+                }
                 None => {
                     panic!("Dequeued when tx pool was not block size");
                 },
@@ -134,13 +143,27 @@ T: TxTrait,
     }
 
     /// Clear removes the transaction hashes from the pool
+    ///
+    /// The transactions are kept until their block commits
     #[cfg(feature="mempool")]
     pub fn clear(&mut self, tx_hashes: &Vec<Hash>) {
         for h in tx_hashes {
-            self.pending_tx.remove(h);
+            if let Some(tx) = self.pending_tx.remove(h) {
+                self.inflight_tx.insert(*h, tx);
+            }
         }
     }
 
+    /// Removes the transactions of a committed block from the storage, and
+    /// returns them in order. A transaction that we never received is
+    /// `None`.
+    #[cfg(feature="mempool")]
+    pub fn take_committed(&mut self, tx_hashes: &[Hash]) -> Vec<Option<Arc<T>>> {
+        tx_hashes.iter()
+            .map(|h| self.inflight_tx.remove(h).or_else(|| self.pending_tx.remove(h)))
+            .collect()
+    }
+
     /// Adds a transaction to the pool
     #[cfg(feature="mempool")]
     pub fn add_transaction(&mut self, t: T) {
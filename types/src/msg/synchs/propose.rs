@@ -1,6 +1,7 @@
 use crypto::hash::{EMPTY_HASH, Hash};
 use serde::{Serialize, Deserialize};
-use super::{Certificate, View, Block};
+use super::{Block, CertType, Certificate, Replica, View};
+use crate::{Signed, Statement};
 use std::sync::Arc;
 
 #[derive(Serialize, Deserialize, Debug,Clone)]
@@ -29,4 +30,34 @@ impl Propose {
             block_hash: EMPTY_HASH,
         }
     }
-}
\ No newline at end of file
+
+    /// The parent of the proposed block, as certified by `cert`. The genesis
+    /// block is certified by the empty certificate.
+    pub fn parent(&self) -> Hash {
+        match &self.cert.msg {
+            CertType::Vote(_, h) => *h,
+            _ => EMPTY_HASH,
+        }
+    }
+
+    /// The statement that the leader signs in `proof`. Two proposals with the
+    /// same view and parent but different blocks are an equivocation.
+    pub fn statement(&self) -> Statement {
+        Statement::ViewProposal {
+            view: self.view,
+            parent: self.parent(),
+            block_hash: self.block_hash,
+        }
+    }
+
+    /// The data that the leader signs in `proof`
+    pub fn sign_data(&self) -> Vec<u8> {
+        self.statement().sign_data()
+    }
+
+    /// The signed statement of this proposal by the leader `origin`, to use
+    /// as evidence
+    pub fn signed(&self, origin: Replica) -> Signed {
+        Signed::new(self.statement(), origin, self.proof.clone())
+    }
+}
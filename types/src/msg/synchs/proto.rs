@@ -3,9 +3,10 @@ use serde::{
     Serialize, 
     Deserialize
 };
-use std::sync::Arc;
-use super::{CertType, Certificate, Payload, View, Block, Propose};
-use crate::WireReady;
+use crypto::{Keypair, PublicKey};
+use std::{collections::HashMap, hash::BuildHasher, sync::Arc};
+use super::{CertType, Certificate, Evidence, Heartbeat, Height, Payload, Replica, View, Block, Propose};
+use crate::{ByzantineMsg, ClientBlockMsg, HeartbeatMsg, MsgKind, WireReady};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ProtocolMsg {
@@ -17,24 +18,138 @@ pub enum ProtocolMsg {
     /// An equivocation blame
     /// Equivocation Blame is sent when two equivocating proposals are heard
     /// It contains
-    /// 1) The leader who equivocated
-    /// 2) The two equivocating blocks
-    EquivcationBlameMsg(Block, Block, Certificate),
+    /// 1) The proof of equivocation: two conflicting proposals signed by the
+    ///    leader
+    /// 2) The blame for the leader who equivocated
+    EquivcationBlameMsg(Evidence, Certificate),
+    /// A blame for a leader that did not propose in time
     NoProgressBlameMsg(Certificate),
-    
+
     /// A message to change the view
     /// View is the old view
     /// Certificate is the certificate for the old view
     ChangeView(View, Certificate),
     /// Certificate saying that all the nodes are waiting to quit the view
+    /// It contains f+1 blames for the view
     QuitViewMsg(View, Certificate), 
-    /// Status: Contains the block and its certificate
+    /// Status: Contains the certificate of the highest certified block
     StatusMsg(Certificate),
+    /// A heartbeat, to measure the delay of the network. It never reaches
+    /// the reactor.
+    Heartbeat(Heartbeat),
+    /// A request for the chain above this height, from a node that restarted
+    /// or fell behind
+    SyncRequest(Height),
+    /// The blocks of the chain above the requested height, in order, and the
+    /// certificate of the last one
+    SyncResponse(Vec<Block>, Certificate),
     /// Invalid message
     INVALID,
 }
 
+impl HeartbeatMsg for ProtocolMsg {
+    fn heartbeat(hb: Heartbeat) -> Self {
+        ProtocolMsg::Heartbeat(hb)
+    }
+
+    fn into_heartbeat(self) -> Result<Heartbeat, Self> {
+        match self {
+            ProtocolMsg::Heartbeat(hb) => Ok(hb),
+            x => Err(x),
+        }
+    }
+}
+
+impl ByzantineMsg for ProtocolMsg {
+    fn kind(&self) -> MsgKind {
+        match self {
+            ProtocolMsg::RawNewProposal(..) => MsgKind::Proposal,
+            ProtocolMsg::VoteMsg(..) => MsgKind::Vote,
+            _ => MsgKind::Other,
+        }
+    }
+
+    fn conflicting(&self, sk: &Keypair) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let b = b.twin();
+                let mut p = p.clone();
+                p.block_hash = b.hash;
+                p.proof = sk.sign(&p.sign_data())
+                    .expect("Failed to sign a proposal");
+                p.block = None;
+                Some(ProtocolMsg::RawNewProposal(p, b))
+            }
+            _ => None,
+        }
+    }
+
+    fn malformed(&self) -> Option<Self> {
+        match self {
+            ProtocolMsg::RawNewProposal(p, b) => {
+                let mut p = p.clone();
+                p.proof = crate::broken(&p.proof);
+                Some(ProtocolMsg::RawNewProposal(p, b.clone()))
+            }
+            ProtocolMsg::VoteMsg(c, p) => {
+                c.malformed().map(|c| ProtocolMsg::VoteMsg(c, p.clone()))
+            }
+            ProtocolMsg::NoProgressBlameMsg(c) => {
+                c.malformed().map(ProtocolMsg::NoProgressBlameMsg)
+            }
+            ProtocolMsg::QuitViewMsg(v, c) => {
+                c.malformed().map(|c| ProtocolMsg::QuitViewMsg(*v, c))
+            }
+            _ => None,
+        }
+    }
+}
+
 impl ProtocolMsg {
+    /// Check all the signatures in the message that can be checked without
+    /// any protocol state: the proposer's signature on the block, the votes
+    /// and the certificates. Messages that fail the check must be dropped.
+    pub fn verify_sigs<S: BuildHasher>(&self, pk_map: &HashMap<Replica, PublicKey, S>) -> bool {
+        match self {
+            ProtocolMsg::NewProposal(p) => {
+                let b = match &p.block {
+                    None => return false,
+                    Some(b) => b,
+                };
+                let pk = match pk_map.get(&b.header.author) {
+                    None => return false,
+                    Some(pk) => pk,
+                };
+                p.block_hash == b.hash && 
+                    pk.verify(&p.sign_data(), &p.proof) && 
+                    p.cert.is_correctly_signed(pk_map) &&
+                    b.header.evidence.iter().all(|e| e.verify(pk_map).is_ok())
+            }
+            ProtocolMsg::VoteMsg(c, _) => {
+                c.votes.len() == 1 && c.is_correctly_signed(pk_map)
+            }
+            ProtocolMsg::EquivcationBlameMsg(e, c) => {
+                // The proof must be against the blamed leader
+                match &c.msg {
+                    CertType::Blame(l, _) if *l == e.offender() => {}
+                    _ => return false,
+                }
+                e.verify(pk_map).is_ok() && c.is_correctly_signed(pk_map)
+            }
+            ProtocolMsg::NoProgressBlameMsg(c) |
+            ProtocolMsg::ChangeView(_, c) |
+            ProtocolMsg::QuitViewMsg(_, c) |
+            ProtocolMsg::StatusMsg(c) |
+            ProtocolMsg::SyncResponse(_, c) => c.is_correctly_signed(pk_map),
+            // Heartbeats are not signed, and are taken out before the
+            // verifiers
+            ProtocolMsg::Heartbeat(_) |
+            ProtocolMsg::SyncRequest(_) => true,
+            // Raw proposals are turned into new proposals when decoded
+            ProtocolMsg::RawNewProposal(_, _) |
+            ProtocolMsg::INVALID => false,
+        }
+    }
 }
 
 impl WireReady for ProtocolMsg {
@@ -64,7 +179,7 @@ impl WireReady for ProtocolMsg {
                     ProtocolMsg::INVALID
                 }
             },
-            ProtocolMsg::EquivcationBlameMsg(_,_,ref c) => {
+            ProtocolMsg::EquivcationBlameMsg(_, ref c) => {
                 if let CertType::Blame(_,_) = &c.msg {
                     return self;
                 } else {
@@ -80,6 +195,36 @@ impl WireReady for ProtocolMsg {
                     ProtocolMsg::INVALID
                 }
             },
+            ProtocolMsg::QuitViewMsg(ref v, ref c) => {
+                if let CertType::Blame(_, ref x) = c.msg {
+                    if *v == *x {
+                        self
+                    } else {
+                        log::debug!("Invalid {:?}", self);
+                        ProtocolMsg::INVALID
+                    }
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            }
+            ProtocolMsg::StatusMsg(ref c) => {
+                if let CertType::Vote(_,_) = &c.msg {
+                    self
+                } else {
+                    log::debug!("Invalid {:?}", self);
+                    ProtocolMsg::INVALID
+                }
+            }
+            ProtocolMsg::SyncResponse(blocks, c) => {
+                if let CertType::Vote(_,_) = &c.msg {
+                    let blocks = blocks.into_iter().map(|b| b.init()).collect();
+                    ProtocolMsg::SyncResponse(blocks, c)
+                } else {
+                    log::debug!("Invalid sync response with {:?}", c);
+                    ProtocolMsg::INVALID
+                }
+            }
             ProtocolMsg::ChangeView(ref v, ref c) => {
                 if let CertType::Vote(ref x,_) = c.msg {
                     if *v == *x {
@@ -113,6 +258,15 @@ pub enum ClientMsg {
     Response(Hash, Block),
 }
 
+impl ClientBlockMsg for ClientMsg {
+    fn new_block(&self) -> Option<&Block> {
+        match self {
+            ClientMsg::NewBlock(b, _) => Some(b),
+            _ => None,
+        }
+    }
+}
+
 impl WireReady for ClientMsg {
     fn from_bytes(bytes: &[u8]) -> Self {
         let c:Self = bincode::deserialize(bytes)
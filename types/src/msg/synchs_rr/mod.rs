@@ -3,6 +3,7 @@ pub type Certificate = super::Certificate;
 pub type CertType = super::CertType;
 pub type Replica = crate::Replica;
 pub type Vote = super::Vote;
+pub type AggregateVote = super::AggregateVote;
 pub type Block = super::Block;
 pub const GENESIS_BLOCK: Block = super::GENESIS_BLOCK;
 pub type Height = crate::Height;
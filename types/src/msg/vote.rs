@@ -9,3 +9,45 @@ pub struct Vote {
     pub origin: Replica,
     pub auth: Vec<u8>,
 }
+
+/// A set of votes on the same message folded into one (BLS) signature
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AggregateVote {
+    /// A bitmap where bit i is set if replica i voted
+    pub signers: Vec<u8>,
+    pub auth: Vec<u8>,
+}
+
+impl AggregateVote {
+    /// Mark the replica as a signer of this vote
+    pub fn add_signer(&mut self, r: Replica) {
+        if self.signers.len() <= r/8 {
+            self.signers.resize(r/8+1, 0);
+        }
+        self.signers[r/8] |= 1 << (r%8);
+    }
+
+    pub fn is_signer(&self, r: Replica) -> bool {
+        match self.signers.get(r/8) {
+            None => false,
+            Some(b) => b & (1 << (r%8)) != 0,
+        }
+    }
+
+    /// Returns the replicas that signed this vote in increasing order
+    pub fn signers(&self) -> Vec<Replica> {
+        let mut signers = Vec::new();
+        for (i, b) in self.signers.iter().enumerate() {
+            for j in 0..8 {
+                if b & (1 << j) != 0 {
+                    signers.push(8*i+j);
+                }
+            }
+        }
+        signers
+    }
+
+    pub fn num_signers(&self) -> usize {
+        self.signers.iter().map(|b| b.count_ones() as usize).sum()
+    }
+}
@@ -1,2 +1,4 @@
 pub type Replica = usize;
-pub type Height = usize;
\ No newline at end of file
+pub type Height = usize;
+/// The destination of a message to every node, which no node can be
+pub const EVERYONE: Replica = Replica::MAX;
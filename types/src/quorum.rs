@@ -0,0 +1,162 @@
+use crate::Replica;
+use fnv::{FnvHashMap as HashMap, FnvHashSet as HashSet};
+
+/// What a protocol assumes about the network, which decides how many faulty
+/// nodes it tolerates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultModel {
+    /// Messages arrive within Δ: f < n/2 (Apollo, Artemis, Sync HotStuff,
+    /// Opt Sync)
+    Synchronous,
+    /// Messages arrive eventually: f < n/3 (HotStuff, PBFT)
+    PartiallySynchronous,
+}
+
+impl std::fmt::Display for FaultModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FaultModel::Synchronous => write!(f, "synchronous (f < n/2)"),
+            FaultModel::PartiallySynchronous => write!(f, "partially synchronous (f < n/3)"),
+        }
+    }
+}
+
+/// How many votes a certificate needs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Threshold {
+    /// More than the faulty nodes, so that one of the votes is honest: f+1
+    Honest,
+    /// All but the faulty nodes: n-f
+    Quorum,
+    /// The 3n/4 votes of a responsive certificate in Opt Sync
+    Responsive,
+}
+
+/// The votes that the certificates of a protocol need, from the number of
+/// nodes, the number of faults and the fault model of the protocol.
+///
+/// With weighted voting, every node votes with its weight instead of one
+/// vote, and the faulty nodes are the `num_faults` heaviest ones.
+#[derive(Debug, Clone)]
+pub struct QuorumPolicy {
+    model: FaultModel,
+    num_faults: usize,
+    /// The weight of every node, empty if every node has one vote
+    weights: HashMap<Replica, u64>,
+    /// The weight of all the nodes
+    total: u64,
+    /// The weight of the faulty nodes
+    faulty: u64,
+}
+
+impl QuorumPolicy {
+    /// The policy for the nodes `members`, `num_faults` of which may be
+    /// faulty. Without `weights`, every node has one vote.
+    pub fn new<I>(
+        model: FaultModel,
+        members: I,
+        num_faults: usize,
+        weights: HashMap<Replica, u64>,
+    ) -> Self
+    where
+        I: IntoIterator<Item = Replica>,
+    {
+        let mut all: Vec<u64> = members.into_iter()
+            .map(|r| if weights.is_empty() { 1 } else { weights.get(&r).cloned().unwrap_or(0) })
+            .collect();
+        all.sort_unstable_by(|a, b| b.cmp(a));
+        let faulty = all.iter().take(num_faults).sum();
+        QuorumPolicy {
+            model,
+            num_faults,
+            weights,
+            total: all.iter().sum(),
+            faulty,
+        }
+    }
+
+    /// The policy of `num_nodes` nodes `0..num_nodes` with one vote each
+    pub fn uniform(model: FaultModel, num_nodes: usize, num_faults: usize) -> Self {
+        Self::new(model, 0..num_nodes, num_faults, HashMap::default())
+    }
+
+    pub fn model(&self) -> FaultModel {
+        self.model
+    }
+
+    pub fn num_faults(&self) -> usize {
+        self.num_faults
+    }
+
+    /// Whether the faulty nodes are few enough for the fault model
+    pub fn is_tolerated(&self) -> bool {
+        match self.model {
+            FaultModel::Synchronous => 2*self.faulty < self.total,
+            FaultModel::PartiallySynchronous => 3*self.faulty < self.total,
+        }
+    }
+
+    /// The weight of a node
+    pub fn weight(&self, r: Replica) -> u64 {
+        if self.weights.is_empty() {
+            1
+        } else {
+            self.weights.get(&r).cloned().unwrap_or(0)
+        }
+    }
+
+    /// The weight of the distinct nodes in `signers`
+    pub fn weight_of<I>(&self, signers: I) -> u64
+    where
+        I: IntoIterator<Item = Replica>,
+    {
+        signers.into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(|r| self.weight(r))
+            .sum()
+    }
+
+    /// The weight that a certificate needs
+    pub fn needed(&self, t: Threshold) -> u64 {
+        match t {
+            Threshold::Honest => self.faulty+1,
+            Threshold::Quorum => self.total-self.faulty,
+            Threshold::Responsive => (3*self.total)/4,
+        }
+    }
+
+    /// Whether the votes of `signers` meet the threshold
+    pub fn is_met<I>(&self, t: Threshold, signers: I) -> bool
+    where
+        I: IntoIterator<Item = Replica>,
+    {
+        self.weight_of(signers) >= self.needed(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_thresholds() {
+        let sync = QuorumPolicy::uniform(FaultModel::Synchronous, 3, 1);
+        assert!(sync.is_tolerated());
+        assert!(sync.is_met(Threshold::Honest, vec![0, 2]));
+        assert!(!sync.is_met(Threshold::Honest, vec![2, 2]));
+        assert!(!QuorumPolicy::uniform(FaultModel::PartiallySynchronous, 3, 1).is_tolerated());
+
+        // The faulty node is the heaviest one
+        let mut weights = HashMap::default();
+        weights.insert(0, 3);
+        weights.insert(1, 1);
+        weights.insert(2, 1);
+        weights.insert(3, 5);
+        let bft = QuorumPolicy::new(FaultModel::PartiallySynchronous, 0..4, 1, weights);
+        assert!(!bft.is_tolerated());
+        assert_eq!(bft.needed(Threshold::Quorum), 5);
+        assert!(bft.is_met(Threshold::Quorum, vec![0, 1, 2]));
+        assert!(!bft.is_met(Threshold::Honest, vec![0, 1, 2]));
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{Serialize, Deserialize};
+use crate::{Height, Replica, Transaction};
+
+/// The tag at the start of `Transaction::data` for a configuration
+/// transaction. The reconfiguration itself is in `Transaction::request`.
+const RECONFIG_TAG: &[u8] = b"libchatter-reconfig";
+
+/// A node that joins the replica set
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Member {
+    pub id: Replica,
+    /// The address of its protocol network
+    pub addr: String,
+    /// Its public key, encoded like in `config::Node::pk_map`
+    pub pk: Vec<u8>,
+}
+
+/// A change of the replica set, which a client submits in a configuration
+/// transaction. Once it commits, the new replica set runs the protocol from
+/// `from_height` on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reconfig {
+    /// The first height that the new replica set builds
+    pub from_height: Height,
+    pub add: Vec<Member>,
+    pub remove: Vec<Replica>,
+    /// The number of faults that the new replica set tolerates
+    pub num_faults: usize,
+}
+
+impl Reconfig {
+    /// The configuration transaction for this change
+    pub fn to_transaction(&self) -> Transaction {
+        Transaction {
+            data: RECONFIG_TAG.to_vec(),
+            request: bincode::serialize(self)
+                .expect("Failed to serialize a reconfiguration"),
+        }
+    }
+
+    /// The change in `tx`, if it is a configuration transaction
+    pub fn from_transaction(tx: &Transaction) -> Option<Self> {
+        if tx.data != RECONFIG_TAG {
+            return None;
+        }
+        bincode::deserialize(&tx.request).ok()
+    }
+}
@@ -1,5 +1,5 @@
-use crypto::hash::Hash;
-use crate::{Height, Replica};
+use crypto::{Keypair, hash::Hash};
+use crate::{Heartbeat, Height, Replica, msg::Block};
 use std::sync::Arc;
 
 /// Block trait, anything that claims itself to be a block must satisfy these traits
@@ -52,4 +52,53 @@ where A:WireReady,
         let y = x.init();
         Arc::new(y)
     }
-}
\ No newline at end of file
+}
+/// Protocol messages that can carry heartbeats, so that the delay of the
+/// network can be measured on the connections of the protocol
+pub trait HeartbeatMsg: Sized {
+    /// Wrap a heartbeat in a message
+    fn heartbeat(hb: Heartbeat) -> Self;
+
+    /// The heartbeat in this message, or the message itself if it is not one
+    fn into_heartbeat(self) -> Result<Heartbeat, Self>;
+}
+
+/// Client messages that carry the blocks proposed by the replicas, so that
+/// one client can serve all the protocols that share a block format
+pub trait ClientBlockMsg {
+    /// The new block in this message, if it carries one
+    fn new_block(&self) -> Option<&Block>;
+}
+
+/// What a protocol message is, for the nodes that misbehave on purpose
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MsgKind {
+    Proposal,
+    Vote,
+    Other,
+}
+
+/// Protocol messages that a Byzantine node knows how to tamper with, to test
+/// the protocols under attack
+pub trait ByzantineMsg: Sized {
+    /// What this message is
+    fn kind(&self) -> MsgKind;
+
+    /// A proposal for a different block in the same slot as this proposal,
+    /// signed with `sk`. None if this is not a proposal.
+    fn conflicting(&self, sk: &Keypair) -> Option<Self>;
+
+    /// A copy of this message whose signature does not verify. None if it is
+    /// not signed.
+    fn malformed(&self) -> Option<Self>;
+}
+
+/// A copy of `sig` that does not verify
+pub(crate) fn broken(sig: &[u8]) -> Vec<u8> {
+    let mut sig = sig.to_vec();
+    match sig.first_mut() {
+        Some(x) => *x ^= 1,
+        None => sig.push(0),
+    }
+    sig
+}